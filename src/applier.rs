@@ -1,19 +1,64 @@
+use crate::apply_budget::{Budget, BudgetStore};
+use crate::apply_journal::{Journal, JournalStore};
+use crate::apply_store::{ApplyStore, CachedStore, DiskStore, OverlayStore, WriteTargetAction, WriteTargetStore};
+use crate::confirmer::is_destructive;
+use crate::feedback_templates::feedback_msg;
+use crate::file_insert::{apply_file_insert, file_insert_applied};
+use crate::generated_file::generated_reason;
+use crate::global_replace::{DEFAULT_MAX_MATCHES, ReplaceCount, Replacer, glob_rel_paths};
+use crate::license_header::with_license_header;
+use crate::line_endings::{restore_line_endings, strip_bom};
+use crate::materializer::read_paths;
+use crate::patch_completer::{CompletedPatch, LineIndex};
+use crate::path_resolver::resolve_fuzzy_path;
+use crate::post_apply_validator::ValidatingStore;
+use crate::protected_regions::modified_protected_region;
+use crate::provenance::line_origins;
+use crate::secret_scan::{scan_file_secrets, scan_secrets};
+use crate::side_by_side::parse_hunk_starts;
+use crate::span_patch::{apply_span_patch, span_patch_applied};
 use crate::{
-	ApplyChangesStatus, DirectiveStatus, Error, FileChanges, FileDirective, HunkError, MatchTier, Result,
-	SecurityPolicy, fs_guard, patch_completer,
+	ApplyChangesStatus, ApplyOptions, ApplyProgress, CompleteOptions, ConfirmDecision, ContextAssertion,
+	DeleteStrategy, Denial, DiffStats, DirectiveStatus, Error, FileChanges, FileDirective, HunkDrift, HunkError,
+	LenientLine, LineOrigin, MatchTier, Materializer, NearestMiss, NewFileCollision, NoopEdit, NoopKind,
+	RenameCollision, Result, SecretScanMode, SecurityPolicy, diff_stats, fs_guard, make_file_patch, patch_completer,
 };
 use diffy::{Patch, apply as diffy_apply};
-use simple_fs::{SPath, ensure_file_dir, read_to_string, safer_trash_dir, safer_trash_file};
-use std::fs;
+use simple_fs::SPath;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 const CRLF_SAVE_TO_LDF: bool = true;
 
+/// A patch whose context similarity with its target is at or below this value is a wrong-target candidate.
+const WRONG_TARGET_MAX_SIMILARITY: f64 = 0.3;
+
+/// Minimum context similarity a context file needs to be suggested as the intended target.
+const WRONG_TARGET_MIN_SUGGESTION_SIMILARITY: f64 = 0.8;
+
+/// Context lines around the changes of a `DirectiveStatus::applied_diff`.
+const APPLIED_DIFF_CONTEXT_LINES: usize = 3;
+
+/// Max numbered `.orig.<n>` paths tried for an existing file renamed aside (see `NewFileCollision::RenameExisting`).
+const RENAME_EXISTING_MAX_NUMBER: usize = 9;
+
 #[derive(Debug, Clone)]
 pub struct ApplyPatchIncrementalData {
 	pub new_content: String,
 	pub max_tier: Option<MatchTier>,
 	pub hunk_errors: Vec<HunkError>,
 	pub total_hunks: usize,
+	/// The completed (numbered) unified diff of each hunk that changed the content, in application order.
+	pub completed_patches: Vec<String>,
+	/// The hunks dropped because they would not change the content.
+	pub noop_edits: Vec<NoopEdit>,
+	/// The 0-based indexes of the applied hunks that matched truncated context lines (suffix/prefix matching).
+	pub truncated_hunks: Vec<usize>,
+	/// The drifts of the applied hunks with a numbered header.
+	pub hunk_drifts: Vec<HunkDrift>,
+	/// The context/removal lines of the applied hunks matched non-strictly (lines of the original content).
+	pub lenient_lines: Vec<LenientLine>,
+	/// The checks of the context-only hunks (see `CompleteOptions::assert_context_hunks`).
+	pub context_assertions: Vec<ContextAssertion>,
 }
 
 /// Executes the file changes defined in `AipFileChanges` relative to `base_dir`.
@@ -33,12 +78,49 @@ pub fn apply_file_changes(
 	base_dir: impl Into<SPath>,
 	file_changes: FileChanges,
 	security_policy: impl Into<SecurityPolicy>,
+) -> Result<ApplyChangesStatus> {
+	apply_file_changes_with_options(base_dir, file_changes, security_policy, None)
+}
+
+/// Same as `apply_file_changes`, with additional `ApplyOptions`.
+///
+/// Passing `None` (via `Option<ApplyOptions>`) is equivalent to `apply_file_changes`.
+pub fn apply_file_changes_with_options(
+	base_dir: impl Into<SPath>,
+	file_changes: FileChanges,
+	security_policy: impl Into<SecurityPolicy>,
+	options: impl Into<ApplyOptions>,
+) -> Result<ApplyChangesStatus> {
+	apply_file_changes_with_progress(base_dir, file_changes, security_policy, options, &mut ())
+}
+
+/// Same as `apply_file_changes_with_options`, reporting each directive start and end to `progress`
+/// as the batch is applied.
+pub fn apply_file_changes_with_progress(
+	base_dir: impl Into<SPath>,
+	file_changes: FileChanges,
+	security_policy: impl Into<SecurityPolicy>,
+	options: impl Into<ApplyOptions>,
+	progress: &mut impl ApplyProgress,
 ) -> Result<ApplyChangesStatus> {
 	let base_dir = base_dir.into();
 	let policy: SecurityPolicy = security_policy.into();
-	let policy_ref = Some(&policy);
+	let options: ApplyOptions = options.into();
+	let base_dir = resolve_base_dir(base_dir, &policy, &options)?;
+
+	Ok(apply_with_store(
+		&mut DiskStore,
+		&base_dir,
+		file_changes,
+		&policy,
+		&options,
+		progress,
+	))
+}
 
-	// Compute absolute, collapsed base_dir and validate via security policy
+/// Computes the absolute, collapsed `base_dir` (relative to the current dir), and checks its write access
+/// (unless `ApplyOptions::allow_outside_cwd`).
+pub(crate) fn resolve_base_dir(base_dir: SPath, policy: &SecurityPolicy, options: &ApplyOptions) -> Result<SPath> {
 	let cwd = std::env::current_dir().map_err(|err| Error::io_read_file(".", err))?;
 	let cwd_spath = SPath::from_std_path(cwd)?;
 
@@ -48,165 +130,924 @@ pub fn apply_file_changes(
 		cwd_spath.join(base_dir).into_collapsed()
 	};
 
-	policy.assert_write_access(&base_dir)?;
+	if !options.allow_outside_cwd {
+		policy.assert_write_access(&base_dir)?;
+	}
+
+	Ok(base_dir)
+}
+
+/// Applies the file changes to the given store (with the all-or-nothing validation if enabled).
+///
+/// `base_dir` must be absolute and collapsed, and already checked against the security policy.
+pub(crate) fn apply_with_store(
+	store: &mut impl ApplyStore,
+	base_dir: &SPath,
+	file_changes: FileChanges,
+	policy: &SecurityPolicy,
+	options: &ApplyOptions,
+	progress: &mut impl ApplyProgress,
+) -> ApplyChangesStatus {
+	if options.validate_all_first
+		&& let Some(failure) = validate_batch(store, base_dir, &file_changes, policy, options)
+	{
+		return failure;
+	}
+
+	let mut options = with_batch_backup_dir(options, base_dir);
+	// The destructive directives were confirmed by the validation.
+	if options.validate_all_first {
+		options.confirmer = None;
+	}
+	let (status, _) = apply_to_store(store, base_dir, file_changes, policy, &options, progress);
+
+	status
+}
+
+/// All-or-nothing: simulates the whole batch in memory (zero writes), and returns the batch failure status
+/// if any directive fails.
+///
+/// The directives are cloned one at a time (not the whole batch) as they are simulated.
+pub(crate) fn validate_batch(
+	store: &impl ApplyStore,
+	base_dir: &SPath,
+	file_changes: &FileChanges,
+	policy: &SecurityPolicy,
+	options: &ApplyOptions,
+) -> Option<ApplyChangesStatus> {
+	// The whole batch is validated, whatever the stop policy (and nothing needs to be undone or backed up).
+	let options = ApplyOptions {
+		stop_on_error: false,
+		rollback_on_error: false,
+		record_undo: false,
+		backup_dir: None,
+		..options.clone()
+	};
+	let (validation, valid_flags) = apply_to_store(
+		&mut OverlayStore::new(store),
+		base_dir,
+		file_changes.iter().cloned(),
+		policy,
+		&options,
+		&mut (),
+	);
+
+	valid_flags
+		.contains(&false)
+		.then(|| into_batch_validation_failure(validation, &valid_flags, &options))
+}
+
+/// Applies every directive to the given store, capturing per-directive failures in the status.
+///
+/// Also returns, for each directive, whether it is valid for an all-or-nothing batch
+/// (a no-op directive is harmless and does not invalidate the batch).
+///
+/// The directives are consumed one by one, so the body of a directive is dropped once applied.
+///
+/// The files written by the batch are read back from memory (see `CachedStore`), so a directive on a file
+/// written earlier in the batch (e.g., a second `FILE_PATCH`) applies on the content the previous directive produced,
+/// whatever the store.
+///
+/// Handles the `stop_on_error`, `rollback_on_error`, `record_undo`, `omit_content_echoes`, `elevate_read_only`,
+/// `symlink_target`, and `post_apply_validators` options, and the safety budget
+/// (see `ApplyOptions::max_files_touched`).
+pub(crate) fn apply_to_store(
+	store: &mut impl ApplyStore,
+	base_dir: &SPath,
+	directives: impl IntoIterator<Item = FileDirective, IntoIter: ExactSizeIterator>,
+	policy: &SecurityPolicy,
+	options: &ApplyOptions,
+	progress: &mut impl ApplyProgress,
+) -> (ApplyChangesStatus, Vec<bool>) {
+	let budget = &mut Budget::new(options);
+	apply_to_store_with_budget(store, base_dir, directives, policy, options, progress, budget)
+}
 
+/// Same as `apply_to_store`, charging the changes to the given `budget` (e.g., spanning several calls).
+pub(crate) fn apply_to_store_with_budget(
+	store: &mut impl ApplyStore,
+	base_dir: &SPath,
+	directives: impl IntoIterator<Item = FileDirective, IntoIter: ExactSizeIterator>,
+	policy: &SecurityPolicy,
+	options: &ApplyOptions,
+	progress: &mut impl ApplyProgress,
+	budget: &mut Budget,
+) -> (ApplyChangesStatus, Vec<bool>) {
+	let store = &mut WriteTargetStore::new(store, options);
+	let store = &mut ValidatingStore::new(store, base_dir, options);
+	let store = &mut CachedStore::new(store);
 	let mut items = Vec::new();
+	let mut valid_flags = Vec::new();
+	let directives = directives.into_iter();
+	let total = directives.len();
+	let mut journal = (options.rollback_on_error || options.record_undo).then(Journal::default);
+	let mut stopped = false;
 
-	for directive in file_changes {
+	for (index, directive) in directives.enumerate() {
+		progress.on_directive_start(index, total, &directive);
 		let mut info = DirectiveStatus::from(&directive);
 
-		let res: Result<()> = (|| {
-			match directive {
-				FileDirective::New { file_path, content } => {
-					let full_path = base_dir.join(&file_path);
-					fs_guard::check_for_write(&full_path, &base_dir, policy_ref)?;
+		if stopped {
+			let skip_err = if budget.is_exhausted() {
+				Error::ApplyBatchBudgetExhausted
+			} else {
+				Error::ApplyBatchStopped
+			};
+			info.error_msg = Some(feedback_msg(&skip_err, options));
+			valid_flags.push(false);
+			if let Some(redactor) = &options.redactor {
+				info.redact(redactor);
+			}
+			progress.on_directive_done(index, total, &info);
+			items.push(info);
+			continue;
+		}
+
+		let undo_start = journal.as_ref().map(Journal::len).unwrap_or_default();
+		// The budget is charged above the journal (a deletion, not the move aside of the deleted path).
+		let started = Instant::now();
+		let (res, bytes_written) = match journal.as_mut() {
+			Some(journal) => {
+				let journal_store = &mut JournalStore::new(store, journal);
+				let store = &mut BudgetStore::new(journal_store, budget);
+				let res = apply_directive(store, base_dir, directive, policy, options, &mut info);
+				(res, store.bytes_written())
+			}
+			None => {
+				let store = &mut BudgetStore::new(store, budget);
+				let res = apply_directive(store, base_dir, directive, policy, options, &mut info);
+				(res, store.bytes_written())
+			}
+		};
+		info.elapsed = started.elapsed();
+		info.bytes_written = bytes_written;
 
-					ensure_file_dir(&full_path).map_err(Error::simple_fs)?;
+		if options.record_undo
+			&& let Some(journal) = journal.as_ref()
+		{
+			match journal.undo_directives(store, base_dir, undo_start) {
+				Ok(undo_directives) => info.undo_directives = undo_directives,
+				Err(err) => info
+					.warnings
+					.push(format!("Could not record the undo of the directive: {err}")),
+			}
+		}
 
-					if full_path.exists() {
-						let existing_content = read_to_string(&full_path).map_err(Error::simple_fs)?;
-						if existing_content == content.content {
-							return Err(Error::apply_no_changes(file_path));
-						}
-						fs::write(&full_path, &content.content)
-							.map_err(|err| Error::io_write_file(full_path.to_string(), err))?;
-					} else {
-						fs::write(&full_path, &content.content)
-							.map_err(|err| Error::io_create_file(full_path.to_string(), err))?;
+		let rel_path = |path: &SPath| {
+			path.diff(base_dir)
+				.map(|p| p.to_string())
+				.unwrap_or_else(|| path.to_string())
+		};
+		for action in store.base_mut().base_mut().take_actions() {
+			info.warnings.push(match action {
+				WriteTargetAction::ReadOnlyElevated(path) => format!(
+					"Temporarily cleared the read-only flag of '{}' to write it (restored)",
+					rel_path(&path)
+				),
+				WriteTargetAction::SymlinkReplaced { path, target } => {
+					let msg = format!("Replaced the symlink '{}' with a regular file", rel_path(&path));
+					// Note: The link is replaced beneath the journal, so its restore is recorded here.
+					if let (Some(journal), Some(target)) = (journal.as_mut(), target) {
+						journal.record_relink(path, target);
 					}
+					msg
 				}
+			});
+		}
 
-				FileDirective::Patch {
-					file_path,
-					content: patch_content,
-				} => {
-					let full_path = base_dir.join(&file_path);
-					fs_guard::check_for_read(&full_path, &base_dir, policy_ref)?;
-					fs_guard::check_for_write(&full_path, &base_dir, policy_ref)?;
-
-					let original_content = if full_path.exists() {
-						read_to_string(&full_path).map_err(Error::simple_fs)?
-					} else {
-						String::new()
-					};
-
-					let apply_data = apply_patch_incremental(&original_content, &patch_content.content)?;
-					info.match_tier = apply_data.max_tier;
-					info.error_hunks = apply_data.hunk_errors;
+		match res {
+			Ok(_) => {
+				info.success = true;
+				valid_flags.push(true);
+			}
+			Err(Error::ApplyNoChanges { .. }) if options.idempotent && info.error_hunks.is_empty() => {
+				info.success = true;
+				info.unchanged = true;
+				valid_flags.push(true);
+			}
+			Err(err) => {
+				// Note: A patch with only failed hunks changes nothing, but is not a no-op.
+				let is_noop = matches!(err, Error::ApplyNoChanges { .. }) && info.error_hunks.is_empty();
+				info.unchanged = is_noop;
+				valid_flags.push(is_noop);
+				info.error_msg = Some(feedback_msg(&err, options));
 
-					if apply_data.new_content == original_content && full_path.exists() {
-						return Err(Error::apply_no_changes(file_path));
+				if budget.is_exhausted() || (!is_noop && (options.stop_on_error || options.rollback_on_error)) {
+					stopped = true;
+					if options.rollback_on_error
+						&& let Some(journal) = journal.take()
+					{
+						// Note: The originals are restored as they were, so beneath the post-apply validators,
+						//       but through the write targets (e.g., to write a read-only file back).
+						rollback_batch(store.base_mut().base_mut(), journal, &mut items, &mut info, options);
+						store.clear();
 					}
+				}
+			}
+		}
 
-					if !full_path.exists() {
-						ensure_file_dir(&full_path).map_err(Error::simple_fs)?;
-					}
+		if options.omit_content_echoes {
+			info.omit_content_echoes();
+		}
+		if let Some(redactor) = &options.redactor {
+			info.redact(redactor);
+		}
+		retain_applied_diff(&mut info, options);
+		progress.on_directive_done(index, total, &info);
+		items.push(info);
+	}
+
+	if let Some(journal) = journal
+		&& let Err(err) = journal.commit(store)
+		&& let Some(info) = items.last_mut()
+	{
+		info.warnings
+			.push(format!("Could not complete the deletions of the batch: {err}"));
+	}
 
-					fs::write(&full_path, apply_data.new_content)
-						.map_err(|err| Error::io_write_file(full_path.to_string(), err))?;
+	(ApplyChangesStatus { items }, valid_flags)
+}
 
-					// If some hunks failed, return an error so success stays false
-					if !info.error_hunks.is_empty() {
-						let failed = info.error_hunks.len();
-						return Err(Error::custom(format!(
-							"{failed} of {} hunks failed to apply for '{file_path}'",
-							apply_data.total_hunks
-						)));
+/// Returns the options with `backup_dir` set to the timestamped backup tree of the batch, `<backup_dir>/<unix millis>`
+/// (absolute), so `apply_to_store` backs up the files directly under it.
+pub(crate) fn with_batch_backup_dir(options: &ApplyOptions, base_dir: &SPath) -> ApplyOptions {
+	let mut options = options.clone();
+	if let Some(backup_dir) = options.backup_dir.take() {
+		let backup_dir = if backup_dir.is_absolute() {
+			backup_dir
+		} else {
+			base_dir.join(backup_dir)
+		};
+		let stamp = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|d| d.as_millis())
+			.unwrap_or_default();
+		options.backup_dir = Some(backup_dir.join(stamp.to_string()));
+	}
+	options
+}
+
+/// Rolls back the changes of the applied directives `items` and of the `failed` directive.
+pub(crate) fn rollback_batch(
+	store: &mut impl ApplyStore,
+	journal: Journal,
+	items: &mut [DirectiveStatus],
+	failed: &mut DirectiveStatus,
+	options: &ApplyOptions,
+) {
+	match journal.rollback(store) {
+		Ok(()) => {
+			for info in items.iter_mut().filter(|info| info.success) {
+				info.success = false;
+				info.unchanged = false;
+				info.error_msg = Some(feedback_msg(&Error::ApplyBatchRolledBack, options));
+				info.diff_stats = None;
+				info.bytes_written = 0;
+				info.undo_directives.clear();
+				info.applied_diff = None;
+				info.applied_diff_path = None;
+			}
+			failed.diff_stats = None;
+			failed.bytes_written = 0;
+			failed.undo_directives.clear();
+			failed.applied_diff = None;
+			failed.applied_diff_path = None;
+		}
+		Err(err) => failed.warnings.push(format!("Could not roll back the batch: {err}")),
+	}
+}
+
+/// Bounds the applied diff of the directive to the `ApplyOptions::max_applied_diff_bytes`: a larger diff is moved to
+/// the `applied_diff_store`, or else truncated at a line boundary (with a `# udiffx:` annotation line).
+fn retain_applied_diff(info: &mut DirectiveStatus, options: &ApplyOptions) {
+	let Some(max) = options.max_applied_diff_bytes else {
+		return;
+	};
+	let Some(applied_diff) = info.applied_diff.take_if(|applied_diff| applied_diff.len() > max) else {
+		return;
+	};
+
+	if let Some(store) = &options.applied_diff_store {
+		match store.put(&applied_diff) {
+			Ok(path) => {
+				info.applied_diff_path = Some(path);
+				return;
+			}
+			Err(err) => info.warnings.push(format!("Could not store the applied diff: {err}")),
+		}
+	}
+
+	let mut kept = 0;
+	for line in applied_diff.split_inclusive('\n') {
+		if kept + line.len() > max {
+			break;
+		}
+		kept += line.len();
+	}
+	info.applied_diff = Some(format!(
+		"{}# udiffx: applied diff truncated, {kept} of {} bytes kept\n",
+		&applied_diff[..kept],
+		applied_diff.len()
+	));
+}
+
+fn apply_directive(
+	store: &mut impl ApplyStore,
+	base_dir: &SPath,
+	directive: FileDirective,
+	policy: &SecurityPolicy,
+	options: &ApplyOptions,
+	info: &mut DirectiveStatus,
+) -> Result<()> {
+	let policy_ref = Some(policy);
+	let is_virtual = store.is_virtual();
+
+	if let Some(materializer) = &options.materializer {
+		materialize_missing_paths(store, base_dir, &directive, policy, materializer)?;
+	}
+	if options.idempotent && already_applied(store, base_dir, &directive, policy, options) {
+		return Err(Error::apply_no_changes(directive.file_path().unwrap_or_default()));
+	}
+	if let Some(confirmer) = &options.confirmer
+		&& is_destructive(store, base_dir, &directive, options)
+		&& confirmer.confirm(&directive) == ConfirmDecision::Reject
+	{
+		let denial = Denial::NotConfirmed;
+		info.denial = Some(denial.clone());
+		return Err(Error::apply_denied(directive.file_path().unwrap_or_default(), denial));
+	}
+
+	match directive {
+		FileDirective::New { file_path, mut content } => {
+			let full_path = base_dir.join(&file_path);
+			fs_guard::check_for_write(&full_path, base_dir, policy_ref, is_virtual)?;
+
+			if store.exists(&full_path) && !store.is_dir(&full_path) {
+				match options.new_file_collision {
+					NewFileCollision::Overwrite => (),
+					NewFileCollision::Skip => {
+						info.warnings.push(format!(
+							"Skipped the FILE_NEW of '{file_path}', the file already exists"
+						));
+						return Ok(());
+					}
+					NewFileCollision::Error => return Err(Error::apply_file_exists(file_path)),
+					NewFileCollision::RenameExisting => {
+						let kept_path = rename_existing_aside(store, base_dir, &file_path, policy_ref)?;
+						info.warnings
+							.push(format!("Renamed the existing '{file_path}' to '{kept_path}'"));
 					}
 				}
+			}
 
-				FileDirective::Append { file_path, content } => {
-					let full_path = base_dir.join(&file_path);
-					fs_guard::check_for_write(&full_path, &base_dir, policy_ref)?;
+			// A spilled content is streamed as is (no license header, no-op, or diff stats computation).
+			if let Some(blob_path) = &content.blob_path {
+				check_blob_secrets(&file_path, blob_path, options, info)?;
+				backup_path(store, base_dir, &full_path, options)?;
+				return store.write_blob(&full_path, blob_path, false);
+			}
 
-					if content.content.is_empty() {
-						return Err(Error::apply_no_changes(file_path));
-					}
+			check_secrets(&file_path, &content.content, false, options, info)?;
 
-					ensure_file_dir(&full_path).map_err(Error::simple_fs)?;
+			let target_exists = store.exists(&full_path);
+			let existing_content = if target_exists {
+				store.read_to_string(&full_path)?
+			} else {
+				String::new()
+			};
 
-					let new_content = if full_path.exists() {
-						let existing_content = read_to_string(&full_path).map_err(Error::simple_fs)?;
-						format!("{existing_content}{}", content.content)
-					} else {
-						content.content
-					};
+			if options.preserve_license_headers
+				&& let Some(new_content) = with_license_header(&to_lf(&existing_content), &content.content)
+			{
+				info.warnings.push(format!(
+					"Re-prepended the license header of '{file_path}' missing from the new content"
+				));
+				content.content = new_content;
+			}
 
-					fs::write(&full_path, new_content)
-						.map_err(|err| Error::io_write_file(full_path.to_string(), err))?;
-				}
+			// An overwritten file keeps its line endings (and BOM).
+			let new_content = restore_line_endings(&existing_content, &content.content);
+			if target_exists && existing_content == new_content {
+				return Err(Error::apply_no_changes(file_path));
+			}
 
-				FileDirective::Copy { from_path, to_path } => {
-					let full_from = base_dir.join(&from_path);
-					let full_to = base_dir.join(&to_path);
+			backup_path(store, base_dir, &full_path, options)?;
+			store.write(&full_path, new_content.as_bytes())?;
+			info.diff_stats = Some(DiffStats {
+				files: 1,
+				..diff_stats(&existing_content, &new_content)
+			});
+			if options.record_provenance {
+				info.line_origins = Some(line_origins(&existing_content, &new_content));
+			}
+			if options.record_applied_diffs && target_exists {
+				info.applied_diff = Some(make_file_patch(
+					&file_path,
+					&existing_content,
+					&new_content,
+					APPLIED_DIFF_CONTEXT_LINES,
+				));
+			}
+		}
 
-					fs_guard::check_for_read(&full_from, &base_dir, policy_ref)?;
-					fs_guard::check_for_write(&full_to, &base_dir, policy_ref)?;
+		FileDirective::Patch {
+			file_path,
+			content: mut patch_content,
+			allow_generated,
+		} => {
+			let file_path = resolve_missing_path(store, base_dir, file_path, options, info)?;
+			let full_path = base_dir.join(&file_path);
+			fs_guard::check_for_read(&full_path, base_dir, policy_ref, is_virtual)?;
+			fs_guard::check_for_write(&full_path, base_dir, policy_ref, is_virtual)?;
 
-					if full_from.exists() {
-						if full_from.is_dir() {
-							return Err(Error::custom(format!("copy source is not a file: {from_path}")));
-						}
+			if options.unescape_patch_bodies
+				&& let Some(unescaped) = patch_completer::unescape_patch_body(&patch_content.content)
+			{
+				info.warnings.push(format!(
+					"Normalized the escaped newlines of the patch for '{file_path}'"
+				));
+				patch_content.content = unescaped;
+			}
 
-						ensure_file_dir(&full_to).map_err(Error::simple_fs)?;
+			let target_exists = store.exists(&full_path);
+			let original_content = if target_exists {
+				store.read_to_string(&full_path)?
+			} else {
+				String::new()
+			};
 
-						let source_bytes =
-							fs::read(&full_from).map_err(|err| Error::io_read_file(full_from.to_string(), err))?;
-						fs::write(&full_to, source_bytes)
-							.map_err(|err| Error::io_write_file(full_to.to_string(), err))?;
-					} else {
-						return Err(Error::apply_path_not_found("copy source", from_path));
-					}
+			if options.refuse_generated
+				&& !allow_generated
+				&& target_exists
+				&& let Some(reason) = generated_reason(&file_path, &original_content, policy)?
+			{
+				let denial = Denial::GeneratedFile(reason);
+				info.denial = Some(denial.clone());
+				return Err(Error::apply_denied(file_path, denial));
+			}
+
+			check_secrets(&file_path, &patch_content.content, true, options, info)?;
+
+			if let Some(suggested_path) = suggest_wrong_target(
+				store,
+				base_dir,
+				&file_path,
+				&original_content,
+				&patch_content.content,
+				&options.context_files,
+				policy_ref,
+			) {
+				info.suggested_file_path = Some(suggested_path.clone());
+				return Err(Error::apply_wrong_target_file(file_path, suggested_path));
+			}
+
+			let complete_options = match &options.complete_options {
+				Some(complete_options) => complete_options.clone(),
+				None => CompleteOptions::for_path(&file_path),
+			};
+			let apply_data = apply_patch_incremental_with_options(
+				strip_bom(&original_content),
+				&patch_content.content,
+				&complete_options,
+			)?;
+			info.match_tier = apply_data.max_tier;
+			info.total_hunks = apply_data.total_hunks;
+			info.error_hunks = apply_data.hunk_errors;
+			info.noop_edits = apply_data.noop_edits;
+			for noop_edit in info.noop_edits.iter().filter(|e| e.kind == NoopKind::AlreadyInserted) {
+				info.warnings.push(format!(
+					"Hunk {} of '{file_path}' not inserted, its lines are already at line {}",
+					noop_edit.hunk_index + 1,
+					noop_edit.line.unwrap_or(1)
+				));
+			}
+			info.truncated_hunks = apply_data.truncated_hunks;
+			info.hunk_drifts = apply_data.hunk_drifts;
+			info.lenient_lines = apply_data.lenient_lines;
+			info.context_assertions = apply_data.context_assertions;
+
+			if let Some(failed) = info.context_assertions.iter().find(|assertion| !assertion.passed()) {
+				let context = patch_completer::split_context_hunks(&patch_content.content)
+					.get(failed.index)
+					.and_then(|hunk| hunk.lines().skip(1).map(str::trim).find(|line| !line.is_empty()))
+					.unwrap_or_default()
+					.to_string();
+				return Err(Error::apply_context_assertion_failed(
+					file_path,
+					failed.index + 1,
+					context,
+				));
+			}
+			if let Some(line) = modified_protected_region(strip_bom(&original_content), &apply_data.new_content) {
+				return Err(Error::apply_protected_region(file_path, line));
+			}
+			if options.write_rejects && !info.error_hunks.is_empty() {
+				let reject_file_path = format!("{file_path}.rej");
+				let full_reject_path = base_dir.join(&reject_file_path);
+				fs_guard::check_for_write(&full_reject_path, base_dir, policy_ref, is_virtual)?;
+				store.write(
+					&full_reject_path,
+					reject_content(&file_path, &info.error_hunks).as_bytes(),
+				)?;
+				info.reject_file_path = Some(reject_file_path);
+			}
+			// The patched file keeps its line endings (and BOM).
+			let new_content = restore_line_endings(&original_content, &apply_data.new_content);
+			if new_content == original_content && target_exists {
+				return Err(Error::apply_no_changes(file_path));
+			}
+
+			backup_path(store, base_dir, &full_path, options)?;
+			store.write(&full_path, new_content.as_bytes())?;
+			let mut stats = DiffStats::default();
+			for completed_patch in &apply_data.completed_patches {
+				stats += DiffStats::from_unified_diff(completed_patch);
+			}
+			stats.files = stats.files.min(1);
+			info.diff_stats = Some(stats);
+			if options.record_provenance {
+				info.line_origins = Some(line_origins(&original_content, &new_content));
+			}
+			if options.record_applied_diffs {
+				info.applied_diff = Some(make_file_patch(
+					&file_path,
+					&original_content,
+					&new_content,
+					APPLIED_DIFF_CONTEXT_LINES,
+				));
+			}
+
+			// If some hunks failed (and were not rejected to a file), return an error so success stays false
+			if let Some(reject_file_path) = &info.reject_file_path {
+				info.warnings.push(format!(
+					"{} of {} hunks rejected to '{reject_file_path}'",
+					info.error_hunks.len(),
+					apply_data.total_hunks
+				));
+			} else if !info.error_hunks.is_empty() {
+				let failed = info.error_hunks.len();
+				let failed_numbers: Vec<String> = info
+					.error_hunks
+					.iter()
+					.map(|h| format!("#{}", h.hunk_index + 1))
+					.collect();
+				return Err(Error::custom(format!(
+					"{failed} of {} hunks failed to apply for '{file_path}' ({})",
+					apply_data.total_hunks,
+					failed_numbers.join(", ")
+				)));
+			}
+		}
+
+		FileDirective::Append { file_path, content } => {
+			let full_path = base_dir.join(&file_path);
+			fs_guard::check_for_write(&full_path, base_dir, policy_ref, is_virtual)?;
+
+			if let Some(blob_path) = &content.blob_path {
+				check_blob_secrets(&file_path, blob_path, options, info)?;
+				return store.write_blob(&full_path, blob_path, true);
+			}
+
+			if content.content.is_empty() {
+				return Err(Error::apply_no_changes(file_path));
+			}
+
+			check_secrets(&file_path, &content.content, false, options, info)?;
+
+			let stats = DiffStats::from_insertion(&content.content);
+			let existing_content = if store.exists(&full_path) {
+				store.read_to_string(&full_path)?
+			} else {
+				String::new()
+			};
+			// The appended lines get the line endings of the existing file.
+			let new_content = format!("{}{}", to_lf(&existing_content), content.content);
+			let new_content = restore_line_endings(&existing_content, &new_content);
+
+			store.write(&full_path, new_content.as_bytes())?;
+			info.diff_stats = Some(stats);
+			if options.record_provenance {
+				info.line_origins = Some(line_origins(&existing_content, &new_content));
+			}
+		}
+
+		FileDirective::SpanPatch {
+			file_path,
+			line,
+			columns,
+			line_hash,
+			replacement,
+		} => {
+			let file_path = resolve_missing_path(store, base_dir, file_path, options, info)?;
+			let full_path = base_dir.join(&file_path);
+			fs_guard::check_for_read(&full_path, base_dir, policy_ref, is_virtual)?;
+			fs_guard::check_for_write(&full_path, base_dir, policy_ref, is_virtual)?;
+
+			if !store.exists(&full_path) {
+				return Err(Error::apply_path_not_found("span patch", file_path));
+			}
+			let original_content = store.read_to_string(&full_path)?;
+
+			if options.refuse_generated
+				&& let Some(reason) = generated_reason(&file_path, &original_content, policy)?
+			{
+				let denial = Denial::GeneratedFile(reason);
+				info.denial = Some(denial.clone());
+				return Err(Error::apply_denied(file_path, denial));
+			}
+
+			check_secrets(&file_path, &replacement, false, options, info)?;
+
+			let original_lf = to_lf(&original_content);
+			let patched = apply_span_patch(&file_path, &original_lf, line, &columns, &line_hash, &replacement)?;
+			if let Some(line) = modified_protected_region(&original_lf, &patched) {
+				return Err(Error::apply_protected_region(file_path, line));
+			}
+			// The patched file keeps its line endings (and BOM).
+			let new_content = restore_line_endings(&original_content, &patched);
+			if new_content == original_content {
+				return Err(Error::apply_no_changes(file_path));
+			}
+
+			backup_path(store, base_dir, &full_path, options)?;
+			store.write(&full_path, new_content.as_bytes())?;
+			info.diff_stats = Some(diff_stats(&original_content, &new_content));
+			if options.record_provenance {
+				info.line_origins = Some(line_origins(&original_content, &new_content));
+			}
+			if options.record_applied_diffs {
+				info.applied_diff = Some(make_file_patch(
+					&file_path,
+					&original_content,
+					&new_content,
+					APPLIED_DIFF_CONTEXT_LINES,
+				));
+			}
+		}
+
+		FileDirective::Insert {
+			file_path,
+			anchor,
+			regex,
+			position,
+			content,
+		} => {
+			let file_path = resolve_missing_path(store, base_dir, file_path, options, info)?;
+			let full_path = base_dir.join(&file_path);
+			fs_guard::check_for_read(&full_path, base_dir, policy_ref, is_virtual)?;
+			fs_guard::check_for_write(&full_path, base_dir, policy_ref, is_virtual)?;
+
+			if !store.exists(&full_path) {
+				return Err(Error::apply_path_not_found("insert", file_path));
+			}
+			let original_content = store.read_to_string(&full_path)?;
+
+			if options.refuse_generated
+				&& let Some(reason) = generated_reason(&file_path, &original_content, policy)?
+			{
+				let denial = Denial::GeneratedFile(reason);
+				info.denial = Some(denial.clone());
+				return Err(Error::apply_denied(file_path, denial));
+			}
+
+			check_secrets(&file_path, &content, false, options, info)?;
+
+			let complete_options = match &options.complete_options {
+				Some(complete_options) => complete_options.clone(),
+				None => CompleteOptions::for_path(&file_path),
+			};
+			let original_lf = to_lf(&original_content);
+			let inserted = apply_file_insert(
+				&file_path,
+				&original_lf,
+				&anchor,
+				regex,
+				position,
+				&content,
+				&complete_options,
+			)?;
+			info.match_tier = inserted.tier;
+			if let Some(line) = modified_protected_region(&original_lf, &inserted.content) {
+				return Err(Error::apply_protected_region(file_path, line));
+			}
+			// The file keeps its line endings (and BOM).
+			let new_content = restore_line_endings(&original_content, &inserted.content);
+			if new_content == original_content {
+				return Err(Error::apply_no_changes(file_path));
+			}
+			if inserted.tier.is_some_and(|tier| tier != MatchTier::Strict) {
+				info.warnings.push(format!(
+					"Anchor of the insert into '{file_path}' matched line {} leniently",
+					inserted.anchor_line
+				));
+			}
+
+			backup_path(store, base_dir, &full_path, options)?;
+			store.write(&full_path, new_content.as_bytes())?;
+			info.diff_stats = Some(diff_stats(&original_content, &new_content));
+			if options.record_provenance {
+				info.line_origins = Some(line_origins(&original_content, &new_content));
+			}
+			if options.record_applied_diffs {
+				info.applied_diff = Some(make_file_patch(
+					&file_path,
+					&original_content,
+					&new_content,
+					APPLIED_DIFF_CONTEXT_LINES,
+				));
+			}
+		}
+
+		FileDirective::GlobalReplace {
+			glob,
+			pattern,
+			replacement,
+			regex,
+			max_matches,
+		} => {
+			let replacer = Replacer::new(&pattern, regex)?;
+
+			// -- Count the matches of each file first, so nothing is written past the cap
+			let mut matches: Vec<(String, SPath, String, usize)> = Vec::new();
+			for rel_path in glob_rel_paths(store, base_dir, &glob)? {
+				let full_path = base_dir.join(&rel_path);
+				if fs_guard::check_for_read(&full_path, base_dir, policy_ref, is_virtual).is_err()
+					|| fs_guard::check_for_write(&full_path, base_dir, policy_ref, is_virtual).is_err()
+				{
+					continue;
+				}
+				// Note: The binary (non UTF-8) files are skipped.
+				let Ok(content) = store.read_to_string(&full_path) else {
+					continue;
+				};
+				let count = replacer.count(&to_lf(&content));
+				if count == 0 {
+					continue;
 				}
+				if options.refuse_generated
+					&& let Some(reason) = generated_reason(&rel_path, &content, policy)?
+				{
+					info.warnings
+						.push(format!("Skipped the generated file '{rel_path}' ({reason})"));
+					continue;
+				}
+				matches.push((rel_path, full_path, content, count));
+			}
+			info.replace_counts = matches
+				.iter()
+				.map(|(rel_path, _, _, count)| ReplaceCount {
+					file_path: rel_path.clone(),
+					count: *count,
+				})
+				.collect();
 
-				FileDirective::Rename { from_path, to_path } => {
-					let full_from = base_dir.join(&from_path);
-					let full_to = base_dir.join(&to_path);
+			let total: usize = matches.iter().map(|(_, _, _, count)| count).sum();
+			let max = max_matches.unwrap_or(DEFAULT_MAX_MATCHES);
+			if total > max {
+				return Err(Error::apply_too_many_matches(glob, total, max));
+			}
 
-					fs_guard::check_for_read(&full_from, &base_dir, policy_ref)?;
-					fs_guard::check_for_write(&full_to, &base_dir, policy_ref)?;
+			check_secrets(&glob, &replacement, false, options, info)?;
 
-					if full_from.exists() {
-						ensure_file_dir(&full_to).map_err(Error::simple_fs)?;
-						fs::rename(&full_from, &full_to)
-							.map_err(|err| Error::io_rename_path(full_from.to_string(), full_to.to_string(), err))?;
-					} else {
-						return Err(Error::apply_path_not_found("rename source", from_path));
-					}
+			// -- Replace in memory first, so a refused file leaves all the files untouched
+			let mut writes: Vec<(String, SPath, String, String)> = Vec::new();
+			for (rel_path, full_path, content, _) in matches {
+				let content_lf = to_lf(&content);
+				let replaced = replacer.replace(&content_lf, &replacement);
+				if let Some(line) = modified_protected_region(strip_bom(&content_lf), strip_bom(&replaced)) {
+					return Err(Error::apply_protected_region(rel_path, line));
+				}
+				// The replaced file keeps its line endings (and BOM).
+				let new_content = restore_line_endings(&content, &replaced).into_owned();
+				if new_content != content {
+					writes.push((rel_path, full_path, content, new_content));
 				}
+			}
+			if writes.is_empty() {
+				return Err(Error::apply_no_changes(glob));
+			}
+
+			let mut stats = DiffStats::default();
+			let mut applied_diffs: Vec<String> = Vec::new();
+			for (rel_path, full_path, content, new_content) in writes {
+				backup_path(store, base_dir, &full_path, options)?;
+				store.write(&full_path, new_content.as_bytes())?;
+				stats += diff_stats(&content, &new_content);
+				if options.record_applied_diffs {
+					applied_diffs.push(make_file_patch(
+						&rel_path,
+						&content,
+						&new_content,
+						APPLIED_DIFF_CONTEXT_LINES,
+					));
+				}
+			}
+			info.diff_stats = Some(stats);
+			if options.record_applied_diffs {
+				info.applied_diff = Some(applied_diffs.concat());
+			}
+		}
+
+		FileDirective::Copy { from_path, to_path } => {
+			let from_path = resolve_missing_path(store, base_dir, from_path, options, info)?;
+			let full_from = base_dir.join(&from_path);
+			let full_to = base_dir.join(&to_path);
+
+			fs_guard::check_for_read(&full_from, base_dir, policy_ref, is_virtual)?;
+			fs_guard::check_for_write(&full_to, base_dir, policy_ref, is_virtual)?;
+
+			if store.exists(&full_from) {
+				if store.is_dir(&full_from) {
+					return Err(Error::custom(format!("copy source is not a file: {from_path}")));
+				}
+
+				let source_bytes = store.read_bytes(&full_from)?;
+				backup_path(store, base_dir, &full_to, options)?;
+				store.write(&full_to, &source_bytes)?;
+				let content = String::from_utf8_lossy(&source_bytes);
+				info.diff_stats = Some(DiffStats::from_insertion(&content));
+				// The copied lines are added to the destination.
+				if options.record_provenance {
+					info.line_origins = Some(vec![LineOrigin::Added; content.lines().count()]);
+				}
+			} else {
+				return Err(Error::apply_path_not_found("copy source", from_path));
+			}
+		}
 
-				FileDirective::Delete { file_path } => {
-					let full_path = base_dir.join(&file_path);
+		FileDirective::Rename { from_path, to_path } => {
+			let from_path = resolve_missing_path(store, base_dir, from_path, options, info)?;
+			let full_from = base_dir.join(&from_path);
+			let full_to = base_dir.join(&to_path);
 
-					if full_path.exists() {
-						if full_path.is_dir() {
-							safer_trash_dir(&full_path, ())
-								.map_err(|err| Error::io_delete_dir_all(full_path.to_string(), err))?;
-						} else {
-							safer_trash_file(&full_path, ())
-								.map_err(|err| Error::io_delete_file(full_path.to_string(), err))?;
+			// Note: The source is removed, so it must be writable as well.
+			fs_guard::check_for_read(&full_from, base_dir, policy_ref, is_virtual)?;
+			fs_guard::check_for_write(&full_from, base_dir, policy_ref, is_virtual)?;
+			fs_guard::check_for_write(&full_to, base_dir, policy_ref, is_virtual)?;
+
+			if store.exists(&full_from) {
+				// Note: A case-only rename (e.g., `readme.md` to `README.md`) on a case-insensitive file system is
+				//       no collision, as both paths are the same file (on a case-sensitive one, they are two files).
+				let is_case_only = from_path.eq_ignore_ascii_case(&to_path) && store.is_same_file(&full_from, &full_to);
+				if store.exists(&full_to) && !is_case_only {
+					match options.rename_collision {
+						RenameCollision::Error => return Err(Error::apply_rename_target_exists(from_path, to_path)),
+						RenameCollision::Overwrite => {
+							backup_path(store, base_dir, &full_to, options)?;
+							info.warnings
+								.push(format!("Overwrote the existing '{to_path}' with '{from_path}'"));
+						}
+						RenameCollision::Skip => {
+							info.warnings.push(format!(
+								"Skipped the FILE_RENAME of '{from_path}', '{to_path}' already exists"
+							));
+							return Ok(());
 						}
-					} else {
-						return Err(Error::apply_path_not_found("delete", file_path));
 					}
 				}
+				store.rename(&full_from, &full_to)?;
+				info.diff_stats = Some(DiffStats {
+					files: 1,
+					..Default::default()
+				});
+			} else {
+				return Err(Error::apply_path_not_found("rename source", from_path));
+			}
+		}
 
-				FileDirective::Fail { error_msg, .. } => {
-					return Err(error_msg.into());
+		FileDirective::Delete { file_path } => {
+			let file_path = resolve_missing_path(store, base_dir, file_path, options, info)?;
+			let full_path = base_dir.join(&file_path);
+			fs_guard::check_for_write(&full_path, base_dir, policy_ref, is_virtual)?;
+
+			if store.exists(&full_path) {
+				if options.delete_strategy == DeleteStrategy::Skip {
+					info.warnings
+						.push(format!("Skipped the deletion of '{file_path}' (DeleteStrategy::Skip)"));
+					return Ok(());
 				}
+				let stats = if store.is_dir(&full_path) {
+					DiffStats {
+						files: 1,
+						..Default::default()
+					}
+				} else {
+					DiffStats::from_deletion(&store.read_to_string(&full_path).unwrap_or_default())
+				};
+				backup_path(store, base_dir, &full_path, options)?;
+				store.delete(&full_path)?;
+				info.diff_stats = Some(stats);
+			} else {
+				return Err(Error::apply_path_not_found("delete", file_path));
 			}
-			Ok(())
-		})();
-
-		match res {
-			Ok(_) => info.success = true,
-			Err(err) => info.error_msg = Some(err.to_string()),
 		}
 
-		items.push(info);
+		FileDirective::Fail { error_msg, .. } => {
+			return Err(error_msg.into());
+		}
 	}
 
-	Ok(ApplyChangesStatus { items })
+	Ok(())
 }
 
 /// Applies a patch incrementally, hunk by hunk, allowing partial success.
@@ -216,6 +1057,15 @@ pub fn apply_file_changes(
 /// - If all hunks fail, returns the unchanged content with all failed hunk details.
 /// - `hunk_errors` contains details for each hunk that failed.
 pub fn apply_patch_incremental(original: &str, patch_raw: &str) -> Result<ApplyPatchIncrementalData> {
+	apply_patch_incremental_with_options(original, patch_raw, &CompleteOptions::default())
+}
+
+/// Same as `apply_patch_incremental`, with the lenient matching of the completion controlled by `options`.
+pub fn apply_patch_incremental_with_options(
+	original: &str,
+	patch_raw: &str,
+	options: &CompleteOptions,
+) -> Result<ApplyPatchIncrementalData> {
 	let original_had_crlf = original.contains("\r\n");
 
 	let original_lf = if original_had_crlf {
@@ -228,6 +1078,8 @@ pub fn apply_patch_incremental(original: &str, patch_raw: &str) -> Result<ApplyP
 	} else {
 		patch_raw.to_string()
 	};
+	// An annotated completed patch (see `CompleteOptions::annotate_hunks`) can be applied again.
+	let patch_lf = patch_completer::strip_hunk_annotations(&patch_lf).unwrap_or(patch_lf);
 
 	// Ensure original has a trailing newline (POSIX compliance)
 	let mut working_content = original_lf;
@@ -237,6 +1089,25 @@ pub fn apply_patch_incremental(original: &str, patch_raw: &str) -> Result<ApplyP
 
 	let raw_hunks = patch_completer::split_raw_hunks(&patch_lf);
 
+	// The context-only hunks are checked against the original content (the one the model saw).
+	let context_assertions: Vec<ContextAssertion> = if options.assert_context_hunks {
+		let orig_index = LineIndex::new(&working_content);
+		patch_completer::split_context_hunks(&patch_lf)
+			.iter()
+			.enumerate()
+			.map(|(index, raw_hunk)| {
+				let matched = patch_completer::match_context_hunk(&orig_index, raw_hunk, options).ok();
+				ContextAssertion {
+					index,
+					line: matched.map(|(line, _)| line),
+					tier: matched.and_then(|(_, tier)| tier),
+				}
+			})
+			.collect()
+	} else {
+		Vec::new()
+	};
+
 	// Zero hunks: nothing to apply, return original unchanged.
 	if raw_hunks.is_empty() {
 		return Ok(ApplyPatchIncrementalData {
@@ -244,44 +1115,138 @@ pub fn apply_patch_incremental(original: &str, patch_raw: &str) -> Result<ApplyP
 			max_tier: None,
 			hunk_errors: Vec::new(),
 			total_hunks: 0,
+			completed_patches: Vec::new(),
+			noop_edits: Vec::new(),
+			truncated_hunks: Vec::new(),
+			hunk_drifts: Vec::new(),
+			lenient_lines: Vec::new(),
+			context_assertions,
 		});
 	}
 
 	let mut max_tier: Option<MatchTier> = None;
 	let mut hunk_errors: Vec<HunkError> = Vec::new();
+	let mut completed_patches: Vec<String> = Vec::new();
+	let mut noop_edits: Vec<NoopEdit> = Vec::new();
+	let mut truncated_hunks: Vec<usize> = Vec::new();
+	let mut hunk_drifts: Vec<HunkDrift> = Vec::new();
+	let mut lenient_lines: Vec<LenientLine> = Vec::new();
 	let total_hunk_count = raw_hunks.len();
+	let expected_lines = hunk_expected_lines(&patch_lf, total_hunk_count);
+	// The line count change of the applied hunks, to map the working content lines to the original ones.
+	let mut line_delta: isize = 0;
 
-	for raw_hunk in &raw_hunks {
-		let result: std::result::Result<(String, Option<MatchTier>), String> = (|| {
-			let (completed_patch, tier) =
-				patch_completer::complete(&working_content, raw_hunk).map_err(|e| e.to_string())?;
+	// Note: The hunks are completed one by one, so they are annotated here (with their index in the whole patch).
+	let hunk_options = CompleteOptions {
+		annotate_hunks: false,
+		..options.clone()
+	};
 
-			if completed_patch.is_empty() {
+	for (hunk_index, raw_hunk) in raw_hunks.iter().enumerate() {
+		let result: std::result::Result<(String, CompletedPatch), String> = (|| {
+			let completed =
+				patch_completer::complete_with_report(&LineIndex::new(&working_content), raw_hunk, &hunk_options)
+					.map_err(|e| e.to_string())?;
+
+			if completed.patch.is_empty() {
 				return Err("Hunk produced empty completed patch".to_string());
 			}
 
-			let patch_obj = Patch::from_str(&completed_patch).map_err(|e| format!("diffy parse error: {e}"))?;
+			let patch_obj = Patch::from_str(&completed.patch).map_err(|e| format!("diffy parse error: {e}"))?;
 
 			let new_content =
 				diffy_apply(&working_content, &patch_obj).map_err(|e| format!("diffy apply error: {e}"))?;
 
-			Ok((new_content, tier))
+			Ok((new_content, completed))
 		})();
 
 		match result {
-			Ok((new_content, tier)) => {
-				if new_content != working_content {
+			Ok((new_content, completed)) => {
+				if new_content != working_content
+					&& let Some(noop_edit) =
+						already_inserted_noop_edit(hunk_index, raw_hunk, &working_content, &completed.patch)
+				{
+					noop_edits.push(noop_edit);
+				} else if new_content != working_content {
+					let line = completed
+						.patch
+						.lines()
+						.find_map(parse_hunk_starts)
+						.map_or(1, |(old_start, _)| old_start);
+
+					if let Some(expected_line) = expected_lines.get(hunk_index).copied().flatten() {
+						let drift = HunkDrift {
+							hunk_index,
+							expected_line,
+							matched_line: (line as isize - line_delta).max(1) as usize,
+						};
+						if let Some(max_drift) = options.max_drift
+							&& drift.drift() > max_drift
+						{
+							hunk_errors.push(HunkError {
+								hunk_index,
+								hunk_body: raw_hunk.clone(),
+								cause: format!(
+									"Hunk matched at line {}, {} lines away from its expected line {expected_line} (max drift {max_drift})",
+									drift.matched_line,
+									drift.drift()
+								),
+								nearest_miss: None,
+							});
+							continue;
+						}
+						hunk_drifts.push(drift);
+					}
+
+					lenient_lines.extend(completed.lenient_lines.iter().map(|lenient| LenientLine {
+						line: (lenient.line as isize - line_delta).max(1) as usize,
+						..*lenient
+					}));
+					line_delta += new_content.lines().count() as isize - working_content.lines().count() as isize;
 					working_content = new_content;
-					if let Some(t) = tier {
+					if options.annotate_hunks {
+						let annotation =
+							patch_completer::hunk_annotation(hunk_index + 1, line, completed.tier, completed.score);
+						completed_patches.push(format!("{annotation}\n{}", completed.patch));
+					} else {
+						completed_patches.push(completed.patch);
+					}
+					if let Some(t) = completed.tier {
 						max_tier = Some(max_tier.map(|m| m.max(t)).unwrap_or(t));
 					}
+					if completed.truncation_matched {
+						truncated_hunks.push(hunk_index);
+					}
+				} else {
+					noop_edits.push(matched_noop_edit(hunk_index, raw_hunk, &completed.patch));
 				}
 			}
 			Err(cause) => {
-				hunk_errors.push(HunkError {
-					hunk_body: raw_hunk.clone(),
-					cause,
-				});
+				if let Some(line) = patch_completer::find_hunk_result(&working_content, raw_hunk) {
+					let proposed = patch_completer::hunk_sides(raw_hunk).1.join("\n");
+					let current = working_content
+						.lines()
+						.skip(line - 1)
+						.take(proposed.lines().count())
+						.collect::<Vec<_>>()
+						.join("\n");
+					noop_edits.push(NoopEdit {
+						hunk_index,
+						line: Some(line),
+						kind: NoopKind::AlreadyPresentElsewhere,
+						current,
+						proposed,
+					});
+				} else {
+					let nearest_miss = patch_completer::nearest_miss(&working_content, raw_hunk)
+						.map(|(line, similarity)| NearestMiss { line, similarity });
+					hunk_errors.push(HunkError {
+						hunk_index,
+						hunk_body: raw_hunk.clone(),
+						cause,
+						nearest_miss,
+					});
+				}
 			}
 		}
 	}
@@ -295,9 +1260,505 @@ pub fn apply_patch_incremental(original: &str, patch_raw: &str) -> Result<ApplyP
 		max_tier,
 		hunk_errors,
 		total_hunks: total_hunk_count,
+		completed_patches,
+		noop_edits,
+		truncated_hunks,
+		hunk_drifts,
+		lenient_lines,
+		context_assertions,
+	})
+}
+
+// region:    --- Support
+
+/// The old start line of the numbered header of each hunk of `patch` (`None` for a simplified `@@` header),
+/// aligned with the `split_raw_hunks` hunks (empty when they cannot be aligned).
+fn hunk_expected_lines(patch: &str, hunk_count: usize) -> Vec<Option<usize>> {
+	let mut segments: Vec<(Option<usize>, String)> = Vec::new();
+	for line in patch.lines() {
+		if line.trim().starts_with("@@") {
+			let expected_line = parse_hunk_starts(line.trim()).map(|(old_start, _)| old_start);
+			segments.push((expected_line, String::new()));
+		}
+		if let Some((_, segment)) = segments.last_mut() {
+			segment.push_str(line);
+			segment.push('\n');
+		}
+	}
+
+	// Note: Only the actionable hunks are kept by `split_raw_hunks`.
+	let expected_lines: Vec<Option<usize>> = segments
+		.into_iter()
+		.filter(|(_, segment)| !patch_completer::split_raw_hunks(segment).is_empty())
+		.map(|(expected_line, _)| expected_line)
+		.collect();
+
+	if expected_lines.len() == hunk_count {
+		expected_lines
+	} else {
+		Vec::new()
+	}
+}
+
+/// Whether the end state of the directive is already in the store (see `ApplyOptions::idempotent`).
+///
+/// Note: A path failing its guard check is never applied, so the directive fails as usual. The `FILE_PATCH` and
+///       `FILE_GLOBAL_REPLACE` report their no-op themselves.
+fn already_applied(
+	store: &impl ApplyStore,
+	base_dir: &SPath,
+	directive: &FileDirective,
+	policy: &SecurityPolicy,
+	options: &ApplyOptions,
+) -> bool {
+	let guarded = |rel_path: &str| {
+		let full_path = base_dir.join(rel_path);
+		let allowed = fs_guard::check_for_read(&full_path, base_dir, Some(policy), store.is_virtual()).is_ok()
+			&& fs_guard::check_for_write(&full_path, base_dir, Some(policy), store.is_virtual()).is_ok();
+		allowed.then_some(full_path)
+	};
+	let is_file = |full_path: &SPath| store.exists(full_path) && !store.is_dir(full_path);
+	let read_lf = |rel_path: &str| {
+		guarded(rel_path)
+			.filter(is_file)
+			.and_then(|full_path| store.read_to_string(&full_path).ok())
+			.map(|content| to_lf(&content))
+	};
+
+	match directive {
+		FileDirective::New { file_path, content } => {
+			content.blob_path.is_none()
+				&& read_lf(file_path).is_some_and(|existing| existing == to_lf(&content.content))
+		}
+		FileDirective::Append { file_path, content } => {
+			content.blob_path.is_none()
+				&& !content.content.is_empty()
+				&& read_lf(file_path).is_some_and(|existing| existing.ends_with(&to_lf(&content.content)))
+		}
+		FileDirective::SpanPatch {
+			file_path,
+			line,
+			columns,
+			line_hash,
+			replacement,
+		} => read_lf(file_path)
+			.is_some_and(|existing| span_patch_applied(&existing, *line, columns, line_hash, replacement)),
+		FileDirective::Insert {
+			file_path,
+			anchor,
+			regex,
+			position,
+			content,
+		} => {
+			let complete_options = match &options.complete_options {
+				Some(complete_options) => complete_options.clone(),
+				None => CompleteOptions::for_path(file_path),
+			};
+			read_lf(file_path).is_some_and(|existing| {
+				file_insert_applied(&existing, anchor, *regex, *position, content, &complete_options)
+			})
+		}
+		FileDirective::Copy { from_path, to_path } => match (guarded(from_path), guarded(to_path)) {
+			(Some(full_from), Some(full_to)) if is_file(&full_from) && is_file(&full_to) => {
+				matches!((store.read_bytes(&full_from), store.read_bytes(&full_to)), (Ok(from), Ok(to)) if from == to)
+			}
+			_ => false,
+		},
+		FileDirective::Rename { from_path, to_path } => {
+			guarded(from_path).is_some_and(|full_from| !store.exists(&full_from))
+				&& guarded(to_path).is_some_and(|full_to| store.exists(&full_to))
+		}
+		FileDirective::Delete { file_path } => guarded(file_path).is_some_and(|full_path| !store.exists(&full_path)),
+		FileDirective::Patch { .. } | FileDirective::GlobalReplace { .. } | FileDirective::Fail { .. } => false,
+	}
+}
+
+/// Builds the `NoopEdit` of a hunk that matched but left the content unchanged.
+fn matched_noop_edit(hunk_index: usize, raw_hunk: &str, completed_patch: &str) -> NoopEdit {
+	let (old_side, new_side) = patch_completer::hunk_sides(raw_hunk);
+	let (current, _) = patch_completer::hunk_sides(completed_patch);
+	let line = completed_patch
+		.lines()
+		.find_map(parse_hunk_starts)
+		.map(|(old_start, _)| old_start);
+
+	// The added lines equal the content lines they replace, so a hunk that is not identical either
+	// differs by whitespace only, or (fuzzy matched) expected lines that were already changed to its result.
+	let without_ws = |lines: &[&str]| -> String { lines.concat().split_whitespace().collect() };
+	let kind = if old_side == new_side {
+		NoopKind::Identical
+	} else if without_ws(&old_side) == without_ws(&new_side) {
+		NoopKind::WhitespaceOnly
+	} else {
+		NoopKind::AlreadyPresentElsewhere
+	};
+
+	NoopEdit {
+		hunk_index,
+		line,
+		kind,
+		current: current.join("\n"),
+		proposed: new_side.join("\n"),
+	}
+}
+
+/// Builds the `NoopEdit` of an insert-only hunk (no removed lines) whose added blocks are each already
+/// right after (or right before) their insertion point, so applying it would duplicate them.
+///
+/// Only the side of a block without hunk context is compared: the context lines next to a block are the ones
+/// the hunk intends to keep there (e.g., ` tick();` then `+tick();` is an intended duplicate).
+///
+/// Note: A block of blank or punctuation only lines (e.g., a closing `}`) is too common to be a duplicate,
+/// so such a hunk is still inserted.
+fn already_inserted_noop_edit(
+	hunk_index: usize,
+	raw_hunk: &str,
+	content: &str,
+	completed_patch: &str,
+) -> Option<NoopEdit> {
+	let mut patch_lines = completed_patch.lines().skip_while(|line| !line.starts_with("@@"));
+	let (old_start, _) = parse_hunk_starts(patch_lines.next()?)?;
+	let body: Vec<&str> = patch_lines.filter(|line| !line.starts_with('\\')).collect();
+	let has_context = body.iter().any(|line| !line.starts_with('+'));
+	if !has_context || body.iter().any(|line| line.starts_with('-')) {
+		return None;
+	}
+
+	// -- The added blocks, with their 0-based insertion index in the content, and whether hunk context is
+	//    right before / right after them
+	let mut blocks: Vec<AddedBlock> = Vec::new();
+	let mut index = old_start - 1;
+	let mut in_block = false;
+	for (i, line) in body.iter().enumerate() {
+		match line.strip_prefix('+') {
+			Some(added) => {
+				if !in_block {
+					blocks.push(AddedBlock {
+						at: index,
+						lines: Vec::new(),
+						context_before: i > 0,
+						context_after: false,
+					});
+				}
+				if let Some(block) = blocks.last_mut() {
+					block.lines.push(added);
+				}
+				in_block = true;
+			}
+			None => {
+				if in_block && let Some(block) = blocks.last_mut() {
+					block.context_after = true;
+				}
+				index += 1;
+				in_block = false;
+			}
+		}
+	}
+	if blocks.is_empty() {
+		return None;
+	}
+
+	// -- Each block must already be next to its insertion index
+	let content_lines: Vec<&str> = content.lines().collect();
+	let same = |existing: &[&str], block: &[&str]| {
+		existing.len() == block.len() && existing.iter().zip(block).all(|(e, b)| e.trim_end() == b.trim_end())
+	};
+	let mut line = None;
+	let mut current: Vec<&str> = Vec::new();
+	for AddedBlock {
+		at,
+		lines: block,
+		context_before,
+		context_after,
+	} in &blocks
+	{
+		if !block.iter().any(|l| l.chars().any(char::is_alphanumeric)) {
+			return None;
+		}
+		let after = (!context_after)
+			.then(|| content_lines.get(*at..at + block.len()))
+			.flatten();
+		let before = (!context_before)
+			.then(|| {
+				at.checked_sub(block.len())
+					.and_then(|start| content_lines.get(start..*at))
+			})
+			.flatten();
+		let (start, existing) = match (after, before) {
+			(Some(after), _) if same(after, block) => (*at, after),
+			(_, Some(before)) if same(before, block) => (at - block.len(), before),
+			_ => return None,
+		};
+		line.get_or_insert(start + 1);
+		current.extend(existing);
+	}
+
+	Some(NoopEdit {
+		hunk_index,
+		line,
+		kind: NoopKind::AlreadyInserted,
+		current: current.join("\n"),
+		proposed: patch_completer::hunk_sides(raw_hunk).1.join("\n"),
 	})
 }
 
+/// An added block of an insert-only hunk (see `already_inserted_noop_edit`).
+struct AddedBlock<'a> {
+	/// The 0-based insertion index in the content.
+	at: usize,
+	lines: Vec<&'a str>,
+	/// Whether a hunk context line is right before the block.
+	context_before: bool,
+	/// Whether a hunk context line is right after the block.
+	context_after: bool,
+}
+
+/// Returns the context file the patch most likely intended to target, if the patch context
+/// barely matches `original_content` but strongly matches one of the `context_files`.
+fn suggest_wrong_target(
+	store: &impl ApplyStore,
+	base_dir: &SPath,
+	file_path: &str,
+	original_content: &str,
+	patch_raw: &str,
+	context_files: &[String],
+	policy: Option<&SecurityPolicy>,
+) -> Option<String> {
+	if context_files.is_empty() {
+		return None;
+	}
+
+	let target_similarity = patch_completer::context_similarity(original_content, patch_raw)?;
+	if target_similarity > WRONG_TARGET_MAX_SIMILARITY {
+		return None;
+	}
+
+	let mut best: Option<(f64, &str)> = None;
+	for context_file in context_files {
+		if context_file == file_path {
+			continue;
+		}
+		let full_path = base_dir.join(context_file);
+		if fs_guard::check_for_read(&full_path, base_dir, policy, store.is_virtual()).is_err()
+			|| store.is_dir(&full_path)
+		{
+			continue;
+		}
+		let Ok(content) = store.read_to_string(&full_path) else {
+			continue;
+		};
+		let Some(similarity) = patch_completer::context_similarity(&content, patch_raw) else {
+			continue;
+		};
+		if similarity >= WRONG_TARGET_MIN_SUGGESTION_SIMILARITY && best.is_none_or(|(s, _)| similarity > s) {
+			best = Some((similarity, context_file));
+		}
+	}
+
+	best.map(|(_, path)| path.to_string())
+}
+
+/// Scans the content to write for likely secrets when `ApplyOptions::secret_scan` is set, recording the findings,
+/// then refuses the directive (`Deny`) or records a warning per finding (`Warn`).
+fn check_secrets(
+	file_path: &str,
+	content: &str,
+	added_lines_only: bool,
+	options: &ApplyOptions,
+	info: &mut DirectiveStatus,
+) -> Result<()> {
+	let Some(mode) = options.secret_scan else {
+		return Ok(());
+	};
+	info.secret_findings = scan_secrets(content, added_lines_only);
+	report_secret_findings(file_path, mode, info)
+}
+
+/// Same as `check_secrets`, for a spilled content, scanned from its blob file.
+fn check_blob_secrets(
+	file_path: &str,
+	blob_path: &SPath,
+	options: &ApplyOptions,
+	info: &mut DirectiveStatus,
+) -> Result<()> {
+	let Some(mode) = options.secret_scan else {
+		return Ok(());
+	};
+	info.secret_findings = scan_file_secrets(blob_path)?;
+	report_secret_findings(file_path, mode, info)
+}
+
+/// Denies the directive, or adds the warnings, for the `info.secret_findings` (if any).
+fn report_secret_findings(file_path: &str, mode: SecretScanMode, info: &mut DirectiveStatus) -> Result<()> {
+	let Some(finding) = info.secret_findings.first().cloned() else {
+		return Ok(());
+	};
+
+	match mode {
+		SecretScanMode::Deny => {
+			let denial = Denial::LikelySecret(finding);
+			info.denial = Some(denial.clone());
+			Err(Error::apply_denied(file_path, denial))
+		}
+		SecretScanMode::Warn => {
+			for finding in &info.secret_findings {
+				info.warnings
+					.push(format!("Likely secret in the content for '{file_path}': {finding}"));
+			}
+			Ok(())
+		}
+	}
+}
+
+/// Renames the existing `file_path` to the first free of `<file_path>.orig`, `<file_path>.orig.1`, ...
+/// (see `orig_paths`), and returns that path.
+fn rename_existing_aside(
+	store: &mut impl ApplyStore,
+	base_dir: &SPath,
+	file_path: &str,
+	policy: Option<&SecurityPolicy>,
+) -> Result<String> {
+	let kept_path = orig_paths(file_path)
+		.find(|path| !store.exists(&base_dir.join(path)))
+		.ok_or_else(|| Error::custom(format!("No free .orig path to rename '{file_path}' aside")))?;
+	let full_kept_path = base_dir.join(&kept_path);
+	fs_guard::check_for_write(&full_kept_path, base_dir, policy, store.is_virtual())?;
+
+	store.rename(&base_dir.join(file_path), &full_kept_path)?;
+	Ok(kept_path)
+}
+
+/// The paths an existing file can be renamed aside to: `<file_path>.orig`, then `.orig.1` to `.orig.9`.
+pub(crate) fn orig_paths(file_path: &str) -> impl Iterator<Item = String> + '_ {
+	std::iter::once(format!("{file_path}.orig"))
+		.chain((1..=RENAME_EXISTING_MAX_NUMBER).map(move |n| format!("{file_path}.orig.{n}")))
+}
+
+/// The content without its BOM, and with LF line endings.
+fn to_lf(content: &str) -> String {
+	strip_bom(content).replace("\r\n", "\n")
+}
+
+/// Copies the existing file (or all the files of the directory) at `full_path` to the batch backup tree
+/// (see `with_batch_backup_dir`), keeping its path relative to `base_dir`.
+///
+/// A file already backed up by the batch is not copied again, so the backup holds its content before the batch.
+fn backup_path(store: &mut impl ApplyStore, base_dir: &SPath, full_path: &SPath, options: &ApplyOptions) -> Result<()> {
+	let Some(backup_dir) = &options.backup_dir else {
+		return Ok(());
+	};
+	if !store.exists(full_path) {
+		return Ok(());
+	}
+
+	let rel_path = full_path
+		.as_str()
+		.strip_prefix(base_dir.as_str())
+		.unwrap_or(full_path.as_str())
+		.trim_start_matches('/');
+	let files: Vec<(SPath, String)> = if store.is_dir(full_path) {
+		store
+			.list_rel_paths(full_path)?
+			.into_iter()
+			.map(|file| (full_path.join(&file), format!("{rel_path}/{file}")))
+			.collect()
+	} else {
+		vec![(full_path.clone(), rel_path.to_string())]
+	};
+
+	for (path, rel_path) in files {
+		let backup_path = backup_dir.join(rel_path);
+		if !store.exists(&backup_path) {
+			let content = store.read_bytes(&path)?;
+			store.write_backup(&backup_path, &content)?;
+		}
+	}
+	Ok(())
+}
+
+/// Materializes the missing paths the directive reads (see `ApplyOptions::materializer`) passing the read checks.
+fn materialize_missing_paths(
+	store: &impl ApplyStore,
+	base_dir: &SPath,
+	directive: &FileDirective,
+	policy: &SecurityPolicy,
+	materializer: &Materializer,
+) -> Result<()> {
+	for rel_path in read_paths(directive) {
+		let full_path = base_dir.join(rel_path);
+		if !store.exists(&full_path)
+			&& fs_guard::check_for_read(&full_path, base_dir, Some(policy), store.is_virtual()).is_ok()
+		{
+			materializer.materialize(base_dir, rel_path)?;
+		}
+	}
+	Ok(())
+}
+
+/// Retargets a missing `rel_path` to a unique close match when `ApplyOptions::fuzzy_path_resolution` is set,
+/// recording a warning on the directive status. Returns `rel_path` unchanged otherwise.
+fn resolve_missing_path(
+	store: &impl ApplyStore,
+	base_dir: &SPath,
+	rel_path: String,
+	options: &ApplyOptions,
+	info: &mut DirectiveStatus,
+) -> Result<String> {
+	if !options.fuzzy_path_resolution || store.exists(&base_dir.join(&rel_path)) {
+		return Ok(rel_path);
+	}
+
+	match resolve_fuzzy_path(&store.list_rel_paths(base_dir)?, &rel_path)? {
+		Some(resolved) => {
+			info.warnings
+				.push(format!("'{rel_path}' not found, resolved to '{resolved}'"));
+			Ok(resolved)
+		}
+		None => Ok(rel_path),
+	}
+}
+
+/// The content of the `.rej` file of the failed hunks of `file_path` (a unified diff of these hunks).
+fn reject_content(file_path: &str, error_hunks: &[HunkError]) -> String {
+	let mut content = format!("--- a/{file_path}\n+++ b/{file_path}\n");
+	for hunk_error in error_hunks {
+		content.push_str(&hunk_error.hunk_body);
+		if !content.ends_with('\n') {
+			content.push('\n');
+		}
+	}
+	content
+}
+
+/// Turns the simulated statuses into the final statuses of a rejected batch (nothing written).
+fn into_batch_validation_failure(
+	validation: ApplyChangesStatus,
+	valid_flags: &[bool],
+	options: &ApplyOptions,
+) -> ApplyChangesStatus {
+	let items = validation
+		.items
+		.into_iter()
+		.zip(valid_flags)
+		.map(|(mut info, valid)| {
+			if *valid {
+				info.error_msg = Some(feedback_msg(&Error::ApplyBatchNotApplied, options));
+			}
+			info.success = false;
+			info.unchanged = false;
+			info.diff_stats = None;
+			info.bytes_written = 0;
+			info.applied_diff = None;
+			info.applied_diff_path = None;
+			info
+		})
+		.collect();
+
+	ApplyChangesStatus { items }
+}
+
+// endregion: --- Support
+
 // region:    --- Tests
 
 #[cfg(test)]
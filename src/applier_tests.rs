@@ -2,7 +2,8 @@
 
 type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
 
-use crate::applier::apply_patch_incremental;
+use crate::CompleteOptions;
+use crate::applier::{apply_patch_incremental, apply_patch_incremental_with_options};
 
 #[test]
 fn test_applier_apply_patch_incremental_noop_hunks_do_not_fail() -> Result<()> {
@@ -31,6 +32,90 @@ fn test_applier_apply_patch_incremental_noop_hunks_do_not_fail() -> Result<()> {
 
 	// -- Check
 	assert_eq!(data.new_content, original);
+	assert_eq!(data.noop_edits.len(), 1);
+	assert_eq!(data.noop_edits[0].kind, crate::NoopKind::Identical);
+	assert_eq!(data.noop_edits[0].line, Some(2));
+
+	Ok(())
+}
+
+#[test]
+fn test_applier_apply_patch_incremental_annotate_hunks() -> Result<()> {
+	// -- Setup & Fixtures
+	let original = "a\nb\nc\nd\n";
+	let patch_raw = "@@\n a\n-b\n+B\n@@\n c\n-d\n+D\n";
+	let options = CompleteOptions::default().with_annotate_hunks(true);
+
+	// -- Exec
+	let data = apply_patch_incremental_with_options(original, patch_raw, &options)?;
+	let reapplied = apply_patch_incremental(original, &data.completed_patches.concat())?;
+
+	// -- Check
+	assert_eq!(data.new_content, "a\nB\nc\nD\n");
+	assert!(
+		data.completed_patches[1].starts_with("# udiffx: hunk 2 matched at 3 via Strict, score 1.00\n@@ -3,2 +3,2 @@"),
+		"{}",
+		data.completed_patches[1]
+	);
+	assert_eq!(reapplied.new_content, data.new_content);
+	assert!(reapplied.hunk_errors.is_empty());
+
+	Ok(())
+}
+
+#[test]
+fn test_applier_apply_patch_incremental_hunk_drifts() -> Result<()> {
+	// -- Setup & Fixtures
+	let original: String = (1..=30).map(|n| format!("line {n}\n")).collect();
+	let patch_raw =
+		"@@ -2,1 +2,2 @@\n-line 2\n+line 2a\n+line 2b\n@@\n-line 5\n+LINE 5\n@@ -13,1 +14,1 @@\n-line 10\n+LINE 10\n";
+
+	// -- Exec
+	let data = apply_patch_incremental(&original, patch_raw)?;
+
+	// -- Check
+	assert!(data.hunk_errors.is_empty());
+	let drifts: Vec<(usize, usize, usize)> = data
+		.hunk_drifts
+		.iter()
+		.map(|d| (d.hunk_index, d.expected_line, d.matched_line))
+		.collect();
+	assert_eq!(drifts, [(0, 2, 2), (2, 13, 10)]);
+
+	Ok(())
+}
+
+#[test]
+fn test_applier_apply_patch_incremental_already_inserted() -> Result<()> {
+	// -- Setup & Fixtures
+	let original = "use a;\nuse b;\n\nfn main() {}\n";
+	let patch_raw = "@@\n use a;\n+use b;\n@@\n+fn helper() {}\n+\n fn main() {}\n";
+
+	// -- Exec
+	let data = apply_patch_incremental(original, patch_raw)?;
+
+	// -- Check
+	assert_eq!(data.new_content, "use a;\nuse b;\n\nfn helper() {}\n\nfn main() {}\n");
+	assert_eq!(data.noop_edits.len(), 1);
+	assert_eq!(data.noop_edits[0].kind, crate::NoopKind::AlreadyInserted);
+	assert_eq!(data.noop_edits[0].line, Some(2));
+	assert_eq!(data.noop_edits[0].current, "use b;");
+
+	Ok(())
+}
+
+#[test]
+fn test_applier_apply_patch_incremental_intended_duplicate() -> Result<()> {
+	// -- Setup & Fixtures
+	let original = "fn a() {\n    tick();\n}\n";
+	let patch_raw = "@@\n fn a() {\n     tick();\n+    tick();\n }\n";
+
+	// -- Exec
+	let data = apply_patch_incremental(original, patch_raw)?;
+
+	// -- Check
+	assert_eq!(data.new_content, "fn a() {\n    tick();\n    tick();\n}\n");
+	assert!(data.noop_edits.is_empty(), "{:?}", data.noop_edits);
 
 	Ok(())
 }
@@ -0,0 +1,231 @@
+use crate::apply_store::ApplyStore;
+use crate::{ApplyOptions, Error, Result};
+use simple_fs::SPath;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// The safety budget of a batch (see `ApplyOptions::max_files_touched`, `max_bytes_written`, and `max_deletions`),
+/// with the usage of the directives applied so far.
+///
+/// A store operation exceeding a limit is refused with `Error::ApplyBudgetExceeded`, and the budget is then
+/// exhausted (the remaining directives are skipped).
+#[derive(Debug, Default)]
+pub(crate) struct Budget {
+	max_files_touched: Option<usize>,
+	max_bytes_written: Option<u64>,
+	max_deletions: Option<usize>,
+	/// The paths written, renamed, or deleted.
+	touched: HashSet<String>,
+	bytes_written: u64,
+	deletions: usize,
+	exhausted: bool,
+}
+
+impl Budget {
+	pub(crate) fn new(options: &ApplyOptions) -> Self {
+		Self {
+			max_files_touched: options.max_files_touched,
+			max_bytes_written: options.max_bytes_written,
+			max_deletions: options.max_deletions,
+			..Default::default()
+		}
+	}
+
+	/// Whether an operation was refused for exceeding the budget.
+	pub(crate) fn is_exhausted(&self) -> bool {
+		self.exhausted
+	}
+
+	/// Charges an operation touching `paths`, writing `bytes`, and deleting `deletions` paths,
+	/// or refuses it (nothing charged) if it exceeds a limit.
+	fn charge(&mut self, paths: &[&SPath], bytes: u64, deletions: usize) -> Result<()> {
+		let new_paths: HashSet<&str> = paths
+			.iter()
+			.map(|path| path.as_str())
+			.filter(|path| !self.touched.contains(*path))
+			.collect();
+
+		let exceeded = if let Some(max) = self.max_files_touched
+			&& self.touched.len() + new_paths.len() > max
+		{
+			Some(("max_files_touched", max as u64))
+		} else if let Some(max) = self.max_bytes_written
+			&& self.bytes_written + bytes > max
+		{
+			Some(("max_bytes_written", max))
+		} else if let Some(max) = self.max_deletions
+			&& self.deletions + deletions > max
+		{
+			Some(("max_deletions", max as u64))
+		} else {
+			None
+		};
+		if let Some((limit, max)) = exceeded {
+			self.exhausted = true;
+			return Err(Error::apply_budget_exceeded(limit, max));
+		}
+
+		self.touched.extend(new_paths.into_iter().map(String::from));
+		self.bytes_written += bytes;
+		self.deletions += deletions;
+		Ok(())
+	}
+}
+
+// region:    --- BudgetStore
+
+/// A store charging the changes made to another store to a `Budget`, refusing the ones exceeding it.
+///
+/// Also counts the bytes actually written (for `DirectiveStatus::bytes_written`).
+pub(crate) struct BudgetStore<'a, S: ApplyStore> {
+	store: &'a mut S,
+	budget: &'a mut Budget,
+	bytes_written: u64,
+}
+
+impl<'a, S: ApplyStore> BudgetStore<'a, S> {
+	pub(crate) fn new(store: &'a mut S, budget: &'a mut Budget) -> Self {
+		Self {
+			store,
+			budget,
+			bytes_written: 0,
+		}
+	}
+
+	/// The bytes written through this store (the writes refused by the store below not counting).
+	pub(crate) fn bytes_written(&self) -> u64 {
+		self.bytes_written
+	}
+}
+
+impl<S: ApplyStore> ApplyStore for BudgetStore<'_, S> {
+	fn exists(&self, path: &SPath) -> bool {
+		self.store.exists(path)
+	}
+
+	fn is_dir(&self, path: &SPath) -> bool {
+		self.store.is_dir(path)
+	}
+
+	fn read_to_string(&self, path: &SPath) -> Result<String> {
+		self.store.read_to_string(path)
+	}
+
+	fn read_bytes(&self, path: &SPath) -> Result<Vec<u8>> {
+		self.store.read_bytes(path)
+	}
+
+	fn write(&mut self, path: &SPath, content: &[u8]) -> Result<()> {
+		self.budget.charge(&[path], content.len() as u64, 0)?;
+		self.store.write(path, content)?;
+		self.bytes_written += content.len() as u64;
+		Ok(())
+	}
+
+	fn rename(&mut self, from: &SPath, to: &SPath) -> Result<()> {
+		self.budget.charge(&[from, to], 0, 0)?;
+		self.store.rename(from, to)
+	}
+
+	fn delete(&mut self, path: &SPath) -> Result<()> {
+		self.budget.charge(&[path], 0, 1)?;
+		self.store.delete(path)
+	}
+
+	fn remove(&mut self, path: &SPath) -> Result<()> {
+		self.budget.charge(&[path], 0, 1)?;
+		self.store.remove(path)
+	}
+
+	fn list_rel_paths(&self, base_dir: &SPath) -> Result<Vec<String>> {
+		self.store.list_rel_paths(base_dir)
+	}
+
+	fn is_virtual(&self) -> bool {
+		self.store.is_virtual()
+	}
+
+	fn is_read_only(&self, path: &SPath) -> bool {
+		self.store.is_read_only(path)
+	}
+
+	fn set_read_only(&mut self, path: &SPath, read_only: bool) -> Result<()> {
+		self.store.set_read_only(path, read_only)
+	}
+
+	fn is_symlink(&self, path: &SPath) -> bool {
+		self.store.is_symlink(path)
+	}
+
+	fn write_backup(&mut self, path: &SPath, content: &[u8]) -> Result<()> {
+		self.store.write_backup(path, content)
+	}
+
+	fn is_same_file(&self, a: &SPath, b: &SPath) -> bool {
+		self.store.is_same_file(a, b)
+	}
+
+	fn remove_symlink(&mut self, path: &SPath) -> Result<()> {
+		self.store.remove_symlink(path)
+	}
+
+	fn read_link(&self, path: &SPath) -> Option<PathBuf> {
+		self.store.read_link(path)
+	}
+
+	fn create_symlink(&mut self, path: &SPath, target: &Path) -> Result<()> {
+		self.store.create_symlink(path, target)
+	}
+
+	fn write_blob(&mut self, path: &SPath, blob_path: &SPath, append: bool) -> Result<()> {
+		let bytes = std::fs::metadata(blob_path)
+			.map_err(|err| Error::io_read_file(blob_path.to_string(), err))?
+			.len();
+		self.budget.charge(&[path], bytes, 0)?;
+		self.store.write_blob(path, blob_path, append)?;
+		self.bytes_written += bytes;
+		Ok(())
+	}
+}
+
+// endregion: --- BudgetStore
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_apply_budget_charge() -> Result<()> {
+		// -- Setup & Fixtures
+		let options = ApplyOptions::default()
+			.with_max_files_touched(2)
+			.with_max_bytes_written(10)
+			.with_max_deletions(1);
+		let mut budget = Budget::new(&options);
+		let (a, b, c) = (SPath::new("/b/a"), SPath::new("/b/b"), SPath::new("/b/c"));
+
+		// -- Exec & Check
+		budget.charge(&[&a], 6, 0)?;
+		// The same file again is not a new touched file.
+		budget.charge(&[&a], 4, 0)?;
+		assert!(matches!(
+			budget.charge(&[&b], 1, 0),
+			Err(Error::ApplyBudgetExceeded { max: 10, .. })
+		));
+		budget.charge(&[&b], 0, 1)?;
+		let err = budget.charge(&[&c], 0, 0).err().ok_or("should fail")?;
+		assert_eq!(
+			err.to_string(),
+			"Apply budget exceeded, more than 2 for max_files_touched"
+		);
+		assert!(budget.is_exhausted());
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -1,9 +1,102 @@
-use crate::{FileDirective, MatchTier};
+use crate::{
+	DiffStats, Error, ExcerptLimits, FileChanges, FileDirective, LenientLine, LineOrigin, MatchTier, ReplaceCount,
+	Result, SecretFinding, truncate_excerpt,
+};
+use derive_more::Display;
+use simple_fs::SPath;
+use std::collections::HashSet;
+use std::time::Duration;
 
 #[derive(Debug, Clone)]
 pub struct HunkError {
+	/// The 0-based index of the hunk in the `FILE_PATCH`.
+	pub hunk_index: usize,
 	pub hunk_body: String,
 	pub cause: String,
+	/// Where the hunk came closest to matching the content it was applied to, if anywhere.
+	pub nearest_miss: Option<NearestMiss>,
+}
+
+impl HunkError {
+	/// The hunk body truncated to `limits`, for logs and model-facing feedback.
+	pub fn hunk_body_excerpt(&self, limits: ExcerptLimits) -> String {
+		truncate_excerpt(&self.hunk_body, limits)
+	}
+}
+
+/// The closest position of a failed hunk in the content.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NearestMiss {
+	/// The 1-based line where the hunk's context/removal lines line up best.
+	pub line: usize,
+	/// The fraction (`0.0..=1.0`) of the hunk's non-blank context/removal lines matching at that line.
+	pub similarity: f64,
+}
+
+/// How far an applied hunk matched from the line its numbered header (`@@ -l,s +l,s @@`) expected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HunkDrift {
+	/// The 0-based index of the hunk in the `FILE_PATCH`.
+	pub hunk_index: usize,
+	/// The 1-based line of the original content given by the hunk header.
+	pub expected_line: usize,
+	/// The 1-based line of the original content where the hunk matched.
+	pub matched_line: usize,
+}
+
+impl HunkDrift {
+	/// The distance (in lines) between the expected and the matched lines.
+	pub fn drift(&self) -> usize {
+		self.expected_line.abs_diff(self.matched_line)
+	}
+}
+
+/// The check of a context-only hunk of a `FILE_PATCH` (see `CompleteOptions::assert_context_hunks`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextAssertion {
+	/// The 0-based index of the hunk among the context-only hunks of the `FILE_PATCH`.
+	pub index: usize,
+	/// The 1-based line of the original content where the context matched, `None` if not found.
+	pub line: Option<usize>,
+	/// How the context matched (`None` if not found).
+	pub tier: Option<MatchTier>,
+}
+
+impl ContextAssertion {
+	pub fn passed(&self) -> bool {
+		self.line.is_some()
+	}
+}
+
+/// Why a hunk was dropped without changing the content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum NoopKind {
+	/// The hunk adds exactly the lines it removes.
+	#[display("identical")]
+	Identical,
+	/// The hunk only differs from the matched content by whitespace.
+	#[display("whitespace-only")]
+	WhitespaceOnly,
+	/// The hunk context did not match, but its result is already in the content (e.g., a resent edit).
+	#[display("already present elsewhere")]
+	AlreadyPresentElsewhere,
+	/// The hunk only adds lines, and they are already right at its matched location (e.g., a resent import).
+	#[display("already inserted")]
+	AlreadyInserted,
+}
+
+/// A hunk of a `FILE_PATCH` that was dropped because it would not change the content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoopEdit {
+	/// The 0-based index of the hunk in the `FILE_PATCH`.
+	pub hunk_index: usize,
+	/// The 1-based line where the hunk matched (or where its result was found), if known.
+	pub line: Option<usize>,
+	pub kind: NoopKind,
+	/// The content lines at that position.
+	pub current: String,
+	/// The lines the hunk proposed (context and added lines).
+	pub proposed: String,
 }
 
 #[derive(Debug, Clone)]
@@ -11,13 +104,152 @@ pub struct ApplyChangesStatus {
 	pub items: Vec<DirectiveStatus>,
 }
 
+impl ApplyChangesStatus {
+	/// Aggregated change counts of all the directives that wrote something
+	/// (`files` counts distinct file paths), e.g., `3 files changed, 42 insertions(+), 7 deletions(-)`.
+	pub fn diff_stats(&self) -> DiffStats {
+		let mut total = DiffStats::default();
+		let mut files: HashSet<&str> = HashSet::new();
+		for item in &self.items {
+			if let Some(stats) = item.diff_stats {
+				total += stats;
+				match &item.kind {
+					DirectiveKind::GlobalReplace { .. } => {
+						files.extend(item.replace_counts.iter().map(|c| c.file_path.as_str()))
+					}
+					_ => {
+						files.insert(item.file_path());
+					}
+				}
+			}
+		}
+		total.files = files.len();
+		total
+	}
+
+	/// The time spent applying the directives (the sum of their `DirectiveStatus::elapsed`).
+	pub fn elapsed(&self) -> Duration {
+		self.items.iter().map(|item| item.elapsed).sum()
+	}
+
+	/// The bytes written by the directives (the sum of their `DirectiveStatus::bytes_written`).
+	pub fn bytes_written(&self) -> u64 {
+		self.items.iter().map(|item| item.bytes_written).sum()
+	}
+
+	/// The `FileChanges` reverting the successful directives, in reverse order (see `ApplyOptions::record_undo`).
+	///
+	/// Empty when the batch was applied without `record_undo`.
+	pub fn undo_changes(&self) -> FileChanges {
+		let directives = self
+			.items
+			.iter()
+			.rev()
+			.filter(|item| item.success)
+			.flat_map(|item| item.undo_directives.iter().cloned())
+			.collect();
+		FileChanges::new(directives)
+	}
+
+	/// Whether a directive failed, the no-op directives not being failures (see `DirectiveStatus::outcome`).
+	pub fn has_failures(&self) -> bool {
+		self.items.iter().any(|item| item.outcome() == DirectiveOutcome::Failed)
+	}
+}
+
+/// How a directive ended (see `DirectiveStatus::outcome`), e.g., to report the harmless no-ops apart from
+/// the real failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum DirectiveOutcome {
+	/// The directive was applied.
+	#[display("success")]
+	Success,
+	/// The directive changed nothing, its end state being already there (e.g., a resent edit).
+	#[display("no change")]
+	NoChange,
+	/// The directive failed, or was not applied (e.g., a stopped or rolled back batch).
+	#[display("failed")]
+	Failed,
+}
+
 #[derive(Debug, Clone)]
 pub struct DirectiveStatus {
 	pub kind: DirectiveKind,
 	pub success: bool,
+	/// The directive changed nothing, its end state being already there: an `Error::ApplyNoChanges` failure
+	/// (not counting a patch whose hunks all failed), or a success with `ApplyOptions::idempotent`.
+	pub unchanged: bool,
 	pub match_tier: Option<MatchTier>,
 	pub error_msg: Option<String>,
 	pub error_hunks: Vec<HunkError>,
+	/// The number of hunks of the `FILE_PATCH` (0 for the other directives).
+	pub total_hunks: usize,
+	/// Hunks dropped because they would not change the content.
+	pub noop_edits: Vec<NoopEdit>,
+	/// The 0-based indexes of the applied hunks that matched truncated context lines (suffix/prefix matching).
+	pub truncated_hunks: Vec<usize>,
+	/// The drifts of the applied hunks with a numbered header (see `CompleteOptions::max_drift`).
+	pub hunk_drifts: Vec<HunkDrift>,
+	/// The checks of the context-only hunks (see `CompleteOptions::assert_context_hunks`).
+	pub context_assertions: Vec<ContextAssertion>,
+	/// Path the `FILE_PATCH` most likely intended to target, when its context
+	/// did not match `file_path` but matched one of the `ApplyOptions::context_files`.
+	pub suggested_file_path: Option<String>,
+	/// Non-fatal notes about how the directive was applied (e.g., a fuzzy-resolved path).
+	pub warnings: Vec<String>,
+	/// Line-level change counts of what was written for this directive, if anything was written.
+	pub diff_stats: Option<DiffStats>,
+	/// The time spent applying the directive (zero for a skipped directive).
+	pub elapsed: Duration,
+	/// The bytes written for this directive, `0` for a rename or a delete, or when rolled back (see
+	/// `ApplyOptions::rollback_on_error`) or not written (see `ApplyOptions::validate_all_first`).
+	pub bytes_written: u64,
+	/// Why the directive was refused before any change, if it was.
+	pub denial: Option<Denial>,
+	/// The `.rej` file the failed hunks were written to (see `ApplyOptions::write_rejects`), relative to the base dir.
+	pub reject_file_path: Option<String>,
+	/// The directives undoing the changes of this directive (see `ApplyOptions::record_undo`).
+	pub undo_directives: Vec<FileDirective>,
+	/// The likely secrets found in the content to write (see `ApplyOptions::secret_scan`).
+	pub secret_findings: Vec<SecretFinding>,
+	/// The context/removal lines of the applied hunks matched non-strictly (lines of the original content).
+	pub lenient_lines: Vec<LenientLine>,
+	/// The origin of each line of the written file (see `ApplyOptions::record_provenance`).
+	pub line_origins: Option<Vec<LineOrigin>>,
+	/// The unified diff (with a `--- a/` / `+++ b/` header) from the content before to the content written,
+	/// for a `FILE_PATCH` or an overwriting `FILE_NEW` (see `ApplyOptions::record_applied_diffs`).
+	pub applied_diff: Option<String>,
+	/// The file holding the applied diff when larger than `ApplyOptions::max_applied_diff_bytes`
+	/// (see `ApplyOptions::applied_diff_store`), `applied_diff` being then `None`.
+	pub applied_diff_path: Option<SPath>,
+	/// The matches of a `FILE_GLOBAL_REPLACE` in each file with at least one (also set when it was refused
+	/// for too many matches, as a preview).
+	pub replace_counts: Vec<ReplaceCount>,
+}
+
+/// Why a directive was refused (nothing written).
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+pub enum Denial {
+	/// The target is a generated file (see `ApplyOptions::refuse_generated`).
+	#[display("generated file ({_0}), use allow_generated=\"true\" to override")]
+	GeneratedFile(GeneratedReason),
+	/// The content to write holds a likely secret (see `ApplyOptions::secret_scan`).
+	#[display("likely secret ({_0})")]
+	LikelySecret(SecretFinding),
+	/// The destructive directive was rejected by the `ApplyOptions::confirmer`.
+	#[display("not confirmed")]
+	NotConfirmed,
+}
+
+/// Why a file is considered generated.
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+pub enum GeneratedReason {
+	#[display("`@generated` marker")]
+	Marker,
+	#[display("lock file")]
+	LockFile,
+	#[display("policy glob '{_0}'")]
+	PolicyGlob(String),
 }
 
 #[derive(Debug, Clone)]
@@ -31,6 +263,15 @@ pub enum DirectiveKind {
 	Append {
 		file_path: String,
 	},
+	SpanPatch {
+		file_path: String,
+	},
+	Insert {
+		file_path: String,
+	},
+	GlobalReplace {
+		glob: String,
+	},
 	Copy {
 		from_path: String,
 		file_path: String,
@@ -55,6 +296,9 @@ impl DirectiveStatus {
 			DirectiveKind::New { file_path } => file_path,
 			DirectiveKind::Patch { file_path } => file_path,
 			DirectiveKind::Append { file_path } => file_path,
+			DirectiveKind::SpanPatch { file_path } => file_path,
+			DirectiveKind::Insert { file_path } => file_path,
+			DirectiveKind::GlobalReplace { glob } => glob,
 			DirectiveKind::Copy { file_path, .. } => file_path,
 			DirectiveKind::Rename { file_path, .. } => file_path,
 			DirectiveKind::Delete { file_path } => file_path,
@@ -70,11 +314,140 @@ impl DirectiveStatus {
 		self.error_msg.as_deref()
 	}
 
+	pub fn suggested_file_path(&self) -> Option<&str> {
+		self.suggested_file_path.as_deref()
+	}
+
+	/// The number of `FILE_PATCH` hunks that changed the content, even when other hunks failed
+	/// (the matching hunks of a patch are applied, the failed ones are listed in `error_hunks`).
+	pub fn applied_hunk_count(&self) -> usize {
+		self.total_hunks
+			.saturating_sub(self.error_hunks.len() + self.noop_edits.len())
+	}
+
+	pub fn noop_edits(&self) -> &[NoopEdit] {
+		&self.noop_edits
+	}
+
+	pub fn truncated_hunks(&self) -> &[usize] {
+		&self.truncated_hunks
+	}
+
+	pub fn hunk_drifts(&self) -> &[HunkDrift] {
+		&self.hunk_drifts
+	}
+
+	pub fn context_assertions(&self) -> &[ContextAssertion] {
+		&self.context_assertions
+	}
+
+	pub fn warnings(&self) -> &[String] {
+		&self.warnings
+	}
+
+	pub fn unchanged(&self) -> bool {
+		self.unchanged
+	}
+
+	/// The outcome of the directive, a no-op being a `NoChange` whether it failed with `Error::ApplyNoChanges`
+	/// or succeeded with `ApplyOptions::idempotent`.
+	pub fn outcome(&self) -> DirectiveOutcome {
+		if self.unchanged {
+			DirectiveOutcome::NoChange
+		} else if self.success {
+			DirectiveOutcome::Success
+		} else {
+			DirectiveOutcome::Failed
+		}
+	}
+
+	pub fn denial(&self) -> Option<&Denial> {
+		self.denial.as_ref()
+	}
+
+	pub fn reject_file_path(&self) -> Option<&str> {
+		self.reject_file_path.as_deref()
+	}
+
+	pub fn undo_directives(&self) -> &[FileDirective] {
+		&self.undo_directives
+	}
+
+	pub fn secret_findings(&self) -> &[SecretFinding] {
+		&self.secret_findings
+	}
+
+	pub fn lenient_lines(&self) -> &[LenientLine] {
+		&self.lenient_lines
+	}
+
+	pub fn line_origins(&self) -> Option<&[LineOrigin]> {
+		self.line_origins.as_deref()
+	}
+
+	pub fn applied_diff(&self) -> Option<&str> {
+		self.applied_diff.as_deref()
+	}
+
+	pub fn applied_diff_path(&self) -> Option<&SPath> {
+		self.applied_diff_path.as_ref()
+	}
+
+	/// The applied diff, from the status or else from its `applied_diff_path` file.
+	pub fn load_applied_diff(&self) -> Result<Option<String>> {
+		if let Some(applied_diff) = &self.applied_diff {
+			return Ok(Some(applied_diff.clone()));
+		}
+		match &self.applied_diff_path {
+			Some(path) => std::fs::read_to_string(path)
+				.map(Some)
+				.map_err(|err| Error::io_read_file(path.to_string(), err)),
+			None => Ok(None),
+		}
+	}
+
+	pub fn replace_counts(&self) -> &[ReplaceCount] {
+		&self.replace_counts
+	}
+
+	pub fn elapsed(&self) -> Duration {
+		self.elapsed
+	}
+
+	pub fn bytes_written(&self) -> u64 {
+		self.bytes_written
+	}
+
+	/// The lines added by the directive (see `diff_stats`), `0` if nothing was written.
+	pub fn lines_added(&self) -> usize {
+		self.diff_stats.map(|stats| stats.insertions).unwrap_or_default()
+	}
+
+	/// The lines removed by the directive (see `diff_stats`), `0` if nothing was written.
+	pub fn lines_removed(&self) -> usize {
+		self.diff_stats.map(|stats| stats.deletions).unwrap_or_default()
+	}
+
+	/// Drops the content echoed from the directive: the failed hunk bodies and the no-op edit lines
+	/// (the causes, indexes, and lines are kept).
+	pub fn omit_content_echoes(&mut self) {
+		for hunk_error in &mut self.error_hunks {
+			hunk_error.hunk_body = String::new();
+		}
+		for noop_edit in &mut self.noop_edits {
+			noop_edit.current = String::new();
+			noop_edit.proposed = String::new();
+		}
+	}
+
 	pub fn kind(&self) -> &'static str {
 		match &self.kind {
 			DirectiveKind::New { .. } => "New",
 			DirectiveKind::Patch { .. } => "Patch",
 			DirectiveKind::Append { .. } => "Append",
+			DirectiveKind::SpanPatch { .. } => "SpanPatch",
+			DirectiveKind::Insert { .. } => "Insert",
+			DirectiveKind::GlobalReplace { .. } => "GlobalReplace",
 			DirectiveKind::Copy { .. } => "Copy",
 			DirectiveKind::Rename { .. } => "Rename",
 			DirectiveKind::Delete { .. } => "Delete",
@@ -99,6 +472,13 @@ impl From<&FileDirective> for DirectiveStatus {
 			FileDirective::Append { file_path, .. } => DirectiveKind::Append {
 				file_path: file_path.clone(),
 			},
+			FileDirective::SpanPatch { file_path, .. } => DirectiveKind::SpanPatch {
+				file_path: file_path.clone(),
+			},
+			FileDirective::Insert { file_path, .. } => DirectiveKind::Insert {
+				file_path: file_path.clone(),
+			},
+			FileDirective::GlobalReplace { glob, .. } => DirectiveKind::GlobalReplace { glob: glob.clone() },
 			FileDirective::Copy { from_path, to_path } => DirectiveKind::Copy {
 				from_path: from_path.clone(),
 				file_path: to_path.clone(),
@@ -126,9 +506,29 @@ impl From<&FileDirective> for DirectiveStatus {
 		Self {
 			kind,
 			success: false,
+			unchanged: false,
 			match_tier: None,
 			error_msg,
 			error_hunks: Vec::new(),
+			total_hunks: 0,
+			noop_edits: Vec::new(),
+			truncated_hunks: Vec::new(),
+			hunk_drifts: Vec::new(),
+			context_assertions: Vec::new(),
+			suggested_file_path: None,
+			warnings: Vec::new(),
+			diff_stats: None,
+			elapsed: Duration::ZERO,
+			bytes_written: 0,
+			denial: None,
+			reject_file_path: None,
+			undo_directives: Vec::new(),
+			secret_findings: Vec::new(),
+			lenient_lines: Vec::new(),
+			line_origins: None,
+			applied_diff: None,
+			applied_diff_path: None,
+			replace_counts: Vec::new(),
 		}
 	}
 }
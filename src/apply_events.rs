@@ -0,0 +1,82 @@
+use crate::applier::{apply_with_store, resolve_base_dir};
+use crate::apply_store::DiskStore;
+use crate::{ApplyChangesStatus, ApplyOptions, ApplyProgress, DirectiveStatus, FileChanges, Result, SecurityPolicy};
+use simple_fs::SPath;
+use std::sync::mpsc::Sender;
+
+/// An event of `apply_file_changes_with_events`, e.g., to stream the apply progress to a TUI or a web frontend.
+#[derive(Debug, Clone)]
+pub enum ApplyEvent {
+	/// The batch of `total` directives is about to be applied.
+	Started { total: usize },
+	/// A warning of the directive at `index` (sent before its `DirectiveDone`).
+	Warning { index: usize, message: String },
+	/// The directive at `index` (0-based, out of `total`) is applied (successfully or not).
+	DirectiveDone {
+		index: usize,
+		total: usize,
+		status: Box<DirectiveStatus>,
+	},
+	/// The batch is applied, with its aggregate status (as returned).
+	Finished { status: ApplyChangesStatus },
+}
+
+/// Same as `apply_file_changes_with_options`, sending the `ApplyEvent`s of the batch to `events` as it is applied.
+///
+/// The events are `Started`, then the `Warning`s and the `DirectiveDone` of each directive, then `Finished`.
+/// A batch refused by the `validate_all_first` simulation only sends `Started` and `Finished`. A send error
+/// (e.g., the receiver dropped) is ignored, the batch being applied anyway.
+///
+/// Note: The `base_dir` is checked first, an error being returned before any event is sent.
+pub fn apply_file_changes_with_events(
+	base_dir: impl Into<SPath>,
+	file_changes: FileChanges,
+	security_policy: impl Into<SecurityPolicy>,
+	options: impl Into<ApplyOptions>,
+	events: Sender<ApplyEvent>,
+) -> Result<ApplyChangesStatus> {
+	let base_dir = base_dir.into();
+	let policy: SecurityPolicy = security_policy.into();
+	let options: ApplyOptions = options.into();
+	let base_dir = resolve_base_dir(base_dir, &policy, &options)?;
+
+	let _ = events.send(ApplyEvent::Started {
+		total: file_changes.iter().len(),
+	});
+	let status = apply_with_store(
+		&mut DiskStore,
+		&base_dir,
+		file_changes,
+		&policy,
+		&options,
+		&mut EventProgress { events: &events },
+	);
+	let _ = events.send(ApplyEvent::Finished { status: status.clone() });
+
+	Ok(status)
+}
+
+// region:    --- EventProgress
+
+/// Sends the progress of the directives as `ApplyEvent`s.
+struct EventProgress<'a> {
+	events: &'a Sender<ApplyEvent>,
+}
+
+impl ApplyProgress for EventProgress<'_> {
+	fn on_directive_done(&mut self, index: usize, total: usize, status: &DirectiveStatus) {
+		for warning in &status.warnings {
+			let _ = self.events.send(ApplyEvent::Warning {
+				index,
+				message: warning.clone(),
+			});
+		}
+		let _ = self.events.send(ApplyEvent::DirectiveDone {
+			index,
+			total,
+			status: Box::new(status.clone()),
+		});
+	}
+}
+
+// endregion: --- EventProgress
@@ -0,0 +1,469 @@
+use crate::apply_store::ApplyStore;
+use crate::{Content, Error, FileDirective, Result};
+use simple_fs::SPath;
+use std::path::{Path, PathBuf};
+
+/// The undo log of the changes a batch made to a store, for `ApplyOptions::rollback_on_error`
+/// (and `ApplyOptions::record_undo`).
+///
+/// Deletions are deferred: the deleted path is first moved aside (next to it), and only deleted on `commit`,
+/// so a rollback can move it back.
+#[derive(Default)]
+pub(crate) struct Journal {
+	undo_ops: Vec<UndoOp>,
+	/// The deletions, moved aside (deleted on `commit`).
+	deleted_paths: Vec<DeletedPath>,
+}
+
+/// A deletion deferred until the `Journal::commit`.
+struct DeletedPath {
+	/// The path the deleted path was moved aside to.
+	aside: SPath,
+	/// The deleted path.
+	path: SPath,
+	/// Whether deleted permanently (`ApplyStore::remove`), rather than with `ApplyStore::delete`.
+	permanent: bool,
+}
+
+enum UndoOp {
+	/// Restores the file content (`None` means the file did not exist).
+	Restore {
+		path: SPath,
+		content: Option<Vec<u8>>,
+	},
+	Rename {
+		from: SPath,
+		to: SPath,
+	},
+	/// Restores a symlink replaced by a regular file (see `SymlinkTarget::Replace`).
+	Relink {
+		path: SPath,
+		target: PathBuf,
+	},
+}
+
+impl Journal {
+	/// Undoes all the recorded changes (in reverse order), keeping on after a failure.
+	///
+	/// The replaced symlinks are restored last, once their path has its original content back
+	/// (so the content is not written through the restored link).
+	///
+	/// Returns the first error, if any.
+	pub(crate) fn rollback(self, store: &mut impl ApplyStore) -> Result<()> {
+		let mut first_err = None;
+		let mut relinks = Vec::new();
+		for op in self.undo_ops.into_iter().rev() {
+			let res = match op {
+				UndoOp::Restore {
+					path,
+					content: Some(content),
+				} => store.write(&path, &content),
+				// Note: A created file is permanently removed (it has nothing to recover from the trash).
+				UndoOp::Restore { path, content: None } => store.remove(&path),
+				UndoOp::Rename { from, to } => store.rename(&from, &to),
+				UndoOp::Relink { path, target } => {
+					relinks.push((path, target));
+					Ok(())
+				}
+			};
+			if let Err(err) = res {
+				first_err.get_or_insert(err);
+			}
+		}
+		for (path, target) in relinks {
+			let res = if store.exists(&path) {
+				store.remove(&path)
+			} else {
+				Ok(())
+			};
+			if let Err(err) = res.and_then(|_| store.create_symlink(&path, &target)) {
+				first_err.get_or_insert(err);
+			}
+		}
+
+		match first_err {
+			Some(err) => Err(err),
+			None => Ok(()),
+		}
+	}
+
+	/// Performs the deferred deletions.
+	///
+	/// A path is first moved back to its original path (unless recreated since), so a trashed file keeps its name.
+	pub(crate) fn commit(self, store: &mut impl ApplyStore) -> Result<()> {
+		for DeletedPath { aside, path, permanent } in self.deleted_paths {
+			let target = if store.exists(&path) {
+				aside
+			} else {
+				store.rename(&aside, &path)?;
+				path
+			};
+			if permanent {
+				store.remove(&target)?;
+			} else {
+				store.delete(&target)?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Records the symlink `path` to `target`, replaced by a regular file below the journaled store
+	/// (see `SymlinkTarget::Replace`), to be restored on rollback.
+	pub(crate) fn record_relink(&mut self, path: SPath, target: PathBuf) {
+		self.undo_ops.push(UndoOp::Relink { path, target });
+	}
+
+	/// The number of undo operations recorded so far.
+	pub(crate) fn len(&self) -> usize {
+		self.undo_ops.len()
+	}
+
+	/// The directives undoing the changes recorded from the undo operation at `start`, in undo order.
+	///
+	/// Paths are relative to `base_dir`. A file content is restored with a `FILE_NEW` of the whole content,
+	/// which must be UTF-8.
+	pub(crate) fn undo_directives(
+		&self,
+		store: &impl ApplyStore,
+		base_dir: &SPath,
+		start: usize,
+	) -> Result<Vec<FileDirective>> {
+		let rel_path = |path: &SPath| {
+			let path = path.as_str();
+			path.strip_prefix(base_dir.as_str())
+				.and_then(|p| p.strip_prefix('/'))
+				.unwrap_or(path)
+				.to_string()
+		};
+		let new_directive = |path: &SPath, content: Vec<u8>| -> Result<FileDirective> {
+			let content = String::from_utf8(content)
+				.map_err(|_| Error::custom(format!("Cannot restore the non UTF-8 content of '{path}'")))?;
+			Ok(FileDirective::New {
+				file_path: rel_path(path),
+				content: Content::from_verbatim(content),
+			})
+		};
+
+		let mut directives = Vec::new();
+		for op in self.undo_ops[start..].iter().rev() {
+			match op {
+				UndoOp::Restore {
+					path,
+					content: Some(content),
+				} => directives.push(new_directive(path, content.clone())?),
+				UndoOp::Restore { path, content: None } => directives.push(FileDirective::Delete {
+					file_path: rel_path(path),
+				}),
+				// A deletion (the path moved aside is restored with its content)
+				UndoOp::Rename { from, to } if self.deleted_paths.iter().any(|deleted| &deleted.aside == from) => {
+					if store.is_dir(from) {
+						for file in store.list_rel_paths(from)? {
+							let content = store.read_bytes(&from.join(&file))?;
+							directives.push(new_directive(&to.join(&file), content)?);
+						}
+					} else {
+						directives.push(new_directive(to, store.read_bytes(from)?)?);
+					}
+				}
+				UndoOp::Rename { from, to } => directives.push(FileDirective::Rename {
+					from_path: rel_path(from),
+					to_path: rel_path(to),
+				}),
+				// Note: A symlink cannot be created by a directive (its path is restored as a regular file).
+				UndoOp::Relink { .. } => (),
+			}
+		}
+
+		Ok(directives)
+	}
+
+	/// A free path next to `path` to move it aside.
+	fn aside_path(&self, store: &impl ApplyStore, path: &SPath) -> SPath {
+		let mut idx = self.deleted_paths.len();
+		loop {
+			let aside = SPath::new(format!("{path}.udiffx-deleted-{idx}"));
+			if !store.exists(&aside) {
+				return aside;
+			}
+			idx += 1;
+		}
+	}
+}
+
+// region:    --- JournalStore
+
+/// A store recording the undo operations of the changes made to another store in a `Journal`.
+pub(crate) struct JournalStore<'a, S: ApplyStore> {
+	store: &'a mut S,
+	journal: &'a mut Journal,
+}
+
+impl<'a, S: ApplyStore> JournalStore<'a, S> {
+	pub(crate) fn new(store: &'a mut S, journal: &'a mut Journal) -> Self {
+		Self { store, journal }
+	}
+
+	/// Runs the write `op` on `path`, recording the restore of its previous content.
+	fn journaled_write(&mut self, path: &SPath, op: impl FnOnce(&mut S) -> Result<()>) -> Result<()> {
+		let previous = if self.store.exists(path) {
+			Some(self.store.read_bytes(path)?)
+		} else {
+			None
+		};
+		op(self.store)?;
+		self.journal.undo_ops.push(UndoOp::Restore {
+			path: path.clone(),
+			content: previous,
+		});
+		Ok(())
+	}
+
+	/// Moves the deleted `path` aside, until the `Journal::commit`.
+	fn move_aside(&mut self, path: &SPath, permanent: bool) -> Result<()> {
+		let aside = self.journal.aside_path(self.store, path);
+		self.store.rename(path, &aside)?;
+		self.journal.undo_ops.push(UndoOp::Rename {
+			from: aside.clone(),
+			to: path.clone(),
+		});
+		self.journal.deleted_paths.push(DeletedPath {
+			aside,
+			path: path.clone(),
+			permanent,
+		});
+		Ok(())
+	}
+}
+
+impl<S: ApplyStore> ApplyStore for JournalStore<'_, S> {
+	fn exists(&self, path: &SPath) -> bool {
+		self.store.exists(path)
+	}
+
+	fn is_dir(&self, path: &SPath) -> bool {
+		self.store.is_dir(path)
+	}
+
+	fn read_to_string(&self, path: &SPath) -> Result<String> {
+		self.store.read_to_string(path)
+	}
+
+	fn read_bytes(&self, path: &SPath) -> Result<Vec<u8>> {
+		self.store.read_bytes(path)
+	}
+
+	fn write(&mut self, path: &SPath, content: &[u8]) -> Result<()> {
+		self.journaled_write(path, |store| store.write(path, content))
+	}
+
+	/// As `write`, the base store writing the blob (e.g., streamed to disk).
+	fn write_blob(&mut self, path: &SPath, blob_path: &SPath, append: bool) -> Result<()> {
+		self.journaled_write(path, |store| store.write_blob(path, blob_path, append))
+	}
+
+	fn rename(&mut self, from: &SPath, to: &SPath) -> Result<()> {
+		if self.store.exists(to) {
+			self.delete(to)?;
+		}
+		self.store.rename(from, to)?;
+		self.journal.undo_ops.push(UndoOp::Rename {
+			from: to.clone(),
+			to: from.clone(),
+		});
+		Ok(())
+	}
+
+	fn delete(&mut self, path: &SPath) -> Result<()> {
+		self.move_aside(path, false)
+	}
+
+	/// As `delete`, the path is moved aside until the `Journal::commit` (which removes it).
+	fn remove(&mut self, path: &SPath) -> Result<()> {
+		self.move_aside(path, true)
+	}
+
+	fn list_rel_paths(&self, base_dir: &SPath) -> Result<Vec<String>> {
+		self.store.list_rel_paths(base_dir)
+	}
+
+	fn is_virtual(&self) -> bool {
+		self.store.is_virtual()
+	}
+
+	fn is_read_only(&self, path: &SPath) -> bool {
+		self.store.is_read_only(path)
+	}
+
+	fn set_read_only(&mut self, path: &SPath, read_only: bool) -> Result<()> {
+		self.store.set_read_only(path, read_only)
+	}
+
+	fn is_symlink(&self, path: &SPath) -> bool {
+		self.store.is_symlink(path)
+	}
+
+	fn write_backup(&mut self, path: &SPath, content: &[u8]) -> Result<()> {
+		self.store.write_backup(path, content)
+	}
+
+	fn is_same_file(&self, a: &SPath, b: &SPath) -> bool {
+		self.store.is_same_file(a, b)
+	}
+
+	fn remove_symlink(&mut self, path: &SPath) -> Result<()> {
+		let target = self.store.read_link(path);
+		self.store.remove_symlink(path)?;
+		if let Some(target) = target {
+			self.journal.record_relink(path.clone(), target);
+		}
+		Ok(())
+	}
+
+	fn read_link(&self, path: &SPath) -> Option<PathBuf> {
+		self.store.read_link(path)
+	}
+
+	fn create_symlink(&mut self, path: &SPath, target: &Path) -> Result<()> {
+		self.store.create_symlink(path, target)
+	}
+}
+
+// endregion: --- JournalStore
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use std::collections::BTreeMap;
+
+	/// An in-memory store recording its deletions (`delete` is the trash, `remove` the permanent deletion).
+	#[derive(Default)]
+	struct MemStore {
+		files: BTreeMap<String, Vec<u8>>,
+		deletions: Vec<String>,
+	}
+
+	impl ApplyStore for MemStore {
+		fn exists(&self, path: &SPath) -> bool {
+			self.files.contains_key(path.as_str())
+		}
+
+		fn is_dir(&self, _path: &SPath) -> bool {
+			false
+		}
+
+		fn read_to_string(&self, path: &SPath) -> crate::Result<String> {
+			Ok(String::from_utf8_lossy(&self.read_bytes(path)?).to_string())
+		}
+
+		fn read_bytes(&self, path: &SPath) -> crate::Result<Vec<u8>> {
+			self.files
+				.get(path.as_str())
+				.cloned()
+				.ok_or_else(|| Error::apply_path_not_found("read", path.to_string()))
+		}
+
+		fn write(&mut self, path: &SPath, content: &[u8]) -> crate::Result<()> {
+			self.files.insert(path.to_string(), content.to_vec());
+			Ok(())
+		}
+
+		fn rename(&mut self, from: &SPath, to: &SPath) -> crate::Result<()> {
+			let content = self.read_bytes(from)?;
+			self.files.remove(from.as_str());
+			self.files.insert(to.to_string(), content);
+			Ok(())
+		}
+
+		fn delete(&mut self, path: &SPath) -> crate::Result<()> {
+			self.files.remove(path.as_str());
+			self.deletions.push(format!("trash {path}"));
+			Ok(())
+		}
+
+		fn remove(&mut self, path: &SPath) -> crate::Result<()> {
+			self.files.remove(path.as_str());
+			self.deletions.push(format!("remove {path}"));
+			Ok(())
+		}
+
+		fn list_rel_paths(&self, _base_dir: &SPath) -> crate::Result<Vec<String>> {
+			Ok(Vec::new())
+		}
+	}
+
+	#[test]
+	fn test_apply_journal_rollback_removes_created() -> Result<()> {
+		// -- Setup & Fixtures
+		let mut store = MemStore::default();
+		let mut journal = Journal::default();
+		JournalStore::new(&mut store, &mut journal).write(&SPath::new("/b/created.txt"), b"new")?;
+
+		// -- Exec
+		journal.rollback(&mut store)?;
+
+		// -- Check
+		assert!(store.files.is_empty());
+		assert_eq!(store.deletions, ["remove /b/created.txt"]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_apply_journal_rollback_write_blob() -> Result<()> {
+		// -- Setup & Fixtures
+		let blob_path = std::env::temp_dir().join(format!("udiffx-test-journal-blob-{}", std::process::id()));
+		std::fs::write(&blob_path, "blob\n")?;
+		let blob_path = SPath::from_std_path(blob_path)?;
+		let mut store = MemStore::default();
+		store.write(&SPath::new("/b/a.txt"), b"a\n")?;
+		let mut journal = Journal::default();
+		JournalStore::new(&mut store, &mut journal).write_blob(&SPath::new("/b/a.txt"), &blob_path, true)?;
+		let appended = store.read_to_string(&SPath::new("/b/a.txt"))?;
+
+		// -- Exec
+		journal.rollback(&mut store)?;
+
+		// -- Check
+		assert_eq!(appended, "a\nblob\n");
+		assert_eq!(store.read_to_string(&SPath::new("/b/a.txt"))?, "a\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_apply_journal_commit_original_names() -> Result<()> {
+		// -- Setup & Fixtures
+		let mut store = MemStore::default();
+		store.write(&SPath::new("/b/d.txt"), b"d")?;
+		store.write(&SPath::new("/b/r.txt"), b"r")?;
+		store.write(&SPath::new("/b/again.txt"), b"old")?;
+		let mut journal = Journal::default();
+		let mut journal_store = JournalStore::new(&mut store, &mut journal);
+		journal_store.delete(&SPath::new("/b/d.txt"))?;
+		journal_store.remove(&SPath::new("/b/r.txt"))?;
+		journal_store.delete(&SPath::new("/b/again.txt"))?;
+		journal_store.write(&SPath::new("/b/again.txt"), b"new")?;
+
+		// -- Exec
+		journal.commit(&mut store)?;
+
+		// -- Check
+		assert_eq!(
+			store.deletions,
+			[
+				"trash /b/d.txt",
+				"remove /b/r.txt",
+				"trash /b/again.txt.udiffx-deleted-2"
+			]
+		);
+		assert_eq!(store.files.keys().collect::<Vec<_>>(), ["/b/again.txt"]);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,450 @@
+use crate::{
+	BlobStore, CompleteOptions, Confirmer, FeedbackTemplates, Materializer, PostApplyValidator, Redactor,
+	SecretScanMode,
+};
+use simple_fs::SPath;
+use std::sync::Arc;
+
+/// Options controlling how `apply_file_changes_with_options` applies a `FileChanges`.
+///
+/// `ApplyOptions::default()` (or `None` via `Option<ApplyOptions>`) yields the same
+/// behavior as `apply_file_changes`.
+#[derive(Debug, Clone, Default)]
+pub struct ApplyOptions {
+	/// Paths (relative to `base_dir`) of the files recently provided to the model as context
+	/// (e.g., via `load_files_context`).
+	///
+	/// When a `FILE_PATCH` context barely matches its target file but strongly matches one of
+	/// these files, the directive fails with a "did you mean" suggestion instead of being applied.
+	pub context_files: Vec<String>,
+
+	/// When `true`, a missing source path (patch, delete, copy/rename source) is retargeted
+	/// to a unique existing file with the same basename (or a very close path) under `base_dir`,
+	/// and a warning is recorded. Multiple candidates fail the directive. (default false)
+	pub fuzzy_path_resolution: bool,
+
+	/// When `true`, the whole batch is first simulated in memory (patch completion, guard checks,
+	/// path checks) with zero writes, and nothing is written unless every directive validates. (default false)
+	pub validate_all_first: bool,
+
+	/// When `true`, `FILE_PATCH` lines holding literal `\n` escapes instead of real newlines are normalized
+	/// before the completion (see `unescape_patch_body`), and a warning is recorded. (default false)
+	pub unescape_patch_bodies: bool,
+
+	/// The options of the `FILE_PATCH` completion, for all the files.
+	/// When `None`, `CompleteOptions::for_path` is used for each file (e.g., markdown headings leniency for `.md` only).
+	pub complete_options: Option<CompleteOptions>,
+
+	/// When `true`, a `FILE_PATCH` targeting a generated file (a `@generated` marker in its first lines, a `*.lock` file,
+	/// or a `SecurityPolicy::generated_globs` match) is refused with a `Denial`, unless the directive
+	/// has the `allow_generated="true"` attribute. (default false)
+	pub refuse_generated: bool,
+
+	/// When `true`, a `FILE_NEW` overwriting a file that begins with a license header (a leading comment block
+	/// mentioning a license or a copyright) with a content without one gets the original header re-prepended,
+	/// and a warning is recorded. (default false)
+	pub preserve_license_headers: bool,
+
+	/// When `true`, the batch stops at the first failed directive (a no-op directive is not a failure),
+	/// and the following directives are not applied. (default false)
+	pub stop_on_error: bool,
+
+	/// When `true`, the batch stops at the first failed directive (as `stop_on_error`), and the changes
+	/// of the already applied directives (and of the failed one) are rolled back. (default false)
+	///
+	/// Note: The deleted paths are moved aside (e.g., `a.txt.udiffx-deleted-0`) until the end of the batch.
+	pub rollback_on_error: bool,
+
+	/// When `true`, the failed hunks of a `FILE_PATCH` are written to a `<file_path>.rej` file (as `git apply --reject`),
+	/// and the directive succeeds if at least one hunk applied. (default false)
+	pub write_rejects: bool,
+
+	/// When `true`, a directive whose end state is already there (e.g., the same `FileChanges` applied again by
+	/// a retried run) succeeds with `DirectiveStatus::unchanged` set, nothing written, instead of failing with
+	/// `Error::ApplyNoChanges` (or a path not found). (default false)
+	///
+	/// Besides the directives changing nothing, these are unchanged: a `FILE_DELETE` of a missing path, a `FILE_RENAME`
+	/// whose source is gone and target exists, a `FILE_COPY` whose target has the source content, and a `FILE_APPEND`,
+	/// `FILE_SPAN_PATCH`, or `FILE_INSERT` whose result is already in the file (see `verify_applied`).
+	///
+	/// Note: A `FILE_APPEND` re-appending the last lines of its file on purpose is then skipped.
+	pub idempotent: bool,
+
+	/// When `true`, each applied directive records the directives undoing its changes in
+	/// `DirectiveStatus::undo_directives`, and `ApplyChangesStatus::undo_changes` returns the `FileChanges`
+	/// reverting the whole batch. (default false)
+	///
+	/// Note: As with `rollback_on_error`, the deleted paths are moved aside until the end of the batch.
+	pub record_undo: bool,
+
+	/// When set, every file a `FILE_NEW` or a `FILE_COPY` overwrites, a `FILE_PATCH` modifies, or a `FILE_DELETE`
+	/// deletes (all the files of a deleted directory) is first copied into a timestamped backup tree,
+	/// `<backup_dir>/<unix millis>/<file_path>`, one per batch. (default None)
+	///
+	/// Relative to `base_dir` unless absolute. The backups are written to the store the batch is applied to
+	/// (e.g., as keys of the content map, which must then be under the map), and are not undone by `rollback_on_error`.
+	pub backup_dir: Option<SPath>,
+
+	/// When set, the texts reported in each `DirectiveStatus` (error message, warnings, failed hunks, no-op edits)
+	/// are redacted (see `DirectiveStatus::redact`) before the status is reported, including to the `ApplyProgress`.
+	/// (default None)
+	pub redactor: Option<Redactor>,
+
+	/// When set, the content a `FILE_NEW` / `FILE_APPEND` writes (the added lines for a `FILE_PATCH`) is scanned
+	/// for likely secrets (private keys, known credential tokens, high-entropy strings), reported in
+	/// `DirectiveStatus::secret_findings`, and the directive is refused (`SecretScanMode::Deny`)
+	/// or applied with a warning per finding (`SecretScanMode::Warn`). (default None)
+	pub secret_scan: Option<SecretScanMode>,
+
+	/// When `true`, the statuses do not echo the contents of the directives: the failed hunk bodies
+	/// and the no-op edit lines are left empty (see `DirectiveStatus::omit_content_echoes`),
+	/// to bound the memory of the status of a large batch. (default false)
+	pub omit_content_echoes: bool,
+
+	/// What a `FILE_NEW` does when its target file already exists (see `NewFileCollision`).
+	/// (default `NewFileCollision::Overwrite`)
+	pub new_file_collision: NewFileCollision,
+
+	/// What a `FILE_RENAME` does when its `to_path` already exists (see `RenameCollision`).
+	/// (default `RenameCollision::Error`)
+	pub rename_collision: RenameCollision,
+
+	/// When `true`, each directive writing a file records the origin of each line it wrote in
+	/// `DirectiveStatus::line_origins`, and `ApplyChangesStatus::provenance` returns, per file, the provenance
+	/// of each final line (unchanged, added or modified by a directive). (default false)
+	///
+	/// Note: A content spilled to a `BlobStore` is streamed, and has no line origins.
+	pub record_provenance: bool,
+
+	/// When `true`, a `FILE_PATCH` or an overwriting `FILE_NEW` records the unified diff of the content it replaced
+	/// to the content it wrote in `DirectiveStatus::applied_diff`, e.g., for a review UI. (default false)
+	pub record_applied_diffs: bool,
+
+	/// When set, the max bytes of a `DirectiveStatus::applied_diff`: a larger diff is moved to the
+	/// `applied_diff_store` (see `DirectiveStatus::applied_diff_path`), or else truncated. (default None)
+	pub max_applied_diff_bytes: Option<usize>,
+
+	/// The store of the applied diffs larger than `max_applied_diff_bytes`, kept on disk rather than in the status.
+	/// (default None)
+	pub applied_diff_store: Option<BlobStore>,
+
+	/// When `true`, the `base_dir` can be outside of the current dir (e.g., a daemon whose current dir is unrelated
+	/// to the target workspace), and the `base_dir` itself is then the containment root. (default false)
+	///
+	/// Note: The `SecurityPolicy` checks (containment in the base dir or the `writable_dirs`, guard globs)
+	///       still apply to every directive path.
+	pub allow_outside_cwd: bool,
+
+	/// When `true`, a read-only file (e.g., checked out read-only by a VCS) a directive writes has its read-only flag
+	/// temporarily cleared for the write, then restored, with a warning in the status. (default false)
+	///
+	/// Note: Only for the file system stores (the async apply writes at the end of the batch, and is not elevated).
+	pub elevate_read_only: bool,
+
+	/// The safety budget of the batch: when set, the max distinct files the batch writes, renames, or deletes.
+	/// The directive exceeding a budget limit fails (with what it already changed kept, unless `rollback_on_error`),
+	/// and the remaining directives are skipped. (default None)
+	pub max_files_touched: Option<usize>,
+
+	/// The safety budget of the batch: when set, the max total bytes the batch writes (see `max_files_touched`).
+	/// (default None)
+	pub max_bytes_written: Option<u64>,
+
+	/// The safety budget of the batch: when set, the max `FILE_DELETE` deletions (see `max_files_touched`).
+	/// (default None)
+	pub max_deletions: Option<usize>,
+
+	/// What a directive writing a file does when the file path is a symlink (see `SymlinkTarget`).
+	/// (default `SymlinkTarget::Follow`)
+	///
+	/// Note: The async apply knows the symlinks of its snapshot, loaded at the start of the batch (a symlink created
+	///       meanwhile by another process is followed).
+	pub symlink_target: SymlinkTarget,
+
+	/// How a `FILE_DELETE` deletes its path (see `DeleteStrategy`). (default `DeleteStrategy::Trash`)
+	pub delete_strategy: DeleteStrategy,
+
+	/// When set, a path a directive reads (e.g., a `FILE_PATCH` target, a `FILE_RENAME` source) that is missing
+	/// is first materialized with this hook (e.g., hydrated from a sparse checkout), see `Materializer`.
+	/// (default None)
+	///
+	/// Note: The hook is also invoked by the `validate_all_first` simulation, before any directive is applied.
+	pub materializer: Option<Materializer>,
+
+	/// The validators of the contents the directives write (e.g., a parse of the Rust files), see
+	/// `PostApplyValidator`. An invalid content fails its directive, and is not written. (default empty)
+	pub post_apply_validators: Vec<Arc<dyn PostApplyValidator>>,
+
+	/// When set, the destructive directives (deletes, renames, full-file overwrites) are only applied once approved
+	/// by this hook, see `Confirmer`. (default None)
+	///
+	/// Note: With `validate_all_first`, the hook is invoked by the simulation only (a rejection fails the batch).
+	pub confirmer: Option<Confirmer>,
+
+	/// When set, the directive error messages are rendered with these templates (e.g., in another language),
+	/// see `FeedbackTemplates`. (default None)
+	pub feedback_templates: Option<FeedbackTemplates>,
+}
+
+/// What a `FILE_NEW` does when its target file already exists (see `ApplyOptions::new_file_collision`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NewFileCollision {
+	/// The existing file is overwritten (a `FILE_NEW` with the same content is a no-op).
+	#[default]
+	Overwrite,
+	/// The existing file is kept, and the directive succeeds with a warning (nothing written).
+	Skip,
+	/// The directive fails with `Error::ApplyFileExists` (nothing written).
+	Error,
+	/// The existing file is first renamed to `<file_path>.orig` (or `.orig.1` to `.orig.9` when taken),
+	/// with a warning.
+	RenameExisting,
+}
+
+/// What a directive writing a file (e.g., `FILE_NEW`, `FILE_PATCH`) does when the file path is a symlink
+/// (see `ApplyOptions::symlink_target`).
+///
+/// Note: The link is always resolved by the security checks first, so a link resolving outside of the base dir
+///       (and of the `SecurityPolicy::writable_dirs`) is refused whatever this policy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkTarget {
+	/// The link target is written (as `fs::write` does).
+	#[default]
+	Follow,
+	/// The link itself is removed, and a regular file is written in its place (the link target is unchanged),
+	/// with a warning.
+	Replace,
+	/// The directive fails with `Error::ApplySymlinkTarget` (nothing written).
+	Refuse,
+}
+
+/// How a `FILE_DELETE` deletes its path (see `ApplyOptions::delete_strategy`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DeleteStrategy {
+	/// The path is moved to the trash (e.g., for a desktop use, recoverable).
+	#[default]
+	Trash,
+	/// The path is permanently deleted (e.g., in a container or a CI without trash).
+	Hard,
+	/// Nothing is deleted, and the directive succeeds with a warning (the deletion is only recorded).
+	Skip,
+}
+
+/// What a `FILE_RENAME` does when its `to_path` already exists (see `ApplyOptions::rename_collision`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RenameCollision {
+	/// The directive fails with `Error::ApplyRenameTargetExists` (nothing renamed).
+	#[default]
+	Error,
+	/// The existing `to_path` is replaced (backed up first with `ApplyOptions::backup_dir`), with a warning.
+	Overwrite,
+	/// Nothing is renamed, and the directive succeeds with a warning.
+	Skip,
+}
+
+impl From<Option<ApplyOptions>> for ApplyOptions {
+	fn from(opt: Option<ApplyOptions>) -> Self {
+		opt.unwrap_or_default()
+	}
+}
+
+/// Fluid apis
+impl ApplyOptions {
+	/// Override the context files with the given iterator.
+	pub fn with_context_files(mut self, paths: impl IntoIterator<Item = impl Into<String>>) -> Self {
+		self.context_files = paths.into_iter().map(|p| p.into()).collect();
+		self
+	}
+
+	/// Retarget missing directive paths to a unique close match.
+	pub fn with_fuzzy_path_resolution(mut self) -> Self {
+		self.fuzzy_path_resolution = true;
+		self
+	}
+
+	/// Validate the entire batch before writing anything (all-or-nothing).
+	pub fn with_validate_all_first(mut self) -> Self {
+		self.validate_all_first = true;
+		self
+	}
+
+	/// Normalize the escaped newlines/tabs of the patch bodies.
+	pub fn with_unescape_patch_bodies(mut self) -> Self {
+		self.unescape_patch_bodies = true;
+		self
+	}
+
+	/// Use these completion options for all the files (instead of the per-file `CompleteOptions::for_path`).
+	pub fn with_complete_options(mut self, complete_options: CompleteOptions) -> Self {
+		self.complete_options = Some(complete_options);
+		self
+	}
+
+	/// Refuse to patch the generated files (without the `allow_generated="true"` attribute).
+	pub fn with_refuse_generated(mut self) -> Self {
+		self.refuse_generated = true;
+		self
+	}
+
+	/// Re-prepend the license header a `FILE_NEW` overwrite drops.
+	pub fn with_preserve_license_headers(mut self) -> Self {
+		self.preserve_license_headers = true;
+		self
+	}
+
+	/// Stop the batch at the first failed directive.
+	pub fn with_stop_on_error(mut self) -> Self {
+		self.stop_on_error = true;
+		self
+	}
+
+	/// Stop the batch at the first failed directive, and roll back the changes already applied.
+	pub fn with_rollback_on_error(mut self) -> Self {
+		self.rollback_on_error = true;
+		self
+	}
+
+	/// Write the failed hunks to a `.rej` file next to the patched file.
+	pub fn with_write_rejects(mut self) -> Self {
+		self.write_rejects = true;
+		self
+	}
+
+	/// Make the directives already applied succeed as unchanged, so applying the same changes again is a no-op.
+	pub fn with_idempotent(mut self) -> Self {
+		self.idempotent = true;
+		self
+	}
+
+	/// Record the directives undoing the applied changes (see `ApplyChangesStatus::undo_changes`).
+	pub fn with_record_undo(mut self) -> Self {
+		self.record_undo = true;
+		self
+	}
+
+	/// Back up the overwritten, patched, and deleted files under this directory.
+	pub fn with_backup_dir(mut self, backup_dir: impl Into<SPath>) -> Self {
+		self.backup_dir = Some(backup_dir.into());
+		self
+	}
+
+	/// Redact the texts of the reported directive statuses.
+	pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+		self.redactor = Some(redactor);
+		self
+	}
+
+	/// Scan the contents to write for likely secrets, refusing or warning on the directives holding some.
+	pub fn with_secret_scan(mut self, mode: SecretScanMode) -> Self {
+		self.secret_scan = Some(mode);
+		self
+	}
+
+	/// Leave the failed hunk bodies and the no-op edit lines out of the statuses.
+	pub fn with_omit_content_echoes(mut self) -> Self {
+		self.omit_content_echoes = true;
+		self
+	}
+
+	/// Set what a `FILE_NEW` does when its target file already exists.
+	pub fn with_new_file_collision(mut self, collision: NewFileCollision) -> Self {
+		self.new_file_collision = collision;
+		self
+	}
+
+	/// Set what a `FILE_RENAME` does when its `to_path` already exists.
+	pub fn with_rename_collision(mut self, collision: RenameCollision) -> Self {
+		self.rename_collision = collision;
+		self
+	}
+
+	/// Record the origin of each written line (see `ApplyChangesStatus::provenance`).
+	pub fn with_record_provenance(mut self) -> Self {
+		self.record_provenance = true;
+		self
+	}
+
+	/// Record the unified diff each `FILE_PATCH` or overwriting `FILE_NEW` applied (see `DirectiveStatus::applied_diff`).
+	pub fn with_record_applied_diffs(mut self) -> Self {
+		self.record_applied_diffs = true;
+		self
+	}
+
+	/// Set the max bytes of an applied diff kept in the status (truncated beyond, unless an `applied_diff_store`).
+	pub fn with_max_applied_diff_bytes(mut self, max: usize) -> Self {
+		self.max_applied_diff_bytes = Some(max);
+		self
+	}
+
+	/// Set the store of the applied diffs larger than `max_applied_diff_bytes`.
+	pub fn with_applied_diff_store(mut self, store: BlobStore) -> Self {
+		self.applied_diff_store = Some(store);
+		self
+	}
+
+	/// Allow a `base_dir` outside of the current dir.
+	pub fn with_allow_outside_cwd(mut self) -> Self {
+		self.allow_outside_cwd = true;
+		self
+	}
+
+	/// Temporarily clear the read-only flag of the files written (restored after the write).
+	pub fn with_elevate_read_only(mut self) -> Self {
+		self.elevate_read_only = true;
+		self
+	}
+
+	/// Set the max distinct files the batch writes, renames, or deletes.
+	pub fn with_max_files_touched(mut self, max: usize) -> Self {
+		self.max_files_touched = Some(max);
+		self
+	}
+
+	/// Set the max total bytes the batch writes.
+	pub fn with_max_bytes_written(mut self, max: u64) -> Self {
+		self.max_bytes_written = Some(max);
+		self
+	}
+
+	/// Set the max deletions of the batch.
+	pub fn with_max_deletions(mut self, max: usize) -> Self {
+		self.max_deletions = Some(max);
+		self
+	}
+
+	/// Set what a directive writing a file does when the file path is a symlink.
+	pub fn with_symlink_target(mut self, symlink_target: SymlinkTarget) -> Self {
+		self.symlink_target = symlink_target;
+		self
+	}
+
+	/// Set how a `FILE_DELETE` deletes its path.
+	pub fn with_delete_strategy(mut self, delete_strategy: DeleteStrategy) -> Self {
+		self.delete_strategy = delete_strategy;
+		self
+	}
+
+	/// Set the hook materializing the missing paths the directives read.
+	pub fn with_materializer(mut self, materializer: Materializer) -> Self {
+		self.materializer = Some(materializer);
+		self
+	}
+
+	/// Append a validator of the contents the directives write.
+	pub fn with_post_apply_validator(mut self, validator: impl PostApplyValidator + 'static) -> Self {
+		self.post_apply_validators.push(Arc::new(validator));
+		self
+	}
+
+	/// Set the hook confirming the destructive directives.
+	pub fn with_confirmer(mut self, confirmer: Confirmer) -> Self {
+		self.confirmer = Some(confirmer);
+		self
+	}
+
+	/// Set the templates of the directive error messages.
+	pub fn with_feedback_templates(mut self, templates: FeedbackTemplates) -> Self {
+		self.feedback_templates = Some(templates);
+		self
+	}
+}
@@ -0,0 +1,19 @@
+use crate::{DirectiveStatus, FileDirective};
+
+/// Receives the per-directive progress of `apply_file_changes_with_progress` (e.g., to render a live progress in a UI).
+///
+/// Both methods do nothing by default, and `()` is the no-op progress.
+/// The in-memory simulation of `ApplyOptions::validate_all_first` does not report any progress.
+pub trait ApplyProgress {
+	/// Called before the directive at `index` (0-based, out of `total`) is applied.
+	fn on_directive_start(&mut self, index: usize, total: usize, directive: &FileDirective) {
+		let _ = (index, total, directive);
+	}
+
+	/// Called once the directive at `index` (0-based, out of `total`) is applied (successfully or not).
+	fn on_directive_done(&mut self, index: usize, total: usize, status: &DirectiveStatus) {
+		let _ = (index, total, status);
+	}
+}
+
+impl ApplyProgress for () {}
@@ -0,0 +1,330 @@
+use crate::{
+	ApplyChangesStatus, ApplyOptions, Error, FileChanges, Result, SecurityPolicy, apply_file_changes_with_options,
+	fs_guard,
+};
+use simple_fs::SPath;
+use std::collections::HashSet;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+/// A shareable (cheap `Clone`) apply service that serializes the batches per base dir,
+/// in submission order, so concurrent callers do not interleave their writes in the same workspace.
+///
+/// Batches targeting unrelated base dirs run concurrently, on a bounded pool of worker threads.
+///
+/// Note: The base dirs are compared resolved (absolute, collapsed, symlinks resolved), and nested base dirs
+///       (e.g., `a/` and `a/b/`) overlap, so their batches are serialized as well.
+#[derive(Debug, Clone, Default)]
+pub struct Applier {
+	inner: Arc<ApplierInner>,
+}
+
+/// A snapshot of the `Applier` queue counters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ApplierMetrics {
+	/// Batches submitted (or applied synchronously) since the `Applier` creation.
+	pub submitted: usize,
+	/// Batches finished (successfully or not).
+	pub finished: usize,
+	/// Batches finished with an `Err` (not counting per-directive failures).
+	pub failed: usize,
+	/// Batches waiting for their turn or running.
+	pub pending: usize,
+	/// Distinct (resolved) base dirs seen.
+	pub base_dirs: usize,
+}
+
+/// The handle of a batch submitted with `Applier::submit`.
+#[derive(Debug)]
+pub struct ApplyHandle {
+	result: Arc<BatchResult>,
+}
+
+impl ApplyHandle {
+	/// Blocks until the batch is applied, and returns its result.
+	pub fn wait(self) -> Result<ApplyChangesStatus> {
+		let mut slot = self.result.lock_slot();
+		loop {
+			if let Some(res) = slot.take() {
+				return res;
+			}
+			slot = self.result.ready.wait(slot).unwrap_or_else(PoisonError::into_inner);
+		}
+	}
+
+	pub fn is_finished(&self) -> bool {
+		self.result.lock_slot().is_some()
+	}
+}
+
+impl Applier {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Caps the worker threads applying the submitted batches (default the available parallelism).
+	pub fn with_max_workers(self, max_workers: usize) -> Self {
+		self.inner.lock_state().max_workers = Some(max_workers.max(1));
+		self
+	}
+
+	/// Queues the batch for `base_dir` and applies it on a worker thread once the previous batches
+	/// of the same (or an overlapping) base dir are done (see `apply_file_changes_with_options` for the arguments).
+	pub fn submit(
+		&self,
+		base_dir: impl Into<SPath>,
+		file_changes: FileChanges,
+		security_policy: impl Into<SecurityPolicy>,
+		options: impl Into<ApplyOptions>,
+	) -> ApplyHandle {
+		let base_dir = base_dir.into();
+		let policy: SecurityPolicy = security_policy.into();
+		let options: ApplyOptions = options.into();
+
+		let dir_key = dir_key(&base_dir);
+		let result = Arc::new(BatchResult::default());
+		let task = Task {
+			job: Box::new(move || apply_file_changes_with_options(base_dir, file_changes, policy, options)),
+			result: result.clone(),
+		};
+		ApplierInner::enqueue(&self.inner, dir_key, Some(task));
+
+		ApplyHandle { result }
+	}
+
+	/// Same as `submit`, but applies on the current thread (blocking until the batch's turn).
+	pub fn apply(
+		&self,
+		base_dir: impl Into<SPath>,
+		file_changes: FileChanges,
+		security_policy: impl Into<SecurityPolicy>,
+		options: impl Into<ApplyOptions>,
+	) -> Result<ApplyChangesStatus> {
+		let base_dir = base_dir.into();
+		let id = ApplierInner::enqueue(&self.inner, dir_key(&base_dir), None);
+		self.inner.wait_turn(id);
+
+		let mut finish = FinishGuard {
+			inner: &self.inner,
+			id,
+			failed: false,
+		};
+		let res = apply_file_changes_with_options(base_dir, file_changes, security_policy, options);
+		finish.failed = res.is_err();
+		res
+	}
+
+	pub fn metrics(&self) -> ApplierMetrics {
+		let state = self.inner.lock_state();
+		ApplierMetrics {
+			submitted: state.submitted,
+			finished: state.finished,
+			failed: state.failed,
+			pending: state.batches.len(),
+			base_dirs: state.base_dirs.len(),
+		}
+	}
+}
+
+// region:    --- Support
+
+type Job = Box<dyn FnOnce() -> Result<ApplyChangesStatus> + Send>;
+
+#[derive(Debug, Default)]
+struct ApplierInner {
+	state: Mutex<SchedulerState>,
+	/// Notified when a batch is queued or finished.
+	changed: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct SchedulerState {
+	/// The unfinished batches (waiting or running), in submission order.
+	batches: Vec<Batch>,
+	next_id: u64,
+	workers: usize,
+	max_workers: Option<usize>,
+	base_dirs: HashSet<String>,
+	submitted: usize,
+	finished: usize,
+	failed: usize,
+}
+
+#[derive(Debug)]
+struct Batch {
+	id: u64,
+	dir_key: String,
+	running: bool,
+	/// The job of a submitted batch, until a worker takes it (`None` for the `Applier::apply` batches).
+	task: Option<Task>,
+}
+
+struct Task {
+	job: Job,
+	result: Arc<BatchResult>,
+}
+
+impl fmt::Debug for Task {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Task").finish_non_exhaustive()
+	}
+}
+
+/// The result slot of a submitted batch, set by the worker once the batch is finished.
+#[derive(Debug, Default)]
+struct BatchResult {
+	slot: Mutex<Option<Result<ApplyChangesStatus>>>,
+	ready: Condvar,
+}
+
+impl BatchResult {
+	fn lock_slot(&self) -> MutexGuard<'_, Option<Result<ApplyChangesStatus>>> {
+		self.slot.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	fn set(&self, res: Result<ApplyChangesStatus>) {
+		*self.lock_slot() = Some(res);
+		self.ready.notify_all();
+	}
+}
+
+impl ApplierInner {
+	fn lock_state(&self) -> MutexGuard<'_, SchedulerState> {
+		self.state.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	/// Appends the batch to the queue, and starts a worker for a submitted batch if the pool is not full.
+	fn enqueue(inner: &Arc<Self>, dir_key: String, task: Option<Task>) -> u64 {
+		let mut state = inner.lock_state();
+		let id = state.next_id;
+		state.next_id += 1;
+		state.submitted += 1;
+		state.base_dirs.insert(dir_key.clone());
+
+		let start_worker = task.is_some() && state.workers < state.max_workers.unwrap_or_else(default_max_workers);
+		state.batches.push(Batch {
+			id,
+			dir_key,
+			running: false,
+			task,
+		});
+		if start_worker {
+			state.workers += 1;
+			let inner = inner.clone();
+			thread::spawn(move || inner.run_worker());
+		}
+		drop(state);
+		inner.changed.notify_all();
+
+		id
+	}
+
+	/// Runs the runnable submitted batches, and exits once no submitted batch is left waiting.
+	fn run_worker(&self) {
+		let mut state = self.lock_state();
+		loop {
+			if let Some((id, task)) = state.take_next_task() {
+				drop(state);
+				let res = panic::catch_unwind(AssertUnwindSafe(task.job))
+					.unwrap_or_else(|_| Err(Error::custom("apply batch panicked")));
+				// Note: Finished before the result is published, so the metrics are up to date after `wait`.
+				self.finish(id, res.is_err());
+				task.result.set(res);
+				state = self.lock_state();
+			} else if state.batches.iter().any(|batch| batch.task.is_some()) {
+				state = self.changed.wait(state).unwrap_or_else(PoisonError::into_inner);
+			} else {
+				state.workers -= 1;
+				return;
+			}
+		}
+	}
+
+	/// Blocks until the (`Applier::apply`) batch is runnable, and marks it running.
+	fn wait_turn(&self, id: u64) {
+		let mut state = self.lock_state();
+		loop {
+			if let Some(idx) = state.position(id)
+				&& state.is_runnable(idx)
+			{
+				state.batches[idx].running = true;
+				return;
+			}
+			state = self.changed.wait(state).unwrap_or_else(PoisonError::into_inner);
+		}
+	}
+
+	fn finish(&self, id: u64, failed: bool) {
+		let mut state = self.lock_state();
+		if let Some(idx) = state.position(id) {
+			state.batches.remove(idx);
+		}
+		state.finished += 1;
+		if failed {
+			state.failed += 1;
+		}
+		drop(state);
+		self.changed.notify_all();
+	}
+}
+
+impl SchedulerState {
+	fn position(&self, id: u64) -> Option<usize> {
+		self.batches.iter().position(|batch| batch.id == id)
+	}
+
+	/// Whether the batch can start: not started yet, and no earlier unfinished batch overlaps its base dir.
+	fn is_runnable(&self, idx: usize) -> bool {
+		let batch = &self.batches[idx];
+		!batch.running
+			&& !self.batches[..idx]
+				.iter()
+				.any(|earlier| dirs_overlap(&earlier.dir_key, &batch.dir_key))
+	}
+
+	/// Takes the task of the first runnable submitted batch, and marks it running.
+	fn take_next_task(&mut self) -> Option<(u64, Task)> {
+		let idx = (0..self.batches.len()).find(|&idx| self.batches[idx].task.is_some() && self.is_runnable(idx))?;
+		let batch = &mut self.batches[idx];
+		batch.running = true;
+		Some((batch.id, batch.task.take()?))
+	}
+}
+
+/// Finishes the (`Applier::apply`) batch on drop, so the next batches are not blocked if the apply panics.
+struct FinishGuard<'a> {
+	inner: &'a ApplierInner,
+	id: u64,
+	failed: bool,
+}
+
+impl Drop for FinishGuard<'_> {
+	fn drop(&mut self) {
+		self.inner.finish(self.id, self.failed);
+	}
+}
+
+fn default_max_workers() -> usize {
+	thread::available_parallelism().map_or(4, usize::from)
+}
+
+/// The absolute, collapsed base dir (as resolved by `apply_file_changes_with_options`), with its symlinks resolved,
+/// so the different spellings of a base dir share the same key.
+fn dir_key(base_dir: &SPath) -> String {
+	let base_dir = match std::env::current_dir() {
+		Ok(cwd) if !base_dir.is_absolute() => SPath::from_std_path(cwd)
+			.map(|cwd| cwd.join(base_dir))
+			.unwrap_or_else(|_| base_dir.clone()),
+		_ => base_dir.clone(),
+	};
+	fs_guard::resolve_symlinks(&base_dir).to_string()
+}
+
+/// Whether one base dir is the other, or contains it.
+fn dirs_overlap(a: &str, b: &str) -> bool {
+	fs_guard::is_path_under(a, b) || fs_guard::is_path_under(b, a)
+}
+
+// endregion: --- Support
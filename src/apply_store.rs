@@ -0,0 +1,727 @@
+use crate::path_resolver::list_rel_paths;
+use crate::{ApplyOptions, DeleteStrategy, Error, Result, SymlinkTarget};
+use simple_fs::{SPath, ensure_file_dir, safer_remove_dir, safer_remove_file, safer_trash_dir, safer_trash_file};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The file operations the applier performs on its target.
+///
+/// Paths are absolute (already joined with the `base_dir` and guard-checked by the applier).
+pub(crate) trait ApplyStore {
+	fn exists(&self, path: &SPath) -> bool;
+
+	fn is_dir(&self, path: &SPath) -> bool;
+
+	fn read_to_string(&self, path: &SPath) -> Result<String>;
+
+	fn read_bytes(&self, path: &SPath) -> Result<Vec<u8>>;
+
+	/// Writes the file, creating the parent directories if needed.
+	fn write(&mut self, path: &SPath, content: &[u8]) -> Result<()>;
+
+	/// Renames a file or directory, creating the destination parent directories if needed.
+	fn rename(&mut self, from: &SPath, to: &SPath) -> Result<()>;
+
+	/// Deletes a file or a directory (recursively), to the trash if the store has one.
+	fn delete(&mut self, path: &SPath) -> Result<()>;
+
+	/// Permanently deletes a file or a directory (recursively), never to the trash.
+	/// By default, as `delete` (a store without trash).
+	fn remove(&mut self, path: &SPath) -> Result<()> {
+		self.delete(path)
+	}
+
+	/// Lists the files under `base_dir`, as relative paths (for the fuzzy path resolution).
+	fn list_rel_paths(&self, base_dir: &SPath) -> Result<Vec<String>>;
+
+	/// Whether the store is virtual (e.g., in memory), its paths not being on the local disk: the guard checks
+	/// them lexically (no symlinks to resolve). By default, `false`.
+	fn is_virtual(&self) -> bool {
+		false
+	}
+
+	/// Whether the file is read-only. By default, `false` (a store without permissions).
+	fn is_read_only(&self, _path: &SPath) -> bool {
+		false
+	}
+
+	/// Sets (or clears) the read-only flag of the file. By default, does nothing.
+	fn set_read_only(&mut self, _path: &SPath, _read_only: bool) -> Result<()> {
+		Ok(())
+	}
+
+	/// Whether the path itself is a symlink. By default, `false` (a store without links).
+	fn is_symlink(&self, _path: &SPath) -> bool {
+		false
+	}
+
+	/// Removes the symlink itself (not its target). By default, does nothing.
+	fn remove_symlink(&mut self, _path: &SPath) -> Result<()> {
+		Ok(())
+	}
+
+	/// The target of the symlink, as stored in the link. By default, `None` (a store without links).
+	fn read_link(&self, _path: &SPath) -> Option<PathBuf> {
+		None
+	}
+
+	/// Creates the symlink `path` to `target` (e.g., to restore a replaced symlink on rollback).
+	/// By default, does nothing.
+	fn create_symlink(&mut self, _path: &SPath, _target: &Path) -> Result<()> {
+		Ok(())
+	}
+
+	/// Writes the backup of a file (see `ApplyOptions::backup_dir`). By default, writes it with `write`.
+	///
+	/// The wrapping stores pass it to their base store as is (a backup is not journaled, charged, or validated).
+	fn write_backup(&mut self, path: &SPath, content: &[u8]) -> Result<()> {
+		self.write(path, content)
+	}
+
+	/// Whether the two existing paths are the same file (e.g., differing only by case on a case-insensitive
+	/// file system). By default, whether the paths are equal (a store with exact keys).
+	fn is_same_file(&self, a: &SPath, b: &SPath) -> bool {
+		a == b
+	}
+
+	/// Writes (or appends to) the file with the content of the blob file at `blob_path` (see `BlobStore`),
+	/// creating the parent directories if needed.
+	///
+	/// By default, the blob is read in memory and written with `write`.
+	fn write_blob(&mut self, path: &SPath, blob_path: &SPath, append: bool) -> Result<()> {
+		let blob = fs::read(blob_path).map_err(|err| Error::io_read_file(blob_path.to_string(), err))?;
+		let content = if append && self.exists(path) {
+			let mut content = self.read_bytes(path)?;
+			content.extend(blob);
+			content
+		} else {
+			blob
+		};
+		self.write(path, &content)
+	}
+}
+
+// region:    --- DiskStore
+
+/// The store writing to the actual file system.
+pub(crate) struct DiskStore;
+
+impl ApplyStore for DiskStore {
+	fn exists(&self, path: &SPath) -> bool {
+		path.exists()
+	}
+
+	fn is_dir(&self, path: &SPath) -> bool {
+		path.is_dir()
+	}
+
+	fn read_to_string(&self, path: &SPath) -> Result<String> {
+		simple_fs::read_to_string(path).map_err(Error::simple_fs)
+	}
+
+	fn read_bytes(&self, path: &SPath) -> Result<Vec<u8>> {
+		fs::read(path).map_err(|err| Error::io_read_file(path.to_string(), err))
+	}
+
+	fn write(&mut self, path: &SPath, content: &[u8]) -> Result<()> {
+		let existed = path.exists();
+		ensure_file_dir(path).map_err(Error::simple_fs)?;
+		fs::write(path, content).map_err(|err| {
+			if existed {
+				Error::io_write_file(path.to_string(), err)
+			} else {
+				Error::io_create_file(path.to_string(), err)
+			}
+		})
+	}
+
+	fn rename(&mut self, from: &SPath, to: &SPath) -> Result<()> {
+		ensure_file_dir(to).map_err(Error::simple_fs)?;
+		fs::rename(from, to).map_err(|err| Error::io_rename_path(from.to_string(), to.to_string(), err))
+	}
+
+	fn list_rel_paths(&self, base_dir: &SPath) -> Result<Vec<String>> {
+		list_rel_paths(base_dir)
+	}
+
+	/// Streams the blob to the file, without loading it in memory.
+	fn write_blob(&mut self, path: &SPath, blob_path: &SPath, append: bool) -> Result<()> {
+		let mut blob = fs::File::open(blob_path).map_err(|err| Error::io_read_file(blob_path.to_string(), err))?;
+		ensure_file_dir(path).map_err(Error::simple_fs)?;
+		let mut file = fs::OpenOptions::new()
+			.create(true)
+			.write(true)
+			.append(append)
+			.truncate(!append)
+			.open(path)
+			.map_err(|err| Error::io_write_file(path.to_string(), err))?;
+		std::io::copy(&mut blob, &mut file).map_err(|err| Error::io_write_file(path.to_string(), err))?;
+		Ok(())
+	}
+
+	fn is_read_only(&self, path: &SPath) -> bool {
+		fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().readonly())
+	}
+
+	fn set_read_only(&mut self, path: &SPath, read_only: bool) -> Result<()> {
+		let mut permissions = fs::metadata(path)
+			.map_err(|err| Error::io_write_file(path.to_string(), err))?
+			.permissions();
+		// Note: On Unix, clearing the flag makes the file writable by its owner only (as the write needs).
+		permissions.set_readonly(read_only);
+		fs::set_permissions(path, permissions).map_err(|err| Error::io_write_file(path.to_string(), err))
+	}
+
+	fn is_symlink(&self, path: &SPath) -> bool {
+		fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_symlink())
+	}
+
+	fn is_same_file(&self, a: &SPath, b: &SPath) -> bool {
+		is_same_disk_file(a, b)
+	}
+
+	fn remove_symlink(&mut self, path: &SPath) -> Result<()> {
+		fs::remove_file(path).map_err(|err| Error::io_delete_file(path.to_string(), err))
+	}
+
+	fn read_link(&self, path: &SPath) -> Option<PathBuf> {
+		fs::read_link(path).ok()
+	}
+
+	fn create_symlink(&mut self, path: &SPath, target: &Path) -> Result<()> {
+		ensure_file_dir(path).map_err(Error::simple_fs)?;
+		#[cfg(unix)]
+		let res = std::os::unix::fs::symlink(target, path);
+		#[cfg(windows)]
+		let res = std::os::windows::fs::symlink_file(target, path);
+		res.map_err(|err| Error::io_create_file(path.to_string(), err))
+	}
+
+	fn delete(&mut self, path: &SPath) -> Result<()> {
+		if path.is_dir() {
+			safer_trash_dir(path, ()).map_err(|err| Error::io_delete_dir_all(path.to_string(), err))?;
+		} else {
+			safer_trash_file(path, ()).map_err(|err| Error::io_delete_file(path.to_string(), err))?;
+		}
+		Ok(())
+	}
+
+	fn remove(&mut self, path: &SPath) -> Result<()> {
+		if path.is_dir() {
+			safer_remove_dir(path, ()).map_err(|err| Error::io_delete_dir_all(path.to_string(), err))?;
+		} else {
+			safer_remove_file(path, ()).map_err(|err| Error::io_delete_file(path.to_string(), err))?;
+		}
+		Ok(())
+	}
+}
+
+/// Whether the two paths are the same entry on disk (same device and inode, or same canonical path on non-Unix).
+pub(crate) fn is_same_disk_file(a: &SPath, b: &SPath) -> bool {
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::MetadataExt;
+		match (fs::symlink_metadata(a), fs::symlink_metadata(b)) {
+			(Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+			_ => false,
+		}
+	}
+	#[cfg(not(unix))]
+	match (fs::canonicalize(a), fs::canonicalize(b)) {
+		(Ok(a), Ok(b)) => a == b,
+		_ => false,
+	}
+}
+
+// endregion: --- DiskStore
+
+// region:    --- OverlayStore
+
+/// An in-memory overlay on top of another store (e.g., the file system), used to simulate a batch with zero writes.
+///
+/// Reads fall through to the base store unless the path was written, renamed, or deleted in the overlay.
+pub(crate) struct OverlayStore<'a, S: ApplyStore> {
+	base: &'a S,
+	/// Written files. `None` means the file was deleted (or renamed away).
+	files: HashMap<String, Option<OverlayFile>>,
+	/// Directories deleted or renamed away (everything under them is gone).
+	removed_dirs: Vec<String>,
+	/// Directories created by a rename.
+	added_dirs: HashSet<String>,
+}
+
+/// A file written in an `OverlayStore`.
+enum OverlayFile {
+	Content(Vec<u8>),
+	/// Written from a blob file (see `ApplyStore::write_blob`), only loaded when read: the `head` content (the existing
+	/// content, when appended), then the blob.
+	Blob {
+		head: Vec<u8>,
+		blob_path: SPath,
+	},
+}
+
+impl OverlayFile {
+	fn load(&self) -> Result<Vec<u8>> {
+		match self {
+			OverlayFile::Content(content) => Ok(content.clone()),
+			OverlayFile::Blob { head, blob_path } => {
+				let blob = fs::read(blob_path).map_err(|err| Error::io_read_file(blob_path.to_string(), err))?;
+				Ok([head.as_slice(), &blob].concat())
+			}
+		}
+	}
+}
+
+impl<'a, S: ApplyStore> OverlayStore<'a, S> {
+	pub(crate) fn new(base: &'a S) -> Self {
+		Self {
+			base,
+			files: HashMap::new(),
+			removed_dirs: Vec::new(),
+			added_dirs: HashSet::new(),
+		}
+	}
+
+	fn is_removed_by_dir(&self, path: &SPath) -> bool {
+		self.removed_dirs
+			.iter()
+			.any(|dir| path.as_str() == dir || path.as_str().starts_with(&format!("{dir}/")))
+	}
+}
+
+impl<S: ApplyStore> ApplyStore for OverlayStore<'_, S> {
+	fn exists(&self, path: &SPath) -> bool {
+		if let Some(entry) = self.files.get(path.as_str()) {
+			return entry.is_some();
+		}
+		if self.added_dirs.contains(path.as_str()) {
+			return true;
+		}
+		!self.is_removed_by_dir(path) && self.base.exists(path)
+	}
+
+	fn is_dir(&self, path: &SPath) -> bool {
+		if self.files.contains_key(path.as_str()) {
+			return false;
+		}
+		if self.added_dirs.contains(path.as_str()) {
+			return true;
+		}
+		!self.is_removed_by_dir(path) && self.base.is_dir(path)
+	}
+
+	fn read_to_string(&self, path: &SPath) -> Result<String> {
+		let bytes = self.read_bytes(path)?;
+		String::from_utf8(bytes).map_err(|err| Error::io_read_file(path.to_string(), err))
+	}
+
+	fn read_bytes(&self, path: &SPath) -> Result<Vec<u8>> {
+		match self.files.get(path.as_str()) {
+			Some(Some(file)) => file.load(),
+			Some(None) => Err(Error::apply_path_not_found("read", path.to_string())),
+			None if self.is_removed_by_dir(path) => Err(Error::apply_path_not_found("read", path.to_string())),
+			None => self.base.read_bytes(path),
+		}
+	}
+
+	fn write(&mut self, path: &SPath, content: &[u8]) -> Result<()> {
+		self.files
+			.insert(path.to_string(), Some(OverlayFile::Content(content.to_vec())));
+		Ok(())
+	}
+
+	fn rename(&mut self, from: &SPath, to: &SPath) -> Result<()> {
+		if self.is_dir(from) {
+			self.removed_dirs.push(from.to_string());
+			self.added_dirs.insert(to.to_string());
+		} else {
+			// Note: A file written in the overlay is moved as is (a blob is not loaded).
+			let file = match self.files.get_mut(from.as_str()).and_then(Option::take) {
+				Some(file) => file,
+				None => OverlayFile::Content(self.read_bytes(from)?),
+			};
+			self.files.insert(from.to_string(), None);
+			self.files.insert(to.to_string(), Some(file));
+		}
+		Ok(())
+	}
+
+	/// Keeps the blob path, the blob being only loaded when read.
+	fn write_blob(&mut self, path: &SPath, blob_path: &SPath, append: bool) -> Result<()> {
+		let head = if append && self.exists(path) {
+			self.read_bytes(path)?
+		} else {
+			Vec::new()
+		};
+		let blob_path = blob_path.clone();
+		self.files
+			.insert(path.to_string(), Some(OverlayFile::Blob { head, blob_path }));
+		Ok(())
+	}
+
+	fn list_rel_paths(&self, base_dir: &SPath) -> Result<Vec<String>> {
+		// Note: The overlay changes are not listed, the fuzzy resolution only targets pre-existing files.
+		self.base.list_rel_paths(base_dir)
+	}
+
+	fn is_virtual(&self) -> bool {
+		self.base.is_virtual()
+	}
+
+	fn is_symlink(&self, path: &SPath) -> bool {
+		!self.files.contains_key(path.as_str()) && !self.is_removed_by_dir(path) && self.base.is_symlink(path)
+	}
+
+	fn is_same_file(&self, a: &SPath, b: &SPath) -> bool {
+		if self.files.contains_key(a.as_str()) || self.files.contains_key(b.as_str()) {
+			return a == b;
+		}
+		self.base.is_same_file(a, b)
+	}
+
+	fn delete(&mut self, path: &SPath) -> Result<()> {
+		if self.is_dir(path) {
+			self.added_dirs.remove(path.as_str());
+			self.removed_dirs.push(path.to_string());
+		} else {
+			self.files.insert(path.to_string(), None);
+		}
+		Ok(())
+	}
+}
+
+// endregion: --- OverlayStore
+
+// region:    --- CachedStore
+
+/// A write-through cache on top of another store, keeping the content of the files written during a batch,
+/// so a later directive on the same file (e.g., a second `FILE_PATCH`) reads the content the previous one produced
+/// from memory rather than from the base store.
+///
+/// Only the written files are cached (a file read but never written is not kept in memory).
+pub(crate) struct CachedStore<'a, S: ApplyStore> {
+	base: &'a mut S,
+	/// The content of the files written through this store, by absolute path.
+	written: HashMap<String, Vec<u8>>,
+}
+
+impl<'a, S: ApplyStore> CachedStore<'a, S> {
+	pub(crate) fn new(base: &'a mut S) -> Self {
+		Self {
+			base,
+			written: HashMap::new(),
+		}
+	}
+
+	pub(crate) fn base_mut(&mut self) -> &mut S {
+		self.base
+	}
+
+	/// Drops all the cached files (e.g., after the base store was written beneath this cache).
+	pub(crate) fn clear(&mut self) {
+		self.written.clear();
+	}
+
+	/// Drops the cached files at or under `path`.
+	fn evict(&mut self, path: &SPath) {
+		let dir_prefix = format!("{path}/");
+		self.written
+			.retain(|cached, _| cached != path.as_str() && !cached.starts_with(&dir_prefix));
+	}
+}
+
+impl<S: ApplyStore> ApplyStore for CachedStore<'_, S> {
+	fn exists(&self, path: &SPath) -> bool {
+		self.written.contains_key(path.as_str()) || self.base.exists(path)
+	}
+
+	fn is_dir(&self, path: &SPath) -> bool {
+		!self.written.contains_key(path.as_str()) && self.base.is_dir(path)
+	}
+
+	fn read_to_string(&self, path: &SPath) -> Result<String> {
+		match self.written.get(path.as_str()) {
+			Some(content) => {
+				String::from_utf8(content.clone()).map_err(|err| Error::io_read_file(path.to_string(), err))
+			}
+			None => self.base.read_to_string(path),
+		}
+	}
+
+	fn read_bytes(&self, path: &SPath) -> Result<Vec<u8>> {
+		match self.written.get(path.as_str()) {
+			Some(content) => Ok(content.clone()),
+			None => self.base.read_bytes(path),
+		}
+	}
+
+	fn write(&mut self, path: &SPath, content: &[u8]) -> Result<()> {
+		self.base.write(path, content)?;
+		self.written.insert(path.to_string(), content.to_vec());
+		Ok(())
+	}
+
+	fn rename(&mut self, from: &SPath, to: &SPath) -> Result<()> {
+		self.base.rename(from, to)?;
+		let moved = self.written.remove(from.as_str());
+		self.evict(from);
+		self.evict(to);
+		if let Some(content) = moved {
+			self.written.insert(to.to_string(), content);
+		}
+		Ok(())
+	}
+
+	fn delete(&mut self, path: &SPath) -> Result<()> {
+		self.base.delete(path)?;
+		self.evict(path);
+		Ok(())
+	}
+
+	fn remove(&mut self, path: &SPath) -> Result<()> {
+		self.base.remove(path)?;
+		self.evict(path);
+		Ok(())
+	}
+
+	fn list_rel_paths(&self, base_dir: &SPath) -> Result<Vec<String>> {
+		self.base.list_rel_paths(base_dir)
+	}
+
+	fn is_virtual(&self) -> bool {
+		self.base.is_virtual()
+	}
+
+	fn is_read_only(&self, path: &SPath) -> bool {
+		self.base.is_read_only(path)
+	}
+
+	fn set_read_only(&mut self, path: &SPath, read_only: bool) -> Result<()> {
+		self.base.set_read_only(path, read_only)
+	}
+
+	fn is_symlink(&self, path: &SPath) -> bool {
+		!self.written.contains_key(path.as_str()) && self.base.is_symlink(path)
+	}
+
+	fn write_backup(&mut self, path: &SPath, content: &[u8]) -> Result<()> {
+		self.base.write_backup(path, content)
+	}
+
+	fn is_same_file(&self, a: &SPath, b: &SPath) -> bool {
+		self.base.is_same_file(a, b)
+	}
+
+	fn remove_symlink(&mut self, path: &SPath) -> Result<()> {
+		self.base.remove_symlink(path)
+	}
+
+	fn read_link(&self, path: &SPath) -> Option<PathBuf> {
+		self.base.read_link(path)
+	}
+
+	fn create_symlink(&mut self, path: &SPath, target: &Path) -> Result<()> {
+		self.base.create_symlink(path, target)?;
+		self.evict(path);
+		Ok(())
+	}
+
+	/// Delegates to the base store (e.g., to stream the blob to disk), and drops the cached content.
+	fn write_blob(&mut self, path: &SPath, blob_path: &SPath, append: bool) -> Result<()> {
+		self.base.write_blob(path, blob_path, append)?;
+		self.evict(path);
+		Ok(())
+	}
+}
+
+// endregion: --- CachedStore
+
+// region:    --- WriteTargetStore
+
+/// The action a `WriteTargetStore` took on the target of a write, to be reported as a warning.
+pub(crate) enum WriteTargetAction {
+	/// The read-only flag was temporarily cleared (see `ApplyOptions::elevate_read_only`).
+	ReadOnlyElevated(SPath),
+	/// The symlink was replaced by a regular file (see `SymlinkTarget::Replace`), with the link target
+	/// (to restore it on rollback).
+	SymlinkReplaced { path: SPath, target: Option<PathBuf> },
+}
+
+/// A store on top of another one, applying the write target options to the files it writes or deletes:
+/// - a read-only file has its read-only flag temporarily cleared (and restored), when `elevate_read_only`,
+/// - a symlink is written through, replaced by a regular file, or refused (see `SymlinkTarget`),
+/// - a deleted path goes to the trash, or is permanently deleted (see `DeleteStrategy`).
+///
+/// The actions taken are kept until taken with `take_actions`, to be reported as warnings.
+pub(crate) struct WriteTargetStore<'a, S: ApplyStore> {
+	base: &'a mut S,
+	elevate_read_only: bool,
+	symlink_target: SymlinkTarget,
+	delete_strategy: DeleteStrategy,
+	actions: Vec<WriteTargetAction>,
+}
+
+impl<'a, S: ApplyStore> WriteTargetStore<'a, S> {
+	pub(crate) fn new(base: &'a mut S, options: &ApplyOptions) -> Self {
+		Self {
+			base,
+			elevate_read_only: options.elevate_read_only,
+			symlink_target: options.symlink_target,
+			delete_strategy: options.delete_strategy,
+			actions: Vec::new(),
+		}
+	}
+
+	/// Takes the actions taken on the written files, since the last call.
+	pub(crate) fn take_actions(&mut self) -> Vec<WriteTargetAction> {
+		std::mem::take(&mut self.actions)
+	}
+
+	/// Runs the write `op` on `path`, with the symlink target policy and the read-only elevation applied.
+	fn write_target(&mut self, path: &SPath, op: impl FnOnce(&mut S) -> Result<()>) -> Result<()> {
+		if self.base.is_symlink(path) {
+			match self.symlink_target {
+				SymlinkTarget::Follow => (),
+				SymlinkTarget::Replace => {
+					let target = self.base.read_link(path);
+					self.base.remove_symlink(path)?;
+					self.actions.push(WriteTargetAction::SymlinkReplaced {
+						path: path.clone(),
+						target,
+					});
+				}
+				SymlinkTarget::Refuse => return Err(Error::apply_symlink_target(path.to_string())),
+			}
+		}
+
+		if !self.elevate_read_only || !self.base.is_read_only(path) {
+			return op(self.base);
+		}
+		self.base.set_read_only(path, false)?;
+		let res = op(self.base);
+		let restored = self.base.set_read_only(path, true);
+		self.actions.push(WriteTargetAction::ReadOnlyElevated(path.clone()));
+		res.and(restored)
+	}
+}
+
+impl<S: ApplyStore> ApplyStore for WriteTargetStore<'_, S> {
+	fn exists(&self, path: &SPath) -> bool {
+		self.base.exists(path)
+	}
+
+	fn is_dir(&self, path: &SPath) -> bool {
+		self.base.is_dir(path)
+	}
+
+	fn read_to_string(&self, path: &SPath) -> Result<String> {
+		self.base.read_to_string(path)
+	}
+
+	fn read_bytes(&self, path: &SPath) -> Result<Vec<u8>> {
+		self.base.read_bytes(path)
+	}
+
+	fn write(&mut self, path: &SPath, content: &[u8]) -> Result<()> {
+		self.write_target(path, |base| base.write(path, content))
+	}
+
+	fn rename(&mut self, from: &SPath, to: &SPath) -> Result<()> {
+		self.base.rename(from, to)
+	}
+
+	/// Note: `DeleteStrategy::Skip` only applies to the `FILE_DELETE` directives (skipped by the applier),
+	///       the other deletions (e.g., a path a `FILE_RENAME` overwrites) go to the trash.
+	fn delete(&mut self, path: &SPath) -> Result<()> {
+		match self.delete_strategy {
+			DeleteStrategy::Trash | DeleteStrategy::Skip => self.base.delete(path),
+			DeleteStrategy::Hard => self.base.remove(path),
+		}
+	}
+
+	fn remove(&mut self, path: &SPath) -> Result<()> {
+		self.base.remove(path)
+	}
+
+	fn list_rel_paths(&self, base_dir: &SPath) -> Result<Vec<String>> {
+		self.base.list_rel_paths(base_dir)
+	}
+
+	fn is_virtual(&self) -> bool {
+		self.base.is_virtual()
+	}
+
+	fn is_read_only(&self, path: &SPath) -> bool {
+		self.base.is_read_only(path)
+	}
+
+	fn set_read_only(&mut self, path: &SPath, read_only: bool) -> Result<()> {
+		self.base.set_read_only(path, read_only)
+	}
+
+	fn is_symlink(&self, path: &SPath) -> bool {
+		self.base.is_symlink(path)
+	}
+
+	fn write_backup(&mut self, path: &SPath, content: &[u8]) -> Result<()> {
+		self.base.write_backup(path, content)
+	}
+
+	fn is_same_file(&self, a: &SPath, b: &SPath) -> bool {
+		self.base.is_same_file(a, b)
+	}
+
+	fn remove_symlink(&mut self, path: &SPath) -> Result<()> {
+		self.base.remove_symlink(path)
+	}
+
+	fn read_link(&self, path: &SPath) -> Option<PathBuf> {
+		self.base.read_link(path)
+	}
+
+	fn create_symlink(&mut self, path: &SPath, target: &Path) -> Result<()> {
+		self.base.create_symlink(path, target)
+	}
+
+	fn write_blob(&mut self, path: &SPath, blob_path: &SPath, append: bool) -> Result<()> {
+		self.write_target(path, |base| base.write_blob(path, blob_path, append))
+	}
+}
+
+// endregion: --- WriteTargetStore
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_apply_store_overlay_write_blob() -> Result<()> {
+		// -- Setup & Fixtures
+		let dir = std::env::temp_dir().join(format!("udiffx-test-overlay-blob-{}", std::process::id()));
+		std::fs::create_dir_all(&dir)?;
+		std::fs::write(dir.join("a.txt"), "a\n")?;
+		std::fs::write(dir.join("blob"), "blob\n")?;
+		let dir = SPath::from_std_path(dir)?;
+		let mut overlay = OverlayStore::new(&DiskStore);
+
+		// -- Exec
+		overlay.write_blob(&dir.join("a.txt"), &dir.join("blob"), true)?;
+		overlay.rename(&dir.join("a.txt"), &dir.join("b.txt"))?;
+
+		// -- Check
+		assert_eq!(overlay.read_to_string(&dir.join("b.txt"))?, "a\nblob\n");
+		assert!(!overlay.exists(&dir.join("a.txt")));
+		assert_eq!(std::fs::read_to_string(dir.join("a.txt"))?, "a\n");
+		assert!(!dir.join("b.txt").exists());
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,317 @@
+use crate::applier::{apply_to_store, resolve_base_dir};
+use crate::apply_store::{DiskStore, OverlayStore};
+use crate::lint::directive_paths;
+use crate::{ApplyOptions, DiffStats, FileChanges, FileDirective, InsertPosition, MatchTier, Result, SecurityPolicy};
+use derive_more::Display;
+use simple_fs::{SPath, get_glob_set};
+
+/// The doc globs of `ApprovalPolicy::docs_only`.
+const DEFAULT_DOC_GLOBS: &[&str] = &["**/*.md", "**/*.mdx", "**/*.txt", "**/*.rst", "docs/**"];
+
+/// The auto-approval rules of `ApprovalDecision::evaluate`. A rule is enforced when set,
+/// and a batch is approved when it passes all of them.
+///
+/// `ApprovalPolicy::default()` only enforces `allow_deletes` (false), and the absence of `Fail` directives.
+#[derive(Debug, Clone, Default)]
+pub struct ApprovalPolicy {
+	/// When not empty, every path the directives touch must match one of these globs (e.g., only docs changed).
+	pub doc_globs: Vec<String>,
+
+	/// The max changed lines (insertions plus deletions) of the batch.
+	pub max_changed_lines: Option<usize>,
+
+	/// When `false`, a `FILE_DELETE` needs a review. (default false)
+	pub allow_deletes: bool,
+
+	/// When set, the batch is first applied in memory on this base dir (zero writes, with `dry_run_security_policy`),
+	/// and every directive must apply, with all its hunks matched at the `MatchTier::Strict` tier.
+	/// The changed lines are then the ones of the dry run.
+	pub dry_run_base_dir: Option<SPath>,
+
+	/// The security policy of the dry run.
+	pub dry_run_security_policy: SecurityPolicy,
+}
+
+/// Constructors
+impl ApprovalPolicy {
+	/// A policy approving the batches only touching docs (Markdown, text, reStructuredText, or under `docs/`).
+	pub fn docs_only() -> Self {
+		Self::default().with_doc_globs(DEFAULT_DOC_GLOBS.iter().copied())
+	}
+}
+
+impl From<Option<ApprovalPolicy>> for ApprovalPolicy {
+	fn from(opt: Option<ApprovalPolicy>) -> Self {
+		opt.unwrap_or_default()
+	}
+}
+
+/// Fluid apis
+impl ApprovalPolicy {
+	/// Override the doc globs with the given iterator.
+	pub fn with_doc_globs(mut self, globs: impl IntoIterator<Item = impl Into<String>>) -> Self {
+		self.doc_globs = globs.into_iter().map(|g| g.into()).collect();
+		self
+	}
+
+	/// Set the max changed lines (insertions plus deletions) of the batch.
+	pub fn with_max_changed_lines(mut self, max: usize) -> Self {
+		self.max_changed_lines = Some(max);
+		self
+	}
+
+	/// Approve the batches with a `FILE_DELETE`.
+	pub fn with_allow_deletes(mut self) -> Self {
+		self.allow_deletes = true;
+		self
+	}
+
+	/// Dry-run the batch on `base_dir` (nothing written), requiring every directive to apply at the strict tier.
+	pub fn with_dry_run(mut self, base_dir: impl Into<SPath>, security_policy: impl Into<SecurityPolicy>) -> Self {
+		self.dry_run_base_dir = Some(base_dir.into());
+		self.dry_run_security_policy = security_policy.into();
+		self
+	}
+}
+
+/// The auto-approval rule a batch failed (see `ApprovalPolicy`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum ApprovalRule {
+	/// A path does not match the `doc_globs`.
+	DocsOnly,
+	/// The batch changes more than `max_changed_lines` lines.
+	MaxChangedLines,
+	/// A `FILE_DELETE` without `allow_deletes`.
+	NoDeletes,
+	/// A directive fails, or applies non-strictly, in the dry run.
+	StrictDryRun,
+	/// A directive failed to parse (a `Fail` directive).
+	ParseFailure,
+}
+
+/// Why a batch needs a review.
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+#[display("[{rule}] {message}")]
+pub struct ReviewReason {
+	pub rule: ApprovalRule,
+	pub message: String,
+}
+
+/// Whether a batch is safe to auto-apply (see `ApprovalDecision::evaluate`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApprovalDecision {
+	Approve,
+	NeedsReview { reasons: Vec<ReviewReason> },
+}
+
+impl ApprovalDecision {
+	/// Evaluates the auto-approval rules of the `policy` on the batch, listing all the failed rules.
+	///
+	/// Without a dry run, the changed lines are counted from the directives (the `+` / `-` lines of the patches,
+	/// the lines of the new or appended contents), and a `FILE_GLOBAL_REPLACE` needs a review when
+	/// `max_changed_lines` is set (its changes are only known from a dry run).
+	pub fn evaluate(changes: &FileChanges, policy: impl Into<ApprovalPolicy>) -> Self {
+		let policy: ApprovalPolicy = policy.into();
+		let mut reasons: Vec<ReviewReason> = Vec::new();
+		let mut push = |rule: ApprovalRule, message: String| reasons.push(ReviewReason { rule, message });
+
+		// -- Directive rules
+		let doc_globs: Vec<&str> = policy.doc_globs.iter().map(String::as_str).collect();
+		let doc_glob_set = if doc_globs.is_empty() {
+			None
+		} else {
+			match get_glob_set(&doc_globs) {
+				Ok(glob_set) => Some(glob_set),
+				Err(err) => {
+					push(ApprovalRule::DocsOnly, format!("invalid doc globs: {err}"));
+					None
+				}
+			}
+		};
+		for directive in changes {
+			if let FileDirective::Fail {
+				kind,
+				file_path,
+				error_msg,
+			} = directive
+			{
+				let file_path = file_path.as_deref().unwrap_or("unknown");
+				push(ApprovalRule::ParseFailure, format!("{kind} '{file_path}': {error_msg}"));
+			}
+			if let FileDirective::Delete { file_path } = directive
+				&& !policy.allow_deletes
+			{
+				push(ApprovalRule::NoDeletes, format!("deletes '{file_path}'"));
+			}
+			if let Some(glob_set) = &doc_glob_set {
+				for path in directive_paths(directive) {
+					if !glob_set.is_match(path) {
+						push(ApprovalRule::DocsOnly, format!("'{path}' is not a doc file"));
+					}
+				}
+			}
+		}
+
+		// -- Dry run
+		let stats = match &policy.dry_run_base_dir {
+			Some(base_dir) => match dry_run(base_dir, changes, &policy.dry_run_security_policy) {
+				Ok((stats, failures)) => {
+					for failure in failures {
+						push(ApprovalRule::StrictDryRun, failure);
+					}
+					Some(stats)
+				}
+				Err(err) => {
+					push(ApprovalRule::StrictDryRun, format!("dry run failed: {err}"));
+					None
+				}
+			},
+			None => directives_stats(changes),
+		};
+
+		// -- Changed lines
+		if let Some(max) = policy.max_changed_lines {
+			match stats {
+				Some(stats) if stats.insertions + stats.deletions > max => push(
+					ApprovalRule::MaxChangedLines,
+					format!("{} changed lines (max {max})", stats.insertions + stats.deletions),
+				),
+				Some(_) => {}
+				None => push(
+					ApprovalRule::MaxChangedLines,
+					"the changed lines are unknown without a dry run".to_string(),
+				),
+			}
+		}
+
+		if reasons.is_empty() {
+			ApprovalDecision::Approve
+		} else {
+			ApprovalDecision::NeedsReview { reasons }
+		}
+	}
+
+	pub fn is_approved(&self) -> bool {
+		matches!(self, ApprovalDecision::Approve)
+	}
+
+	/// The reasons the batch needs a review (empty when approved).
+	pub fn reasons(&self) -> &[ReviewReason] {
+		match self {
+			ApprovalDecision::Approve => &[],
+			ApprovalDecision::NeedsReview { reasons } => reasons,
+		}
+	}
+}
+
+// region:    --- Support
+
+/// Applies the batch in memory on `base_dir`, returning its diff stats and the failed or non-strict directives.
+fn dry_run(base_dir: &SPath, changes: &FileChanges, policy: &SecurityPolicy) -> Result<(DiffStats, Vec<String>)> {
+	let options = ApplyOptions::default();
+	let base_dir = resolve_base_dir(base_dir.clone(), policy, &options)?;
+	let (status, _) = apply_to_store(
+		&mut OverlayStore::new(&DiskStore),
+		&base_dir,
+		changes.iter().cloned(),
+		policy,
+		&options,
+		&mut (),
+	);
+
+	let mut failures = Vec::new();
+	for item in &status.items {
+		if let Some(error_msg) = item.error_msg() {
+			failures.push(format!("'{}' would fail: {error_msg}", item.file_path()));
+		} else if let Some(tier) = item.match_tier.filter(|tier| *tier != MatchTier::Strict) {
+			failures.push(format!("'{}' would apply at the {tier:?} tier", item.file_path()));
+		}
+	}
+	Ok((status.diff_stats(), failures))
+}
+
+/// The change counts of the directives themselves, or `None` if unknown (a `FILE_GLOBAL_REPLACE`).
+fn directives_stats(changes: &FileChanges) -> Option<DiffStats> {
+	let mut stats = DiffStats::default();
+	for directive in changes {
+		match directive {
+			FileDirective::New { content, .. } | FileDirective::Append { content, .. } => {
+				let content = content.load().ok()?;
+				stats += DiffStats::from_insertion(&content);
+			}
+			FileDirective::Patch { content, .. } => stats += DiffStats::from_unified_diff(&content.content),
+			FileDirective::SpanPatch { .. } => {
+				stats += DiffStats {
+					files: 1,
+					insertions: 1,
+					deletions: 1,
+				}
+			}
+			FileDirective::Insert { position, content, .. } => {
+				stats += DiffStats::from_insertion(content);
+				if *position == InsertPosition::ReplaceFirst {
+					stats.deletions += 1;
+				}
+			}
+			FileDirective::GlobalReplace { .. } => return None,
+			FileDirective::Copy { .. } | FileDirective::Rename { .. } | FileDirective::Delete { .. } => {}
+			FileDirective::Fail { .. } => {}
+		}
+	}
+	Some(stats)
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::extract_file_changes;
+
+	#[test]
+	fn test_approval_evaluate() -> Result<()> {
+		// -- Setup & Fixtures
+		let input = r#"
+<FILE_CHANGES>
+<FILE_PATCH file_path="docs/guide.md">
+@@
+-Old line
++New line
++Added line
+</FILE_PATCH>
+<FILE_NEW file_path="src/main.rs">
+fn main() {}
+</FILE_NEW>
+<FILE_DELETE file_path="NOTES.txt" />
+</FILE_CHANGES>
+"#;
+		let (changes, _) = extract_file_changes(input, false)?;
+
+		// -- Exec
+		let docs_only = ApprovalDecision::evaluate(&changes, ApprovalPolicy::docs_only().with_allow_deletes());
+		let small = ApprovalDecision::evaluate(&changes, ApprovalPolicy::default().with_max_changed_lines(3));
+		let large = ApprovalDecision::evaluate(
+			&changes,
+			ApprovalPolicy::default().with_allow_deletes().with_max_changed_lines(4),
+		);
+
+		// -- Check
+		let rules = |decision: &ApprovalDecision| decision.reasons().iter().map(|r| r.rule).collect::<Vec<_>>();
+		assert_eq!(rules(&docs_only), [ApprovalRule::DocsOnly]);
+		assert_eq!(
+			docs_only.reasons()[0].to_string(),
+			"[DocsOnly] 'src/main.rs' is not a doc file"
+		);
+		assert_eq!(rules(&small), [ApprovalRule::NoDeletes, ApprovalRule::MaxChangedLines]);
+		assert_eq!(small.reasons()[1].message, "4 changed lines (max 3)");
+		assert!(large.is_approved());
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
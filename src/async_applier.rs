@@ -0,0 +1,616 @@
+use crate::applier::{
+	apply_to_store_with_budget, orig_paths, resolve_base_dir, rollback_batch, validate_batch, with_batch_backup_dir,
+};
+use crate::apply_budget::Budget;
+use crate::apply_journal::{Journal, JournalStore};
+use crate::apply_store::{ApplyStore, WriteTargetStore, is_same_disk_file};
+use crate::feedback_templates::feedback_msg;
+use crate::materializer::read_paths;
+use crate::path_resolver::{list_rel_paths, resolve_fuzzy_path};
+use crate::{
+	ApplyChangesStatus, ApplyOptions, DirectiveStatus, Error, FileChanges, FileDirective, NewFileCollision, Result,
+	SecurityPolicy, fs_guard,
+};
+use simple_fs::{SPath, safer_remove_dir, safer_remove_file, safer_trash_dir, safer_trash_file};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Same as `apply_file_changes_with_options`, but with all the file system IO done with `tokio::fs`,
+/// so applying large change sets does not block the async runtime.
+///
+/// The files the directives touch are first loaded, then each directive is applied in memory
+/// and its writes are flushed before the next one. Deletions still go to the trash (on a blocking task).
+pub async fn apply_file_changes_async(
+	base_dir: impl Into<SPath>,
+	file_changes: FileChanges,
+	security_policy: impl Into<SecurityPolicy>,
+	options: impl Into<ApplyOptions>,
+) -> Result<ApplyChangesStatus> {
+	let policy: SecurityPolicy = security_policy.into();
+	let options: ApplyOptions = options.into();
+	let base_dir = resolve_base_dir(base_dir.into(), &policy, &options)?;
+
+	let mut store = SnapshotStore::load(&base_dir, &file_changes, &policy, &options).await?;
+	// The missing paths are materialized when the snapshot is loaded (not by the directives).
+	let options = ApplyOptions {
+		materializer: None,
+		..options
+	};
+
+	if options.validate_all_first
+		&& let Some(failure) = validate_batch(&store, &base_dir, &file_changes, &policy, &options)
+	{
+		return Ok(failure);
+	}
+
+	// The stop policy spans the directives, so it is handled here (each directive is applied on its own).
+	// The destructive directives were confirmed by the validation, if any.
+	let directive_options = ApplyOptions {
+		stop_on_error: false,
+		rollback_on_error: false,
+		confirmer: options.confirmer.clone().filter(|_| !options.validate_all_first),
+		..with_batch_backup_dir(&options, &base_dir)
+	};
+	let mut journal = options.rollback_on_error.then(Journal::default);
+	let mut budget = Budget::new(&options);
+	let mut stopped = false;
+
+	let mut items = Vec::new();
+	for directive in file_changes {
+		if stopped {
+			let mut info = DirectiveStatus::from(&directive);
+			let skip_err = if budget.is_exhausted() {
+				Error::ApplyBatchBudgetExhausted
+			} else {
+				Error::ApplyBatchStopped
+			};
+			info.error_msg = Some(feedback_msg(&skip_err, &options));
+			items.push(info);
+			continue;
+		}
+
+		let (status, valid_flags) = match journal.as_mut() {
+			Some(journal) => apply_to_store_with_budget(
+				&mut JournalStore::new(&mut store, journal),
+				&base_dir,
+				std::iter::once(directive),
+				&policy,
+				&directive_options,
+				&mut (),
+				&mut budget,
+			),
+			None => apply_to_store_with_budget(
+				&mut store,
+				&base_dir,
+				std::iter::once(directive),
+				&policy,
+				&directive_options,
+				&mut (),
+				&mut budget,
+			),
+		};
+
+		for (mut info, valid) in status.items.into_iter().zip(valid_flags) {
+			let mut failed = !valid;
+			if let Err(err) = store.flush().await {
+				info.success = false;
+				info.error_msg = Some(feedback_msg(&err, &options));
+				failed = true;
+			}
+
+			if budget.is_exhausted() || (failed && (options.stop_on_error || options.rollback_on_error)) {
+				stopped = true;
+				if let Some(journal) = journal.take() {
+					// Note: Through the write targets (e.g., to write a read-only file back).
+					rollback_batch(
+						&mut WriteTargetStore::new(&mut store, &options),
+						journal,
+						&mut items,
+						&mut info,
+						&options,
+					);
+					if let Err(err) = store.flush().await {
+						info.warnings.push(format!("Could not roll back the batch: {err}"));
+					}
+				}
+			}
+			items.push(info);
+		}
+	}
+
+	if let Some(journal) = journal {
+		let res = match journal.commit(&mut WriteTargetStore::new(&mut store, &options)) {
+			Ok(()) => store.flush().await,
+			Err(err) => Err(err),
+		};
+		if let Err(err) = res
+			&& let Some(info) = items.last_mut()
+		{
+			info.warnings
+				.push(format!("Could not complete the deletions of the batch: {err}"));
+		}
+	}
+
+	Ok(ApplyChangesStatus { items })
+}
+
+// region:    --- SnapshotStore
+
+/// An in-memory snapshot of the files the directives touch, recording the write operations
+/// to be flushed to the file system.
+#[derive(Default)]
+struct SnapshotStore {
+	/// The file contents, by absolute path.
+	files: HashMap<String, Vec<u8>>,
+	/// The existing directories, by absolute path.
+	dirs: HashSet<String>,
+	/// The files that are symlinks (their content being the one of the target), with their link target,
+	/// by absolute path.
+	symlinks: HashMap<String, PathBuf>,
+	/// The base dir the `rel_paths` are relative to.
+	base_dir: Option<SPath>,
+	/// The files under the base dir (for the fuzzy path resolution).
+	rel_paths: Vec<String>,
+	/// The operations not yet flushed.
+	ops: Vec<StoreOp>,
+}
+
+enum StoreOp {
+	Write(SPath, Vec<u8>),
+	Rename(SPath, SPath),
+	/// Deletes to the trash.
+	Delete(SPath),
+	/// Deletes permanently.
+	Remove(SPath),
+	/// Removes the symlink itself (not its target).
+	RemoveSymlink(SPath),
+	/// Creates the symlink to the target.
+	CreateSymlink(SPath, PathBuf),
+}
+
+impl SnapshotStore {
+	async fn load(
+		base_dir: &SPath,
+		file_changes: &FileChanges,
+		policy: &SecurityPolicy,
+		options: &ApplyOptions,
+	) -> Result<Self> {
+		let mut store = Self {
+			base_dir: Some(base_dir.clone()),
+			..Default::default()
+		};
+
+		// -- The missing paths the directives read are first materialized (see `ApplyOptions::materializer`)
+		if let Some(materializer) = &options.materializer {
+			for rel_path in file_changes.iter().flat_map(read_paths) {
+				let full_path = base_dir.join(rel_path);
+				if fs_guard::check_for_read(&full_path, base_dir, Some(policy), false).is_ok()
+					&& !tokio::fs::try_exists(&full_path).await.unwrap_or(false)
+				{
+					let (materializer, base_dir, rel_path) =
+						(materializer.clone(), base_dir.clone(), rel_path.to_string());
+					tokio::task::spawn_blocking(move || materializer.materialize(&base_dir, &rel_path))
+						.await
+						.map_err(Error::custom_from_err)??;
+				}
+			}
+		}
+
+		let mut rel_paths: Vec<&str> = Vec::new();
+		for directive in file_changes.iter() {
+			match directive {
+				FileDirective::Copy { from_path, to_path } | FileDirective::Rename { from_path, to_path } => {
+					rel_paths.extend([from_path.as_str(), to_path.as_str()]);
+				}
+				_ => rel_paths.extend(directive.file_path()),
+			}
+		}
+		rel_paths.extend(options.context_files.iter().map(String::as_str));
+
+		// -- The paths an existing FILE_NEW target can be renamed aside to
+		let mut orig_rel_paths: Vec<String> = Vec::new();
+		if options.new_file_collision == NewFileCollision::RenameExisting {
+			for directive in file_changes.iter() {
+				if let FileDirective::New { file_path, .. } = directive {
+					orig_rel_paths.extend(orig_paths(file_path));
+				}
+			}
+		}
+		rel_paths.extend(orig_rel_paths.iter().map(String::as_str));
+
+		// -- The fuzzy resolution targets of the missing paths are loaded as well
+		let mut fuzzy_targets: Vec<String> = Vec::new();
+		if options.fuzzy_path_resolution {
+			let list_dir = base_dir.clone();
+			store.rel_paths = tokio::task::spawn_blocking(move || list_rel_paths(&list_dir))
+				.await
+				.map_err(Error::custom_from_err)??;
+			for rel_path in &rel_paths {
+				if !store.rel_paths.iter().any(|p| p == rel_path)
+					&& let Ok(Some(resolved)) = resolve_fuzzy_path(&store.rel_paths, rel_path)
+				{
+					fuzzy_targets.push(resolved);
+				}
+			}
+		}
+		rel_paths.extend(fuzzy_targets.iter().map(String::as_str));
+
+		// -- The files matching the glob of a FILE_GLOBAL_REPLACE (listed from the base dir)
+		let globs: Vec<&str> = file_changes
+			.iter()
+			.filter_map(|directive| match directive {
+				FileDirective::GlobalReplace { glob, .. } => Some(glob.as_str()),
+				_ => None,
+			})
+			.collect();
+		let mut glob_targets: Vec<String> = Vec::new();
+		if !globs.is_empty() {
+			if store.rel_paths.is_empty() {
+				let list_dir = base_dir.clone();
+				store.rel_paths = tokio::task::spawn_blocking(move || list_rel_paths(&list_dir))
+					.await
+					.map_err(Error::custom_from_err)??;
+			}
+			let glob_set = simple_fs::get_glob_set(&globs).map_err(Error::simple_fs)?;
+			glob_targets.extend(
+				store
+					.rel_paths
+					.iter()
+					.filter(|p| glob_set.is_match(p.as_str()))
+					.cloned(),
+			);
+		}
+		rel_paths.extend(glob_targets.iter().map(String::as_str));
+
+		// -- The files of a directory are only loaded when read: moved by a FILE_RENAME (a later directive can read
+		//    them), or backed up or recorded for the undo (a deleted directory is otherwise only moved or removed)
+		let reads_dir_files = options.backup_dir.is_some() || options.record_undo;
+		let renamed_paths: HashSet<&str> = file_changes
+			.iter()
+			.filter_map(|directive| match directive {
+				FileDirective::Rename { from_path, .. } => Some(from_path.as_str()),
+				_ => None,
+			})
+			.collect();
+
+		for rel_path in rel_paths {
+			let full_path = base_dir.join(rel_path);
+			let policy = Some(policy);
+			if fs_guard::check_for_read(&full_path, base_dir, policy, false).is_ok()
+				|| fs_guard::check_for_write(&full_path, base_dir, policy, false).is_ok()
+			{
+				let with_files = reads_dir_files || renamed_paths.contains(rel_path);
+				store.load_path(full_path, with_files).await?;
+			}
+		}
+
+		Ok(store)
+	}
+
+	/// Loads a file, or a directory with all the files under it when `with_files` (a missing path is skipped).
+	///
+	/// Note: A symlink is not followed into a directory (the files under it, possibly outside of the base dir,
+	///       are not loaded), only a file content is read through it.
+	async fn load_path(&mut self, path: SPath, with_files: bool) -> Result<()> {
+		let mut pending = vec![path];
+		while let Some(path) = pending.pop() {
+			if self.files.contains_key(path.as_str()) || self.dirs.contains(path.as_str()) {
+				continue;
+			}
+			let Ok(metadata) = tokio::fs::symlink_metadata(&path).await else {
+				continue;
+			};
+
+			let is_dir = if metadata.file_type().is_symlink() {
+				// Note: A dangling link is skipped, as a missing path.
+				let Ok(target_metadata) = tokio::fs::metadata(&path).await else {
+					continue;
+				};
+				if let Ok(target) = tokio::fs::read_link(&path).await {
+					self.symlinks.insert(path.to_string(), target);
+				}
+				if target_metadata.is_dir() {
+					self.dirs.insert(path.to_string());
+					continue;
+				}
+				false
+			} else {
+				metadata.is_dir()
+			};
+
+			if is_dir {
+				if with_files {
+					let mut entries = tokio::fs::read_dir(&path)
+						.await
+						.map_err(|err| Error::io_read_file(path.to_string(), err))?;
+					while let Some(entry) = entries
+						.next_entry()
+						.await
+						.map_err(|err| Error::io_read_file(path.to_string(), err))?
+					{
+						pending.push(SPath::from_std_path(entry.path())?);
+					}
+				}
+				self.dirs.insert(path.to_string());
+			} else {
+				let content = tokio::fs::read(&path)
+					.await
+					.map_err(|err| Error::io_read_file(path.to_string(), err))?;
+				self.files.insert(path.to_string(), content);
+			}
+		}
+		Ok(())
+	}
+
+	/// Performs the operations not yet flushed on the file system (stops at the first failure).
+	async fn flush(&mut self) -> Result<()> {
+		for op in std::mem::take(&mut self.ops) {
+			op.flush().await?;
+		}
+		Ok(())
+	}
+
+	/// Drops the file or the directory (and everything under it) from the snapshot.
+	fn drop_paths(&mut self, path: &SPath) {
+		let (files, dirs) = self.paths_under(path);
+		for file in files {
+			self.files.remove(&file);
+		}
+		for dir in dirs {
+			self.dirs.remove(&dir);
+		}
+		self.files.remove(path.as_str());
+		self.dirs.remove(path.as_str());
+		self.symlinks.remove(path.as_str());
+	}
+
+	/// The paths under the `path` directory (the files, then the sub dirs).
+	fn paths_under(&self, path: &SPath) -> (Vec<String>, Vec<String>) {
+		let prefix = format!("{path}/");
+		let files = self.files.keys().filter(|p| p.starts_with(&prefix)).cloned().collect();
+		let dirs = self.dirs.iter().filter(|p| p.starts_with(&prefix)).cloned().collect();
+		(files, dirs)
+	}
+}
+
+impl ApplyStore for SnapshotStore {
+	fn exists(&self, path: &SPath) -> bool {
+		self.files.contains_key(path.as_str()) || self.is_dir(path)
+	}
+
+	fn is_dir(&self, path: &SPath) -> bool {
+		self.dirs.contains(path.as_str()) || self.files.keys().any(|p| p.starts_with(&format!("{path}/")))
+	}
+
+	fn read_to_string(&self, path: &SPath) -> Result<String> {
+		let bytes = self.read_bytes(path)?;
+		String::from_utf8(bytes).map_err(|err| Error::io_read_file(path.to_string(), err))
+	}
+
+	fn read_bytes(&self, path: &SPath) -> Result<Vec<u8>> {
+		self.files
+			.get(path.as_str())
+			.cloned()
+			.ok_or_else(|| Error::apply_path_not_found("read", path.to_string()))
+	}
+
+	fn write(&mut self, path: &SPath, content: &[u8]) -> Result<()> {
+		self.files.insert(path.to_string(), content.to_vec());
+		self.ops.push(StoreOp::Write(path.clone(), content.to_vec()));
+		Ok(())
+	}
+
+	fn rename(&mut self, from: &SPath, to: &SPath) -> Result<()> {
+		if self.is_dir(from) {
+			let (files, dirs) = self.paths_under(from);
+			for file in files {
+				let to_file = format!("{to}{}", &file[from.as_str().len()..]);
+				if let Some(target) = self.symlinks.remove(&file) {
+					self.symlinks.insert(to_file.clone(), target);
+				}
+				if let Some(content) = self.files.remove(&file) {
+					self.files.insert(to_file, content);
+				}
+			}
+			for dir in dirs {
+				self.dirs.remove(&dir);
+				self.dirs.insert(format!("{to}{}", &dir[from.as_str().len()..]));
+			}
+			self.dirs.remove(from.as_str());
+			self.dirs.insert(to.to_string());
+		} else {
+			let content = self.read_bytes(from)?;
+			self.files.remove(from.as_str());
+			self.files.insert(to.to_string(), content);
+			if let Some(target) = self.symlinks.remove(from.as_str()) {
+				self.symlinks.insert(to.to_string(), target);
+			}
+		}
+		self.ops.push(StoreOp::Rename(from.clone(), to.clone()));
+		Ok(())
+	}
+
+	fn delete(&mut self, path: &SPath) -> Result<()> {
+		self.drop_paths(path);
+		self.ops.push(StoreOp::Delete(path.clone()));
+		Ok(())
+	}
+
+	fn remove(&mut self, path: &SPath) -> Result<()> {
+		self.drop_paths(path);
+		self.ops.push(StoreOp::Remove(path.clone()));
+		Ok(())
+	}
+
+	fn is_symlink(&self, path: &SPath) -> bool {
+		self.symlinks.contains_key(path.as_str())
+	}
+
+	fn remove_symlink(&mut self, path: &SPath) -> Result<()> {
+		self.symlinks.remove(path.as_str());
+		self.ops.push(StoreOp::RemoveSymlink(path.clone()));
+		Ok(())
+	}
+
+	fn read_link(&self, path: &SPath) -> Option<PathBuf> {
+		self.symlinks.get(path.as_str()).cloned()
+	}
+
+	/// Note: The content of the link is not loaded (the snapshot is not read after a rollback).
+	fn create_symlink(&mut self, path: &SPath, target: &Path) -> Result<()> {
+		self.symlinks.insert(path.to_string(), target.to_path_buf());
+		self.ops
+			.push(StoreOp::CreateSymlink(path.clone(), target.to_path_buf()));
+		Ok(())
+	}
+
+	/// Note: The snapshot is flushed after each directive, so the disk has the same files.
+	fn is_same_file(&self, a: &SPath, b: &SPath) -> bool {
+		a == b || is_same_disk_file(a, b)
+	}
+
+	/// The base dir files (loaded for the fuzzy path resolution), or the loaded files under another dir.
+	fn list_rel_paths(&self, base_dir: &SPath) -> Result<Vec<String>> {
+		if self.base_dir.as_ref() == Some(base_dir) {
+			return Ok(self.rel_paths.clone());
+		}
+		let prefix = format!("{base_dir}/");
+		let mut rel_paths: Vec<String> = self
+			.files
+			.keys()
+			.filter_map(|p| p.strip_prefix(&prefix).map(str::to_string))
+			.collect();
+		rel_paths.sort();
+		Ok(rel_paths)
+	}
+}
+
+impl StoreOp {
+	/// Performs the operation on the file system.
+	async fn flush(self) -> Result<()> {
+		match self {
+			StoreOp::Write(path, content) => {
+				let existed = tokio::fs::try_exists(&path).await.unwrap_or(false);
+				ensure_parent_dir(&path).await?;
+				tokio::fs::write(&path, content).await.map_err(|err| {
+					if existed {
+						Error::io_write_file(path.to_string(), err)
+					} else {
+						Error::io_create_file(path.to_string(), err)
+					}
+				})
+			}
+
+			StoreOp::Rename(from, to) => {
+				ensure_parent_dir(&to).await?;
+				tokio::fs::rename(&from, &to)
+					.await
+					.map_err(|err| Error::io_rename_path(from.to_string(), to.to_string(), err))
+			}
+
+			StoreOp::Delete(path) => {
+				let is_dir = tokio::fs::metadata(&path).await.is_ok_and(|m| m.is_dir());
+				tokio::task::spawn_blocking(move || {
+					if is_dir {
+						safer_trash_dir(&path, ()).map_err(|err| Error::io_delete_dir_all(path.to_string(), err))?;
+					} else {
+						safer_trash_file(&path, ()).map_err(|err| Error::io_delete_file(path.to_string(), err))?;
+					}
+					Ok(())
+				})
+				.await
+				.map_err(Error::custom_from_err)?
+			}
+
+			StoreOp::Remove(path) => {
+				let is_dir = tokio::fs::metadata(&path).await.is_ok_and(|m| m.is_dir());
+				tokio::task::spawn_blocking(move || {
+					if is_dir {
+						safer_remove_dir(&path, ()).map_err(|err| Error::io_delete_dir_all(path.to_string(), err))?;
+					} else {
+						safer_remove_file(&path, ()).map_err(|err| Error::io_delete_file(path.to_string(), err))?;
+					}
+					Ok(())
+				})
+				.await
+				.map_err(Error::custom_from_err)?
+			}
+
+			StoreOp::RemoveSymlink(path) => tokio::fs::remove_file(&path)
+				.await
+				.map_err(|err| Error::io_delete_file(path.to_string(), err)),
+
+			StoreOp::CreateSymlink(path, target) => {
+				ensure_parent_dir(&path).await?;
+				#[cfg(unix)]
+				let res = tokio::fs::symlink(&target, &path).await;
+				#[cfg(windows)]
+				let res = tokio::fs::symlink_file(&target, &path).await;
+				res.map_err(|err| Error::io_create_file(path.to_string(), err))
+			}
+		}
+	}
+}
+
+async fn ensure_parent_dir(path: &SPath) -> Result<()> {
+	if let Some(parent) = path.parent() {
+		tokio::fs::create_dir_all(&parent)
+			.await
+			.map_err(|err| Error::io_create_file(parent.to_string(), err))?;
+	}
+	Ok(())
+}
+
+// endregion: --- SnapshotStore
+
+// region:    --- Tests
+
+#[cfg(all(test, unix))]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::extract_file_changes;
+
+	#[tokio::test]
+	async fn test_async_applier_snapshot_load_dir_files() -> Result<()> {
+		// -- Setup & Fixtures
+		let dir = std::env::temp_dir().join(format!("udiffx-test-snapshot-load-{}", std::process::id()));
+		let base_dir = SPath::from_std_path(dir.join("base"))?;
+		std::fs::create_dir_all(base_dir.join("d"))?;
+		std::fs::create_dir_all(dir.join("outside"))?;
+		std::fs::write(base_dir.join("d/a.txt"), "a\n")?;
+		std::fs::write(dir.join("outside/secret.txt"), "secret\n")?;
+		let _ = std::fs::remove_file(base_dir.join("d/out"));
+		std::os::unix::fs::symlink("../../outside", base_dir.join("d/out"))?;
+		let input = r#"
+<FILE_CHANGES>
+<FILE_DELETE file_path="d" />
+</FILE_CHANGES>
+"#;
+		let (changes, _extruded) = extract_file_changes(input, false)?;
+		let policy = SecurityPolicy::from_writable_dirs([base_dir.clone()]);
+
+		// -- Exec
+		let deleted = SnapshotStore::load(&base_dir, &changes, &policy, &ApplyOptions::default()).await?;
+		let undone = SnapshotStore::load(
+			&base_dir,
+			&changes,
+			&policy,
+			&ApplyOptions::default().with_record_undo(),
+		)
+		.await?;
+
+		// -- Check
+		assert!(deleted.is_dir(&base_dir.join("d")));
+		assert!(deleted.files.is_empty());
+		let mut files: Vec<&String> = undone.files.keys().collect();
+		files.sort();
+		assert_eq!(files, [&base_dir.join("d/a.txt").to_string()]);
+		assert!(undone.is_dir(&base_dir.join("d/out")) && undone.is_symlink(&base_dir.join("d/out")));
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,260 @@
+use crate::{FileChanges, FileDirective};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+/// The structured difference between two `FileChanges` batches (e.g., two iterations of a model's plan).
+///
+/// Directives are matched by kind and primary path (`file_path`, or `from_path` for copy/rename),
+/// in order of occurrence when a batch has several directives with the same key.
+#[derive(Debug, Clone, Default)]
+pub struct BatchDiff {
+	/// Directives only in the other (newer) batch.
+	pub added: Vec<FileDirective>,
+	/// Directives only in this (older) batch.
+	pub removed: Vec<FileDirective>,
+	/// Directives in both batches, with a different payload (content or destination path).
+	pub changed: Vec<DirectiveChange>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DirectiveChange {
+	pub before: FileDirective,
+	pub after: FileDirective,
+}
+
+impl BatchDiff {
+	pub fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+	}
+}
+
+impl FileChanges {
+	/// Computes what changed from this batch to `other`.
+	pub fn diff(&self, other: &FileChanges) -> BatchDiff {
+		let mut other_by_key: HashMap<(&'static str, &str), VecDeque<&FileDirective>> = HashMap::new();
+		for directive in other {
+			other_by_key
+				.entry(directive_key(directive))
+				.or_default()
+				.push_back(directive);
+		}
+
+		let mut diff = BatchDiff::default();
+
+		for directive in self {
+			match other_by_key
+				.get_mut(&directive_key(directive))
+				.and_then(|q| q.pop_front())
+			{
+				Some(other_directive) => {
+					if !same_payload(directive, other_directive) {
+						diff.changed.push(DirectiveChange {
+							before: directive.clone(),
+							after: other_directive.clone(),
+						});
+					}
+				}
+				None => diff.removed.push(directive.clone()),
+			}
+		}
+
+		// Remaining directives of `other`, in their original order.
+		for directive in other {
+			if let Some(queue) = other_by_key.get_mut(&directive_key(directive))
+				&& queue.front().is_some_and(|d| std::ptr::eq(*d, directive))
+			{
+				queue.pop_front();
+				diff.added.push(directive.clone());
+			}
+		}
+
+		diff
+	}
+}
+
+// region:    --- Display
+
+impl fmt::Display for BatchDiff {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for directive in &self.removed {
+			writeln!(f, "- no longer {}", describe(directive))?;
+		}
+		for directive in &self.added {
+			writeln!(f, "+ now also {}", describe(directive))?;
+		}
+		for change in &self.changed {
+			writeln!(f, "~ revised: {}", describe(&change.after))?;
+		}
+		Ok(())
+	}
+}
+
+fn describe(directive: &FileDirective) -> String {
+	match directive {
+		FileDirective::New { file_path, .. } => format!("creates {file_path}"),
+		FileDirective::Patch { file_path, .. } => format!("patches {file_path}"),
+		FileDirective::Append { file_path, .. } => format!("appends to {file_path}"),
+		FileDirective::SpanPatch { file_path, line, .. } => format!("span-patches {file_path}:{line}"),
+		FileDirective::Insert {
+			file_path, position, ..
+		} => format!("inserts into {file_path} ({position} anchor)"),
+		FileDirective::GlobalReplace { glob, pattern, .. } => format!("replaces '{pattern}' in {glob}"),
+		FileDirective::Copy { from_path, to_path } => format!("copies {from_path} to {to_path}"),
+		FileDirective::Rename { from_path, to_path } => format!("renames {from_path} to {to_path}"),
+		FileDirective::Delete { file_path } => format!("deletes {file_path}"),
+		FileDirective::Fail { kind, file_path, .. } => {
+			format!("fails {kind} {}", file_path.as_deref().unwrap_or("unknown"))
+		}
+	}
+}
+
+// endregion: --- Display
+
+// region:    --- Support
+
+pub(crate) fn directive_key(directive: &FileDirective) -> (&'static str, &str) {
+	let path = match directive {
+		FileDirective::New { file_path, .. }
+		| FileDirective::Patch { file_path, .. }
+		| FileDirective::Append { file_path, .. }
+		| FileDirective::SpanPatch { file_path, .. }
+		| FileDirective::Insert { file_path, .. }
+		| FileDirective::Delete { file_path } => file_path.as_str(),
+		FileDirective::GlobalReplace { glob, .. } => glob.as_str(),
+		FileDirective::Copy { from_path, .. } | FileDirective::Rename { from_path, .. } => from_path.as_str(),
+		FileDirective::Fail { file_path, .. } => file_path.as_deref().unwrap_or(""),
+	};
+	(directive.kind(), path)
+}
+
+/// Compares the payload of two directives with the same key (code fences are ignored).
+pub(crate) fn same_payload(a: &FileDirective, b: &FileDirective) -> bool {
+	match (a, b) {
+		(FileDirective::New { content: a, .. }, FileDirective::New { content: b, .. })
+		| (FileDirective::Patch { content: a, .. }, FileDirective::Patch { content: b, .. })
+		| (FileDirective::Append { content: a, .. }, FileDirective::Append { content: b, .. }) => a.content == b.content,
+		(FileDirective::Copy { to_path: a, .. }, FileDirective::Copy { to_path: b, .. })
+		| (FileDirective::Rename { to_path: a, .. }, FileDirective::Rename { to_path: b, .. }) => a == b,
+		(
+			FileDirective::SpanPatch {
+				line: a_line,
+				columns: a_columns,
+				line_hash: a_hash,
+				replacement: a_replacement,
+				..
+			},
+			FileDirective::SpanPatch {
+				line: b_line,
+				columns: b_columns,
+				line_hash: b_hash,
+				replacement: b_replacement,
+				..
+			},
+		) => (a_line, a_columns, a_hash, a_replacement) == (b_line, b_columns, b_hash, b_replacement),
+		(
+			FileDirective::GlobalReplace {
+				pattern: a_pattern,
+				replacement: a_replacement,
+				regex: a_regex,
+				max_matches: a_max,
+				..
+			},
+			FileDirective::GlobalReplace {
+				pattern: b_pattern,
+				replacement: b_replacement,
+				regex: b_regex,
+				max_matches: b_max,
+				..
+			},
+		) => (a_pattern, a_replacement, a_regex, a_max) == (b_pattern, b_replacement, b_regex, b_max),
+		(
+			FileDirective::Insert {
+				anchor: a_anchor,
+				regex: a_regex,
+				position: a_position,
+				content: a_content,
+				..
+			},
+			FileDirective::Insert {
+				anchor: b_anchor,
+				regex: b_regex,
+				position: b_position,
+				content: b_content,
+				..
+			},
+		) => (a_anchor, a_regex, a_position, a_content) == (b_anchor, b_regex, b_position, b_content),
+		(FileDirective::Delete { .. }, FileDirective::Delete { .. }) => true,
+		(FileDirective::Fail { error_msg: a, .. }, FileDirective::Fail { error_msg: b, .. }) => a == b,
+		_ => false,
+	}
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::Content;
+
+	fn patch(file_path: &str, content: &str) -> FileDirective {
+		FileDirective::Patch {
+			file_path: file_path.to_string(),
+			content: Content::from_raw(content.to_string()),
+			allow_generated: false,
+		}
+	}
+
+	#[test]
+	fn test_batch_diff_added_removed_changed() -> Result<()> {
+		// -- Setup & Fixtures
+		let before = FileChanges::new(vec![
+			FileDirective::Delete {
+				file_path: "x.txt".to_string(),
+			},
+			patch("a.rs", "@@\n-a\n+b\n"),
+			patch("c.rs", "@@\n-c\n+d\n"),
+		]);
+		let after = FileChanges::new(vec![
+			patch("a.rs", "@@\n-a\n+b\n"),
+			patch("c.rs", "@@\n-c\n+e\n"),
+			patch("y.rs", "@@\n-y\n+z\n"),
+		]);
+
+		// -- Exec
+		let diff = before.diff(&after);
+
+		// -- Check
+		assert_eq!(diff.removed.len(), 1);
+		assert_eq!(diff.removed[0].kind(), "Delete");
+		assert_eq!(diff.added.len(), 1);
+		assert_eq!(diff.added[0].file_path(), Some("y.rs"));
+		assert_eq!(diff.changed.len(), 1);
+		assert_eq!(diff.changed[0].after.file_path(), Some("c.rs"));
+		assert_eq!(
+			diff.to_string(),
+			"- no longer deletes x.txt\n+ now also patches y.rs\n~ revised: patches c.rs\n"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_batch_diff_same_batch_is_empty() -> Result<()> {
+		// -- Setup & Fixtures
+		let changes = FileChanges::new(vec![patch("a.rs", "@@\n-a\n+b\n"), patch("a.rs", "@@\n-c\n+d\n")]);
+
+		// -- Exec
+		let diff = changes.diff(&changes.clone());
+
+		// -- Check
+		assert!(diff.is_empty(), "Expected empty diff, got: {diff:?}");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
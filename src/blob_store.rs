@@ -0,0 +1,94 @@
+use crate::content_hash::content_address;
+use crate::{Error, Result};
+use simple_fs::{SPath, ensure_dir};
+
+/// Default minimum size, in bytes, of a directive body to be spilled to the blob store.
+const DEFAULT_BLOB_MIN_BYTES: usize = 1024 * 1024;
+
+/// A content-addressed store of directive bodies on disk (e.g., in a temp dir), so that a batch with
+/// multi-MB `FILE_NEW` / `FILE_APPEND` bodies does not need to be held in memory (see `ExtractOptions::blob_store`).
+///
+/// Each blob is a file named by the hash and size of its content (e.g., `3f2a9c0e1b7d4a55-2097152`),
+/// so identical bodies share the same blob.
+#[derive(Debug, Clone)]
+pub struct BlobStore {
+	dir: SPath,
+	min_bytes: usize,
+}
+
+impl BlobStore {
+	/// Creates the store in `dir` (created if missing), spilling the bodies of at least 1 MiB.
+	pub fn new(dir: impl Into<SPath>) -> Result<Self> {
+		let dir = dir.into();
+		ensure_dir(&dir).map_err(Error::simple_fs)?;
+		Ok(Self {
+			dir,
+			min_bytes: DEFAULT_BLOB_MIN_BYTES,
+		})
+	}
+
+	/// Set the minimum size, in bytes, of a body to be spilled.
+	pub fn with_min_bytes(mut self, min_bytes: usize) -> Self {
+		self.min_bytes = min_bytes;
+		self
+	}
+
+	pub fn dir(&self) -> &SPath {
+		&self.dir
+	}
+
+	/// Whether a body of `len` bytes is spilled to this store.
+	pub fn spills(&self, len: usize) -> bool {
+		len >= self.min_bytes
+	}
+
+	/// Stores `content` (if not already stored), and returns the path of its blob.
+	pub fn put(&self, content: &str) -> Result<SPath> {
+		let blob_path = self.dir.join(content_address(content));
+
+		if !blob_path.exists() {
+			std::fs::write(&blob_path, content).map_err(|err| Error::io_create_file(blob_path.to_string(), err))?;
+		}
+
+		Ok(blob_path)
+	}
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::Content;
+
+	#[test]
+	fn test_blob_store_put_content_addressed() -> Result<()> {
+		// -- Setup & Fixtures
+		let dir = std::env::temp_dir().join(format!("udiffx-test-blob-store-{}", std::process::id()));
+		let store = BlobStore::new(SPath::from_std_path(&dir)?)?.with_min_bytes(4);
+
+		// -- Exec
+		let a = store.put("hello")?;
+		let b = store.put("hello")?;
+		let c = store.put("world")?;
+
+		// -- Check
+		assert_eq!(a, b);
+		assert_ne!(a, c);
+		assert!(store.spills(4) && !store.spills(3));
+		let content = Content {
+			content: String::new(),
+			code_fence: None,
+			blob_path: Some(a),
+		};
+		assert_eq!(content.load()?, "hello");
+		assert!(content.blob_hash().is_some_and(|h| h.ends_with("-5")));
+
+		std::fs::remove_dir_all(dir)?;
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
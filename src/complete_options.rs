@@ -0,0 +1,121 @@
+/// Default minimum length for a context fragment to be eligible for suffix matching.
+/// This prevents very short strings (e.g., `"x"`) from false-positive matching.
+const DEFAULT_SUFFIX_MATCH_MIN_LEN: usize = 10;
+
+/// Default minimum length for a context fragment to be eligible for prefix matching.
+/// Higher than the suffix one, as many code lines share their beginning (e.g., `let x = `).
+const DEFAULT_PREFIX_MATCH_MIN_LEN: usize = 20;
+
+/// The file extensions for which `CompleteOptions::for_path` enables the markdown heading leniency.
+const MARKDOWN_EXTENSIONS: &[&str] = &["md", "markdown", "mdx", "mdown", "mkd"];
+
+/// Options controlling the lenient (Resilient/Fuzzy) line matching of the patch completion.
+///
+/// `CompleteOptions::default()` yields the same behavior as `complete` and `apply_patch_incremental`.
+#[derive(Debug, Clone)]
+pub struct CompleteOptions {
+	/// When `true`, two markdown heading lines (`#` to `######` followed by a space, at column 0 in the file)
+	/// match regardless of their heading level (e.g., `## Intro` and `### Intro`). (default true)
+	///
+	/// Disable it for files where `#` begins comments (e.g., shell or Python), see `CompleteOptions::for_path`.
+	pub markdown_headings: bool,
+
+	/// Minimum length of the shorter line for a context line to match as the end of the file line, or
+	/// the reverse (e.g., a line whose beginning was dropped by the model). `None` disables it. (default `Some(10)`)
+	pub suffix_match_min_len: Option<usize>,
+
+	/// Minimum length of a context line to match as the beginning of the file line (e.g., a long line truncated
+	/// by the model, with or without a trailing `...`). `None` disables it. (default `Some(20)`)
+	pub prefix_match_min_len: Option<usize>,
+
+	/// When `true`, each completed hunk is preceded by a comment line telling how it matched
+	/// (e.g., `# udiffx: hunk 2 matched at 143 via Resilient, score 0.86`), for review in logs and reports.
+	/// These lines are stripped before the patch is applied. (default false)
+	pub annotate_hunks: bool,
+
+	/// Maximum distance (in lines) between where a hunk with a numbered header (`@@ -l,s +l,s @@`) was expected
+	/// and where it matched. A farther match fails the hunk (needing a confirmation, e.g., a higher value).
+	/// `None` keeps the search only bounded by the lenient proximity cap. (default `None`)
+	///
+	/// Note: Only enforced by the incremental apply (e.g., `apply_file_changes`, `complete_and_apply`),
+	/// which reports the drifts in `DirectiveStatus::hunk_drifts`.
+	pub max_drift: Option<usize>,
+
+	/// When `true`, a context-only hunk (no `+` or `-` line, e.g., a "for reference" hunk) is an assertion:
+	/// its context must be found in the original content (recorded in `DirectiveStatus::context_assertions`),
+	/// the `FILE_PATCH` failing otherwise. When `false`, it is skipped unchecked. No hunk is produced for it
+	/// either way. (default false)
+	///
+	/// Note: Only checked by the incremental apply (e.g., `apply_file_changes`, `complete_and_apply`).
+	pub assert_context_hunks: bool,
+}
+
+impl Default for CompleteOptions {
+	fn default() -> Self {
+		Self {
+			markdown_headings: true,
+			suffix_match_min_len: Some(DEFAULT_SUFFIX_MATCH_MIN_LEN),
+			prefix_match_min_len: Some(DEFAULT_PREFIX_MATCH_MIN_LEN),
+			annotate_hunks: false,
+			max_drift: None,
+			assert_context_hunks: false,
+		}
+	}
+}
+
+impl CompleteOptions {
+	/// The options for the file at `path`: the markdown heading leniency is only enabled
+	/// for markdown extensions (e.g., `.md`).
+	pub fn for_path(path: &str) -> Self {
+		let extension = path.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase());
+		Self {
+			markdown_headings: extension.is_some_and(|ext| MARKDOWN_EXTENSIONS.contains(&ext.as_str())),
+			..Default::default()
+		}
+	}
+}
+
+impl From<Option<CompleteOptions>> for CompleteOptions {
+	fn from(opt: Option<CompleteOptions>) -> Self {
+		opt.unwrap_or_default()
+	}
+}
+
+/// Fluid apis
+impl CompleteOptions {
+	/// Enable or disable the markdown heading leniency.
+	pub fn with_markdown_headings(mut self, enabled: bool) -> Self {
+		self.markdown_headings = enabled;
+		self
+	}
+
+	/// Set the suffix matching minimum length (`None` disables it).
+	pub fn with_suffix_match_min_len(mut self, min_len: Option<usize>) -> Self {
+		self.suffix_match_min_len = min_len;
+		self
+	}
+
+	/// Set the prefix matching minimum length (`None` disables it).
+	pub fn with_prefix_match_min_len(mut self, min_len: Option<usize>) -> Self {
+		self.prefix_match_min_len = min_len;
+		self
+	}
+
+	/// Enable or disable the `# udiffx: ...` match annotation of the completed hunks.
+	pub fn with_annotate_hunks(mut self, enabled: bool) -> Self {
+		self.annotate_hunks = enabled;
+		self
+	}
+
+	/// Set the maximum drift of a numbered hunk from its expected line (`None` for no limit).
+	pub fn with_max_drift(mut self, max_drift: Option<usize>) -> Self {
+		self.max_drift = max_drift;
+		self
+	}
+
+	/// Enable or disable the context-only hunks as assertions.
+	pub fn with_assert_context_hunks(mut self, enabled: bool) -> Self {
+		self.assert_context_hunks = enabled;
+		self
+	}
+}
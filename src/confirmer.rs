@@ -0,0 +1,71 @@
+use crate::apply_store::ApplyStore;
+use crate::{ApplyOptions, FileDirective, NewFileCollision};
+use simple_fs::SPath;
+use std::fmt;
+use std::sync::Arc;
+
+/// The decision of a `Confirmer` on a destructive directive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfirmDecision {
+	/// The directive is applied.
+	Approve,
+	/// The directive is refused (nothing changed), failing with a `Denial::NotConfirmed`.
+	Reject,
+}
+
+/// A confirmation hook invoked before a destructive directive is applied, e.g., to ask the user interactively,
+/// while the other directives (patches, appends, new files) are applied without confirmation.
+///
+/// The destructive directives are the `FILE_DELETE`, the `FILE_RENAME`, and the full-file overwrites
+/// (a `FILE_NEW` overwriting an existing file, or a `FILE_COPY` onto an existing file).
+///
+/// See `ApplyOptions::confirmer`.
+#[derive(Clone)]
+pub struct Confirmer {
+	confirm_fn: Arc<dyn Fn(&FileDirective) -> ConfirmDecision + Send + Sync>,
+}
+
+impl Confirmer {
+	/// The `confirm_fn` decides whether the destructive directive is applied.
+	pub fn new(confirm_fn: impl Fn(&FileDirective) -> ConfirmDecision + Send + Sync + 'static) -> Self {
+		Self {
+			confirm_fn: Arc::new(confirm_fn),
+		}
+	}
+
+	pub fn confirm(&self, directive: &FileDirective) -> ConfirmDecision {
+		(self.confirm_fn)(directive)
+	}
+}
+
+impl fmt::Debug for Confirmer {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str("Confirmer")
+	}
+}
+
+/// Whether the directive needs a confirmation (see `Confirmer`).
+pub(crate) fn is_destructive(
+	store: &impl ApplyStore,
+	base_dir: &SPath,
+	directive: &FileDirective,
+	options: &ApplyOptions,
+) -> bool {
+	let is_existing_file = |file_path: &str| {
+		let full_path = base_dir.join(file_path);
+		store.exists(&full_path) && !store.is_dir(&full_path)
+	};
+	match directive {
+		FileDirective::Delete { .. } | FileDirective::Rename { .. } => true,
+		FileDirective::New { file_path, .. } => {
+			options.new_file_collision == NewFileCollision::Overwrite && is_existing_file(file_path)
+		}
+		FileDirective::Copy { to_path, .. } => is_existing_file(to_path),
+		FileDirective::Patch { .. }
+		| FileDirective::Append { .. }
+		| FileDirective::SpanPatch { .. }
+		| FileDirective::Insert { .. }
+		| FileDirective::GlobalReplace { .. }
+		| FileDirective::Fail { .. } => false,
+	}
+}
@@ -0,0 +1,220 @@
+use crate::{ApplyPatchIncrementalData, Content, FileChanges, FileDirective};
+use std::borrow::Cow;
+
+/// A 64-bit FNV-1a hasher, stable across runs, platforms, and Rust versions
+/// (unlike `std::collections::hash_map::DefaultHasher`).
+pub(crate) struct StableHasher(u64);
+
+impl StableHasher {
+	pub(crate) fn new() -> Self {
+		Self(0xcbf2_9ce4_8422_2325)
+	}
+
+	pub(crate) fn write_bytes(&mut self, bytes: &[u8]) {
+		for byte in bytes {
+			self.0 ^= u64::from(*byte);
+			self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+		}
+	}
+
+	/// Writes a length-prefixed field, so that `("ab", "c")` and `("a", "bc")` hash differently.
+	fn write_field(&mut self, value: &str) {
+		self.write_bytes(&(value.len() as u64).to_le_bytes());
+		self.write_bytes(value.as_bytes());
+	}
+
+	pub(crate) fn finish_hex(&self) -> String {
+		format!("{:016x}", self.0)
+	}
+}
+
+/// The content address of a body: its hash and size (e.g., `3f2a9c0e1b7d4a55-2097152`), naming its blob in a
+/// `BlobStore`.
+pub(crate) fn content_address(content: &str) -> String {
+	let mut hasher = StableHasher::new();
+	hasher.write_bytes(content.as_bytes());
+	format!("{}-{}", hasher.finish_hex(), content.len())
+}
+
+/// The content address of a directive body, spilled (its blob name) or not, so both hash the same.
+fn body_address(content: &Content) -> Cow<'_, str> {
+	match content.blob_hash() {
+		Some(blob_hash) => Cow::Borrowed(blob_hash),
+		None => Cow::Owned(content_address(&content.content)),
+	}
+}
+
+impl FileChanges {
+	/// A stable hash (16 hex chars) of the directives, in order, to cache or dedupe applies.
+	///
+	/// Code fences are ignored (as in `FileChanges::diff`), so the same change re-fenced hashes the same.
+	pub fn content_hash(&self) -> String {
+		let mut hasher = StableHasher::new();
+		for directive in self {
+			hasher.write_field(directive.kind());
+			match directive {
+				FileDirective::New { file_path, content } | FileDirective::Append { file_path, content } => {
+					hasher.write_field(file_path);
+					// Note: A body is hashed by its content address, so the same as a blob (not loaded).
+					hasher.write_field(&body_address(content));
+				}
+				FileDirective::Patch {
+					file_path,
+					content,
+					allow_generated,
+				} => {
+					hasher.write_field(file_path);
+					hasher.write_field(&body_address(content));
+					// Note: Only hashed when set, so the hash of a regular patch is unchanged.
+					if *allow_generated {
+						hasher.write_field("allow_generated");
+					}
+				}
+				FileDirective::SpanPatch {
+					file_path,
+					line,
+					columns,
+					line_hash,
+					replacement,
+				} => {
+					hasher.write_field(file_path);
+					hasher.write_field(&format!("{line}:{}-{}", columns.start(), columns.end()));
+					hasher.write_field(line_hash);
+					hasher.write_field(replacement);
+				}
+				FileDirective::Insert {
+					file_path,
+					anchor,
+					regex,
+					position,
+					content,
+				} => {
+					hasher.write_field(file_path);
+					hasher.write_field(anchor);
+					hasher.write_field(&format!("{regex}:{position}"));
+					hasher.write_field(content);
+				}
+				FileDirective::GlobalReplace {
+					glob,
+					pattern,
+					replacement,
+					regex,
+					max_matches,
+				} => {
+					hasher.write_field(glob);
+					hasher.write_field(pattern);
+					hasher.write_field(replacement);
+					hasher.write_field(&format!("{regex}:{max_matches:?}"));
+				}
+				FileDirective::Copy { from_path, to_path } | FileDirective::Rename { from_path, to_path } => {
+					hasher.write_field(from_path);
+					hasher.write_field(to_path);
+				}
+				FileDirective::Delete { file_path } => hasher.write_field(file_path),
+				FileDirective::Fail {
+					kind,
+					file_path,
+					error_msg,
+				} => {
+					hasher.write_field(kind);
+					hasher.write_field(file_path.as_deref().unwrap_or(""));
+					hasher.write_field(error_msg);
+				}
+			}
+		}
+		hasher.finish_hex()
+	}
+}
+
+impl ApplyPatchIncrementalData {
+	/// A stable hash (16 hex chars) of the patch preview (new content and completed patches).
+	pub fn content_hash(&self) -> String {
+		let mut hasher = StableHasher::new();
+		hasher.write_field(&self.new_content);
+		for completed_patch in &self.completed_patches {
+			hasher.write_field(completed_patch);
+		}
+		hasher.finish_hex()
+	}
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::Content;
+	use crate::applier::apply_patch_incremental;
+
+	fn changes(content: &str) -> FileChanges {
+		FileChanges::new(vec![FileDirective::New {
+			file_path: "a.txt".to_string(),
+			content: Content::from_raw(content.to_string()),
+		}])
+	}
+
+	#[test]
+	fn test_content_hash_stable_and_distinct() -> Result<()> {
+		// -- Exec
+		let hash = changes("hello").content_hash();
+
+		// -- Check
+		assert_eq!(hash.len(), 16);
+		assert_eq!(hash, changes("hello").content_hash());
+		assert_eq!(
+			changes("```\nhello\n```").content_hash(),
+			changes("````rust\nhello\n````").content_hash()
+		);
+		assert_ne!(hash, changes("hello!").content_hash());
+		assert_eq!(FileChanges::new(Vec::new()).content_hash(), "cbf29ce484222325");
+		let patch = |allow_generated| {
+			FileChanges::new(vec![FileDirective::Patch {
+				file_path: "a.txt".to_string(),
+				content: Content::from_raw("@@\n-a\n+A\n".to_string()),
+				allow_generated,
+			}])
+			.content_hash()
+		};
+		assert_ne!(patch(false), patch(true));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_content_hash_spilled_as_inline() -> Result<()> {
+		// -- Setup & Fixtures
+		let dir = std::env::temp_dir().join(format!("udiffx-test-content-hash-{}", std::process::id()));
+		let blob_store = crate::BlobStore::new(simple_fs::SPath::from_std_path(&dir)?)?;
+		let spilled = FileChanges::new(vec![FileDirective::New {
+			file_path: "a.txt".to_string(),
+			content: Content {
+				content: String::new(),
+				code_fence: None,
+				blob_path: Some(blob_store.put("hello")?),
+			},
+		}]);
+
+		// -- Exec & Check
+		assert_eq!(spilled.content_hash(), changes("hello").content_hash());
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_content_hash_patch_preview() -> Result<()> {
+		// -- Exec
+		let a = apply_patch_incremental("a\nb\n", "@@\n-a\n+A\n")?;
+		let b = apply_patch_incremental("a\nb\n", "@@\n-a\n+A\n")?;
+		let c = apply_patch_incremental("a\nb\n", "@@\n-b\n+B\n")?;
+
+		// -- Check
+		assert_eq!(a.content_hash(), b.content_hash());
+		assert_ne!(a.content_hash(), c.content_hash());
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
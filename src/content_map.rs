@@ -0,0 +1,240 @@
+use crate::applier::apply_with_store;
+use crate::apply_store::ApplyStore;
+use crate::{ApplyChangesStatus, ApplyOptions, Error, FileChanges, Result, SecurityPolicy};
+use simple_fs::SPath;
+use std::collections::HashMap;
+
+/// The virtual base dir the map keys are mapped under, so the applier path guards still apply
+/// (e.g., a `../x` or `/etc/x` directive path is rejected as outside of the workspace).
+const CONTENT_MAP_ROOT: &str = "/content-map";
+
+/// Applies the file changes to the in-memory `files` (relative path → content), with the default options.
+///
+/// See `apply_file_changes_to_map_with_options`.
+pub fn apply_file_changes_to_map(
+	files: &mut HashMap<String, String>,
+	file_changes: FileChanges,
+) -> Result<ApplyChangesStatus> {
+	apply_file_changes_to_map_with_options(files, file_changes, None)
+}
+
+/// Same as `apply_file_changes_with_options`, but applied to the in-memory `files` (relative path → content)
+/// instead of the local file system (e.g., from WASM, or a server with no workspace on disk).
+///
+/// Keys are relative file paths with `/` separators (e.g., `src/main.rs`). There are no directories,
+/// a "directory" is the set of keys sharing its prefix. The default strict `SecurityPolicy` is used:
+/// every directive path must stay within the map.
+pub fn apply_file_changes_to_map_with_options(
+	files: &mut HashMap<String, String>,
+	file_changes: FileChanges,
+	options: impl Into<ApplyOptions>,
+) -> Result<ApplyChangesStatus> {
+	let options: ApplyOptions = options.into();
+	let base_dir = SPath::new(CONTENT_MAP_ROOT);
+
+	Ok(apply_with_store(
+		&mut MapStore { files },
+		&base_dir,
+		file_changes,
+		&SecurityPolicy::default(),
+		&options,
+		&mut (),
+	))
+}
+
+// region:    --- MapStore
+
+/// Adapts a content map to the applier operations.
+struct MapStore<'a> {
+	files: &'a mut HashMap<String, String>,
+}
+
+impl MapStore<'_> {
+	fn key(path: &SPath) -> Result<String> {
+		path.as_str()
+			.strip_prefix(CONTENT_MAP_ROOT)
+			.and_then(|rest| rest.strip_prefix('/'))
+			.map(|key| key.to_string())
+			.ok_or_else(|| Error::security_violation(path.to_string(), CONTENT_MAP_ROOT))
+	}
+
+	/// The keys under the `key` "directory", sorted.
+	fn keys_under(&self, key: &str) -> Vec<String> {
+		let prefix = format!("{key}/");
+		let mut keys: Vec<String> = self.files.keys().filter(|k| k.starts_with(&prefix)).cloned().collect();
+		keys.sort();
+		keys
+	}
+}
+
+impl ApplyStore for MapStore<'_> {
+	fn exists(&self, path: &SPath) -> bool {
+		Self::key(path).is_ok_and(|key| self.files.contains_key(&key)) || self.is_dir(path)
+	}
+
+	fn is_dir(&self, path: &SPath) -> bool {
+		Self::key(path).is_ok_and(|key| !self.keys_under(&key).is_empty())
+	}
+
+	fn read_to_string(&self, path: &SPath) -> Result<String> {
+		self.files
+			.get(&Self::key(path)?)
+			.cloned()
+			.ok_or_else(|| Error::apply_path_not_found("read", path.to_string()))
+	}
+
+	fn read_bytes(&self, path: &SPath) -> Result<Vec<u8>> {
+		self.read_to_string(path).map(String::into_bytes)
+	}
+
+	fn write(&mut self, path: &SPath, content: &[u8]) -> Result<()> {
+		// Note: The map only holds text, a non UTF-8 content is refused.
+		let content = String::from_utf8(content.to_vec()).map_err(|err| Error::io_write_file(path.to_string(), err))?;
+		self.files.insert(Self::key(path)?, content);
+		Ok(())
+	}
+
+	fn rename(&mut self, from: &SPath, to: &SPath) -> Result<()> {
+		let from_key = Self::key(from)?;
+		let to_key = Self::key(to)?;
+
+		// -- A "directory" is renamed file by file
+		let keys = if self.files.contains_key(&from_key) {
+			vec![from_key.clone()]
+		} else {
+			self.keys_under(&from_key)
+		};
+		if keys.is_empty() {
+			return Err(Error::apply_path_not_found("rename", from.to_string()));
+		}
+
+		for key in keys {
+			if let Some(content) = self.files.remove(&key) {
+				let new_key = format!("{to_key}{}", &key[from_key.len()..]);
+				self.files.insert(new_key, content);
+			}
+		}
+		Ok(())
+	}
+
+	fn delete(&mut self, path: &SPath) -> Result<()> {
+		let key = Self::key(path)?;
+		if self.files.remove(&key).is_some() {
+			return Ok(());
+		}
+		for key in self.keys_under(&key) {
+			self.files.remove(&key);
+		}
+		Ok(())
+	}
+
+	fn list_rel_paths(&self, base_dir: &SPath) -> Result<Vec<String>> {
+		let prefix = match Self::key(base_dir) {
+			Ok(key) => format!("{key}/"),
+			Err(_) => String::new(),
+		};
+		let mut rel_paths: Vec<String> = self
+			.files
+			.keys()
+			.filter_map(|key| key.strip_prefix(&prefix).map(|p| p.to_string()))
+			.collect();
+		rel_paths.sort();
+		Ok(rel_paths)
+	}
+
+	fn is_virtual(&self) -> bool {
+		true
+	}
+}
+
+// endregion: --- MapStore
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::extract_file_changes;
+
+	#[test]
+	fn test_content_map_apply_file_changes() -> Result<()> {
+		// -- Setup & Fixtures
+		let mut files: HashMap<String, String> = HashMap::from([
+			(
+				"src/main.rs".to_string(),
+				"fn main() {\n    println!(\"hi\");\n}\n".to_string(),
+			),
+			("docs/a.md".to_string(), "a".to_string()),
+			("docs/b.md".to_string(), "b".to_string()),
+			("old.txt".to_string(), "old".to_string()),
+		]);
+		let input = r#"
+<FILE_CHANGES>
+<FILE_PATCH file_path="src/main.rs">
+@@
+ fn main() {
+-    println!("hi");
++    println!("hello");
+ }
+</FILE_PATCH>
+<FILE_NEW file_path="README.md">
+# Title
+</FILE_NEW>
+<FILE_RENAME from_path="docs" to_path="guide" />
+<FILE_DELETE file_path="old.txt" />
+<FILE_NEW file_path="../outside.txt">
+x
+</FILE_NEW>
+</FILE_CHANGES>
+"#;
+		let (changes, _) = extract_file_changes(input, false)?;
+
+		// -- Exec
+		let status = apply_file_changes_to_map(&mut files, changes)?;
+
+		// -- Check
+		let successes: Vec<bool> = status.items.iter().map(|i| i.success()).collect();
+		assert_eq!(successes, vec![true, true, true, true, false]);
+		let msg = status.items[4].error_msg().unwrap_or_default();
+		assert!(msg.contains("Security violation"), "{msg}");
+		let mut keys: Vec<&str> = files.keys().map(String::as_str).collect();
+		keys.sort();
+		assert_eq!(keys, vec!["README.md", "guide/a.md", "guide/b.md", "src/main.rs"]);
+		assert_eq!(files["src/main.rs"], "fn main() {\n    println!(\"hello\");\n}\n");
+		assert_eq!(files["README.md"], "# Title\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_content_map_apply_file_changes_backup_dir() -> Result<()> {
+		// -- Setup & Fixtures
+		let mut files: HashMap<String, String> = HashMap::from([("a.txt".to_string(), "a\n".to_string())]);
+		let input = r#"
+<FILE_CHANGES>
+<FILE_NEW file_path="a.txt">
+A
+</FILE_NEW>
+</FILE_CHANGES>
+"#;
+		let (changes, _) = extract_file_changes(input, false)?;
+		let options = ApplyOptions::default().with_backup_dir("backups");
+
+		// -- Exec
+		let status = apply_file_changes_to_map_with_options(&mut files, changes, options)?;
+
+		// -- Check
+		assert!(status.items[0].success(), "{status:#?}");
+		assert_eq!(files["a.txt"], "A\n");
+		let backup_keys: Vec<&String> = files.keys().filter(|key| key.starts_with("backups/")).collect();
+		assert_eq!(backup_keys.len(), 1, "{files:?}");
+		assert!(backup_keys[0].ends_with("/a.txt"));
+		assert_eq!(files[backup_keys[0]], "a\n");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
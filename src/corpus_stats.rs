@@ -0,0 +1,190 @@
+use crate::applier::apply_patch_incremental;
+use crate::{Error, FileDirective, MatchTier, Result, extract_file_changes};
+use derive_more::Display;
+use simple_fs::SPath;
+use std::fmt;
+
+/// The file of a scenario holding the content the patches are applied to.
+const SCENARIO_ORIGINAL_FILE: &str = "original.txt";
+
+/// The file of a scenario holding the recorded model output (a `<FILE_CHANGES>` block).
+const SCENARIO_CHANGES_FILE: &str = "changes.txt";
+
+/// The result of completing and applying the patches of one recorded scenario.
+#[derive(Debug, Clone)]
+pub struct ScenarioOutcome {
+	/// The scenario dir name.
+	pub name: String,
+	pub total_hunks: usize,
+	pub failed_hunks: usize,
+	/// The most lenient tier needed by the scenario hunks (`None` if no hunk needed matching, or all failed).
+	pub max_tier: Option<MatchTier>,
+	/// Set when the scenario could not be run (e.g., unreadable files, or no `FILE_PATCH`).
+	pub error: Option<String>,
+}
+
+impl ScenarioOutcome {
+	/// `true` when every hunk applied.
+	pub fn is_success(&self) -> bool {
+		self.error.is_none() && self.failed_hunks == 0
+	}
+
+	/// `true` when some (but not all) hunks applied.
+	pub fn is_partial(&self) -> bool {
+		self.error.is_none() && self.failed_hunks > 0 && self.failed_hunks < self.total_hunks
+	}
+}
+
+/// The scenario counts per `ScenarioOutcome::max_tier`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Display)]
+#[display("strict {strict}, resilient {resilient}, fuzzy {fuzzy}, none {none}")]
+pub struct TierHistogram {
+	pub strict: usize,
+	pub resilient: usize,
+	pub fuzzy: usize,
+	pub none: usize,
+}
+
+/// The statistics of `corpus_stats`, with one outcome per scenario (sorted by name).
+#[derive(Debug, Clone, Default)]
+pub struct CorpusStats {
+	pub outcomes: Vec<ScenarioOutcome>,
+}
+
+/// Runs the patch completion on every recorded scenario of `corpus_dir`, to measure how exact model patches are
+/// (e.g., before and after a prompt change).
+///
+/// Each direct sub dir with an `original.txt` is a scenario, and its `changes.txt` holds the recorded model output.
+/// The `FILE_PATCH` directives are applied in order to the evolving content (other directives are ignored).
+pub fn corpus_stats(corpus_dir: impl Into<SPath>) -> Result<CorpusStats> {
+	let corpus_dir = corpus_dir.into();
+	let entries = std::fs::read_dir(&corpus_dir).map_err(|err| Error::io_read_file(corpus_dir.to_string(), err))?;
+
+	let mut scenario_dirs: Vec<SPath> = Vec::new();
+	for entry in entries {
+		let path = entry
+			.map_err(|err| Error::io_read_file(corpus_dir.to_string(), err))?
+			.path();
+		let path = SPath::from_std_path(path)?;
+		if path.join(SCENARIO_ORIGINAL_FILE).is_file() {
+			scenario_dirs.push(path);
+		}
+	}
+	scenario_dirs.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+	let outcomes = scenario_dirs.iter().map(run_scenario).collect();
+
+	Ok(CorpusStats { outcomes })
+}
+
+impl CorpusStats {
+	pub fn succeeded(&self) -> usize {
+		self.outcomes.iter().filter(|o| o.is_success()).count()
+	}
+
+	pub fn partial(&self) -> usize {
+		self.outcomes.iter().filter(|o| o.is_partial()).count()
+	}
+
+	pub fn failed(&self) -> usize {
+		self.outcomes.len() - self.succeeded() - self.partial()
+	}
+
+	/// The fraction (`0.0..=1.0`) of fully applied scenarios (`0.0` for an empty corpus).
+	pub fn success_rate(&self) -> f64 {
+		ratio(self.succeeded(), self.outcomes.len())
+	}
+
+	pub fn total_hunks(&self) -> usize {
+		self.outcomes.iter().map(|o| o.total_hunks).sum()
+	}
+
+	pub fn failed_hunks(&self) -> usize {
+		self.outcomes.iter().map(|o| o.failed_hunks).sum()
+	}
+
+	/// The tier distribution of the scenarios that ran.
+	pub fn tier_histogram(&self) -> TierHistogram {
+		let mut histogram = TierHistogram::default();
+		for outcome in self.outcomes.iter().filter(|o| o.error.is_none()) {
+			match outcome.max_tier {
+				Some(MatchTier::Strict) => histogram.strict += 1,
+				Some(MatchTier::Resilient) => histogram.resilient += 1,
+				Some(MatchTier::Fuzzy) => histogram.fuzzy += 1,
+				None => histogram.none += 1,
+			}
+		}
+		histogram
+	}
+}
+
+impl fmt::Display for CorpusStats {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(
+			f,
+			"scenarios: {}, succeeded: {} ({:.1}%), partial: {}, failed: {}",
+			self.outcomes.len(),
+			self.succeeded(),
+			self.success_rate() * 100.,
+			self.partial(),
+			self.failed()
+		)?;
+		writeln!(
+			f,
+			"hunks: {}, failed: {} ({:.1}%)",
+			self.total_hunks(),
+			self.failed_hunks(),
+			ratio(self.failed_hunks(), self.total_hunks()) * 100.
+		)?;
+		writeln!(f, "tiers: {}", self.tier_histogram())
+	}
+}
+
+// region:    --- Support
+
+fn run_scenario(dir: &SPath) -> ScenarioOutcome {
+	let mut outcome = ScenarioOutcome {
+		name: dir.name().to_string(),
+		total_hunks: 0,
+		failed_hunks: 0,
+		max_tier: None,
+		error: None,
+	};
+	if let Err(err) = run_scenario_patches(dir, &mut outcome) {
+		outcome.error = Some(err.to_string());
+	}
+	outcome
+}
+
+fn run_scenario_patches(dir: &SPath, outcome: &mut ScenarioOutcome) -> Result<()> {
+	let original_path = dir.join(SCENARIO_ORIGINAL_FILE);
+	let mut content =
+		std::fs::read_to_string(&original_path).map_err(|err| Error::io_read_file(original_path.to_string(), err))?;
+	let changes_path = dir.join(SCENARIO_CHANGES_FILE);
+	let changes_str =
+		std::fs::read_to_string(&changes_path).map_err(|err| Error::io_read_file(changes_path.to_string(), err))?;
+
+	let (changes, _) = extract_file_changes(&changes_str, false)?;
+	let mut has_patch = false;
+	for directive in changes {
+		if let FileDirective::Patch { content: patch, .. } = directive {
+			has_patch = true;
+			let data = apply_patch_incremental(&content, &patch.content)?;
+			outcome.total_hunks += data.total_hunks;
+			outcome.failed_hunks += data.hunk_errors.len();
+			outcome.max_tier = outcome.max_tier.max(data.max_tier);
+			content = data.new_content;
+		}
+	}
+
+	if !has_patch {
+		return Err(Error::custom(format!("no FILE_PATCH in '{changes_path}'")));
+	}
+	Ok(())
+}
+
+fn ratio(count: usize, total: usize) -> f64 {
+	if total == 0 { 0. } else { count as f64 / total as f64 }
+}
+
+// endregion: --- Support
@@ -0,0 +1,231 @@
+use derive_more::Display;
+
+/// A change format (dialect) recognized by `detect_change_format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum DetectedFormat {
+	/// The udiffx `<FILE_CHANGES>` envelope (see `extract_file_changes`).
+	#[display("udiffx tags")]
+	UdiffxTags,
+	/// The Codex `*** Begin Patch` / `*** Update File:` envelope.
+	#[display("Codex apply_patch")]
+	CodexApplyPatch,
+	/// Aider `<<<<<<< SEARCH` / `=======` / `>>>>>>> REPLACE` blocks.
+	#[display("aider search/replace")]
+	AiderSearchReplace,
+	/// A raw unified diff (`diff --git`, or `---` / `+++` headers followed by a `@@` hunk), outside of udiffx tags.
+	#[display("unified diff")]
+	UnifiedDiff,
+	/// Hashline edits, where lines are referenced as `{line}:{hash}|` (e.g., `12:a3|let x = 1;`).
+	#[display("hashline edits")]
+	HashlineEdits,
+}
+
+/// Minimum number of `{line}:{hash}|` lines to consider the input as hashline edits.
+const HASHLINE_MIN_LINES: usize = 2;
+
+/// Detects the change format(s) contained in a model response, in order of first appearance.
+///
+/// This is a cheap line-based detection meant to route a response to the right extractor
+/// (only `UdiffxTags` is extracted by this crate). Returns an empty list if nothing is recognized.
+pub fn detect_change_format(input: &str) -> Vec<DetectedFormat> {
+	let mut found: Vec<(usize, DetectedFormat)> = Vec::new();
+	let mut found_at = |idx: Option<usize>, format: DetectedFormat| {
+		if let Some(idx) = idx {
+			found.push((idx, format));
+		}
+	};
+
+	found_at(find_udiffx_tags(input), DetectedFormat::UdiffxTags);
+	found_at(
+		find_line_starting(input, "*** Begin Patch"),
+		DetectedFormat::CodexApplyPatch,
+	);
+	found_at(find_aider_block(input), DetectedFormat::AiderSearchReplace);
+	found_at(
+		find_unified_diff(&blank_udiffx_blocks(input)),
+		DetectedFormat::UnifiedDiff,
+	);
+	found_at(find_hashlines(input), DetectedFormat::HashlineEdits);
+
+	found.sort_by_key(|(idx, _)| *idx);
+	found.into_iter().map(|(_, format)| format).collect()
+}
+
+// region:    --- Support
+
+/// Iterates over the lines of `input` with their byte offset.
+fn lines_with_offset(input: &str) -> impl Iterator<Item = (usize, &str)> {
+	input.split_inclusive('\n').scan(0, |offset, line| {
+		let start = *offset;
+		*offset += line.len();
+		Some((start, line.trim_end_matches(['\r', '\n'])))
+	})
+}
+
+fn find_line_starting(input: &str, prefix: &str) -> Option<usize> {
+	lines_with_offset(input)
+		.find(|(_, line)| line.trim_start().starts_with(prefix))
+		.map(|(idx, _)| idx)
+}
+
+fn find_udiffx_tags(input: &str) -> Option<usize> {
+	let upper = input.to_ascii_uppercase();
+	upper.find("<FILE_CHANGES")
+}
+
+/// Finds a `<<<<<<< SEARCH` line followed by a `=======` line and a `>>>>>>> REPLACE` line.
+fn find_aider_block(input: &str) -> Option<usize> {
+	let mut search_idx: Option<usize> = None;
+	let mut has_divider = false;
+
+	for (idx, line) in lines_with_offset(input) {
+		let line = line.trim();
+		if line.starts_with("<<<<<<<") && line.ends_with("SEARCH") {
+			search_idx = Some(idx);
+			has_divider = false;
+		} else if search_idx.is_some() && line == "=======" {
+			has_divider = true;
+		} else if has_divider && line.starts_with(">>>>>>>") && line.ends_with("REPLACE") {
+			return search_idx;
+		}
+	}
+
+	None
+}
+
+/// Finds a `diff --git` line, or a `---` line directly followed by a `+++` line and then a `@@` line.
+fn find_unified_diff(input: &str) -> Option<usize> {
+	let lines: Vec<(usize, &str)> = lines_with_offset(input).collect();
+
+	for (i, (idx, line)) in lines.iter().enumerate() {
+		if line.starts_with("diff --git ") {
+			return Some(*idx);
+		}
+		if line.starts_with("--- ")
+			&& lines.get(i + 1).is_some_and(|(_, next)| next.starts_with("+++ "))
+			&& lines.get(i + 2).is_some_and(|(_, hunk)| hunk.starts_with("@@"))
+		{
+			return Some(*idx);
+		}
+	}
+
+	None
+}
+
+/// Finds the first of at least `HASHLINE_MIN_LINES` lines starting with `{line}:{hash}|`.
+fn find_hashlines(input: &str) -> Option<usize> {
+	let mut matches = lines_with_offset(input).filter(|(_, line)| is_hashline(line.trim_start()));
+	let first = matches.next()?.0;
+	(matches.count() + 1 >= HASHLINE_MIN_LINES).then_some(first)
+}
+
+/// Returns `true` for `{digits}:{2+ hex}|...`.
+fn is_hashline(line: &str) -> bool {
+	let Some((num, rest)) = line.split_once(':') else {
+		return false;
+	};
+	let Some((hash, _)) = rest.split_once('|') else {
+		return false;
+	};
+	!num.is_empty()
+		&& num.chars().all(|c| c.is_ascii_digit())
+		&& hash.len() >= 2
+		&& hash.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Replaces the `<FILE_CHANGES>...</FILE_CHANGES>` blocks with spaces (keeping the byte offsets),
+/// so the patches inside them are not reported as raw unified diffs.
+fn blank_udiffx_blocks(input: &str) -> String {
+	let upper = input.to_ascii_uppercase();
+	let mut out = input.to_string();
+	let mut pos = 0;
+
+	while let Some(start) = upper[pos..].find("<FILE_CHANGES").map(|i| pos + i) {
+		let end = upper[start..]
+			.find("</FILE_CHANGES>")
+			.map(|i| start + i + "</FILE_CHANGES>".len())
+			.unwrap_or(input.len());
+		let blank: String = input[start..end]
+			.chars()
+			.map(|c| if c == '\n' { '\n' } else { ' ' })
+			.collect();
+		// Multi-byte chars become single spaces, so pad to keep the same byte length.
+		let blank = format!("{blank:<width$}", width = end - start);
+		out.replace_range(start..end, &blank);
+		pos = end;
+	}
+
+	out
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_detect_change_format_multiple_in_order() -> Result<()> {
+		// -- Setup & Fixtures
+		let input = r#"First an aider edit:
+
+src/main.rs
+<<<<<<< SEARCH
+old
+=======
+new
+>>>>>>> REPLACE
+
+Then udiffx:
+
+<FILE_CHANGES>
+<FILE_PATCH file_path="a.rs">
+--- a/a.rs
++++ b/a.rs
+@@
+-x
++y
+</FILE_PATCH>
+</FILE_CHANGES>
+
+*** Begin Patch
+*** Update File: b.rs
+*** End Patch
+"#;
+
+		// -- Exec
+		let formats = detect_change_format(input);
+
+		// -- Check
+		assert_eq!(
+			formats,
+			vec![
+				DetectedFormat::AiderSearchReplace,
+				DetectedFormat::UdiffxTags,
+				DetectedFormat::CodexApplyPatch
+			]
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_detect_change_format_unified_diff_and_hashline() -> Result<()> {
+		// -- Setup & Fixtures
+		let diff = "Here is the diff:\n--- a/x.rs\n+++ b/x.rs\n@@ -1 +1 @@\n-a\n+b\n";
+		let hashline = "12:a3|fn main() {\n13:0f|}\n";
+
+		// -- Exec & Check
+		assert_eq!(detect_change_format(diff), vec![DetectedFormat::UnifiedDiff]);
+		assert_eq!(detect_change_format(hashline), vec![DetectedFormat::HashlineEdits]);
+		assert!(detect_change_format("just prose, 12:30 is lunch").is_empty());
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
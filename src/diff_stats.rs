@@ -0,0 +1,138 @@
+use derive_more::Display;
+use std::ops::AddAssign;
+
+/// Line-level change counts, as in `git diff --stat` summaries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Display)]
+#[display(
+	"{files} file{} changed, {insertions} insertion{}(+), {deletions} deletion{}(-)",
+	plural(*files),
+	plural(*insertions),
+	plural(*deletions)
+)]
+pub struct DiffStats {
+	pub files: usize,
+	pub insertions: usize,
+	pub deletions: usize,
+}
+
+/// Computes the stats of the change from `old` to `new` (`files` is `1` if they differ, `0` otherwise).
+pub fn diff_stats(old: &str, new: &str) -> DiffStats {
+	if old == new {
+		return DiffStats::default();
+	}
+	let patch = diffy::create_patch(old, new).to_string();
+	let mut stats = DiffStats::from_unified_diff(&patch);
+	stats.files = 1;
+	stats
+}
+
+impl DiffStats {
+	/// Counts the `+` / `-` lines of the hunks of a unified diff (file headers are ignored).
+	/// `files` is `1` if there is at least one insertion or deletion, `0` otherwise.
+	pub fn from_unified_diff(patch: &str) -> Self {
+		let mut stats = Self::default();
+		let mut in_hunk = false;
+
+		for line in patch.lines() {
+			if line.starts_with("@@") {
+				in_hunk = true;
+			} else if !in_hunk {
+				continue;
+			} else if line.starts_with('+') {
+				stats.insertions += 1;
+			} else if line.starts_with('-') {
+				stats.deletions += 1;
+			}
+		}
+
+		if stats.insertions > 0 || stats.deletions > 0 {
+			stats.files = 1;
+		}
+		stats
+	}
+
+	/// Stats of a file created (or appended to) with `content`.
+	pub(crate) fn from_insertion(content: &str) -> Self {
+		Self {
+			files: 1,
+			insertions: content.lines().count(),
+			deletions: 0,
+		}
+	}
+
+	/// Stats of a file deleted with `content`.
+	pub(crate) fn from_deletion(content: &str) -> Self {
+		Self {
+			files: 1,
+			insertions: 0,
+			deletions: content.lines().count(),
+		}
+	}
+}
+
+impl AddAssign for DiffStats {
+	fn add_assign(&mut self, other: Self) {
+		self.files += other.files;
+		self.insertions += other.insertions;
+		self.deletions += other.deletions;
+	}
+}
+
+// region:    --- Support
+
+fn plural(count: usize) -> &'static str {
+	if count == 1 { "" } else { "s" }
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_diff_stats_simple() -> Result<()> {
+		// -- Setup & Fixtures
+		let old = "a\nb\nc\n";
+		let new = "a\nB\nc\nd\n";
+
+		// -- Exec
+		let stats = diff_stats(old, new);
+
+		// -- Check
+		assert_eq!(
+			stats,
+			DiffStats {
+				files: 1,
+				insertions: 2,
+				deletions: 1
+			}
+		);
+		assert_eq!(stats.to_string(), "1 file changed, 2 insertions(+), 1 deletion(-)");
+		assert_eq!(diff_stats(old, old), DiffStats::default());
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_diff_stats_from_unified_diff_ignores_headers() -> Result<()> {
+		// -- Setup & Fixtures
+		let patch = "--- a/x.rs\n+++ b/x.rs\n@@ -1,2 +1,2 @@\n-old\n+new\n ctx\n";
+
+		// -- Exec
+		let stats = DiffStats::from_unified_diff(patch);
+
+		// -- Check
+		assert_eq!(stats.insertions, 1);
+		assert_eq!(stats.deletions, 1);
+		assert_eq!(stats.files, 1);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
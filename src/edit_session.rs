@@ -0,0 +1,182 @@
+use crate::content_hash::StableHasher;
+use crate::files_context::{list_context_files, push_file_content};
+use crate::{
+	ApplyChangesStatus, ApplyOptions, DirectiveKind, Error, FileChanges, Result, SecurityPolicy,
+	apply_file_changes_with_options,
+};
+use simple_fs::SPath;
+use std::collections::BTreeMap;
+
+/// A multi-turn edit session on a base dir: tracks the content version of each file the model last saw,
+/// applies the model batches, and produces the deltas to re-send to the model.
+///
+/// A file is seen when loaded with `EditSession::load_files_context` (or marked with `mark_seen`),
+/// and after each apply, the files a successful directive wrote (or removed) are seen as they now are,
+/// since the model knows what it wrote. The other changes (a partially applied patch, an edit outside of the session)
+/// are the `deltas`.
+#[derive(Debug, Clone)]
+pub struct EditSession {
+	base_dir: SPath,
+	security_policy: SecurityPolicy,
+	options: ApplyOptions,
+	/// The content hash of each file as the model last saw it (`None` when seen as missing), by relative path.
+	seen: BTreeMap<String, Option<String>>,
+	/// The statuses of the applied batches, in order.
+	batches: Vec<ApplyChangesStatus>,
+}
+
+/// A tracked file whose content changed since the model last saw it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileDelta {
+	/// The file has a new content (or was created).
+	Changed { file_path: String, content: String },
+	/// The file was removed.
+	Removed { file_path: String },
+}
+
+impl FileDelta {
+	pub fn file_path(&self) -> &str {
+		match self {
+			FileDelta::Changed { file_path, .. } | FileDelta::Removed { file_path } => file_path,
+		}
+	}
+}
+
+impl EditSession {
+	/// The `security_policy` and `options` are used for every apply of the session.
+	pub fn new(
+		base_dir: impl Into<SPath>,
+		security_policy: impl Into<SecurityPolicy>,
+		options: impl Into<ApplyOptions>,
+	) -> Self {
+		Self {
+			base_dir: base_dir.into(),
+			security_policy: security_policy.into(),
+			options: options.into(),
+			seen: BTreeMap::new(),
+			batches: Vec::new(),
+		}
+	}
+
+	pub fn base_dir(&self) -> &SPath {
+		&self.base_dir
+	}
+
+	/// The statuses of the batches applied in this session, in order.
+	pub fn batches(&self) -> &[ApplyChangesStatus] {
+		&self.batches
+	}
+
+	/// Same as `load_files_context`, marking the loaded files as seen by the model.
+	pub fn load_files_context(&mut self, globs: &[&str]) -> Result<Option<String>> {
+		let rel_files = list_context_files(&self.base_dir, globs)?;
+		if rel_files.is_empty() {
+			return Ok(None);
+		}
+
+		let mut out = String::new();
+		for (rel_path, file) in rel_files {
+			let content = simple_fs::read_to_string(&file).map_err(Error::simple_fs)?;
+			push_file_content(&mut out, &rel_path, &content);
+			self.seen.insert(rel_path, Some(hash_content(content.as_bytes())));
+		}
+		Ok(Some(out))
+	}
+
+	/// Marks the files (relative to the base dir) as seen by the model in their current content.
+	pub fn mark_seen(&mut self, rel_paths: impl IntoIterator<Item = impl Into<String>>) -> Result<()> {
+		for rel_path in rel_paths {
+			let rel_path = rel_path.into();
+			let hash = self.current_hash(&rel_path)?;
+			self.seen.insert(rel_path, hash);
+		}
+		Ok(())
+	}
+
+	/// Applies the batch to the base dir, then marks the files the successful directives wrote or removed as seen.
+	pub fn apply(&mut self, file_changes: FileChanges) -> Result<&ApplyChangesStatus> {
+		let status = apply_file_changes_with_options(
+			&self.base_dir,
+			file_changes,
+			self.security_policy.clone(),
+			self.options.clone(),
+		)?;
+
+		let mut written: Vec<String> = Vec::new();
+		for info in status.items.iter().filter(|info| info.success) {
+			match &info.kind {
+				DirectiveKind::Rename { from_path, file_path } => {
+					written.extend([from_path.clone(), file_path.clone()])
+				}
+				DirectiveKind::GlobalReplace { .. } => {
+					written.extend(info.replace_counts.iter().map(|c| c.file_path.clone()))
+				}
+				DirectiveKind::Fail { .. } => {}
+				_ => written.push(info.file_path().to_string()),
+			}
+		}
+		self.mark_seen(written)?;
+
+		self.batches.push(status);
+		Ok(&self.batches[self.batches.len() - 1])
+	}
+
+	/// The tracked files whose content changed since the model last saw them (sorted by path).
+	pub fn deltas(&self) -> Result<Vec<FileDelta>> {
+		let mut deltas = Vec::new();
+		for (rel_path, seen_hash) in &self.seen {
+			let full_path = self.base_dir.join(rel_path);
+			if !full_path.is_file() {
+				if seen_hash.is_some() {
+					deltas.push(FileDelta::Removed {
+						file_path: rel_path.clone(),
+					});
+				}
+				continue;
+			}
+
+			let content = simple_fs::read_to_string(&full_path).map_err(Error::simple_fs)?;
+			if seen_hash.as_deref() != Some(hash_content(content.as_bytes()).as_str()) {
+				deltas.push(FileDelta::Changed {
+					file_path: rel_path.clone(),
+					content,
+				});
+			}
+		}
+		Ok(deltas)
+	}
+
+	/// The `<FILE_CONTENT path="...">` blocks of the changed `deltas` to re-send to the model
+	/// (`None` when nothing changed), marking all the deltas (the removed files included) as seen.
+	pub fn take_delta_context(&mut self) -> Result<Option<String>> {
+		let deltas = self.deltas()?;
+		let mut out = String::new();
+		for delta in &deltas {
+			if let FileDelta::Changed { file_path, content } = delta {
+				push_file_content(&mut out, file_path, content);
+			}
+		}
+		self.mark_seen(deltas.iter().map(|d| d.file_path().to_string()))?;
+
+		Ok((!out.is_empty()).then_some(out))
+	}
+
+	fn current_hash(&self, rel_path: &str) -> Result<Option<String>> {
+		let full_path = self.base_dir.join(rel_path);
+		if !full_path.is_file() {
+			return Ok(None);
+		}
+		let bytes = std::fs::read(&full_path).map_err(|err| Error::io_read_file(full_path.to_string(), err))?;
+		Ok(Some(hash_content(&bytes)))
+	}
+}
+
+// region:    --- Support
+
+fn hash_content(bytes: &[u8]) -> String {
+	let mut hasher = StableHasher::new();
+	hasher.write_bytes(bytes);
+	hasher.finish_hex()
+}
+
+// endregion: --- Support
@@ -1,4 +1,6 @@
+use crate::{Denial, ExcerptLimits, truncate_excerpt};
 use derive_more::{Display, From};
+use std::ops::RangeInclusive;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
@@ -13,22 +15,104 @@ pub enum Error {
 	ParseMissingAttribute { tag: String, attr: String },
 	#[display("Unknown directive tag '{tag}'")]
 	ParseUnknownDirectiveTag { tag: String },
+	#[display("Duplicate attribute '{attr}' with different values for tag '{tag}'")]
+	ParseDuplicateAttribute { tag: String, attr: String },
+	#[display("Attribute '{conflicting}' conflicts with '{attr}' for tag '{tag}'")]
+	ParseConflictingAttributes {
+		tag: String,
+		attr: String,
+		conflicting: String,
+	},
+	#[display("Body of '{tag}' is {size} bytes, exceeding the limit of {max} bytes")]
+	ParseBodyTooLarge { tag: String, size: usize, max: usize },
+	#[display("Too many directives, {count} found, limit is {max}")]
+	ParseTooManyDirectives { count: usize, max: usize },
+	#[display("Invalid value '{value}' for attribute '{attr}' of tag '{tag}'")]
+	ParseInvalidAttribute { tag: String, attr: String, value: String },
 
 	// -- Apply / Operations
 	#[display("Path not found for {op}: {path}")]
 	ApplyPathNotFound { op: String, path: String },
 	#[display("No changes applied to '{file_path}'")]
 	ApplyNoChanges { file_path: String },
+	#[display("Patch context does not match '{file_path}', did you mean '{suggested_path}'?")]
+	ApplyWrongTargetFile { file_path: String, suggested_path: String },
+	#[display("Path '{path}' not found and multiple candidates exist: {}", candidates.join(", "))]
+	ApplyAmbiguousPath { path: String, candidates: Vec<String> },
+	#[display("Not applied, another directive of the batch failed validation")]
+	ApplyBatchNotApplied,
+	#[display("Not applied, a previous directive of the batch failed")]
+	ApplyBatchStopped,
+	#[display("Rolled back, a later directive of the batch failed")]
+	ApplyBatchRolledBack,
+	#[display("Skipped, the apply budget of the batch is exhausted")]
+	ApplyBatchBudgetExhausted,
+	#[display("Apply budget exceeded, more than {max} for {limit}")]
+	ApplyBudgetExceeded { limit: String, max: u64 },
+	#[display("Refused to apply the change to '{file_path}': {denial}")]
+	ApplyDenied { file_path: String, denial: Denial },
+	#[display("Patch modifies the protected region at line {line} of '{file_path}'")]
+	ApplyProtectedRegion { file_path: String, line: usize },
+	#[display("File '{file_path}' already exists, not overwritten by FILE_NEW")]
+	ApplyFileExists { file_path: String },
+	#[display("Could not materialize '{file_path}': {cause}")]
+	ApplyMaterializeFailed { file_path: String, cause: String },
+	#[display("Post-apply validation failed for '{file_path}': {cause}")]
+	ApplyValidationFailed { file_path: String, cause: String },
+	#[display("Target '{path}' is a symlink, not written")]
+	ApplySymlinkTarget { path: String },
+	#[display("Rename target '{to_path}' already exists, '{from_path}' not renamed")]
+	ApplyRenameTargetExists { from_path: String, to_path: String },
+	#[display("Line {line} of '{file_path}' does not match the line hash '{line_hash}', the span was not replaced")]
+	ApplySpanLineMismatch {
+		file_path: String,
+		line: usize,
+		line_hash: String,
+	},
+	#[display("Columns {start}-{end} are out of the {len} columns of line {line} of '{file_path}'")]
+	ApplySpanOutOfRange {
+		file_path: String,
+		line: usize,
+		start: usize,
+		end: usize,
+		len: usize,
+	},
+	#[display("Invalid replace pattern '{pattern}': {cause}")]
+	ApplyInvalidPattern { pattern: String, cause: String },
+	#[display("Pattern matches {count} times in '{glob}' (max {max}), nothing replaced")]
+	ApplyTooManyMatches { glob: String, count: usize, max: usize },
+	#[display("Anchor '{anchor}' not found in '{file_path}', nothing inserted")]
+	ApplyAnchorNotFound { file_path: String, anchor: String },
+	#[display(
+		"Context-only hunk {hunk_number} of '{file_path}' not found (starting with '{context}'), nothing applied"
+	)]
+	ApplyContextAssertionFailed {
+		file_path: String,
+		hunk_number: usize,
+		context: String,
+	},
 
 	// -- Security / Guard
 	#[display("Security violation, target '{target}' is outside base dir '{base_dir}'")]
 	SecurityViolation { target: String, base_dir: String },
+	#[display("Security violation, target '{target}' is denied by the guard glob '{glob}'")]
+	GuardDenied { target: String, glob: String },
+	#[display("Security violation, target '{target}' does not match any guard allow glob")]
+	GuardNotAllowed { target: String },
+	#[display("Security violation, target '{target}' is a write through a symlink (resolved to '{resolved}')")]
+	SymlinkDenied { target: String, resolved: String },
 
 	// -- diffy
-	#[display("diffy parse patch error for '{path}': {cause}\nPatch:\n{patch}")]
+	#[display(
+		"diffy parse patch error for '{path}': {cause}\nPatch:\n{}",
+		truncate_excerpt(patch, ExcerptLimits::default())
+	)]
 	DiffyParsePatch { path: String, cause: String, patch: String },
 
-	#[display("diffy apply patch error for '{path}': {cause}\nPatch:\n{patch}")]
+	#[display(
+		"diffy apply patch error for '{path}': {cause}\nPatch:\n{}",
+		truncate_excerpt(patch, ExcerptLimits::default())
+	)]
 	DiffyApplyPatch { path: String, cause: String, patch: String },
 
 	#[display("patch completion error: {cause}")]
@@ -59,6 +143,9 @@ pub enum Error {
 
 	#[display("simple_fs error: {cause}")]
 	SimpleFs { cause: String },
+
+	#[display("git {args} failed: {cause}")]
+	Git { args: String, cause: String },
 }
 
 #[derive(Debug, Clone, Display)]
@@ -90,6 +177,45 @@ impl Error {
 		Self::ParseUnknownDirectiveTag { tag: tag.into() }
 	}
 
+	pub fn parse_duplicate_attribute(tag: impl Into<String>, attr: impl Into<String>) -> Self {
+		Self::ParseDuplicateAttribute {
+			tag: tag.into(),
+			attr: attr.into(),
+		}
+	}
+
+	pub fn parse_conflicting_attributes(
+		tag: impl Into<String>,
+		attr: impl Into<String>,
+		conflicting: impl Into<String>,
+	) -> Self {
+		Self::ParseConflictingAttributes {
+			tag: tag.into(),
+			attr: attr.into(),
+			conflicting: conflicting.into(),
+		}
+	}
+
+	pub fn parse_body_too_large(tag: impl Into<String>, size: usize, max: usize) -> Self {
+		Self::ParseBodyTooLarge {
+			tag: tag.into(),
+			size,
+			max,
+		}
+	}
+
+	pub fn parse_too_many_directives(count: usize, max: usize) -> Self {
+		Self::ParseTooManyDirectives { count, max }
+	}
+
+	pub fn parse_invalid_attribute(tag: impl Into<String>, attr: impl Into<String>, value: impl Into<String>) -> Self {
+		Self::ParseInvalidAttribute {
+			tag: tag.into(),
+			attr: attr.into(),
+			value: value.into(),
+		}
+	}
+
 	pub fn apply_path_not_found(op: impl Into<String>, path: impl Into<String>) -> Self {
 		Self::ApplyPathNotFound {
 			op: op.into(),
@@ -103,6 +229,129 @@ impl Error {
 		}
 	}
 
+	pub fn apply_wrong_target_file(file_path: impl Into<String>, suggested_path: impl Into<String>) -> Self {
+		Self::ApplyWrongTargetFile {
+			file_path: file_path.into(),
+			suggested_path: suggested_path.into(),
+		}
+	}
+
+	pub fn apply_ambiguous_path(path: impl Into<String>, candidates: Vec<String>) -> Self {
+		Self::ApplyAmbiguousPath {
+			path: path.into(),
+			candidates,
+		}
+	}
+
+	pub fn apply_denied(file_path: impl Into<String>, denial: Denial) -> Self {
+		Self::ApplyDenied {
+			file_path: file_path.into(),
+			denial,
+		}
+	}
+
+	pub fn apply_protected_region(file_path: impl Into<String>, line: usize) -> Self {
+		Self::ApplyProtectedRegion {
+			file_path: file_path.into(),
+			line,
+		}
+	}
+
+	pub fn apply_file_exists(file_path: impl Into<String>) -> Self {
+		Self::ApplyFileExists {
+			file_path: file_path.into(),
+		}
+	}
+
+	pub fn apply_rename_target_exists(from_path: impl Into<String>, to_path: impl Into<String>) -> Self {
+		Self::ApplyRenameTargetExists {
+			from_path: from_path.into(),
+			to_path: to_path.into(),
+		}
+	}
+
+	pub fn apply_span_line_mismatch(file_path: impl Into<String>, line: usize, line_hash: impl Into<String>) -> Self {
+		Self::ApplySpanLineMismatch {
+			file_path: file_path.into(),
+			line,
+			line_hash: line_hash.into(),
+		}
+	}
+
+	pub fn apply_span_out_of_range(
+		file_path: impl Into<String>,
+		line: usize,
+		columns: &RangeInclusive<usize>,
+		len: usize,
+	) -> Self {
+		Self::ApplySpanOutOfRange {
+			file_path: file_path.into(),
+			line,
+			start: *columns.start(),
+			end: *columns.end(),
+			len,
+		}
+	}
+
+	pub fn apply_invalid_pattern(pattern: impl Into<String>, cause: impl Into<String>) -> Self {
+		Self::ApplyInvalidPattern {
+			pattern: pattern.into(),
+			cause: cause.into(),
+		}
+	}
+
+	pub fn apply_anchor_not_found(file_path: impl Into<String>, anchor: impl Into<String>) -> Self {
+		Self::ApplyAnchorNotFound {
+			file_path: file_path.into(),
+			anchor: anchor.into(),
+		}
+	}
+
+	pub fn apply_context_assertion_failed(
+		file_path: impl Into<String>,
+		hunk_number: usize,
+		context: impl Into<String>,
+	) -> Self {
+		Self::ApplyContextAssertionFailed {
+			file_path: file_path.into(),
+			hunk_number,
+			context: context.into(),
+		}
+	}
+
+	pub fn apply_materialize_failed(file_path: impl Into<String>, cause: impl Into<String>) -> Self {
+		Self::ApplyMaterializeFailed {
+			file_path: file_path.into(),
+			cause: cause.into(),
+		}
+	}
+
+	pub fn apply_validation_failed(file_path: impl Into<String>, cause: impl Into<String>) -> Self {
+		Self::ApplyValidationFailed {
+			file_path: file_path.into(),
+			cause: cause.into(),
+		}
+	}
+
+	pub fn apply_symlink_target(path: impl Into<String>) -> Self {
+		Self::ApplySymlinkTarget { path: path.into() }
+	}
+
+	pub fn apply_budget_exceeded(limit: impl Into<String>, max: u64) -> Self {
+		Self::ApplyBudgetExceeded {
+			limit: limit.into(),
+			max,
+		}
+	}
+
+	pub fn apply_too_many_matches(glob: impl Into<String>, count: usize, max: usize) -> Self {
+		Self::ApplyTooManyMatches {
+			glob: glob.into(),
+			count,
+			max,
+		}
+	}
+
 	pub fn security_violation(target: impl Into<String>, base_dir: impl Into<String>) -> Self {
 		Self::SecurityViolation {
 			target: target.into(),
@@ -110,6 +359,24 @@ impl Error {
 		}
 	}
 
+	pub fn guard_denied(target: impl Into<String>, glob: impl Into<String>) -> Self {
+		Self::GuardDenied {
+			target: target.into(),
+			glob: glob.into(),
+		}
+	}
+
+	pub fn guard_not_allowed(target: impl Into<String>) -> Self {
+		Self::GuardNotAllowed { target: target.into() }
+	}
+
+	pub fn symlink_denied(target: impl Into<String>, resolved: impl Into<String>) -> Self {
+		Self::SymlinkDenied {
+			target: target.into(),
+			resolved: resolved.into(),
+		}
+	}
+
 	pub fn io_read_file(path: impl Into<String>, err: impl std::error::Error) -> Self {
 		Self::IoReadFile(PathAndCause {
 			path: path.into(),
@@ -161,6 +428,13 @@ impl Error {
 		Self::SimpleFs { cause: err.to_string() }
 	}
 
+	pub fn git(args: &[&str], cause: impl Into<String>) -> Self {
+		Self::Git {
+			args: args.join(" "),
+			cause: cause.into(),
+		}
+	}
+
 	pub fn diffy_parse_patch(path: impl Into<String>, err: impl std::error::Error, patch: impl Into<String>) -> Self {
 		Self::DiffyParsePatch {
 			path: path.into(),
@@ -184,6 +458,28 @@ impl Error {
 
 // endregion: --- Custom
 
+// region:    --- Display With Limits
+
+impl Error {
+	/// Same as `to_string()`, but with the embedded patch/content truncated to `limits`
+	/// (`Display` uses `ExcerptLimits::default()`).
+	pub fn to_string_with_limits(&self, limits: ExcerptLimits) -> String {
+		match self {
+			Self::DiffyParsePatch { path, cause, patch } => format!(
+				"diffy parse patch error for '{path}': {cause}\nPatch:\n{}",
+				truncate_excerpt(patch, limits)
+			),
+			Self::DiffyApplyPatch { path, cause, patch } => format!(
+				"diffy apply patch error for '{path}': {cause}\nPatch:\n{}",
+				truncate_excerpt(patch, limits)
+			),
+			_ => self.to_string(),
+		}
+	}
+}
+
+// endregion: --- Display With Limits
+
 // region:    --- Error Boilerplate
 
 impl std::error::Error for Error {}
@@ -0,0 +1,126 @@
+/// Limits applied when embedding file or patch content in error messages and diagnostics,
+/// so logs and model-facing feedback stay readable when the content is huge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExcerptLimits {
+	/// Maximum number of lines kept, the rest is replaced by a `… (N more lines)` marker. (default 40)
+	pub max_lines: usize,
+	/// Maximum number of chars kept per line, longer lines end with `…`. (default 200)
+	pub max_width: usize,
+}
+
+impl Default for ExcerptLimits {
+	fn default() -> Self {
+		Self {
+			max_lines: 40,
+			max_width: 200,
+		}
+	}
+}
+
+/// Fluid apis
+impl ExcerptLimits {
+	pub fn with_max_lines(mut self, max_lines: usize) -> Self {
+		self.max_lines = max_lines;
+		self
+	}
+
+	pub fn with_max_width(mut self, max_width: usize) -> Self {
+		self.max_width = max_width;
+		self
+	}
+}
+
+/// Truncates `text` to the given limits, marking the cut lines with `…`
+/// and the omitted lines with a trailing `… (N more lines)` line.
+///
+/// Text within the limits is returned unchanged.
+pub fn truncate_excerpt(text: &str, limits: ExcerptLimits) -> String {
+	let total_lines = text.lines().count();
+	let mut out = String::with_capacity(text.len().min(limits.max_lines * (limits.max_width + 1)));
+
+	for line in text.split_inclusive('\n').take(limits.max_lines) {
+		let body = line.trim_end_matches(['\n', '\r']);
+		match body.char_indices().nth(limits.max_width) {
+			Some((cut, _)) => {
+				out.push_str(&body[..cut]);
+				out.push('…');
+				out.push_str(&line[body.len()..]);
+			}
+			None => out.push_str(line),
+		}
+	}
+
+	if total_lines > limits.max_lines {
+		if !out.is_empty() && !out.ends_with('\n') {
+			out.push('\n');
+		}
+		let more = total_lines - limits.max_lines;
+		out.push_str(&format!("… ({more} more line{})", if more == 1 { "" } else { "s" }));
+	}
+
+	out
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_excerpt_truncate_lines_and_width() -> Result<()> {
+		// -- Setup & Fixtures
+		let text = "short\nthis line is long\nthird\nfourth\nfifth\n";
+		let limits = ExcerptLimits::default().with_max_lines(3).with_max_width(9);
+
+		// -- Exec
+		let excerpt = truncate_excerpt(text, limits);
+
+		// -- Check
+		assert_eq!(excerpt, "short\nthis line…\nthird\n… (2 more lines)");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_excerpt_truncate_within_limits_and_multibyte() -> Result<()> {
+		// -- Setup & Fixtures
+		let text = "a\nb\n";
+		let wide = "ééééé\n";
+
+		// -- Exec & Check
+		assert_eq!(truncate_excerpt(text, ExcerptLimits::default()), text);
+		assert_eq!(
+			truncate_excerpt(wide, ExcerptLimits::default().with_max_width(2)),
+			"éé…\n"
+		);
+		assert_eq!(
+			truncate_excerpt("x\ny", ExcerptLimits::default().with_max_lines(1)),
+			"x\n… (1 more line)"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_excerpt_error_display_with_limits() -> Result<()> {
+		// -- Setup & Fixtures
+		let patch: String = (1..=100).map(|i| format!("+line {i}\n")).collect();
+		let err = crate::Error::diffy_apply_patch("a.rs", std::fmt::Error, patch);
+
+		// -- Exec
+		let default_msg = err.to_string();
+		let short_msg = err.to_string_with_limits(ExcerptLimits::default().with_max_lines(2));
+
+		// -- Check
+		assert!(default_msg.contains("+line 40\n… (60 more lines)"));
+		assert!(!default_msg.contains("+line 41"));
+		assert!(short_msg.ends_with("Patch:\n+line 1\n+line 2\n… (98 more lines)"));
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -1,52 +1,212 @@
-use crate::{Content, Error, FileChanges, FileDirective, Result};
-use markex::tag;
+use crate::span_patch::parse_columns;
+use crate::tag_parser::{TagElem, TagPart, extract_tags};
+use crate::{
+	BlobStore, Content, Error, ExtractOptions, ExtractReport, ExtrudedKind, ExtrudedSegment, FileChanges,
+	FileDirective, InsertPosition, Result,
+};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// The directive tags recognized inside a `FILE_CHANGES` block.
+const DIRECTIVE_TAGS: &[&str] = &[
+	"FILE_NEW",
+	"FILE_PATCH",
+	"FILE_APPEND",
+	"FILE_SPAN_PATCH",
+	"FILE_INSERT",
+	"FILE_GLOBAL_REPLACE",
+	"FILE_COPY",
+	"FILE_RENAME",
+	"FILE_DELETE",
+];
+
+/// Stand-in for a `>` inside a quoted attribute value while the tag parser parses the tags.
+const QUOTED_GT_PLACEHOLDER: char = '\u{E000}';
+
+/// Suffix appended (once per extra occurrence) to a repeated attribute name, so the tag parser keeps all occurrences.
+const DUPLICATE_ATTR_MARKER: char = '\u{E001}';
 
 /// Extracts the first `FILE_CHANGES` block from the input string.
 pub fn extract_file_changes(input: &str, extrude_other_content: bool) -> Result<(FileChanges, Option<String>)> {
-	let parts = tag::extract(input, &["FILE_CHANGES"], extrude_other_content);
+	extract_file_changes_with_options(input, extrude_other_content, None)
+}
+
+/// Same as `extract_file_changes`, with `ExtractOptions` (e.g., body size and directive count limits).
+pub fn extract_file_changes_with_options(
+	input: &str,
+	extrude_other_content: bool,
+	options: impl Into<ExtractOptions>,
+) -> Result<(FileChanges, Option<String>)> {
+	let report = extract_file_changes_report(input, extrude_other_content, options)?;
+	Ok((report.changes, report.extruded))
+}
+
+/// Same as `extract_file_changes_with_options`, returning an `ExtractReport` which also lists the
+/// warnings about the lossy or heuristic extraction steps.
+pub fn extract_file_changes_report(
+	input: &str,
+	extrude_other_content: bool,
+	options: impl Into<ExtractOptions>,
+) -> Result<ExtractReport> {
+	let options: ExtractOptions = options.into();
+	let mut warnings: Vec<String> = Vec::new();
+	let raw_input = input;
 
-	let (tag_elems, extruded) = if extrude_other_content {
-		let (elems, s) = parts.into_with_extrude_content();
-		(elems, Some(s))
+	// -- Normalize the tag case, and recover a FILE_CHANGES block cut before its closing tag
+	let mut input = normalize_tag_case(input, &["FILE_CHANGES"], &mut warnings);
+	if find_open_tag(&input, "FILE_CHANGES").is_some() && !input.contains("</FILE_CHANGES>") {
+		warnings.push("'<FILE_CHANGES>' is not closed, recovered up to the end of the input".to_string());
+		input.push_str("\n</FILE_CHANGES>");
+	}
+
+	let parts = extract_tags(options.tag_parser, &input, &["FILE_CHANGES"], extrude_other_content);
+	let outer_spans = if extrude_other_content {
+		part_spans(&input, &parts)
 	} else {
-		(parts.into_tag_elems(), None)
+		Vec::new()
 	};
 
-	let Some(changes_tag) = tag_elems.into_iter().next() else {
-		return Ok((FileChanges::new(Vec::new()), extruded));
+	// -- Split the outer text around the first FILE_CHANGES block (so the inner prose can be extruded in its place)
+	let mut changes_tag: Option<(TagElem, Option<Range<usize>>)> = None;
+	let mut text_before = String::new();
+	let mut text_after = String::new();
+	let mut segments_before: Vec<ExtrudedSegment> = Vec::new();
+	let mut segments_after: Vec<ExtrudedSegment> = Vec::new();
+	for (idx, part) in parts.into_iter().enumerate() {
+		let span = outer_spans.get(idx).cloned().flatten();
+		match part {
+			TagPart::Elem(elem) if changes_tag.is_none() => changes_tag = Some((elem, span)),
+			TagPart::Elem(_) => (),
+			TagPart::Text(text) if changes_tag.is_none() => {
+				segments_before.extend(extruded_segment(raw_input, ExtrudedKind::ProseBefore, span, false));
+				text_before.push_str(&text);
+			}
+			TagPart::Text(text) => {
+				segments_after.extend(extruded_segment(raw_input, ExtrudedKind::ProseAfter, span, false));
+				text_after.push_str(&text);
+			}
+		}
+	}
+
+	let Some((changes_tag, changes_span)) = changes_tag else {
+		return Ok(ExtractReport {
+			changes: FileChanges::new(Vec::new()),
+			extruded: extrude_other_content.then_some(text_before),
+			extruded_segments: segments_before,
+			warnings,
+		});
 	};
 
-	let inner_content = changes_tag.content;
+	let normalized_content = normalize_tag_case(&changes_tag.content, DIRECTIVE_TAGS, &mut warnings);
 
-	// -- Pre-process to expand potential self-closing tags (since markex might skip them)
-	let inner_content = expand_self_closing_tags(inner_content);
+	// -- Pre-process to expand potential self-closing tags (since the tag parser might skip them)
+	let inner_content = expand_self_closing_tags(normalized_content.clone(), &mut warnings);
 
-	let child_parts = tag::extract(
-		&inner_content,
-		&[
-			"FILE_NEW",
-			"FILE_PATCH",
-			"FILE_APPEND",
-			"FILE_COPY",
-			"FILE_RENAME",
-			"FILE_DELETE",
-		],
-		false,
-	);
+	let child_parts = extract_tags(options.tag_parser, &inner_content, DIRECTIVE_TAGS, true);
+	// The expanded tags change the offsets, so the prose is located in the content before the expansion.
+	let child_spans = match changes_span.as_ref().filter(|_| extrude_other_content) {
+		Some(changes_span) => part_spans(&normalized_content, &child_parts)
+			.into_iter()
+			.map(|span| span.map(|span| changes_span.start + span.start..changes_span.start + span.end))
+			.collect(),
+		None => Vec::new(),
+	};
+
+	// -- Separate the directive tags from the prose the model may have interleaved between them
+	let mut tag_elems: Vec<TagElem> = Vec::new();
+	let mut inner_prose: Vec<String> = Vec::new();
+	let mut extruded_segments = segments_before;
+	for (idx, part) in child_parts.into_iter().enumerate() {
+		match part {
+			TagPart::Elem(elem) => tag_elems.push(elem),
+			TagPart::Text(text) => {
+				for tag in DIRECTIVE_TAGS {
+					if find_open_tag(&text, tag).is_some() {
+						warnings.push(format!("'<{tag}>' is not closed and was ignored (truncated output?)"));
+					}
+				}
+				let span = child_spans.get(idx).cloned().flatten();
+				extruded_segments.extend(extruded_segment(raw_input, ExtrudedKind::Commentary, span, true));
+				let text = text.trim();
+				if !text.is_empty() {
+					inner_prose.push(text.to_string());
+				}
+			}
+		}
+	}
+	extruded_segments.extend(segments_after);
+
+	let extruded = extrude_other_content.then(|| {
+		let mut extruded = text_before;
+		if !inner_prose.is_empty() {
+			extruded.push_str(&inner_prose.join("\n\n"));
+			extruded.push('\n');
+		}
+		extruded.push_str(&text_after);
+		extruded
+	});
 
 	let mut directives = Vec::new();
 
-	for elem in child_parts.into_tag_elems() {
+	let mut too_many_err: Option<Error> = None;
+	if let Some(max) = options.max_directives
+		&& tag_elems.len() > max
+	{
+		let err = Error::parse_too_many_directives(tag_elems.len(), max);
+		if options.fail_on_limit {
+			return Err(err);
+		}
+		tag_elems.truncate(max);
+		too_many_err = Some(err);
+	}
+
+	for elem in tag_elems {
 		let tag_name = elem.tag.clone();
-		let mut attrs = elem.attrs.unwrap_or_default();
+		let mut attrs = elem.attrs;
+		for value in attrs.values_mut() {
+			if value.contains(QUOTED_GT_PLACEHOLDER) {
+				*value = value.replace(QUOTED_GT_PLACEHOLDER, ">");
+			}
+			if value.contains('&') {
+				*value = unescape_attr_value(value);
+			}
+		}
 
 		// Try to find a path for better reporting if it fails.
 		let file_path_attr = attrs
 			.get("file_path")
 			.or_else(|| attrs.get("to_path"))
 			.or_else(|| attrs.get("from_path"))
+			.or_else(|| attrs.get("glob"))
 			.cloned();
 
+		if let Err(err) = check_attrs(&tag_name, &mut attrs) {
+			if options.strict_attributes {
+				return Err(err);
+			}
+			directives.push(FileDirective::Fail {
+				kind: tag_name,
+				file_path: file_path_attr,
+				error_msg: err.to_string(),
+			});
+			continue;
+		}
+
+		if let Some(max) = options.max_body_bytes
+			&& elem.content.len() > max
+		{
+			let err = Error::parse_body_too_large(&tag_name, elem.content.len(), max);
+			if options.fail_on_limit {
+				return Err(err);
+			}
+			directives.push(FileDirective::Fail {
+				kind: tag_name,
+				file_path: file_path_attr,
+				error_msg: err.to_string(),
+			});
+			continue;
+		}
+
 		let directive_res = (|| -> Result<FileDirective> {
 			match tag_name.as_str() {
 				"FILE_NEW" => {
@@ -56,7 +216,7 @@ pub fn extract_file_changes(input: &str, extrude_other_content: bool) -> Result<
 
 					Ok(FileDirective::New {
 						file_path,
-						content: Content::from_raw(elem.content),
+						content: content_from_body(elem.content, &attrs),
 					})
 				}
 				"FILE_PATCH" => {
@@ -66,7 +226,8 @@ pub fn extract_file_changes(input: &str, extrude_other_content: bool) -> Result<
 
 					Ok(FileDirective::Patch {
 						file_path,
-						content: Content::from_raw(elem.content),
+						content: content_from_body(elem.content, &attrs),
+						allow_generated: attrs.get("allow_generated").is_some_and(|v| v == "true"),
 					})
 				}
 				"FILE_APPEND" => {
@@ -76,7 +237,87 @@ pub fn extract_file_changes(input: &str, extrude_other_content: bool) -> Result<
 
 					Ok(FileDirective::Append {
 						file_path,
-						content: Content::from_raw(elem.content),
+						content: content_from_body(elem.content, &attrs),
+					})
+				}
+				"FILE_SPAN_PATCH" => {
+					let tag = "FILE_SPAN_PATCH";
+					let mut required = |attr: &str| {
+						attrs
+							.remove(attr)
+							.ok_or_else(|| Error::parse_missing_attribute(tag, attr))
+					};
+					let file_path = required("file_path")?;
+					let line_value = required("line")?;
+					let columns_value = required("columns")?;
+					let line_hash = required("line_hash")?;
+
+					let line = line_value
+						.trim()
+						.parse()
+						.ok()
+						.filter(|line| *line >= 1)
+						.ok_or_else(|| Error::parse_invalid_attribute(tag, "line", &line_value))?;
+					let columns = parse_columns(&columns_value)
+						.ok_or_else(|| Error::parse_invalid_attribute(tag, "columns", &columns_value))?;
+
+					Ok(FileDirective::SpanPatch {
+						file_path,
+						line,
+						columns,
+						line_hash,
+						replacement: span_replacement(elem.content),
+					})
+				}
+				"FILE_INSERT" => {
+					let tag = "FILE_INSERT";
+					let file_path = attrs
+						.remove("file_path")
+						.ok_or_else(|| Error::parse_missing_attribute(tag, "file_path"))?;
+					let anchor = attrs
+						.remove("anchor")
+						.ok_or_else(|| Error::parse_missing_attribute(tag, "anchor"))?;
+					if anchor.trim().is_empty() {
+						return Err(Error::parse_invalid_attribute(tag, "anchor", &anchor));
+					}
+					let position = match attrs.remove("position") {
+						Some(value) => InsertPosition::parse(&value)
+							.ok_or_else(|| Error::parse_invalid_attribute(tag, "position", &value))?,
+						None => InsertPosition::default(),
+					};
+
+					Ok(FileDirective::Insert {
+						file_path,
+						anchor,
+						regex: attrs.get("regex").is_some_and(|v| v == "true"),
+						position,
+						content: span_replacement(elem.content),
+					})
+				}
+				"FILE_GLOBAL_REPLACE" => {
+					let tag = "FILE_GLOBAL_REPLACE";
+					let glob = attrs
+						.remove("glob")
+						.ok_or_else(|| Error::parse_missing_attribute(tag, "glob"))?;
+					let pattern = attrs
+						.remove("pattern")
+						.ok_or_else(|| Error::parse_missing_attribute(tag, "pattern"))?;
+					let max_matches = match attrs.remove("max_matches") {
+						Some(value) => Some(
+							value
+								.trim()
+								.parse()
+								.map_err(|_| Error::parse_invalid_attribute(tag, "max_matches", &value))?,
+						),
+						None => None,
+					};
+
+					Ok(FileDirective::GlobalReplace {
+						glob,
+						pattern,
+						replacement: span_replacement(elem.content),
+						regex: attrs.get("regex").is_some_and(|v| v == "true"),
+						max_matches,
 					})
 				}
 				"FILE_COPY" => {
@@ -110,8 +351,24 @@ pub fn extract_file_changes(input: &str, extrude_other_content: bool) -> Result<
 			}
 		})();
 
+		let directive_res = match &options.blob_store {
+			Some(blob_store) => directive_res.and_then(|d| spill_to_blob(d, blob_store)),
+			None => directive_res,
+		};
+
 		let directive = match directive_res {
-			Ok(d) => d,
+			Ok(d) => {
+				if let FileDirective::New { file_path, content }
+				| FileDirective::Patch { file_path, content, .. }
+				| FileDirective::Append { file_path, content } = &d
+					&& content.code_fence.is_some()
+				{
+					warnings.push(format!(
+						"Stripped the code fence around the {tag_name} body of '{file_path}'"
+					));
+				}
+				d
+			}
 			Err(err) => FileDirective::Fail {
 				kind: tag_name,
 				file_path: file_path_attr,
@@ -122,44 +379,672 @@ pub fn extract_file_changes(input: &str, extrude_other_content: bool) -> Result<
 		directives.push(directive);
 	}
 
-	Ok((FileChanges::new(directives), extruded))
+	if let Some(err) = too_many_err {
+		directives.push(FileDirective::Fail {
+			kind: "FILE_CHANGES".to_string(),
+			file_path: None,
+			error_msg: err.to_string(),
+		});
+	}
+
+	Ok(ExtractReport {
+		changes: FileChanges::new(directives),
+		extruded,
+		extruded_segments,
+		warnings,
+	})
 }
 
 // region:    --- Support
 
-/// Expands self-closing tags like <TAG /> to <TAG></TAG> so markex can find them.
-fn expand_self_closing_tags(mut content: String) -> String {
-	let tags = [
-		"FILE_NEW",
-		"FILE_PATCH",
-		"FILE_APPEND",
-		"FILE_COPY",
-		"FILE_RENAME",
-		"FILE_DELETE",
-	];
-	for tag in tags {
-		let mut search_pos = 0;
-		let tag_pattern = format!("<{tag}");
-		while let Some(start_idx) = content[search_pos..].find(&tag_pattern) {
-			let start_idx = search_pos + start_idx;
-			if let Some(end_idx) = content[start_idx..].find('>') {
-				let end_idx = start_idx + end_idx;
-				// Check if the tag is self-closing (ends with />)
-				let trimmed_part = content[..end_idx].trim_end();
-				if trimmed_part.ends_with('/') {
-					let slash_idx = trimmed_part.len() - 1;
-					let expansion = format!("></{tag}>");
-					content.replace_range(slash_idx..end_idx + 1, &expansion);
-					search_pos = slash_idx + expansion.len();
-				} else {
-					search_pos = end_idx + 1;
+/// The byte range in `source` (the text the parts were extracted from) of each part, the content for an element.
+/// The elements are skipped with the tag parser rules (first `>` ends the opening tag, first closing tag ends
+/// the content). `None` when not found (e.g., an element altered before its extraction).
+fn part_spans(source: &str, parts: &[TagPart]) -> Vec<Option<Range<usize>>> {
+	let mut cursor = 0;
+	parts
+		.iter()
+		.map(|part| match part {
+			TagPart::Text(text) => {
+				let start = cursor + source.get(cursor..)?.find(text.as_str())?;
+				cursor = start + text.len();
+				Some(start..cursor)
+			}
+			TagPart::Elem(elem) => {
+				let tag = elem.tag.as_str();
+				let open_idx = cursor + find_open_tag(source.get(cursor..)?, tag)?;
+				let open_tag = scan_open_tag(&source[open_idx + 1 + tag.len()..])?;
+				let content_start = open_idx + 1 + tag.len() + open_tag.end;
+				if open_tag.self_closing {
+					cursor = content_start;
+					return Some(content_start..content_start);
+				}
+				let close_tag = format!("</{tag}>");
+				let content_end = content_start + source[content_start..].find(&close_tag)?;
+				cursor = content_end + close_tag.len();
+				Some(content_start..content_end)
+			}
+		})
+		.collect()
+}
+
+/// The segment of the `raw_input` at `span` (trimmed if `trim`), `None` if blank or without span.
+fn extruded_segment(
+	raw_input: &str,
+	kind: ExtrudedKind,
+	span: Option<Range<usize>>,
+	trim: bool,
+) -> Option<ExtrudedSegment> {
+	let span = span?;
+	let text = raw_input.get(span.clone())?;
+	if text.trim().is_empty() {
+		return None;
+	}
+	let span = if trim {
+		let start = span.start + (text.len() - text.trim_start().len());
+		start..start + text.trim().len()
+	} else {
+		span
+	};
+	Some(ExtrudedSegment {
+		kind,
+		text: raw_input[span.clone()].to_string(),
+		span,
+	})
+}
+
+/// Moves the `FILE_NEW` / `FILE_APPEND` body to the blob store, if large enough.
+fn spill_to_blob(mut directive: FileDirective, blob_store: &BlobStore) -> Result<FileDirective> {
+	if let FileDirective::New { content, .. } | FileDirective::Append { content, .. } = &mut directive
+		&& blob_store.spills(content.content.len())
+	{
+		content.blob_path = Some(blob_store.put(&content.content)?);
+		content.content = String::new();
+	}
+	Ok(directive)
+}
+
+/// The replacement of a `FILE_SPAN_PATCH` (or a `FILE_GLOBAL_REPLACE`) from its body, without the structural newlines
+/// after the opening tag and before the closing tag.
+fn span_replacement(body: String) -> String {
+	let body = body
+		.strip_prefix("\r\n")
+		.or_else(|| body.strip_prefix('\n'))
+		.unwrap_or(&body);
+	let body = body
+		.strip_suffix("\r\n")
+		.or_else(|| body.strip_suffix('\n'))
+		.unwrap_or(body);
+	body.to_string()
+}
+
+/// Builds the directive content from its body.
+/// With `raw="true"`, the body is taken byte-for-byte (no fence detection, no newline stripping).
+fn content_from_body(body: String, attrs: &HashMap<String, String>) -> Content {
+	if attrs.get("raw").is_some_and(|v| v == "true") {
+		Content::from_verbatim(body)
+	} else {
+		Content::from_raw(body)
+	}
+}
+
+/// Expands self-closing tags like `<TAG />` to `<TAG></TAG>` so the tag parser can find them,
+/// and protects the `>` of quoted attribute values (the tag parser ends the opening tag at the first `>`).
+///
+/// The opening tag is scanned attribute-aware: a `/` inside a quoted value, or at the end of an
+/// unquoted value (e.g., `to_path=src/bin/>`), is part of the value and does not self-close the tag.
+/// Self-closing content directives (`FILE_NEW`, `FILE_PATCH`, `FILE_APPEND`) get a warning, since their body is lost.
+fn expand_self_closing_tags(content: String, warnings: &mut Vec<String>) -> String {
+	let mut out = String::with_capacity(content.len());
+	let mut rest = content.as_str();
+
+	while let Some(lt_idx) = rest.find('<') {
+		out.push_str(&rest[..lt_idx]);
+		rest = &rest[lt_idx..];
+
+		let Some(tag) = DIRECTIVE_TAGS.iter().find(|tag| is_open_tag_start(rest, tag)) else {
+			out.push('<');
+			rest = &rest[1..];
+			continue;
+		};
+
+		let after_name = &rest[tag.len() + 1..];
+		let Some(open_tag) = scan_open_tag(after_name) else {
+			// No end of tag, leave the rest as is.
+			break;
+		};
+
+		out.push('<');
+		out.push_str(tag);
+		if open_tag.self_closing {
+			if matches!(*tag, "FILE_NEW" | "FILE_PATCH" | "FILE_APPEND") {
+				warnings.push(format!("Self-closing '<{tag} />' expanded to an empty body"));
+			}
+			let attrs = open_tag.attrs.trim_end();
+			out.push_str(attrs.strip_suffix('/').unwrap_or(attrs));
+			out.push_str(&format!("></{tag}>"));
+		} else {
+			out.push_str(&open_tag.attrs);
+			// A value ending with `/` must not be followed by `>` (the tag parser would read it as self-closing).
+			if open_tag.attrs.ends_with('/') {
+				out.push(' ');
+			}
+			out.push('>');
+		}
+		rest = &after_name[open_tag.end..];
+	}
+	out.push_str(rest);
+
+	out
+}
+
+/// The entities decoded in attribute values (and encoded by `escape_attr_value`).
+const ATTR_ENTITIES: &[(&str, char)] = &[
+	("&amp;", '&'),
+	("&quot;", '"'),
+	("&apos;", '\''),
+	("&lt;", '<'),
+	("&gt;", '>'),
+];
+
+/// Escapes an attribute value to be written inside double quotes (`&`, `"`, `<`, `>`).
+pub(crate) fn escape_attr_value(value: &str) -> String {
+	let mut out = String::with_capacity(value.len());
+	for c in value.chars() {
+		match c {
+			'&' => out.push_str("&amp;"),
+			'"' => out.push_str("&quot;"),
+			'<' => out.push_str("&lt;"),
+			'>' => out.push_str("&gt;"),
+			_ => out.push(c),
+		}
+	}
+	out
+}
+
+/// Decodes the `ATTR_ENTITIES` of an attribute value in a single pass (unknown `&...` sequences are kept as is).
+fn unescape_attr_value(value: &str) -> String {
+	let mut out = String::with_capacity(value.len());
+	let mut rest = value;
+	while let Some(amp_idx) = rest.find('&') {
+		out.push_str(&rest[..amp_idx]);
+		rest = &rest[amp_idx..];
+		match ATTR_ENTITIES.iter().find(|(entity, _)| rest.starts_with(entity)) {
+			Some((entity, c)) => {
+				out.push(*c);
+				rest = &rest[entity.len()..];
+			}
+			None => {
+				out.push('&');
+				rest = &rest[1..];
+			}
+		}
+	}
+	out.push_str(rest);
+	out
+}
+
+/// The attributes part of a directive opening tag, as scanned by `scan_open_tag`.
+struct OpenTag {
+	/// The text between the tag name and the `>`, with the quoted `>` replaced by `QUOTED_GT_PLACEHOLDER`.
+	attrs: String,
+	/// The byte index just after the closing `>`.
+	end: usize,
+	self_closing: bool,
+}
+
+/// Scans the text following `<TAG` up to the `>` closing the opening tag (outside quotes).
+/// Returns `None` if there is no such `>`.
+fn scan_open_tag(s: &str) -> Option<OpenTag> {
+	let mut attrs = String::new();
+	let mut quote: Option<char> = None;
+	let mut after_eq = false;
+	let mut in_unquoted_value = false;
+
+	for (idx, c) in s.char_indices() {
+		if let Some(q) = quote {
+			if c == q {
+				quote = None;
+			}
+			attrs.push(if c == '>' { QUOTED_GT_PLACEHOLDER } else { c });
+			continue;
+		}
+
+		match c {
+			'>' => {
+				let self_closing = !in_unquoted_value && attrs.trim_end().ends_with('/');
+				return Some(OpenTag {
+					attrs: mark_duplicate_attrs(&attrs),
+					end: idx + 1,
+					self_closing,
+				});
+			}
+			'"' | '\'' => {
+				quote = Some(c);
+				after_eq = false;
+				in_unquoted_value = false;
+			}
+			'=' => after_eq = true,
+			c if c.is_whitespace() => in_unquoted_value = false,
+			_ => {
+				if after_eq {
+					in_unquoted_value = true;
 				}
-			} else {
-				break;
+				after_eq = false;
+			}
+		}
+		attrs.push(c);
+	}
+
+	None
+}
+
+/// Appends `DUPLICATE_ATTR_MARKER` to the names of repeated attributes (once per extra occurrence).
+fn mark_duplicate_attrs(attrs: &str) -> String {
+	let mut seen: HashMap<&str, usize> = HashMap::new();
+	let mut insertions: Vec<(usize, usize)> = Vec::new();
+	let mut rest = attrs;
+
+	loop {
+		let trimmed = rest.trim_start();
+		if trimmed.is_empty() {
+			break;
+		}
+		let offset = attrs.len() - trimmed.len();
+
+		// -- Name
+		let name_end = trimmed
+			.find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+			.unwrap_or(trimmed.len());
+		let name = &trimmed[..name_end];
+		if !name.is_empty() {
+			let count = seen.entry(name).or_insert(0);
+			if *count > 0 {
+				insertions.push((offset + name_end, *count));
+			}
+			*count += 1;
+		}
+		rest = &trimmed[name_end.max(1)..];
+
+		// -- Value
+		let after_name = rest.trim_start();
+		let Some(value) = after_name.strip_prefix('=') else {
+			continue;
+		};
+		let value = value.trim_start();
+		rest = match value.chars().next() {
+			Some(q @ ('"' | '\'')) => match value[1..].find(q) {
+				Some(end) => &value[end + 2..],
+				None => "",
+			},
+			_ => {
+				let end = value.find(char::is_whitespace).unwrap_or(value.len());
+				&value[end..]
 			}
+		};
+	}
+
+	let mut out = attrs.to_string();
+	for (idx, count) in insertions.into_iter().rev() {
+		out.insert_str(idx, &DUPLICATE_ATTR_MARKER.to_string().repeat(count));
+	}
+	out
+}
+
+/// Checks the directive attributes for conflicts, and removes the duplicate attributes marked by
+/// `mark_duplicate_attrs` (duplicates with the same value are accepted).
+fn check_attrs(tag: &str, attrs: &mut HashMap<String, String>) -> Result<()> {
+	// -- Duplicates
+	// Note: Sorted, so the reported duplicate does not depend on the `HashMap` iteration order.
+	let mut dup_keys: Vec<String> = attrs
+		.keys()
+		.filter(|k| k.ends_with(DUPLICATE_ATTR_MARKER))
+		.cloned()
+		.collect();
+	dup_keys.sort();
+	for dup_key in dup_keys {
+		let dup_value = attrs.remove(&dup_key).unwrap_or_default();
+		let name = dup_key.trim_end_matches(DUPLICATE_ATTR_MARKER);
+		if attrs.get(name) != Some(&dup_value) {
+			return Err(Error::parse_duplicate_attribute(tag, name));
 		}
 	}
-	content
+
+	// -- Conflicts
+	let (valid, invalid): (&[&str], &[&str]) = match tag {
+		"FILE_COPY" | "FILE_RENAME" => (&["from_path", "to_path"], &["file_path"]),
+		_ => (&["file_path"], &["from_path", "to_path"]),
+	};
+	if let Some(conflicting) = invalid.iter().find(|a| attrs.contains_key(**a))
+		&& let Some(attr) = valid.iter().find(|a| attrs.contains_key(**a))
+	{
+		return Err(Error::parse_conflicting_attributes(tag, *attr, *conflicting));
+	}
+
+	Ok(())
+}
+
+/// Rewrites the opening and closing tags matching one of `tags` case-insensitively (e.g., `<file_new`) to the exact tag name.
+fn normalize_tag_case(content: &str, tags: &[&str], warnings: &mut Vec<String>) -> String {
+	let mut out = String::with_capacity(content.len());
+	let mut rest = content;
+
+	while let Some(lt_idx) = rest.find('<') {
+		out.push_str(&rest[..lt_idx + 1]);
+		rest = &rest[lt_idx + 1..];
+		if let Some(after_slash) = rest.strip_prefix('/') {
+			out.push('/');
+			rest = after_slash;
+		}
+
+		let found = tags.iter().find(|tag| {
+			rest.get(..tag.len()).is_some_and(|name| name.eq_ignore_ascii_case(tag))
+				&& rest[tag.len()..]
+					.chars()
+					.next()
+					.is_some_and(|c| c.is_whitespace() || c == '/' || c == '>')
+		});
+		if let Some(tag) = found {
+			let name = &rest[..tag.len()];
+			if name != *tag {
+				let warning = format!("Tag '{name}' matched case-insensitively as '{tag}'");
+				if !warnings.contains(&warning) {
+					warnings.push(warning);
+				}
+			}
+			out.push_str(tag);
+			rest = &rest[tag.len()..];
+		}
+	}
+	out.push_str(rest);
+
+	out
+}
+
+/// Returns the byte index of the first opening tag `<{tag}` (followed by whitespace, `/`, or `>`) in `s`.
+fn find_open_tag(s: &str, tag: &str) -> Option<usize> {
+	s.match_indices('<')
+		.map(|(idx, _)| idx)
+		.find(|idx| is_open_tag_start(&s[*idx..], tag))
+}
+
+/// Returns `true` if `s` starts with `<{tag}` followed by whitespace, `/`, or `>`.
+fn is_open_tag_start(s: &str, tag: &str) -> bool {
+	s.strip_prefix('<')
+		.and_then(|s| s.strip_prefix(tag))
+		.and_then(|s| s.chars().next())
+		.is_some_and(|c| c.is_whitespace() || c == '/' || c == '>')
 }
 
 // endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_extract_raw_attribute_keeps_body_verbatim() -> Result<()> {
+		// -- Setup & Fixtures
+		let input = "<FILE_CHANGES>\n<FILE_NEW file_path=\"doc.md\" raw=\"true\">\n```\nstarts with a fence\n```\n</FILE_NEW>\n<FILE_NEW file_path=\"other.md\">\n```\nfenced\n```\n</FILE_NEW>\n</FILE_CHANGES>";
+
+		// -- Exec
+		let (changes, _) = extract_file_changes(input, false)?;
+
+		// -- Check
+		let directives: Vec<_> = changes.into_iter().collect();
+		let FileDirective::New { content, .. } = &directives[0] else {
+			return Err("should be a FILE_NEW".into());
+		};
+		assert_eq!(content.content, "\n```\nstarts with a fence\n```\n");
+		assert!(content.code_fence.is_none());
+		let FileDirective::New { content, .. } = &directives[1] else {
+			return Err("should be a FILE_NEW".into());
+		};
+		assert_eq!(content.content, "fenced\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_extract_interleaved_prose_is_skipped_and_extruded() -> Result<()> {
+		// -- Setup & Fixtures
+		let input = "Intro.\n\n<FILE_CHANGES>\nFirst, create the file.\n<FILE_NEW file_path=\"a.txt\">\nA\n</FILE_NEW>\n\nThen remove the old one.\n<FILE_DELETE file_path=\"b.txt\" />\n</FILE_CHANGES>\nOutro.";
+
+		// -- Exec
+		let (changes, extruded) = extract_file_changes(input, true)?;
+
+		// -- Check
+		let directives: Vec<_> = changes.into_iter().collect();
+		assert_eq!(directives.len(), 2);
+		assert!(matches!(&directives[0], FileDirective::New { file_path, .. } if file_path == "a.txt"));
+		assert!(matches!(&directives[1], FileDirective::Delete { file_path } if file_path == "b.txt"));
+		assert_eq!(
+			extruded.as_deref(),
+			Some("Intro.\n\nFirst, create the file.\n\nThen remove the old one.\n\nOutro.")
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_extract_extruded_segments() -> Result<()> {
+		// -- Setup & Fixtures
+		let input = "Intro.\n<file_changes>\n  First, the file.\n<FILE_NEW file_path=\"a.txt\">\nThen\n</FILE_NEW>\nThen remove.\n<file_delete file_path=\"b.txt\" />\n\n</file_changes>\nOutro.\n<FILE_CHANGES>\n</FILE_CHANGES>\nEnd.";
+
+		// -- Exec
+		let report = extract_file_changes_report(input, true, None)?;
+		let no_block = extract_file_changes_report("Just prose.\n", true, None)?;
+		let not_extruded = extract_file_changes_report(input, false, None)?;
+
+		// -- Check
+		assert_eq!(report.changes.iter().len(), 2);
+		let segments: Vec<(ExtrudedKind, &str)> = report
+			.extruded_segments
+			.iter()
+			.map(|segment| (segment.kind, segment.text.as_str()))
+			.collect();
+		assert_eq!(
+			segments,
+			[
+				(ExtrudedKind::ProseBefore, "Intro.\n"),
+				(ExtrudedKind::Commentary, "First, the file."),
+				(ExtrudedKind::Commentary, "Then remove."),
+				(ExtrudedKind::ProseAfter, "\nOutro.\n"),
+				(ExtrudedKind::ProseAfter, "\nEnd."),
+			]
+		);
+		for segment in &report.extruded_segments {
+			assert_eq!(&input[segment.span.clone()], segment.text);
+		}
+		assert_eq!(no_block.extruded_segments.len(), 1);
+		assert_eq!(no_block.extruded_segments[0].span, 0..12);
+		assert!(not_extruded.extruded_segments.is_empty());
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_extract_self_closing_with_slash_in_attribute_values() -> Result<()> {
+		// -- Setup & Fixtures
+		let input = "<FILE_CHANGES>\n<FILE_RENAME from_path=\"src/old/\" to_path=\"src/bin/\"/>\n<FILE_COPY from_path=a.txt to_path=src/bin/>\n</FILE_COPY>\n<FILE_DELETE file_path=c.txt />\n</FILE_CHANGES>";
+
+		// -- Exec
+		let (changes, _) = extract_file_changes(input, false)?;
+
+		// -- Check
+		let directives: Vec<_> = changes.into_iter().collect();
+		assert_eq!(directives.len(), 3);
+		assert!(
+			matches!(&directives[0], FileDirective::Rename { from_path, to_path } if from_path == "src/old/" && to_path == "src/bin/")
+		);
+		assert!(matches!(&directives[1], FileDirective::Copy { to_path, .. } if to_path == "src/bin/"));
+		assert!(matches!(&directives[2], FileDirective::Delete { file_path } if file_path == "c.txt"));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_extract_gt_inside_quoted_attribute() -> Result<()> {
+		// -- Setup & Fixtures
+		let input = "<FILE_CHANGES>\n<FILE_DELETE file_path=\"docs/a>b.md\" />\n<FILE_NEW file_path='x>y.txt'>\nX\n</FILE_NEW>\n</FILE_CHANGES>";
+
+		// -- Exec
+		let (changes, _) = extract_file_changes(input, false)?;
+
+		// -- Check
+		let directives: Vec<_> = changes.into_iter().collect();
+		assert_eq!(directives.len(), 2);
+		assert!(matches!(&directives[0], FileDirective::Delete { file_path } if file_path == "docs/a>b.md"));
+		let FileDirective::New { file_path, content } = &directives[1] else {
+			return Err("should be a FILE_NEW".into());
+		};
+		assert_eq!(file_path, "x>y.txt");
+		assert_eq!(content.content, "X\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_extract_special_character_paths() -> Result<()> {
+		// -- Setup & Fixtures
+		let input = "<FILE_CHANGES>\n<FILE_DELETE file_path=\"my docs/Ünïcödé 文件.md\" />\n<FILE_DELETE file_path='say \"hi\".txt' />\n<FILE_DELETE file_path=\"a &quot;b&quot; &amp;lt; c.txt\" />\n</FILE_CHANGES>";
+
+		// -- Exec
+		let (changes, _) = extract_file_changes(input, false)?;
+
+		// -- Check
+		let paths: Vec<_> = changes.iter().filter_map(|d| d.file_path()).collect();
+		assert_eq!(
+			paths,
+			vec!["my docs/Ünïcödé 文件.md", "say \"hi\".txt", "a \"b\" &lt; c.txt"]
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_extract_duplicate_and_conflicting_attributes() -> Result<()> {
+		// -- Setup & Fixtures
+		let input = r#"<FILE_CHANGES>
+<FILE_DELETE file_path="a.txt" file_path="b.txt" />
+<FILE_DELETE file_path="same.txt" file_path="same.txt" />
+<FILE_NEW file_path="c.txt" to_path="d.txt">
+C
+</FILE_NEW>
+<FILE_RENAME from_path="e.txt" to_path="f.txt" file_path="g.txt" />
+</FILE_CHANGES>"#;
+
+		// -- Exec
+		let (changes, _) = extract_file_changes(input, false)?;
+
+		// -- Check
+		let directives: Vec<_> = changes.into_iter().collect();
+		assert_eq!(directives.len(), 4);
+		let FileDirective::Fail { error_msg, .. } = &directives[0] else {
+			return Err("should be a Fail for the duplicate".into());
+		};
+		assert_eq!(
+			error_msg,
+			"Duplicate attribute 'file_path' with different values for tag 'FILE_DELETE'"
+		);
+		assert!(matches!(&directives[1], FileDirective::Delete { file_path } if file_path == "same.txt"));
+		let FileDirective::Fail { error_msg, .. } = &directives[2] else {
+			return Err("should be a Fail for the conflict".into());
+		};
+		assert_eq!(
+			error_msg,
+			"Attribute 'to_path' conflicts with 'file_path' for tag 'FILE_NEW'"
+		);
+		assert!(matches!(&directives[3], FileDirective::Fail { kind, .. } if kind == "FILE_RENAME"));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_extract_duplicate_attribute_strict() -> Result<()> {
+		// -- Setup & Fixtures
+		let input = r#"<FILE_CHANGES><FILE_DELETE file_path="a.txt" file_path="b.txt" /></FILE_CHANGES>"#;
+		let options = ExtractOptions::default().with_strict_attributes();
+
+		// -- Exec
+		let res = extract_file_changes_with_options(input, false, options);
+
+		// -- Check
+		assert!(matches!(res, Err(Error::ParseDuplicateAttribute { .. })));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_extract_report_warnings() -> Result<()> {
+		// -- Setup & Fixtures
+		let input = "<file_changes>\n<File_New file_path=\"a.rs\">\n```rust\nfn a() {}\n```\n</File_New>\n<FILE_APPEND file_path=\"b.txt\" />\n<FILE_PATCH file_path=\"c.rs\">\n@@\n-x";
+
+		// -- Exec
+		let report = extract_file_changes_report(input, false, None)?;
+
+		// -- Check
+		let directives: Vec<_> = report.changes.into_iter().collect();
+		assert_eq!(directives.len(), 2);
+		assert_eq!(
+			report.warnings,
+			vec![
+				"Tag 'file_changes' matched case-insensitively as 'FILE_CHANGES'",
+				"'<FILE_CHANGES>' is not closed, recovered up to the end of the input",
+				"Tag 'File_New' matched case-insensitively as 'FILE_NEW'",
+				"Self-closing '<FILE_APPEND />' expanded to an empty body",
+				"'<FILE_PATCH>' is not closed and was ignored (truncated output?)",
+				"Stripped the code fence around the FILE_NEW body of 'a.rs'",
+			]
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_extract_limits_produce_fail_directives() -> Result<()> {
+		// -- Setup & Fixtures
+		let input = "<FILE_CHANGES>\n<FILE_NEW file_path=\"big.txt\">\n0123456789\n</FILE_NEW>\n<FILE_DELETE file_path=\"a.txt\" />\n<FILE_DELETE file_path=\"b.txt\" />\n</FILE_CHANGES>";
+		let options = ExtractOptions::default().with_max_body_bytes(8).with_max_directives(2);
+
+		// -- Exec
+		let (changes, _) = extract_file_changes_with_options(input, false, options)?;
+
+		// -- Check
+		let directives: Vec<_> = changes.into_iter().collect();
+		assert_eq!(directives.len(), 3);
+		let FileDirective::Fail { kind, file_path, .. } = &directives[0] else {
+			return Err("should be a Fail for the large body".into());
+		};
+		assert_eq!(kind, "FILE_NEW");
+		assert_eq!(file_path.as_deref(), Some("big.txt"));
+		assert!(matches!(&directives[1], FileDirective::Delete { file_path } if file_path == "a.txt"));
+		let FileDirective::Fail { error_msg, .. } = &directives[2] else {
+			return Err("should be a Fail for the dropped directives".into());
+		};
+		assert!(error_msg.contains("3 found, limit is 2"));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_extract_limits_fail_on_limit() -> Result<()> {
+		// -- Setup & Fixtures
+		let input = "<FILE_CHANGES>\n<FILE_NEW file_path=\"big.txt\">\n0123456789\n</FILE_NEW>\n</FILE_CHANGES>";
+		let options = ExtractOptions::default().with_max_body_bytes(8).with_fail_on_limit();
+
+		// -- Exec
+		let res = extract_file_changes_with_options(input, false, options);
+
+		// -- Check
+		assert!(matches!(res, Err(Error::ParseBodyTooLarge { max: 8, .. })));
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,102 @@
+use crate::{
+	ApplyChangesStatus, ApplyOptions, Error, ExtractOptions, SecurityPolicy, apply_file_changes_with_options,
+	extract_file_changes_report,
+};
+use simple_fs::SPath;
+
+/// Options for `extract_and_apply`, grouping the options of each stage.
+///
+/// `ExtractApplyOptions::default()` (or `None`) extracts without limits and applies with the strict default policy.
+#[derive(Debug, Clone, Default)]
+pub struct ExtractApplyOptions {
+	pub extract: ExtractOptions,
+	pub apply: ApplyOptions,
+	pub security_policy: SecurityPolicy,
+}
+
+impl From<Option<ExtractApplyOptions>> for ExtractApplyOptions {
+	fn from(opt: Option<ExtractApplyOptions>) -> Self {
+		opt.unwrap_or_default()
+	}
+}
+
+/// Fluid apis
+impl ExtractApplyOptions {
+	pub fn with_extract_options(mut self, extract: ExtractOptions) -> Self {
+		self.extract = extract;
+		self
+	}
+
+	pub fn with_apply_options(mut self, apply: ApplyOptions) -> Self {
+		self.apply = apply;
+		self
+	}
+
+	pub fn with_security_policy(mut self, security_policy: SecurityPolicy) -> Self {
+		self.security_policy = security_policy;
+		self
+	}
+}
+
+/// The unified report of `extract_and_apply`.
+#[derive(Debug)]
+pub struct ExtractApplyReport {
+	/// The model output outside of the `FILE_CHANGES` block (including the prose between directives).
+	pub extruded: Option<String>,
+	/// The extraction warnings (see `ExtractReport::warnings`).
+	pub extract_warnings: Vec<String>,
+	/// The status of each directive, including the directives that failed extraction (`DirectiveKind::Fail`).
+	/// `None` when the extraction or the apply failed as a whole (see `error`).
+	pub status: Option<ApplyChangesStatus>,
+	/// The error which stopped the extraction (e.g., a strict limit) or the apply (e.g., a security violation).
+	pub error: Option<Error>,
+}
+
+impl ExtractApplyReport {
+	/// `true` when there is no error and every directive succeeded (an output without directives is a success).
+	pub fn is_success(&self) -> bool {
+		self.error.is_none()
+			&& self
+				.status
+				.as_ref()
+				.is_some_and(|s| s.items.iter().all(|i| i.success()))
+	}
+}
+
+/// Extracts the `FILE_CHANGES` of `llm_output` and applies them to `base_dir`, in one call.
+///
+/// Errors of either stage are reported in `ExtractApplyReport::error` rather than returned,
+/// so the extraction warnings and extruded content are never lost.
+pub fn extract_and_apply(
+	base_dir: impl Into<SPath>,
+	llm_output: &str,
+	options: impl Into<ExtractApplyOptions>,
+) -> ExtractApplyReport {
+	let options: ExtractApplyOptions = options.into();
+
+	let extract_report = match extract_file_changes_report(llm_output, true, options.extract) {
+		Ok(report) => report,
+		Err(err) => {
+			return ExtractApplyReport {
+				extruded: None,
+				extract_warnings: Vec::new(),
+				status: None,
+				error: Some(err),
+			};
+		}
+	};
+
+	let (status, error) =
+		match apply_file_changes_with_options(base_dir, extract_report.changes, options.security_policy, options.apply)
+		{
+			Ok(status) => (Some(status), None),
+			Err(err) => (None, Some(err)),
+		};
+
+	ExtractApplyReport {
+		extruded: extract_report.extruded,
+		extract_warnings: extract_report.warnings,
+		status,
+		error,
+	}
+}
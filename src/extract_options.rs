@@ -0,0 +1,77 @@
+use crate::{BlobStore, TagParser};
+
+/// Options controlling how `extract_file_changes_with_options` extracts a `FileChanges`.
+///
+/// `ExtractOptions::default()` (or `None` via `Option<ExtractOptions>`) yields the same
+/// behavior as `extract_file_changes` (no limits).
+#[derive(Debug, Clone, Default)]
+pub struct ExtractOptions {
+	/// Maximum size, in bytes, of a single directive body. A larger directive becomes a
+	/// `FileDirective::Fail` (or fails the extraction with `fail_on_limit`).
+	pub max_body_bytes: Option<usize>,
+
+	/// Maximum number of directives. The directives beyond the limit are dropped and reported
+	/// by a trailing `FileDirective::Fail` (or fail the extraction with `fail_on_limit`).
+	pub max_directives: Option<usize>,
+
+	/// When `true`, exceeding a limit fails the whole extraction with an `Err`
+	/// instead of producing `Fail` directives. (default false)
+	pub fail_on_limit: bool,
+
+	/// When `true`, duplicate attributes with different values, or conflicting attributes
+	/// (e.g., `to_path` on a `FILE_NEW`), fail the whole extraction with an `Err`
+	/// instead of producing a `Fail` directive. (default false)
+	pub strict_attributes: bool,
+
+	/// The tag parser implementation. (default `TagParser::Markex` with the `markex` feature, `TagParser::Builtin` otherwise)
+	pub tag_parser: TagParser,
+
+	/// When set, the large `FILE_NEW` / `FILE_APPEND` bodies (see `BlobStore::with_min_bytes`) are spilled
+	/// to this store, keeping only their blob in `Content::blob_path`, and the applier streams them at write time.
+	pub blob_store: Option<BlobStore>,
+}
+
+impl From<Option<ExtractOptions>> for ExtractOptions {
+	fn from(opt: Option<ExtractOptions>) -> Self {
+		opt.unwrap_or_default()
+	}
+}
+
+/// Fluid apis
+impl ExtractOptions {
+	/// Limit the size of each directive body.
+	pub fn with_max_body_bytes(mut self, max: usize) -> Self {
+		self.max_body_bytes = Some(max);
+		self
+	}
+
+	/// Limit the number of directives.
+	pub fn with_max_directives(mut self, max: usize) -> Self {
+		self.max_directives = Some(max);
+		self
+	}
+
+	/// Fail the whole extraction when a limit is exceeded.
+	pub fn with_fail_on_limit(mut self) -> Self {
+		self.fail_on_limit = true;
+		self
+	}
+
+	/// Fail the whole extraction on duplicate or conflicting attributes.
+	pub fn with_strict_attributes(mut self) -> Self {
+		self.strict_attributes = true;
+		self
+	}
+
+	/// Use the given tag parser implementation.
+	pub fn with_tag_parser(mut self, tag_parser: TagParser) -> Self {
+		self.tag_parser = tag_parser;
+		self
+	}
+
+	/// Spill the large directive bodies to the given blob store.
+	pub fn with_blob_store(mut self, blob_store: BlobStore) -> Self {
+		self.blob_store = Some(blob_store);
+		self
+	}
+}
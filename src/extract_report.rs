@@ -0,0 +1,36 @@
+use crate::FileChanges;
+use std::ops::Range;
+
+/// The result of `extract_file_changes_report`, with the notes about the lossy or heuristic
+/// steps taken while extracting (useful to judge the quality of the model output).
+#[derive(Debug, Clone)]
+pub struct ExtractReport {
+	pub changes: FileChanges,
+	/// The content outside of the `FILE_CHANGES` block (when `extrude_other_content` is `true`).
+	pub extruded: Option<String>,
+	/// The same content as ordered segments, with their spans in the input (when `extrude_other_content` is `true`,
+	/// empty otherwise), e.g., to re-render the model message with the `FILE_CHANGES` block replaced by a widget.
+	pub extruded_segments: Vec<ExtrudedSegment>,
+	/// E.g., recovered truncation, case-insensitive tag matches, stripped fences, self-closing expansions.
+	pub warnings: Vec<String>,
+}
+
+/// Where an `ExtrudedSegment` is, relative to the `FILE_CHANGES` block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtrudedKind {
+	/// The prose before the block (all of the input without a block).
+	ProseBefore,
+	/// The prose the model interleaved between the directives of the block (trimmed).
+	Commentary,
+	/// The prose after the block (the later `FILE_CHANGES` blocks being ignored).
+	ProseAfter,
+}
+
+/// A non-blank part of the input outside of the directives (see `ExtractReport::extruded_segments`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtrudedSegment {
+	pub kind: ExtrudedKind,
+	pub text: String,
+	/// The byte range of the text in the input.
+	pub span: Range<usize>,
+}
@@ -0,0 +1,235 @@
+use crate::{ApplyOptions, Error, ExcerptLimits, truncate_excerpt};
+use std::collections::HashMap;
+
+/// The keys of the templates of `FeedbackTemplates`, with their placeholders.
+pub const FEEDBACK_TEMPLATE_KEYS: &[(&str, &[&str])] = &[
+	("ApplyPathNotFound", &["op", "path"]),
+	("ApplyNoChanges", &["file_path"]),
+	("ApplyWrongTargetFile", &["file_path", "suggested_path"]),
+	("ApplyAmbiguousPath", &["path", "candidates"]),
+	("ApplyBatchNotApplied", &[]),
+	("ApplyBatchStopped", &[]),
+	("ApplyBatchRolledBack", &[]),
+	("ApplyBatchBudgetExhausted", &[]),
+	("ApplyProtectedRegion", &["file_path", "line"]),
+	("ApplyFileExists", &["file_path"]),
+	("ApplyRenameTargetExists", &["from_path", "to_path"]),
+	("ApplySpanLineMismatch", &["file_path", "line", "line_hash"]),
+	("ApplySpanOutOfRange", &["file_path", "line", "start", "end", "len"]),
+	("ApplyTooManyMatches", &["glob", "count", "max"]),
+	("ApplyAnchorNotFound", &["file_path", "anchor"]),
+	("ApplyContextAssertionFailed", &["file_path", "hunk_number", "context"]),
+	("ExcerptMoreLines", &["more"]),
+];
+
+/// Overridable templates of the feedback text sent back to a model: the directive error messages
+/// (`DirectiveStatus::error_msg`) and the omitted lines marker of the excerpts, e.g., to prompt in another language,
+/// or with custom instructions (e.g., "re-read the file and resend the span").
+///
+/// A template is keyed by the `Error` variant name (see `FEEDBACK_TEMPLATE_KEYS`), with `{placeholder}` fields
+/// (e.g., `"La ligne {line} de '{file_path}' a changé"`). A text without a template keeps the default English wording.
+///
+/// See `ApplyOptions::feedback_templates`.
+#[derive(Debug, Clone, Default)]
+pub struct FeedbackTemplates {
+	templates: HashMap<String, String>,
+}
+
+/// Fluid apis
+impl FeedbackTemplates {
+	/// Set the template of the text `key` (see `FEEDBACK_TEMPLATE_KEYS`).
+	pub fn with_template(mut self, key: impl Into<String>, template: impl Into<String>) -> Self {
+		self.templates.insert(key.into(), template.into());
+		self
+	}
+}
+
+impl FeedbackTemplates {
+	pub fn template(&self, key: &str) -> Option<&str> {
+		self.templates.get(key).map(String::as_str)
+	}
+
+	/// The error message, from its template if any (otherwise its `Display`).
+	pub fn error_msg(&self, err: &Error) -> String {
+		if let Some((key, params)) = error_params(err)
+			&& let Some(template) = self.template(key)
+		{
+			return fill(template, &params);
+		}
+		err.to_string()
+	}
+
+	/// Same as `truncate_excerpt`, with the `ExcerptMoreLines` template for the omitted lines marker.
+	pub fn excerpt(&self, text: &str, limits: ExcerptLimits) -> String {
+		let excerpt = truncate_excerpt(text, limits);
+		let Some(template) = self.template("ExcerptMoreLines") else {
+			return excerpt;
+		};
+		let total_lines = text.lines().count();
+		if total_lines <= limits.max_lines {
+			return excerpt;
+		}
+
+		// The marker is the last line of the excerpt.
+		let more = total_lines - limits.max_lines;
+		let body = excerpt.rsplit_once('\n').map(|(body, _)| body).unwrap_or_default();
+		let marker = fill(template, &[("more", more.to_string())]);
+		if body.is_empty() {
+			marker
+		} else {
+			format!("{body}\n{marker}")
+		}
+	}
+}
+
+/// The directive error message, with the `ApplyOptions::feedback_templates` if any.
+pub(crate) fn feedback_msg(err: &Error, options: &ApplyOptions) -> String {
+	match &options.feedback_templates {
+		Some(templates) => templates.error_msg(err),
+		None => err.to_string(),
+	}
+}
+
+// region:    --- Support
+
+/// The template key and placeholder values of the error, if it has a template.
+fn error_params(err: &Error) -> Option<(&'static str, Vec<(&'static str, String)>)> {
+	let params = match err {
+		Error::ApplyPathNotFound { op, path } => {
+			("ApplyPathNotFound", vec![("op", op.clone()), ("path", path.clone())])
+		}
+		Error::ApplyNoChanges { file_path } => ("ApplyNoChanges", vec![("file_path", file_path.clone())]),
+		Error::ApplyWrongTargetFile {
+			file_path,
+			suggested_path,
+		} => (
+			"ApplyWrongTargetFile",
+			vec![
+				("file_path", file_path.clone()),
+				("suggested_path", suggested_path.clone()),
+			],
+		),
+		Error::ApplyAmbiguousPath { path, candidates } => (
+			"ApplyAmbiguousPath",
+			vec![("path", path.clone()), ("candidates", candidates.join(", "))],
+		),
+		Error::ApplyBatchNotApplied => ("ApplyBatchNotApplied", Vec::new()),
+		Error::ApplyBatchStopped => ("ApplyBatchStopped", Vec::new()),
+		Error::ApplyBatchRolledBack => ("ApplyBatchRolledBack", Vec::new()),
+		Error::ApplyBatchBudgetExhausted => ("ApplyBatchBudgetExhausted", Vec::new()),
+		Error::ApplyProtectedRegion { file_path, line } => (
+			"ApplyProtectedRegion",
+			vec![("file_path", file_path.clone()), ("line", line.to_string())],
+		),
+		Error::ApplyFileExists { file_path } => ("ApplyFileExists", vec![("file_path", file_path.clone())]),
+		Error::ApplyRenameTargetExists { from_path, to_path } => (
+			"ApplyRenameTargetExists",
+			vec![("from_path", from_path.clone()), ("to_path", to_path.clone())],
+		),
+		Error::ApplySpanLineMismatch {
+			file_path,
+			line,
+			line_hash,
+		} => (
+			"ApplySpanLineMismatch",
+			vec![
+				("file_path", file_path.clone()),
+				("line", line.to_string()),
+				("line_hash", line_hash.clone()),
+			],
+		),
+		Error::ApplySpanOutOfRange {
+			file_path,
+			line,
+			start,
+			end,
+			len,
+		} => (
+			"ApplySpanOutOfRange",
+			vec![
+				("file_path", file_path.clone()),
+				("line", line.to_string()),
+				("start", start.to_string()),
+				("end", end.to_string()),
+				("len", len.to_string()),
+			],
+		),
+		Error::ApplyTooManyMatches { glob, count, max } => (
+			"ApplyTooManyMatches",
+			vec![
+				("glob", glob.clone()),
+				("count", count.to_string()),
+				("max", max.to_string()),
+			],
+		),
+		Error::ApplyAnchorNotFound { file_path, anchor } => (
+			"ApplyAnchorNotFound",
+			vec![("file_path", file_path.clone()), ("anchor", anchor.clone())],
+		),
+		Error::ApplyContextAssertionFailed {
+			file_path,
+			hunk_number,
+			context,
+		} => (
+			"ApplyContextAssertionFailed",
+			vec![
+				("file_path", file_path.clone()),
+				("hunk_number", hunk_number.to_string()),
+				("context", context.clone()),
+			],
+		),
+		_ => return None,
+	};
+	Some(params)
+}
+
+/// The template with its `{name}` placeholders replaced by their values.
+fn fill(template: &str, params: &[(&str, String)]) -> String {
+	params.iter().fold(template.to_string(), |text, (name, value)| {
+		text.replace(&format!("{{{name}}}"), value)
+	})
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_feedback_templates_error_msg_and_excerpt() -> Result<()> {
+		// -- Setup & Fixtures
+		let templates = FeedbackTemplates::default()
+			.with_template(
+				"ApplySpanLineMismatch",
+				"La ligne {line} de '{file_path}' a changé (hash {line_hash}), relisez le fichier.",
+			)
+			.with_template("ExcerptMoreLines", "[{more} lignes omises]");
+		let mismatch = Error::apply_span_line_mismatch("src/main.rs", 3, "a1b2");
+		let no_changes = Error::ApplyNoChanges {
+			file_path: "a.txt".to_string(),
+		};
+
+		// -- Exec & Check
+		assert_eq!(
+			templates.error_msg(&mismatch),
+			"La ligne 3 de 'src/main.rs' a changé (hash a1b2), relisez le fichier."
+		);
+		// No template, the default wording.
+		assert_eq!(templates.error_msg(&no_changes), "No changes applied to 'a.txt'");
+		let limits = ExcerptLimits::default().with_max_lines(2);
+		assert_eq!(
+			templates.excerpt("one\ntwo\nthree\nfour\n", limits),
+			"one\ntwo\n[2 lignes omises]"
+		);
+		assert_eq!(templates.excerpt("one\n", limits), "one\n");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
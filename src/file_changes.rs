@@ -1,4 +1,7 @@
-use crate::FileDirective;
+use crate::extract::escape_attr_value;
+use crate::lint::directive_paths;
+use crate::{Content, FileDirective};
+use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
 pub struct FileChanges {
@@ -15,6 +18,190 @@ impl FileChanges {
 	}
 }
 
+// region:    --- Serialization
+
+impl FileChanges {
+	/// Serializes the directives back into a `<FILE_CHANGES>` block.
+	///
+	/// Directive contents are re-wrapped in code fences safe for their content
+	/// (see `Content::to_fenced`). `Fail` directives cannot be represented and are skipped.
+	pub fn to_markdown(&self) -> String {
+		let mut out = String::from("<FILE_CHANGES>\n");
+
+		for directive in &self.directives {
+			let block = match directive {
+				FileDirective::New { file_path, content } => content_block("FILE_NEW", file_path, "", content),
+				FileDirective::Patch {
+					file_path,
+					content,
+					allow_generated,
+				} => {
+					let extra_attrs = if *allow_generated {
+						" allow_generated=\"true\""
+					} else {
+						""
+					};
+					content_block("FILE_PATCH", file_path, extra_attrs, content)
+				}
+				FileDirective::Append { file_path, content } => content_block("FILE_APPEND", file_path, "", content),
+				FileDirective::SpanPatch {
+					file_path,
+					line,
+					columns,
+					line_hash,
+					replacement,
+				} => format!(
+					"<FILE_SPAN_PATCH file_path=\"{}\" line=\"{line}\" columns=\"{}-{}\" line_hash=\"{}\">\n\
+					 {replacement}\n</FILE_SPAN_PATCH>",
+					escape_attr_value(file_path),
+					columns.start(),
+					columns.end(),
+					escape_attr_value(line_hash)
+				),
+				FileDirective::Insert {
+					file_path,
+					anchor,
+					regex,
+					position,
+					content,
+				} => {
+					let mut attrs = format!(
+						"file_path=\"{}\" anchor=\"{}\" position=\"{position}\"",
+						escape_attr_value(file_path),
+						escape_attr_value(anchor)
+					);
+					if *regex {
+						attrs.push_str(" regex=\"true\"");
+					}
+					format!("<FILE_INSERT {attrs}>\n{content}\n</FILE_INSERT>")
+				}
+				FileDirective::GlobalReplace {
+					glob,
+					pattern,
+					replacement,
+					regex,
+					max_matches,
+				} => {
+					let mut attrs = format!(
+						"glob=\"{}\" pattern=\"{}\"",
+						escape_attr_value(glob),
+						escape_attr_value(pattern)
+					);
+					if *regex {
+						attrs.push_str(" regex=\"true\"");
+					}
+					if let Some(max_matches) = max_matches {
+						attrs.push_str(&format!(" max_matches=\"{max_matches}\""));
+					}
+					format!("<FILE_GLOBAL_REPLACE {attrs}>\n{replacement}\n</FILE_GLOBAL_REPLACE>")
+				}
+				FileDirective::Copy { from_path, to_path } => format!(
+					"<FILE_COPY from_path=\"{}\" to_path=\"{}\" />",
+					escape_attr_value(from_path),
+					escape_attr_value(to_path)
+				),
+				FileDirective::Rename { from_path, to_path } => format!(
+					"<FILE_RENAME from_path=\"{}\" to_path=\"{}\" />",
+					escape_attr_value(from_path),
+					escape_attr_value(to_path)
+				),
+				FileDirective::Delete { file_path } => {
+					format!("<FILE_DELETE file_path=\"{}\" />", escape_attr_value(file_path))
+				}
+				FileDirective::Fail { .. } => continue,
+			};
+			out.push('\n');
+			out.push_str(&block);
+			out.push('\n');
+		}
+
+		out.push_str("\n</FILE_CHANGES>\n");
+		out
+	}
+}
+
+fn content_block(tag: &str, file_path: &str, extra_attrs: &str, content: &Content) -> String {
+	format!(
+		"<{tag} file_path=\"{}\"{extra_attrs}>\n{}\n</{tag}>",
+		escape_attr_value(file_path),
+		content.to_fenced()
+	)
+}
+
+// endregion: --- Serialization
+
+// region:    --- Split
+
+impl FileChanges {
+	/// Groups the directives by target path (the destination for copy/rename, `""` for a `Fail` without a path),
+	/// in the order of the first directive of each path, keeping the order of the directives of each path
+	/// (e.g., for review UIs presenting and applying the changes file by file).
+	pub fn split_per_path(&self) -> Vec<(String, FileChanges)> {
+		let mut groups: Vec<(String, FileChanges)> = Vec::new();
+		for directive in &self.directives {
+			let path = directive.file_path().unwrap_or_default();
+			match groups.iter_mut().find(|(group_path, _)| group_path == path) {
+				Some((_, changes)) => changes.directives.push(directive.clone()),
+				None => groups.push((path.to_string(), FileChanges::new(vec![directive.clone()]))),
+			}
+		}
+		groups
+	}
+}
+
+// endregion: --- Split
+
+// region:    --- Merge
+
+/// How `FileChanges::merge` handles a path touched by several sources.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergeStrategy {
+	/// The directives of all the sources are kept, in order, so the ones on the same path apply one after the other
+	/// (e.g., a model patch, then a human fix on top).
+	#[default]
+	Sequential,
+	/// Only the directives of the first source touching a path are kept for that path.
+	KeepFirst,
+	/// Only the directives of the last source touching a path are kept for that path (e.g., a later correction wins).
+	KeepLast,
+}
+
+impl FileChanges {
+	/// Merges the `sources` (e.g., the changes of several model calls, or of a model and a human) into one batch,
+	/// in source order, keeping the order of the directives of each source.
+	///
+	/// With `KeepFirst` / `KeepLast`, a source keeps all its directives on a path it owns (e.g., two patches), and a copy
+	/// or rename is kept only if its source owns both of its paths. A `Fail` without a path is always kept.
+	pub fn merge(sources: Vec<FileChanges>, strategy: MergeStrategy) -> FileChanges {
+		let mut owners: HashMap<String, usize> = HashMap::new();
+		for (source_idx, source) in sources.iter().enumerate() {
+			for path in source.iter().flat_map(directive_paths) {
+				match strategy {
+					MergeStrategy::Sequential => {}
+					MergeStrategy::KeepFirst => {
+						owners.entry(path.to_string()).or_insert(source_idx);
+					}
+					MergeStrategy::KeepLast => {
+						owners.insert(path.to_string(), source_idx);
+					}
+				}
+			}
+		}
+
+		let mut directives = Vec::new();
+		for (source_idx, source) in sources.into_iter().enumerate() {
+			directives.extend(source.into_iter().filter(|directive| {
+				directive_paths(directive)
+					.iter()
+					.all(|path| owners.get(*path).is_none_or(|owner| *owner == source_idx))
+			}));
+		}
+		FileChanges::new(directives)
+	}
+}
+
+// endregion: --- Merge
+
 // region:    --- Iterators
 
 impl FileChanges {
@@ -42,3 +229,176 @@ impl<'a> IntoIterator for &'a FileChanges {
 }
 
 // endregion: --- Iterators
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::extract_file_changes;
+
+	#[test]
+	fn test_file_changes_to_markdown_roundtrip() -> Result<()> {
+		// -- Setup & Fixtures
+		let input = r#"
+<FILE_CHANGES>
+<FILE_NEW file_path="src/main.rs">
+```rust
+fn main() {}
+```
+</FILE_NEW>
+<FILE_PATCH file_path="notes.md">
+@@
+-old
++new
+</FILE_PATCH>
+<FILE_DELETE file_path="tmp.txt" />
+</FILE_CHANGES>
+"#;
+		let (changes, _) = extract_file_changes(input, false)?;
+
+		// -- Exec
+		let markdown = changes.to_markdown();
+		let (reparsed, _) = extract_file_changes(&markdown, false)?;
+
+		// -- Check
+		assert!(markdown.contains("```rust\nfn main() {}\n```"));
+		assert!(
+			changes.diff(&reparsed).is_empty(),
+			"Roundtrip should be lossless:\n{markdown}"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_file_changes_to_markdown_roundtrip_special_paths() -> Result<()> {
+		// -- Setup & Fixtures
+		let changes = FileChanges::new(vec![
+			FileDirective::Delete {
+				file_path: "my docs/say \"hi\" & <bye>.md".to_string(),
+			},
+			FileDirective::Rename {
+				from_path: "src/bin/".to_string(),
+				to_path: "src/Ünïcödé 文件/".to_string(),
+			},
+			FileDirective::New {
+				file_path: "it's a/b.txt".to_string(),
+				content: Content::from_raw("hello\n".to_string()),
+			},
+		]);
+
+		// -- Exec
+		let markdown = changes.to_markdown();
+		let (reparsed, _) = extract_file_changes(&markdown, false)?;
+
+		// -- Check
+		assert!(
+			changes.diff(&reparsed).is_empty(),
+			"Roundtrip should be lossless:\n{markdown}"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_file_changes_split_per_path() -> Result<()> {
+		// -- Setup & Fixtures
+		let input = r#"
+<FILE_CHANGES>
+<FILE_PATCH file_path="a.rs">
+@@
+-1
++2
+</FILE_PATCH>
+<FILE_RENAME from_path="old.rs" to_path="b.rs" />
+<FILE_APPEND file_path="a.rs">
+x
+</FILE_APPEND>
+<FILE_PATCH file_path="b.rs">
+@@
+-3
++4
+</FILE_PATCH>
+</FILE_CHANGES>
+"#;
+		let (changes, _) = extract_file_changes(input, false)?;
+
+		// -- Exec
+		let groups = changes.split_per_path();
+
+		// -- Check
+		let summary: Vec<(&str, Vec<&str>)> = groups
+			.iter()
+			.map(|(path, changes)| (path.as_str(), changes.iter().map(|d| d.kind()).collect()))
+			.collect();
+		assert_eq!(
+			summary,
+			[("a.rs", vec!["Patch", "Append"]), ("b.rs", vec!["Rename", "Patch"])]
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_file_changes_merge() -> Result<()> {
+		// -- Setup & Fixtures
+		let model = r#"
+<FILE_CHANGES>
+<FILE_NEW file_path="a.rs">
+model a
+</FILE_NEW>
+<FILE_APPEND file_path="a.rs">
+model a2
+</FILE_APPEND>
+<FILE_NEW file_path="b.rs">
+model b
+</FILE_NEW>
+</FILE_CHANGES>
+"#;
+		let human = r#"
+<FILE_CHANGES>
+<FILE_NEW file_path="a.rs">
+human a
+</FILE_NEW>
+<FILE_RENAME from_path="b.rs" to_path="c.rs" />
+<FILE_DELETE file_path="d.rs" />
+</FILE_CHANGES>
+"#;
+		let sources = || -> Result<Vec<FileChanges>> {
+			Ok(vec![
+				extract_file_changes(model, false)?.0,
+				extract_file_changes(human, false)?.0,
+			])
+		};
+		let summary = |changes: &FileChanges| -> Vec<String> {
+			changes
+				.iter()
+				.map(|d| format!("{} {}", d.kind(), d.file_path().unwrap_or_default()))
+				.collect()
+		};
+
+		// -- Exec
+		let sequential = FileChanges::merge(sources()?, MergeStrategy::Sequential);
+		let keep_first = FileChanges::merge(sources()?, MergeStrategy::KeepFirst);
+		let keep_last = FileChanges::merge(sources()?, MergeStrategy::KeepLast);
+
+		// -- Check
+		assert_eq!(summary(&sequential).len(), 6);
+		assert_eq!(
+			summary(&keep_first),
+			["New a.rs", "Append a.rs", "New b.rs", "Delete d.rs"]
+		);
+		assert_eq!(summary(&keep_last), ["New a.rs", "Rename c.rs", "Delete d.rs"]);
+		let FileDirective::New { content, .. } = keep_last.iter().next().ok_or("should have a directive")? else {
+			return Err("should be a FILE_NEW".into());
+		};
+		assert_eq!(content.content, "human a\n");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
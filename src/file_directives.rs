@@ -1,3 +1,8 @@
+use crate::{Error, InsertPosition, Result};
+use simple_fs::SPath;
+use std::borrow::Cow;
+use std::ops::RangeInclusive;
+
 #[derive(Debug, Clone)]
 pub enum FileDirective {
 	New {
@@ -7,11 +12,49 @@ pub enum FileDirective {
 	Patch {
 		file_path: String,
 		content: Content,
+		/// Set by the `allow_generated="true"` attribute, to patch a generated file anyway
+		/// (see `ApplyOptions::refuse_generated`).
+		allow_generated: bool,
 	},
 	Append {
 		file_path: String,
 		content: Content,
 	},
+	/// Replaces a span of a line (see `FILE_SPAN_PATCH`), e.g., one identifier occurrence.
+	SpanPatch {
+		file_path: String,
+		/// The 1-based line.
+		line: usize,
+		/// The 1-based, inclusive range of the grapheme columns of the line to replace.
+		columns: RangeInclusive<usize>,
+		/// The `line_hash` of the line as the model saw it, the span is only replaced if the line still matches.
+		line_hash: String,
+		replacement: String,
+	},
+	/// Inserts `content` before or after (or in place of) the first line matching `anchor` (see `FILE_INSERT`),
+	/// e.g., a line after the imports.
+	Insert {
+		file_path: String,
+		/// A literal line (matched as a hunk context line), or a regex when `regex` is set
+		/// (requires the `regex` feature).
+		anchor: String,
+		regex: bool,
+		position: InsertPosition,
+		content: String,
+	},
+	/// Replaces all the matches of `pattern` in the files matching `glob` (see `FILE_GLOBAL_REPLACE`),
+	/// e.g., a bulk rename.
+	GlobalReplace {
+		/// The glob of the files (relative to the base dir), e.g., `src/**/*.rs`.
+		glob: String,
+		/// A literal string, or a regex when `regex` is set (requires the `regex` feature).
+		pattern: String,
+		/// The replacement, which can reference the regex capture groups (e.g., `$1`) when `regex` is set.
+		replacement: String,
+		regex: bool,
+		/// Nothing is replaced when the total matches exceed it (default `DEFAULT_MAX_MATCHES`).
+		max_matches: Option<usize>,
+	},
 	Copy {
 		from_path: String,
 		to_path: String,
@@ -31,10 +74,48 @@ pub enum FileDirective {
 	},
 }
 
+impl FileDirective {
+	/// Returns the directive kind, in `{ "New" | "Patch" | "Append" | "SpanPatch" | "Insert" | "GlobalReplace" | "Copy"
+	/// | "Rename" | "Delete" | "Fail" }`.
+	pub fn kind(&self) -> &'static str {
+		match self {
+			FileDirective::New { .. } => "New",
+			FileDirective::Patch { .. } => "Patch",
+			FileDirective::Append { .. } => "Append",
+			FileDirective::SpanPatch { .. } => "SpanPatch",
+			FileDirective::Insert { .. } => "Insert",
+			FileDirective::GlobalReplace { .. } => "GlobalReplace",
+			FileDirective::Copy { .. } => "Copy",
+			FileDirective::Rename { .. } => "Rename",
+			FileDirective::Delete { .. } => "Delete",
+			FileDirective::Fail { .. } => "Fail",
+		}
+	}
+
+	/// Returns the target path of the directive (the destination for copy/rename, the glob for a global replace),
+	/// or `None` for a `Fail` without a path.
+	pub fn file_path(&self) -> Option<&str> {
+		match self {
+			FileDirective::New { file_path, .. }
+			| FileDirective::Patch { file_path, .. }
+			| FileDirective::Append { file_path, .. }
+			| FileDirective::SpanPatch { file_path, .. }
+			| FileDirective::Insert { file_path, .. }
+			| FileDirective::Delete { file_path } => Some(file_path),
+			FileDirective::GlobalReplace { glob, .. } => Some(glob),
+			FileDirective::Copy { to_path, .. } | FileDirective::Rename { to_path, .. } => Some(to_path),
+			FileDirective::Fail { file_path, .. } => file_path.as_deref(),
+		}
+	}
+}
+
 #[derive(Debug, Clone)]
 pub struct Content {
 	pub content: String,
 	pub code_fence: Option<CodeFence>,
+	/// The blob holding the content when it was spilled to a `BlobStore` by the extraction
+	/// (see `ExtractOptions::blob_store`), in which case `content` is empty.
+	pub blob_path: Option<SPath>,
 }
 
 #[derive(Debug, Clone)]
@@ -43,61 +124,158 @@ pub struct CodeFence {
 	pub end: String,
 }
 
+impl CodeFence {
+	/// Builds a backtick fence safe for `content`: at least 3 backticks,
+	/// and longer than any backtick run inside the content, so the content cannot close it early.
+	pub fn for_content(content: &str, info: Option<&str>) -> Self {
+		let ticks = "`".repeat(longest_backtick_run(content).max(2) + 1);
+		Self {
+			start: format!("{ticks}{}", info.unwrap_or_default()),
+			end: ticks,
+		}
+	}
+
+	/// Returns the info string (e.g., the language) following the opening fence, possibly empty.
+	pub fn info(&self) -> &str {
+		self.start.trim_start().trim_start_matches(['`', '~']).trim()
+	}
+}
+
 impl Content {
+	/// Returns the content wrapped in a code fence safe for it (see `CodeFence::for_content`),
+	/// keeping the info string (e.g., the language) of the original fence if any.
+	///
+	/// Note: The content of a blob is loaded (an unreadable blob is serialized as empty).
+	pub fn to_fenced(&self) -> String {
+		let content = self.load().unwrap_or_default();
+		let info = self.code_fence.as_ref().map(|f| f.info()).filter(|i| !i.is_empty());
+		let fence = CodeFence::for_content(&content, info);
+
+		let mut out = format!("{}\n{content}", fence.start);
+		if !content.is_empty() && !content.ends_with('\n') {
+			out.push('\n');
+		}
+		out.push_str(&fence.end);
+		out
+	}
+
+	/// The content, read from its blob if it was spilled to a `BlobStore`.
+	pub fn load(&self) -> Result<Cow<'_, str>> {
+		match &self.blob_path {
+			Some(blob_path) => Ok(Cow::Owned(
+				simple_fs::read_to_string(blob_path).map_err(Error::simple_fs)?,
+			)),
+			None => Ok(Cow::Borrowed(&self.content)),
+		}
+	}
+
+	/// The content-addressed hash of the blob holding the content, if it was spilled to a `BlobStore`.
+	pub fn blob_hash(&self) -> Option<&str> {
+		self.blob_path.as_ref().map(|p| p.name())
+	}
+
+	/// Builds the content from the directive body byte-for-byte, without any fence detection
+	/// or newline stripping (used for `raw="true"` directives).
+	pub fn from_verbatim(body: String) -> Self {
+		Self {
+			content: body,
+			code_fence: None,
+			blob_path: None,
+		}
+	}
+
+	/// Builds the content from the raw directive body, stripping an outer code fence if present.
+	///
+	/// Only structural newlines are removed (the one after the opening tag, and the ones
+	/// around the fence lines), all other whitespace of the body is preserved exactly
+	/// (e.g., Makefile tabs, YAML indentation, leading blank lines).
+	///
+	/// The closing fence must use the same character (`` ` `` or `~`) as the opening fence
+	/// and be at least as long, so bodies containing shorter inner fences (e.g., a README
+	/// wrapped in a 4-backtick fence) are kept intact.
 	pub fn from_raw(raw: String) -> Self {
 		let mut raw = raw;
-		if let Some(stripped) = raw.strip_prefix('\n') {
+		if let Some(stripped) = raw.strip_prefix("\r\n").or_else(|| raw.strip_prefix('\n')) {
 			raw = stripped.to_string();
 		}
 
-		let trimmed_start = raw.trim_start();
-		if trimmed_start.starts_with("```")
-			&& let Some(f_idx) = trimmed_start.find('\n')
+		// Note: Blank lines before an opening fence are not content, but indentation is,
+		//       so an indented fence-like line is not treated as a fence.
+		let fence_start = raw.trim_start_matches(['\n', '\r']);
+		if let Some((fence_char, fence_len)) = parse_opening_fence(fence_start)
+			&& let Some(f_idx) = fence_start.find('\n')
 		{
-			let start_fence = trimmed_start[..f_idx].to_string();
-			let remaining = &trimmed_start[f_idx + 1..];
+			let start_fence = fence_start[..f_idx].trim_end_matches('\r').to_string();
+			let remaining = &fence_start[f_idx + 1..];
 			let trimmed_end = remaining.trim_end();
 
-			if trimmed_end.ends_with("```") {
-				if let Some(l_idx) = trimmed_end.rfind('\n')
-					&& let last_line = &trimmed_end[l_idx + 1..]
-					&& last_line.trim_start().starts_with("```")
-				{
-					let end_fence = last_line.to_string();
-					let mut content = remaining[..l_idx + 1].to_string();
-
-					// Note: We also strip the first newline if it exists inside the code fence,
-					//       to match the behavior of non-fenced content where one level of newlines is removed.
-					if let Some(stripped) = content.strip_prefix('\n') {
-						content = stripped.to_string();
-					}
-
-					return Self {
-						content,
-						code_fence: Some(CodeFence {
-							start: start_fence,
-							end: end_fence,
-						}),
-					};
-				} else if trimmed_end.trim_start().starts_with("```") {
-					return Self {
-						content: String::new(),
-						code_fence: Some(CodeFence {
-							start: start_fence,
-							end: trimmed_end.to_string(),
-						}),
-					};
-				}
+			if let Some(l_idx) = trimmed_end.rfind('\n')
+				&& let last_line = &trimmed_end[l_idx + 1..]
+				&& is_closing_fence(last_line, fence_char, fence_len)
+			{
+				let end_fence = last_line.to_string();
+				let content = remaining[..l_idx + 1].to_string();
+
+				return Self {
+					content,
+					code_fence: Some(CodeFence {
+						start: start_fence,
+						end: end_fence,
+					}),
+					blob_path: None,
+				};
+			} else if !trimmed_end.contains('\n') && is_closing_fence(trimmed_end, fence_char, fence_len) {
+				return Self {
+					content: String::new(),
+					code_fence: Some(CodeFence {
+						start: start_fence,
+						end: trimmed_end.to_string(),
+					}),
+					blob_path: None,
+				};
 			}
 		}
 
 		Self {
 			content: raw,
 			code_fence: None,
+			blob_path: None,
 		}
 	}
 }
 
+// region:    --- Support
+
+/// Returns the fence character and length if `s` starts with a code fence (3+ backticks or tildes).
+fn parse_opening_fence(s: &str) -> Option<(char, usize)> {
+	let fence_char = s.chars().next().filter(|c| *c == '`' || *c == '~')?;
+	let fence_len = s.chars().take_while(|c| *c == fence_char).count();
+	(fence_len >= 3).then_some((fence_char, fence_len))
+}
+
+/// Whether `line` closes a fence opened with `fence_len` times `fence_char`
+/// (same character, at least as long, nothing else but whitespace).
+fn is_closing_fence(line: &str, fence_char: char, fence_len: usize) -> bool {
+	let line = line.trim();
+	line.chars().count() >= fence_len && line.chars().all(|c| c == fence_char)
+}
+
+fn longest_backtick_run(content: &str) -> usize {
+	let mut longest = 0;
+	let mut current = 0;
+	for ch in content.chars() {
+		if ch == '`' {
+			current += 1;
+			longest = longest.max(current);
+		} else {
+			current = 0;
+		}
+	}
+	longest
+}
+
+// endregion: --- Support
+
 // region:    --- Tests
 
 #[cfg(test)]
@@ -137,6 +315,125 @@ mod tests {
 
 		Ok(())
 	}
+
+	#[test]
+	fn test_file_directives_content_from_raw_preserves_leading_whitespace() -> Result<()> {
+		// -- Setup & Fixtures
+		let makefile_raw = "\n\n\tall:\n\t\tgcc main.c\n".to_string();
+		let yaml_raw = "\r\n  key: value\r\n  list:\r\n    - a\r\n".to_string();
+		let indented_fence_raw = "\n    ```\n    code\n    ```\n".to_string();
+
+		// -- Exec
+		let makefile = Content::from_raw(makefile_raw);
+		let yaml = Content::from_raw(yaml_raw);
+		let indented_fence = Content::from_raw(indented_fence_raw);
+
+		// -- Check
+		assert_eq!(makefile.content, "\n\tall:\n\t\tgcc main.c\n");
+		assert_eq!(yaml.content, "  key: value\r\n  list:\r\n    - a\r\n");
+		assert!(indented_fence.code_fence.is_none());
+		assert_eq!(indented_fence.content, "    ```\n    code\n    ```\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_file_directives_content_from_raw_fence_keeps_leading_blank_line() -> Result<()> {
+		// -- Setup & Fixtures
+		let raw = "\n```yaml\n\n  key: value\n```\n".to_string();
+
+		// -- Exec
+		let content = Content::from_raw(raw);
+
+		// -- Check
+		assert_eq!(content.content, "\n  key: value\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_file_directives_content_from_raw_four_backtick_outer_fence() -> Result<()> {
+		// -- Setup & Fixtures
+		let raw =
+			"\n````md\n# Readme\n\n```sh\ncargo run\n```\n\nMore text.\n```rust\nfn a() {}\n```\n````\n".to_string();
+
+		// -- Exec
+		let content = Content::from_raw(raw);
+
+		// -- Check
+		assert_eq!(
+			content.content,
+			"# Readme\n\n```sh\ncargo run\n```\n\nMore text.\n```rust\nfn a() {}\n```\n"
+		);
+		let fence = content.code_fence.ok_or("should have code fence")?;
+		assert_eq!(fence.start, "````md");
+		assert_eq!(fence.end, "````");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_file_directives_content_from_raw_shorter_closing_fence_is_not_a_close() -> Result<()> {
+		// -- Setup & Fixtures
+		// The outer 4-backtick fence is never closed, the trailing ``` belongs to the body.
+		let raw = "````md\n# Readme\n```sh\ncargo run\n```\n".to_string();
+
+		// -- Exec
+		let content = Content::from_raw(raw.clone());
+
+		// -- Check
+		assert!(content.code_fence.is_none());
+		assert_eq!(content.content, raw);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_file_directives_content_from_raw_tilde_fence() -> Result<()> {
+		// -- Setup & Fixtures
+		let raw = "\n~~~toml\n[a]\nb = 1\n```\n~~~\n".to_string();
+
+		// -- Exec
+		let content = Content::from_raw(raw);
+
+		// -- Check
+		assert_eq!(content.content, "[a]\nb = 1\n```\n");
+		assert_eq!(content.code_fence.ok_or("should have code fence")?.end, "~~~");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_file_directives_code_fence_for_content_longer_than_inner_runs() -> Result<()> {
+		// -- Setup & Fixtures
+		let content = "# Readme\n\n````sh\ncargo run\n````\n";
+
+		// -- Exec
+		let fence = CodeFence::for_content(content, Some("md"));
+		let plain_fence = CodeFence::for_content("no ticks here\n", None);
+
+		// -- Check
+		assert_eq!(fence.start, "`````md");
+		assert_eq!(fence.end, "`````");
+		assert_eq!(fence.info(), "md");
+		assert_eq!(plain_fence.start, "```");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_file_directives_content_to_fenced_keeps_info() -> Result<()> {
+		// -- Setup & Fixtures
+		let content = Content::from_raw("\n```rust\nfn main() {}\n```".to_string());
+
+		// -- Exec
+		let fenced = content.to_fenced();
+
+		// -- Check
+		assert_eq!(fenced, "```rust\nfn main() {}\n```");
+
+		Ok(())
+	}
 }
 
 // endregion: --- Tests
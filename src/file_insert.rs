@@ -0,0 +1,232 @@
+use crate::global_replace::Replacer;
+use crate::patch_completer::find_anchor_line;
+use crate::{CompleteOptions, Error, MatchTier, Result};
+use derive_more::Display;
+
+/// Where a `FILE_INSERT` puts its content, relative to its anchor line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Display)]
+pub enum InsertPosition {
+	#[display("before")]
+	Before,
+	#[default]
+	#[display("after")]
+	After,
+	/// Replaces the (first) anchor line, an empty content removing it.
+	#[display("replace-first")]
+	ReplaceFirst,
+}
+
+impl InsertPosition {
+	/// Parses a `position` attribute value (`before`, `after`, or `replace-first`).
+	pub(crate) fn parse(value: &str) -> Option<Self> {
+		match value.trim().to_ascii_lowercase().as_str() {
+			"before" => Some(Self::Before),
+			"after" => Some(Self::After),
+			"replace-first" | "replace_first" | "replace" => Some(Self::ReplaceFirst),
+			_ => None,
+		}
+	}
+}
+
+/// The result of a `FILE_INSERT` on a content.
+pub(crate) struct InsertedContent {
+	pub(crate) content: String,
+	/// The 1-based line of the anchor.
+	pub(crate) anchor_line: usize,
+	/// How a literal anchor matched (`None` for a regex anchor).
+	pub(crate) tier: Option<MatchTier>,
+}
+
+/// Inserts `insert` before or after (or in place of) the first line of the LF-normalized `content` matching `anchor`.
+///
+/// A literal anchor is matched as a hunk context line (see `CompleteOptions`), a regex one (`regex`) against each line.
+pub(crate) fn apply_file_insert(
+	file_path: &str,
+	content: &str,
+	anchor: &str,
+	regex: bool,
+	position: InsertPosition,
+	insert: &str,
+	options: &CompleteOptions,
+) -> Result<InsertedContent> {
+	let lines: Vec<&str> = content.split_inclusive('\n').collect();
+	let Some((idx, tier)) = find_anchor(&lines, anchor, regex, options)? else {
+		return Err(Error::apply_anchor_not_found(file_path, anchor));
+	};
+
+	let mut block = insert.to_string();
+	if !block.is_empty() && !block.ends_with('\n') {
+		block.push('\n');
+	}
+
+	let mut out = String::with_capacity(content.len() + block.len() + 1);
+	for before in &lines[..idx] {
+		out.push_str(before);
+	}
+	match position {
+		InsertPosition::Before => {
+			out.push_str(&block);
+			out.push_str(lines[idx]);
+		}
+		InsertPosition::After => {
+			out.push_str(lines[idx]);
+			if !block.is_empty() && !lines[idx].ends_with('\n') {
+				out.push('\n');
+			}
+			out.push_str(&block);
+		}
+		InsertPosition::ReplaceFirst => out.push_str(&block),
+	}
+	for after in &lines[idx + 1..] {
+		out.push_str(after);
+	}
+
+	Ok(InsertedContent {
+		content: out,
+		anchor_line: idx + 1,
+		tier,
+	})
+}
+
+/// Whether the insert is applied to `content`: its lines are right before, right after, or in place of the anchor
+/// line, or, for a `replace-first` whose anchor is gone, anywhere (an empty content only needing the anchor gone).
+pub(crate) fn file_insert_applied(
+	content: &str,
+	anchor: &str,
+	regex: bool,
+	position: InsertPosition,
+	insert: &str,
+	options: &CompleteOptions,
+) -> bool {
+	let lines: Vec<&str> = content.lines().collect();
+	let block: Vec<&str> = insert.lines().collect();
+	let is_block_at = |start: usize| {
+		lines.get(start..start + block.len()).is_some_and(|window| {
+			window
+				.iter()
+				.zip(&block)
+				.all(|(line, block_line)| line.trim_end() == block_line.trim_end())
+		})
+	};
+
+	match find_anchor(&lines, anchor, regex, options) {
+		Ok(Some((idx, _))) => {
+			!block.is_empty()
+				&& match position {
+					InsertPosition::Before => idx.checked_sub(block.len()).is_some_and(is_block_at),
+					InsertPosition::After => is_block_at(idx + 1),
+					InsertPosition::ReplaceFirst => is_block_at(idx),
+				}
+		}
+		Ok(None) => position == InsertPosition::ReplaceFirst && (block.is_empty() || (0..lines.len()).any(is_block_at)),
+		Err(_) => false,
+	}
+}
+
+// region:    --- Support
+
+/// The 0-based index of the first line matching the anchor, with its tier (`None` for a regex anchor).
+fn find_anchor(
+	lines: &[&str],
+	anchor: &str,
+	regex: bool,
+	options: &CompleteOptions,
+) -> Result<Option<(usize, Option<MatchTier>)>> {
+	if !regex {
+		let lines: Vec<&str> = lines.iter().map(|line| line.trim_end_matches('\n')).collect();
+		return Ok(find_anchor_line(&lines, anchor, options).map(|(idx, tier)| (idx, Some(tier))));
+	}
+	let replacer = Replacer::new(anchor, true)?;
+	Ok(lines
+		.iter()
+		.position(|line| replacer.is_match(line.trim_end_matches('\n')))
+		.map(|idx| (idx, None)))
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_file_insert_apply_file_insert() -> Result<()> {
+		// -- Setup & Fixtures
+		let content = "use std::io;\n\nfn main() {}";
+		let options = CompleteOptions::default();
+
+		// -- Exec
+		let after = apply_file_insert(
+			"a.rs",
+			content,
+			"use std::io;",
+			false,
+			InsertPosition::After,
+			"use std::fs;",
+			&options,
+		)?;
+		let before = apply_file_insert(
+			"a.rs",
+			content,
+			"  FN MAIN() {}",
+			false,
+			InsertPosition::Before,
+			"// x\n",
+			&options,
+		)?;
+		let replaced = apply_file_insert(
+			"a.rs",
+			content,
+			"fn main",
+			true,
+			InsertPosition::ReplaceFirst,
+			"",
+			&options,
+		)?;
+		let last = apply_file_insert(
+			"a.rs",
+			content,
+			"fn main() {}",
+			false,
+			InsertPosition::After,
+			"fn b() {}",
+			&options,
+		)?;
+
+		// -- Check
+		assert_eq!(after.content, "use std::io;\nuse std::fs;\n\nfn main() {}");
+		assert_eq!((after.anchor_line, after.tier), (1, Some(MatchTier::Strict)));
+		assert_eq!(before.content, "use std::io;\n\n// x\nfn main() {}");
+		assert_eq!(before.tier, Some(MatchTier::Fuzzy));
+		assert_eq!(replaced.content, "use std::io;\n\n");
+		assert_eq!(replaced.tier, None);
+		assert_eq!(last.content, "use std::io;\n\nfn main() {}\nfn b() {}\n");
+		let err = apply_file_insert("a.rs", content, "mod x;", false, InsertPosition::After, "x", &options)
+			.err()
+			.ok_or("should fail")?;
+		assert!(matches!(err, Error::ApplyAnchorNotFound { .. }), "{err}");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_file_insert_position_parse() -> Result<()> {
+		// -- Exec & Check
+		assert_eq!(InsertPosition::parse("Before"), Some(InsertPosition::Before));
+		assert_eq!(InsertPosition::parse(" after "), Some(InsertPosition::After));
+		assert_eq!(
+			InsertPosition::parse("replace-first"),
+			Some(InsertPosition::ReplaceFirst)
+		);
+		assert_eq!(InsertPosition::parse("middle"), None);
+		assert_eq!(InsertPosition::ReplaceFirst.to_string(), "replace-first");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
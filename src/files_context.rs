@@ -1,27 +1,19 @@
 use crate::Result;
+use crate::path_resolver::to_slash_path;
 use simple_fs::{SPath, list_files, read_to_string};
 
 /// Gathers file contents based on globs relative to a `base_dir` and formats them
 /// into a `<FILE_CONTENT path="...">content</FILE_CONTENT>` block.
 pub fn load_files_context(base_dir: impl Into<SPath>, globs: &[&str]) -> Result<Option<String>> {
 	let base_dir = base_dir.into();
-	let files = list_files(&base_dir, Some(globs), None)?;
+	let rel_files = list_context_files(&base_dir, globs)?;
 
-	let res = if !files.is_empty() {
+	let res = if !rel_files.is_empty() {
 		let mut out = String::new();
 
-		for file in files {
-			let rel_path = file.diff(base_dir.path()).ok_or_else(|| {
-				crate::Error::Custom(format!("Could not get relative path for '{}'", file.path().as_str()))
-			})?;
+		for (rel_path, file) in rel_files {
 			let content = read_to_string(file.path()).map_err(crate::Error::simple_fs)?;
-
-			out.push_str(&format!("<FILE_CONTENT path=\"{}\">\n", rel_path.as_str()));
-			out.push_str(&content);
-			if !content.ends_with('\n') {
-				out.push('\n');
-			}
-			out.push_str("</FILE_CONTENT>\n\n");
+			push_file_content(&mut out, &rel_path, &content);
 		}
 		Some(out)
 	} else {
@@ -31,6 +23,34 @@ pub fn load_files_context(base_dir: impl Into<SPath>, globs: &[&str]) -> Result<
 	Ok(res)
 }
 
+/// Lists the files matching `globs` under `base_dir`, with their relative paths (with `/` separators).
+///
+/// Sorted by relative path, so the context is identical across runs and platforms.
+pub(crate) fn list_context_files(base_dir: &SPath, globs: &[&str]) -> Result<Vec<(String, SPath)>> {
+	let files = list_files(base_dir, Some(globs), None)?;
+
+	let mut rel_files = Vec::with_capacity(files.len());
+	for file in files {
+		let rel_path = file.diff(base_dir.path()).ok_or_else(|| {
+			crate::Error::Custom(format!("Could not get relative path for '{}'", file.path().as_str()))
+		})?;
+		rel_files.push((to_slash_path(rel_path.as_str()), file));
+	}
+	rel_files.sort_by(|a, b| a.0.cmp(&b.0));
+
+	Ok(rel_files)
+}
+
+/// Appends the `<FILE_CONTENT path="...">` block of a file to `out`.
+pub(crate) fn push_file_content(out: &mut String, rel_path: &str, content: &str) {
+	out.push_str(&format!("<FILE_CONTENT path=\"{rel_path}\">\n"));
+	out.push_str(content);
+	if !content.ends_with('\n') {
+		out.push('\n');
+	}
+	out.push_str("</FILE_CONTENT>\n\n");
+}
+
 // region:    --- Tests
 
 #[cfg(test)]
@@ -1,40 +1,260 @@
+use crate::path_resolver::to_slash_path;
 use crate::{Error, Result, SecurityPolicy};
 use simple_fs::SPath;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
 
-/// Checks if the target path is safe to write, ensuring it remains within the base directory.
-pub fn check_for_write(target: &SPath, base_dir: &SPath, policy: Option<&SecurityPolicy>) -> Result<()> {
+/// Checks if the target path is safe to write, ensuring it remains within the base directory (symlinks resolved),
+/// and is not denied (or not allowed) by the `SecurityPolicy::guard_policy`.
+///
+/// The paths of a virtual store (see `ApplyStore::is_virtual`) are only checked lexically.
+pub fn check_for_write(
+	target: &SPath,
+	base_dir: &SPath,
+	policy: Option<&SecurityPolicy>,
+	is_virtual: bool,
+) -> Result<()> {
 	if let Some(policy) = policy
 		&& policy.bypass_all_checks
 	{
 		return Ok(());
 	}
-	if is_under_dir(target, base_dir) {
+	let dir = containing_dir(target, base_dir, policy)
+		.ok_or_else(|| Error::security_violation(target.to_string(), base_dir.to_string()))?;
+	check_guard_policy(target, dir, policy)?;
+	if is_virtual {
 		return Ok(());
 	}
-	if let Some(policy) = policy {
-		for dir in &policy.writable_dirs {
-			if is_under_dir(target, dir) {
-				return Ok(());
-			}
-		}
-	}
-	Err(Error::security_violation(target.to_string(), base_dir.to_string()))
+	check_resolved(target, dir, base_dir, policy, true)
 }
 
-/// Checks if the target path is safe to read, ensuring it remains within the base directory.
-pub fn check_for_read(target: &SPath, base_dir: &SPath, policy: Option<&SecurityPolicy>) -> Result<()> {
+/// Checks if the target path is safe to read, ensuring it remains within the base directory (symlinks resolved),
+/// and is not denied (or not allowed) by the `SecurityPolicy::guard_policy`.
+///
+/// The paths of a virtual store (see `ApplyStore::is_virtual`) are only checked lexically.
+pub fn check_for_read(
+	target: &SPath,
+	base_dir: &SPath,
+	policy: Option<&SecurityPolicy>,
+	is_virtual: bool,
+) -> Result<()> {
 	if let Some(p) = policy {
-		return p.assert_path_read_access(target, base_dir);
+		p.assert_path_read_access(target, base_dir)?;
+		if p.bypass_all_checks {
+			return Ok(());
+		}
+		// Note: A target outside of the base dir is checked relative to the writable dir it is under, if any.
+		let dir = containing_dir(target, base_dir, policy).unwrap_or(base_dir);
+		check_guard_policy(target, dir, policy)?;
+		if p.read_anywhere || is_virtual {
+			return Ok(());
+		}
+		return check_resolved(target, dir, base_dir, policy, false);
+	}
+	SecurityPolicy::default().assert_path_read_access(target, base_dir)?;
+	if is_virtual {
+		return Ok(());
 	}
-	SecurityPolicy::default().assert_path_read_access(target, base_dir)
+	check_resolved(target, base_dir, base_dir, None, false)
 }
 
 // region:    --- Support
 
+/// Max symlinks followed when resolving a path (as the `ELOOP` limit of most systems).
+const MAX_SYMLINK_HOPS: usize = 40;
+
+/// The dir containing `target`: the base dir, or else the first writable dir of the policy it is under.
+fn containing_dir<'a>(target: &SPath, base_dir: &'a SPath, policy: Option<&'a SecurityPolicy>) -> Option<&'a SPath> {
+	if is_under_dir(target, base_dir) {
+		return Some(base_dir);
+	}
+	policy?.writable_dirs.iter().find(|dir| is_under_dir(target, dir))
+}
+
+/// Checks the `target` (lexically under `dir`) once its symlinks are resolved: it must remain under the base dir
+/// or a writable dir (resolved as well), and pass the guard globs there.
+/// A write through a symlink is refused with `SecurityPolicy::deny_symlink_writes`.
+fn check_resolved(
+	target: &SPath,
+	dir: &SPath,
+	base_dir: &SPath,
+	policy: Option<&SecurityPolicy>,
+	is_write: bool,
+) -> Result<()> {
+	let resolved_target = resolve_symlinks(target);
+	let resolved_dir = resolve_symlinks(dir);
+	let rel_path = target
+		.clone()
+		.into_collapsed()
+		.diff(dir.clone().into_collapsed().path());
+	let unlinked_target = rel_path.map(|rel_path| resolved_dir.join(rel_path).into_collapsed());
+	if unlinked_target.as_ref() == Some(&resolved_target) {
+		return Ok(());
+	}
+
+	if is_write && policy.is_some_and(|p| p.deny_symlink_writes) {
+		return Err(Error::symlink_denied(target.to_string(), resolved_target.to_string()));
+	}
+
+	let writable_dirs = policy.map(|p| p.writable_dirs.as_slice()).unwrap_or_default();
+	let resolved_root = std::iter::once(base_dir)
+		.chain(writable_dirs)
+		.map(resolve_symlinks)
+		.find(|root| is_under_dir(&resolved_target, root));
+	match resolved_root {
+		Some(root) => check_guard_policy(&resolved_target, &root, policy),
+		None => Err(Error::security_violation(
+			format!("{target} (resolved to {resolved_target})"),
+			base_dir.to_string(),
+		)),
+	}
+}
+
+/// The path with its symlinks resolved: the deepest existing ancestor canonicalized, and the remaining components
+/// appended (a dangling symlink is followed to its target, as a write would).
+pub(crate) fn resolve_symlinks(path: &SPath) -> SPath {
+	let path = path.clone().into_collapsed();
+	let resolved = resolve_std_path(path.std_path(), 0);
+	SPath::from_std_path(resolved)
+		.map(SPath::into_collapsed)
+		.unwrap_or(path)
+}
+
+fn resolve_std_path(path: &Path, hops: usize) -> PathBuf {
+	if let Ok(canonical) = std::fs::canonicalize(path) {
+		return canonical;
+	}
+	if hops < MAX_SYMLINK_HOPS
+		&& std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_symlink())
+		&& let Ok(link) = std::fs::read_link(path)
+	{
+		let link = match path.parent() {
+			Some(parent) if link.is_relative() => parent.join(link),
+			_ => link,
+		};
+		let link = SPath::from_std_path(&link)
+			.map(|link| link.into_collapsed().std_path().to_path_buf())
+			.unwrap_or(link);
+		return resolve_std_path(&link, hops + 1);
+	}
+	match (path.parent(), path.file_name()) {
+		(Some(parent), Some(name)) => resolve_std_path(parent, hops).join(name),
+		_ => path.to_path_buf(),
+	}
+}
+
 fn is_under_dir(target: &SPath, dir: &SPath) -> bool {
 	let dir = dir.clone().into_collapsed();
 	let target = target.clone().into_collapsed();
-	target.as_str().starts_with(dir.as_str())
+	is_path_under(target.as_str(), dir.as_str())
+}
+
+/// Whether the `target` path is `dir` or under it, compared by whole components (`/base-other` is not under `/base`),
+/// and case-insensitively on the case-insensitive file systems (macOS, Windows).
+pub(crate) fn is_path_under(target: &str, dir: &str) -> bool {
+	let (target, dir) = (normalize_case(target), normalize_case(dir));
+	let dir = dir.trim_end_matches(['/', '\\']);
+	match target.strip_prefix(dir) {
+		Some(rest) => rest.is_empty() || rest.starts_with(['/', '\\']),
+		None => false,
+	}
+}
+
+fn normalize_case(path: &str) -> Cow<'_, str> {
+	if cfg!(any(target_os = "macos", windows)) {
+		Cow::Owned(path.to_lowercase())
+	} else {
+		Cow::Borrowed(path)
+	}
+}
+
+/// Checks the path of `target` relative to `dir` against the guard globs of the policy, if any.
+fn check_guard_policy(target: &SPath, dir: &SPath, policy: Option<&SecurityPolicy>) -> Result<()> {
+	let Some(policy) = policy else {
+		return Ok(());
+	};
+	let guard_policy = &policy.guard_policy;
+	if guard_policy.deny_globs.is_empty() && guard_policy.allow_globs.is_empty() {
+		return Ok(());
+	}
+
+	let target = target.clone().into_collapsed();
+	let rel_path = target
+		.diff(dir.clone().into_collapsed().path())
+		.map(|p| to_slash_path(p.as_str()))
+		.unwrap_or_else(|| target.to_string());
+	guard_policy.assert_access(target.as_str(), &rel_path)
 }
 
 // endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_fs_guard_is_path_under() -> Result<()> {
+		// -- Exec & Check
+		assert!(is_path_under("/base/src/main.rs", "/base"));
+		assert!(is_path_under("/base", "/base"));
+		assert!(is_path_under("/base/a", "/base/"));
+		assert!(is_path_under("/a", "/"));
+		// Prefix collisions.
+		assert!(!is_path_under("/base-other/a", "/base"));
+		assert!(!is_path_under("/basement", "/base"));
+		// Case differences (only equal on the case-insensitive file systems).
+		let case_insensitive = cfg!(any(target_os = "macos", windows));
+		assert_eq!(is_path_under("/Users/Me/Proj/a", "/users/me/proj"), case_insensitive);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_fs_guard_check_for_write_prefix_collision() -> Result<()> {
+		// -- Setup & Fixtures
+		let base_dir = SPath::new("/base");
+
+		// -- Exec & Check
+		assert!(check_for_write(&SPath::new("/base/a.txt"), &base_dir, None, false).is_ok());
+		assert!(matches!(
+			check_for_write(&SPath::new("/base-other/a.txt"), &base_dir, None, false),
+			Err(Error::SecurityViolation { .. })
+		));
+		let policy = SecurityPolicy::from_writable_dirs([SPath::new("/out")]);
+		assert!(check_for_write(&SPath::new("/out/a.txt"), &base_dir, Some(&policy), false).is_ok());
+		assert!(check_for_write(&SPath::new("/outside/a.txt"), &base_dir, Some(&policy), false).is_err());
+		assert!(check_for_read(&SPath::new("/base-other/a.txt"), &base_dir, None, false).is_err());
+
+		Ok(())
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_fs_guard_check_for_write_virtual() -> Result<()> {
+		// -- Setup & Fixtures
+		let dir = std::env::temp_dir().join(format!("udiffx-test-fs-guard-{}", std::process::id()));
+		let base_dir = SPath::from_std_path(dir.join("base"))?;
+		std::fs::create_dir_all(&base_dir)?;
+		std::fs::create_dir_all(dir.join("outside"))?;
+		let _ = std::fs::remove_file(base_dir.join("escape"));
+		std::os::unix::fs::symlink("../outside", base_dir.join("escape"))?;
+		let target = base_dir.join("escape/a.txt");
+
+		// -- Exec & Check
+		// The disk paths are resolved, whatever the base dir, the virtual ones are only checked lexically.
+		assert!(matches!(
+			check_for_write(&target, &base_dir, None, false),
+			Err(Error::SecurityViolation { .. })
+		));
+		assert!(check_for_write(&target, &base_dir, None, true).is_ok());
+		assert!(check_for_write(&SPath::new("/other/a.txt"), &base_dir, None, true).is_err());
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
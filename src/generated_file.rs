@@ -0,0 +1,74 @@
+use crate::{Error, GeneratedReason, Result, SecurityPolicy};
+use simple_fs::get_glob_set;
+
+/// The marker of a generated file (e.g., `// @generated by build.rs`), looked for in its first lines.
+const GENERATED_MARKER: &str = "@generated";
+
+/// Number of lines at the top of a file searched for the `GENERATED_MARKER`.
+const GENERATED_MARKER_MAX_LINES: usize = 10;
+
+/// Returns why the file at `rel_path` (relative to the base dir) with `content` is generated, if it is.
+///
+/// The policy globs are checked first, then the `*.lock` extension, then the `@generated` marker.
+pub(crate) fn generated_reason(
+	rel_path: &str,
+	content: &str,
+	policy: &SecurityPolicy,
+) -> Result<Option<GeneratedReason>> {
+	for glob in &policy.generated_globs {
+		if get_glob_set(&[glob.as_str()])
+			.map_err(Error::simple_fs)?
+			.is_match(rel_path)
+		{
+			return Ok(Some(GeneratedReason::PolicyGlob(glob.clone())));
+		}
+	}
+
+	if rel_path.ends_with(".lock") {
+		return Ok(Some(GeneratedReason::LockFile));
+	}
+
+	if content
+		.lines()
+		.take(GENERATED_MARKER_MAX_LINES)
+		.any(|line| line.contains(GENERATED_MARKER))
+	{
+		return Ok(Some(GeneratedReason::Marker));
+	}
+
+	Ok(None)
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_generated_file_generated_reason() -> Result<()> {
+		// -- Setup & Fixtures
+		let policy = SecurityPolicy::default().with_generated_globs(["src/gen/**"]);
+
+		// -- Exec & Check
+		assert_eq!(
+			generated_reason("src/gen/api.rs", "", &policy)?,
+			Some(GeneratedReason::PolicyGlob("src/gen/**".to_string()))
+		);
+		assert_eq!(
+			generated_reason("Cargo.lock", "", &policy)?,
+			Some(GeneratedReason::LockFile)
+		);
+		assert_eq!(
+			generated_reason("src/schema.rs", "// @generated by diesel\n", &policy)?,
+			Some(GeneratedReason::Marker)
+		);
+		assert_eq!(generated_reason("src/main.rs", "fn main() {}\n", &policy)?, None);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
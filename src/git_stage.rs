@@ -0,0 +1,135 @@
+use crate::{ApplyChangesStatus, DirectiveKind, Error, Result};
+use simple_fs::SPath;
+use std::process::Command;
+
+/// Options of `git_stage_applied`.
+#[derive(Debug, Clone, Default)]
+pub struct GitStageOptions {
+	/// When set, a commit with this message is created once the paths are staged.
+	pub commit_message: Option<String>,
+}
+
+impl From<Option<GitStageOptions>> for GitStageOptions {
+	fn from(options: Option<GitStageOptions>) -> Self {
+		options.unwrap_or_default()
+	}
+}
+
+/// Fluid apis
+impl GitStageOptions {
+	pub fn with_commit_message(mut self, message: impl Into<String>) -> Self {
+		self.commit_message = Some(message.into());
+		self
+	}
+}
+
+/// What `git_stage_applied` staged (and committed).
+#[derive(Debug, Clone, Default)]
+pub struct GitStageReport {
+	/// The staged paths, relative to the base dir (the removed paths included).
+	pub staged_paths: Vec<String>,
+	/// The id of the created commit, if one was created.
+	pub commit_id: Option<String>,
+}
+
+/// Stages the paths the successful directives of `status` changed into the index of the git repository
+/// `base_dir` is in (`git add`, or `git rm --cached` for the removed paths), then commits the index
+/// when `GitStageOptions::commit_message` is set.
+///
+/// The commit includes anything else already staged, and is skipped when the index has no changes.
+/// Requires the `git` executable.
+pub fn git_stage_applied(
+	base_dir: impl Into<SPath>,
+	status: &ApplyChangesStatus,
+	options: impl Into<GitStageOptions>,
+) -> Result<GitStageReport> {
+	let base_dir: SPath = base_dir.into();
+	let options: GitStageOptions = options.into();
+
+	let mut staged_paths: Vec<String> = Vec::new();
+	for info in status.items.iter().filter(|i| i.success) {
+		let paths = match &info.kind {
+			DirectiveKind::Rename { from_path, file_path } => vec![from_path.as_str(), file_path.as_str()],
+			DirectiveKind::GlobalReplace { .. } => info.replace_counts.iter().map(|c| c.file_path.as_str()).collect(),
+			DirectiveKind::Fail { .. } => continue,
+			_ => vec![info.file_path()],
+		};
+		for path in paths {
+			if !staged_paths.iter().any(|p| p == path) {
+				staged_paths.push(path.to_string());
+			}
+		}
+	}
+
+	let (present, removed): (Vec<&str>, Vec<&str>) = staged_paths
+		.iter()
+		.map(String::as_str)
+		.partition(|path| base_dir.join(path).exists());
+	// Note: The paths are literal (e.g., a `:` prefixed file name is not a pathspec magic).
+	if !present.is_empty() {
+		run_git(
+			&base_dir,
+			&[&["--literal-pathspecs", "add", "-A", "--"], present.as_slice()].concat(),
+		)?;
+	}
+	if !removed.is_empty() {
+		run_git(
+			&base_dir,
+			&[
+				&[
+					"--literal-pathspecs",
+					"rm",
+					"-r",
+					"-q",
+					"--cached",
+					"--ignore-unmatch",
+					"--",
+				],
+				removed.as_slice(),
+			]
+			.concat(),
+		)?;
+	}
+
+	let mut commit_id = None;
+	if let Some(message) = options.commit_message.as_deref()
+		&& !run_git_status(&base_dir, &["diff", "--cached", "--quiet"])?
+	{
+		run_git(&base_dir, &["commit", "-q", "-m", message])?;
+		commit_id = Some(run_git(&base_dir, &["rev-parse", "HEAD"])?.trim().to_string());
+	}
+
+	Ok(GitStageReport {
+		staged_paths,
+		commit_id,
+	})
+}
+
+// region:    --- Support
+
+/// Runs `git` in `dir`, returning its stdout (a non-zero exit is an error).
+fn run_git(dir: &SPath, args: &[&str]) -> Result<String> {
+	let output = Command::new("git")
+		.arg("-C")
+		.arg(dir.as_str())
+		.args(args)
+		.output()
+		.map_err(|err| Error::git(args, err.to_string()))?;
+	if !output.status.success() {
+		return Err(Error::git(args, String::from_utf8_lossy(&output.stderr).trim()));
+	}
+	Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Runs `git` in `dir`, returning whether it exited successfully.
+fn run_git_status(dir: &SPath, args: &[&str]) -> Result<bool> {
+	let status = Command::new("git")
+		.arg("-C")
+		.arg(dir.as_str())
+		.args(args)
+		.status()
+		.map_err(|err| Error::git(args, err.to_string()))?;
+	Ok(status.success())
+}
+
+// endregion: --- Support
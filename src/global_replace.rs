@@ -0,0 +1,129 @@
+use crate::apply_store::ApplyStore;
+use crate::{Error, Result};
+use simple_fs::SPath;
+
+/// The max matches of a `FILE_GLOBAL_REPLACE` without a `max_matches` attribute.
+pub const DEFAULT_MAX_MATCHES: usize = 1000;
+
+/// The replacements a `FILE_GLOBAL_REPLACE` made (or would make) in one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplaceCount {
+	/// The file path, relative to the base dir.
+	pub file_path: String,
+	pub count: usize,
+}
+
+/// The pattern of a `FILE_GLOBAL_REPLACE`.
+pub(crate) enum Replacer<'a> {
+	Literal(&'a str),
+	#[cfg(feature = "regex")]
+	Regex(regex::Regex),
+}
+
+impl<'a> Replacer<'a> {
+	/// Builds the replacer, failing on an empty pattern, or an invalid regex
+	/// (or any regex when the `regex` feature is disabled).
+	pub(crate) fn new(pattern: &'a str, is_regex: bool) -> Result<Self> {
+		if pattern.is_empty() {
+			return Err(Error::apply_invalid_pattern(pattern, "empty pattern"));
+		}
+		if !is_regex {
+			return Ok(Self::Literal(pattern));
+		}
+
+		#[cfg(feature = "regex")]
+		{
+			regex::Regex::new(pattern)
+				.map(Self::Regex)
+				.map_err(|err| Error::apply_invalid_pattern(pattern, err.to_string()))
+		}
+		#[cfg(not(feature = "regex"))]
+		{
+			Err(Error::apply_invalid_pattern(
+				pattern,
+				"regex patterns require the `regex` feature",
+			))
+		}
+	}
+
+	pub(crate) fn count(&self, content: &str) -> usize {
+		match self {
+			Self::Literal(pattern) => content.matches(pattern).count(),
+			#[cfg(feature = "regex")]
+			Self::Regex(regex) => regex.find_iter(content).count(),
+		}
+	}
+
+	pub(crate) fn is_match(&self, text: &str) -> bool {
+		match self {
+			Self::Literal(pattern) => text.contains(pattern),
+			#[cfg(feature = "regex")]
+			Self::Regex(regex) => regex.is_match(text),
+		}
+	}
+
+	/// Replaces all the matches (a regex `replacement` can reference the capture groups, e.g., `$1`).
+	pub(crate) fn replace(&self, content: &str, replacement: &str) -> String {
+		match self {
+			Self::Literal(pattern) => content.replace(pattern, replacement),
+			#[cfg(feature = "regex")]
+			Self::Regex(regex) => regex.replace_all(content, replacement).into_owned(),
+		}
+	}
+}
+
+/// The files of the store under `base_dir` matching `glob` (relative paths, sorted).
+pub(crate) fn glob_rel_paths(store: &impl ApplyStore, base_dir: &SPath, glob: &str) -> Result<Vec<String>> {
+	let glob_set = simple_fs::get_glob_set(&[glob]).map_err(Error::simple_fs)?;
+	let rel_paths = store.list_rel_paths(base_dir)?;
+	Ok(rel_paths
+		.into_iter()
+		.filter(|rel_path| glob_set.is_match(rel_path))
+		.collect())
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_global_replace_replacer_literal() -> Result<()> {
+		// -- Setup & Fixtures
+		let replacer = Replacer::new("old_name", false)?;
+		let content = "let old_name = 1;\nold_name += old_name_suffix;\n";
+
+		// -- Exec & Check
+		assert_eq!(replacer.count(content), 3);
+		assert_eq!(
+			replacer.replace(content, "new_name"),
+			"let new_name = 1;\nnew_name += new_name_suffix;\n"
+		);
+		assert!(Replacer::new("", false).is_err());
+
+		Ok(())
+	}
+
+	#[cfg(feature = "regex")]
+	#[test]
+	fn test_global_replace_replacer_regex() -> Result<()> {
+		// -- Setup & Fixtures
+		let replacer = Replacer::new(r"\bold_(\w+)\b", true)?;
+		let content = "old_name(old_value, bold_x);\n";
+
+		// -- Exec & Check
+		assert_eq!(replacer.count(content), 2);
+		assert_eq!(replacer.replace(content, "new_$1"), "new_name(new_value, bold_x);\n");
+		assert!(matches!(
+			Replacer::new("(", true),
+			Err(Error::ApplyInvalidPattern { .. })
+		));
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,176 @@
+use crate::{Error, Result};
+use globset::GlobSet;
+use simple_fs::get_glob_set;
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+/// The deny globs of `GuardPolicy::recommended`: the VCS metadata and the usual secret files.
+const RECOMMENDED_DENY_GLOBS: &[&str] = &[
+	"**/.git/**",
+	"**/.hg/**",
+	"**/.svn/**",
+	"**/.env",
+	"**/.env.*",
+	"**/*.pem",
+	"**/*.key",
+	"**/id_rsa",
+	"**/id_ed25519",
+];
+
+/// The globs restricting the paths the directives can read or write, on top of the base dir containment
+/// (see `SecurityPolicy::guard_policy`).
+///
+/// The globs are matched against the path relative to the base dir (or to the writable dir the path is under),
+/// e.g., `.git/**`, `**/.env`, `Cargo.lock`. A glob matching a directory's content (e.g., `.git/**`) also matches
+/// the directory itself, so it cannot be deleted or renamed as a whole.
+#[derive(Debug, Clone, Default)]
+pub struct GuardPolicy {
+	/// The paths never read or written, even when matching an `allow_globs` glob.
+	pub deny_globs: Vec<String>,
+
+	/// When not empty, only the paths matching one of these globs can be read or written.
+	pub allow_globs: Vec<String>,
+
+	/// The glob sets of the globs, compiled on first use.
+	compiled: OnceLock<CompiledGlobs>,
+}
+
+/// The compiled glob sets of a `GuardPolicy`, one per glob (to report the denying glob).
+#[derive(Debug, Clone)]
+struct CompiledGlobs {
+	/// The globs compiled, to tell a later change of the policy globs.
+	deny_globs: Vec<String>,
+	allow_globs: Vec<String>,
+	deny: Vec<GlobSet>,
+	allow: Vec<GlobSet>,
+}
+
+impl CompiledGlobs {
+	fn new(deny_globs: &[String], allow_globs: &[String]) -> Result<Self> {
+		let compile = |globs: &[String]| -> Result<Vec<GlobSet>> {
+			globs
+				.iter()
+				.map(|glob| get_glob_set(&[glob.as_str()]).map_err(Error::simple_fs))
+				.collect()
+		};
+		Ok(Self {
+			deny_globs: deny_globs.to_vec(),
+			allow_globs: allow_globs.to_vec(),
+			deny: compile(deny_globs)?,
+			allow: compile(allow_globs)?,
+		})
+	}
+}
+
+/// Constructors
+impl GuardPolicy {
+	/// A policy denying the VCS metadata (`.git`, `.hg`, `.svn`) and the usual secret files
+	/// (`.env`, `.env.*`, `*.pem`, `*.key`, SSH private keys), anywhere under the base dir.
+	pub fn recommended() -> Self {
+		Self::default().with_deny_globs(RECOMMENDED_DENY_GLOBS.iter().copied())
+	}
+}
+
+/// Fluid apis
+impl GuardPolicy {
+	/// Override the deny globs with the given iterator.
+	pub fn with_deny_globs(mut self, globs: impl IntoIterator<Item = impl Into<String>>) -> Self {
+		self.deny_globs = globs.into_iter().map(|g| g.into()).collect();
+		self.compiled = OnceLock::new();
+		self
+	}
+
+	/// Append additional deny globs to the existing list.
+	pub fn append_deny_globs(mut self, globs: impl IntoIterator<Item = impl Into<String>>) -> Self {
+		self.deny_globs.extend(globs.into_iter().map(|g| g.into()));
+		self.compiled = OnceLock::new();
+		self
+	}
+
+	/// Override the allow globs with the given iterator.
+	pub fn with_allow_globs(mut self, globs: impl IntoIterator<Item = impl Into<String>>) -> Self {
+		self.allow_globs = globs.into_iter().map(|g| g.into()).collect();
+		self.compiled = OnceLock::new();
+		self
+	}
+}
+
+impl GuardPolicy {
+	/// Asserts the path (relative to the base dir, `/` separated) is not denied, and is allowed.
+	///
+	/// `target` is the full path, for the error message.
+	pub(crate) fn assert_access(&self, target: &str, rel_path: &str) -> Result<()> {
+		let compiled = self.compiled_globs()?;
+		let as_dir = format!("{rel_path}/");
+		let matches = |glob_set: &GlobSet| glob_set.is_match(rel_path) || glob_set.is_match(&as_dir);
+
+		if let Some((glob, _)) = self.deny_globs.iter().zip(&compiled.deny).find(|(_, set)| matches(set)) {
+			return Err(Error::guard_denied(target, glob));
+		}
+
+		if !compiled.allow.is_empty() && !compiled.allow.iter().any(matches) {
+			return Err(Error::guard_not_allowed(target));
+		}
+
+		Ok(())
+	}
+
+	/// The compiled glob sets, compiled on the first call (or again if the globs were changed since).
+	fn compiled_globs(&self) -> Result<Cow<'_, CompiledGlobs>> {
+		if let Some(compiled) = self.compiled.get()
+			&& compiled.deny_globs == self.deny_globs
+			&& compiled.allow_globs == self.allow_globs
+		{
+			return Ok(Cow::Borrowed(compiled));
+		}
+
+		let compiled = CompiledGlobs::new(&self.deny_globs, &self.allow_globs)?;
+		// Note: Only the first compilation is kept (the globs are not expected to change once the policy is in use).
+		if self.compiled.get().is_none() {
+			return Ok(Cow::Borrowed(self.compiled.get_or_init(|| compiled)));
+		}
+		Ok(Cow::Owned(compiled))
+	}
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_guard_policy_assert_access() -> Result<()> {
+		// -- Setup & Fixtures
+		let policy = GuardPolicy::recommended()
+			.append_deny_globs(["Cargo.lock"])
+			.with_allow_globs(["src/**", "Cargo.*", ".git/**"]);
+
+		// -- Exec & Check
+		assert!(policy.assert_access("/b/src/main.rs", "src/main.rs").is_ok());
+		assert!(policy.assert_access("/b/Cargo.toml", "Cargo.toml").is_ok());
+		// Denied even when allowed.
+		assert!(matches!(
+			policy.assert_access("/b/Cargo.lock", "Cargo.lock"),
+			Err(Error::GuardDenied { .. })
+		));
+		assert!(policy.assert_access("/b/.git/config", ".git/config").is_err());
+		// The denied directory itself.
+		assert!(policy.assert_access("/b/.git", ".git").is_err());
+		assert!(policy.assert_access("/b/src/.env", "src/.env").is_err());
+		assert!(matches!(
+			policy.assert_access("/b/README.md", "README.md"),
+			Err(Error::GuardNotAllowed { .. })
+		));
+		// The globs changed after the first use.
+		let mut policy = policy;
+		policy.deny_globs.push("src/main.rs".to_string());
+		assert!(policy.assert_access("/b/src/main.rs", "src/main.rs").is_err());
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -3,25 +3,111 @@
 mod fs_guard;
 
 mod applier;
+mod approval;
+mod apply_budget;
 mod apply_changes_status;
+mod apply_events;
+mod apply_journal;
+mod apply_options;
+mod apply_progress;
+mod apply_queue;
+mod apply_store;
+mod batch_diff;
+mod blob_store;
+mod complete_options;
+mod confirmer;
+mod content_map;
+mod content_hash;
+mod corpus_stats;
+mod detect_format;
+mod diff_stats;
+mod edit_session;
 mod error;
+mod excerpt;
 mod extract;
+mod extract_apply;
+mod extract_options;
+mod extract_report;
+mod feedback_templates;
 mod file_changes;
 mod file_directives;
+mod file_insert;
 mod files_context;
+mod generated_file;
+mod global_replace;
+mod guard_policy;
+mod license_header;
+mod line_endings;
+mod lint;
+mod make_patch;
+mod materializer;
 mod patch_completer;
+mod path_resolver;
+mod post_apply_validator;
+mod protected_regions;
+mod provenance;
+mod redactor;
+mod run_summary;
+mod secret_scan;
 mod security_policy;
+mod side_by_side;
+mod span_patch;
+mod tag_parser;
+mod temp_workspace;
+mod text_apply;
+mod verify;
 
+pub use secret_scan::{SecretFinding, SecretKind, SecretScanMode};
 pub use security_policy::SecurityPolicy;
+pub use side_by_side::{Line, RowKind, RowPair, side_by_side, side_by_side_from_contents, side_by_side_preview};
 
-pub use applier::{ApplyPatchIncrementalData, apply_file_changes};
+pub use applier::{
+	ApplyPatchIncrementalData, apply_file_changes, apply_file_changes_with_options, apply_file_changes_with_progress,
+};
 pub use apply_changes_status::*;
+pub use apply_events::{ApplyEvent, apply_file_changes_with_events};
+pub use approval::{ApprovalDecision, ApprovalPolicy, ApprovalRule, ReviewReason};
+pub use apply_options::{ApplyOptions, DeleteStrategy, NewFileCollision, RenameCollision, SymlinkTarget};
+pub use apply_progress::ApplyProgress;
+pub use apply_queue::{Applier, ApplierMetrics, ApplyHandle};
+pub use batch_diff::{BatchDiff, DirectiveChange};
+pub use blob_store::BlobStore;
+pub use complete_options::CompleteOptions;
+pub use confirmer::{ConfirmDecision, Confirmer};
+pub use content_map::{apply_file_changes_to_map, apply_file_changes_to_map_with_options};
+pub use corpus_stats::{CorpusStats, ScenarioOutcome, TierHistogram, corpus_stats};
+pub use detect_format::{DetectedFormat, detect_change_format};
+pub use diff_stats::{DiffStats, diff_stats};
+pub use edit_session::{EditSession, FileDelta};
 pub use error::*;
+pub use excerpt::{ExcerptLimits, truncate_excerpt};
 pub use extract::*;
+pub use extract_apply::{ExtractApplyOptions, ExtractApplyReport, extract_and_apply};
+pub use extract_options::ExtractOptions;
+pub use extract_report::{ExtractReport, ExtrudedKind, ExtrudedSegment};
+pub use feedback_templates::{FEEDBACK_TEMPLATE_KEYS, FeedbackTemplates};
 pub use file_changes::*;
 pub use file_directives::*;
+pub use file_insert::InsertPosition;
 pub use files_context::load_files_context;
-pub use patch_completer::{MatchTier, has_actionable_hunks, has_tilde_ranges, split_raw_hunks};
+pub use global_replace::{DEFAULT_MAX_MATCHES, ReplaceCount};
+pub use guard_policy::GuardPolicy;
+pub use lint::{LintFinding, LintKind, lint_file_changes};
+pub use make_patch::{make_file_patch, make_patch};
+pub use materializer::Materializer;
+pub use patch_completer::{
+	LenientLine, LineIndex, MatchTier, complete_with_line_index, context_similarity, has_actionable_hunks, has_tilde_ranges,
+	split_raw_hunks, strip_hunk_annotations, unescape_patch_body,
+};
+pub use post_apply_validator::PostApplyValidator;
+pub use provenance::{LineOrigin, LineProvenance};
+pub use redactor::Redactor;
+pub use run_summary::run_summary_markdown;
+pub use span_patch::line_hash;
+pub use tag_parser::TagParser;
+pub use temp_workspace::{TempWorkspace, apply_in_temp_workspace};
+pub use text_apply::{AppliedText, TextApplyReport, complete_and_apply};
+pub use verify::{VerifyItem, VerifyReport, VerifyState, resume_apply, verify_applied};
 
 // -- feature prompt
 #[cfg(feature = "prompt")]
@@ -29,10 +115,30 @@ mod prompt;
 #[cfg(feature = "prompt")]
 pub use prompt::prompt_file_changes;
 
+// -- feature async
+#[cfg(feature = "async")]
+mod async_applier;
+#[cfg(feature = "async")]
+pub use async_applier::apply_file_changes_async;
+
+// -- feature git
+#[cfg(feature = "git")]
+mod git_stage;
+#[cfg(feature = "git")]
+pub use git_stage::{GitStageOptions, GitStageReport, git_stage_applied};
+
+// -- feature object-store
+#[cfg(feature = "object-store")]
+mod object_store;
+#[cfg(feature = "object-store")]
+pub use object_store::{ObjectStore, apply_file_changes_to_object_store};
+
 #[cfg(any(test, feature = "test-support"))]
 pub mod for_test {
-	pub use crate::applier::apply_patch_incremental;
-	pub use crate::patch_completer::{complete, has_actionable_hunks, has_tilde_ranges, split_raw_hunks};
+	pub use crate::applier::{apply_patch_incremental, apply_patch_incremental_with_options};
+	pub use crate::patch_completer::{
+		complete, complete_with_options, has_actionable_hunks, has_tilde_ranges, split_raw_hunks,
+	};
 }
 
 // endregion: --- Modules
@@ -0,0 +1,107 @@
+/// Line prefixes (after the leading whitespace) of the comment lines a license header is made of.
+const COMMENT_PREFIXES: &[&str] = &["//", "/*", "*", "#", "--", "<!--", "-->", ";", "%"];
+
+/// Words (lowercase) identifying a comment block as a license header.
+const LICENSE_KEYWORDS: &[&str] = &["license", "licence", "copyright", "spdx-license-identifier"];
+
+/// Returns `new_content` with the license header of `existing` re-prepended, when `existing` begins with a license
+/// header (a leading comment block mentioning a license or a copyright) and `new_content` does not.
+///
+/// A leading shebang line is not part of the header, and the header is inserted after the shebang of `new_content`.
+pub(crate) fn with_license_header(existing: &str, new_content: &str) -> Option<String> {
+	let header = license_header(existing)?;
+	if license_header(new_content).is_some() {
+		return None;
+	}
+
+	let shebang_len = shebang_len(new_content);
+	let (shebang, rest) = new_content.split_at(shebang_len);
+	Some(format!("{shebang}{header}{rest}"))
+}
+
+// region:    --- Support
+
+/// The leading license header of `content` (with its line endings, and the blank lines following it).
+fn license_header(content: &str) -> Option<&str> {
+	let start = shebang_len(content);
+	let mut end = start;
+	let mut in_comments = true;
+	for line in content[start..].split_inclusive('\n') {
+		let trimmed = line.trim();
+		let is_comment = COMMENT_PREFIXES.iter().any(|prefix| trimmed.starts_with(prefix));
+		if in_comments && is_comment {
+			end += line.len();
+		} else if end > start && trimmed.is_empty() {
+			in_comments = false;
+			end += line.len();
+		} else {
+			break;
+		}
+	}
+
+	let header = &content[start..end];
+	let lower = header.to_lowercase();
+	LICENSE_KEYWORDS
+		.iter()
+		.any(|keyword| lower.contains(keyword))
+		.then_some(header)
+}
+
+/// The length of the shebang line of `content` (with its line ending), or 0.
+fn shebang_len(content: &str) -> usize {
+	if content.starts_with("#!") {
+		content.find('\n').map(|idx| idx + 1).unwrap_or(content.len())
+	} else {
+		0
+	}
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_license_header_with_license_header() -> Result<()> {
+		// -- Setup & Fixtures
+		let existing = "// Copyright 2024 Acme\n// SPDX-License-Identifier: MIT\n\nfn main() {}\n";
+
+		// -- Exec & Check
+		assert_eq!(
+			with_license_header(existing, "fn main() { run() }\n").as_deref(),
+			Some("// Copyright 2024 Acme\n// SPDX-License-Identifier: MIT\n\nfn main() { run() }\n")
+		);
+		assert_eq!(
+			with_license_header(existing, "// Copyright 2025 Acme\nfn x() {}\n"),
+			None
+		);
+		assert_eq!(with_license_header("// Some notes\nfn a() {}\n", "fn b() {}\n"), None);
+		assert_eq!(with_license_header("fn a() {}\n", "fn b() {}\n"), None);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_license_header_with_license_header_shebang() -> Result<()> {
+		// -- Setup & Fixtures
+		let existing = "#!/usr/bin/env python3\n# Licensed under the Apache License 2.0\nprint(1)\n";
+
+		// -- Exec
+		let content = with_license_header(existing, "#!/usr/bin/env python3\nprint(2)\n");
+
+		// -- Check
+		assert_eq!(
+			content.as_deref(),
+			Some("#!/usr/bin/env python3\n# Licensed under the Apache License 2.0\nprint(2)\n")
+		);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
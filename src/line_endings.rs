@@ -0,0 +1,143 @@
+use diffy::Line;
+use std::borrow::Cow;
+
+/// The UTF-8 byte order mark.
+const BOM: char = '\u{feff}';
+
+/// Returns `content` without its leading byte order mark, if any.
+pub(crate) fn strip_bom(content: &str) -> &str {
+	content.strip_prefix(BOM).unwrap_or(content)
+}
+
+/// Returns `new_content` (as produced from the LF-normalized `original`) with the line endings and the BOM of `original`.
+///
+/// The lines `new_content` keeps from `original` get their original line ending back (so mixed-ending files stay as is),
+/// and the other lines get the dominant line ending of `original`.
+///
+/// Note: A BOM of `new_content` is only dropped when `original` has one (restored once), otherwise it comes from the
+///       directive content and is kept.
+pub(crate) fn restore_line_endings<'a>(original: &str, new_content: &'a str) -> Cow<'a, str> {
+	let has_bom = original.starts_with(BOM);
+	let original = strip_bom(original);
+	let new_content = if has_bom { strip_bom(new_content) } else { new_content };
+	if !has_bom && !original.contains("\r\n") {
+		return Cow::Borrowed(new_content);
+	}
+
+	// -- The ending of each original line, and the dominant one
+	let endings: Vec<&str> = original
+		.split_inclusive('\n')
+		.map(|line| {
+			if line.ends_with("\r\n") {
+				"\r\n"
+			} else if line.ends_with('\n') {
+				"\n"
+			} else {
+				""
+			}
+		})
+		.collect();
+	let crlf_count = endings.iter().filter(|e| **e == "\r\n").count();
+	let lf_count = endings.iter().filter(|e| **e == "\n").count();
+	let dominant = if crlf_count > lf_count { "\r\n" } else { "\n" };
+
+	// -- The original line each new line is kept from, if any
+	let original_lf = original.replace("\r\n", "\n");
+	let patch = diffy::create_patch(&original_lf, new_content);
+	let mut kept_from: Vec<Option<usize>> = Vec::new();
+	let mut old_idx = 0;
+	for hunk in patch.hunks() {
+		let range = hunk.old_range();
+		let hunk_start = if range.is_empty() {
+			range.start()
+		} else {
+			range.start() - 1
+		};
+		while old_idx < hunk_start {
+			kept_from.push(Some(old_idx));
+			old_idx += 1;
+		}
+		for line in hunk.lines() {
+			match line {
+				Line::Context(_) => {
+					kept_from.push(Some(old_idx));
+					old_idx += 1;
+				}
+				Line::Delete(_) => old_idx += 1,
+				Line::Insert(_) => kept_from.push(None),
+			}
+		}
+	}
+
+	let mut out = String::with_capacity(new_content.len() + new_content.len() / 20 + BOM.len_utf8());
+	if has_bom {
+		out.push(BOM);
+	}
+	for (idx, line) in new_content.split_inclusive('\n').enumerate() {
+		let Some(text) = line.strip_suffix('\n') else {
+			out.push_str(line);
+			continue;
+		};
+		// Lines past the last hunk are kept from the original in order.
+		let kept = match kept_from.get(idx) {
+			Some(kept) => *kept,
+			None => Some(old_idx + idx - kept_from.len()),
+		};
+		let ending = match kept.and_then(|i| endings.get(i)) {
+			Some(ending) if !ending.is_empty() => ending,
+			_ => dominant,
+		};
+		out.push_str(text);
+		out.push_str(ending);
+	}
+
+	Cow::Owned(out)
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_line_endings_restore_line_endings_crlf() -> Result<()> {
+		// -- Exec & Check
+		assert_eq!(restore_line_endings("a\r\nb\r\n", "a\nx\nb\n"), "a\r\nx\r\nb\r\n");
+		assert_eq!(restore_line_endings("a\nb\n", "a\nx\nb\n"), "a\nx\nb\n");
+		assert_eq!(
+			restore_line_endings("\u{feff}a\r\nb", "a\nb\nc\n"),
+			"\u{feff}a\r\nb\r\nc\r\n"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_line_endings_restore_line_endings_keeps_new_bom() -> Result<()> {
+		// -- Exec & Check
+		assert_eq!(restore_line_endings("a\n", "\u{feff}a\nb\n"), "\u{feff}a\nb\n");
+		assert_eq!(restore_line_endings("a\r\n", "\u{feff}a\n"), "\u{feff}a\r\n");
+		assert_eq!(restore_line_endings("\u{feff}a\n", "\u{feff}b\n"), "\u{feff}b\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_line_endings_restore_line_endings_mixed() -> Result<()> {
+		// -- Setup & Fixtures
+		let original = "one\r\ntwo\nthree\r\nfour\r\nfive\n";
+
+		// -- Exec
+		let content = restore_line_endings(original, "one\ntwo\nTHREE\nfour\nfive\nsix\n");
+
+		// -- Check
+		assert_eq!(content, "one\r\ntwo\nTHREE\r\nfour\r\nfive\nsix\r\n");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
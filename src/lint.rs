@@ -0,0 +1,214 @@
+use crate::batch_diff::{directive_key, same_payload};
+use crate::{FileChanges, FileDirective, split_raw_hunks};
+use derive_more::Display;
+
+/// Minimum number of removed lines for a `FILE_PATCH` to be flagged as a large deletion.
+const LARGE_DELETION_MIN_LINES: usize = 200;
+
+/// The kind of suspicious pattern flagged by `lint_file_changes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum LintKind {
+	/// A hunk whose added lines are identical to its removed lines.
+	NoopHunk,
+	/// A `FILE_PATCH` without any added or removed line.
+	EmptyPatch,
+	/// An absolute path (e.g., `/etc/hosts`, `C:\x`).
+	AbsolutePath,
+	/// A path with a `..` segment.
+	ParentDirSegment,
+	/// A `FILE_PATCH` removing at least `LARGE_DELETION_MIN_LINES` lines.
+	LargeDeletion,
+	/// A directive identical to a previous one.
+	DuplicateDirective,
+}
+
+/// A suspicious pattern found in a directive.
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+#[display("[{kind}] directive #{}: {message}", directive_index + 1)]
+pub struct LintFinding {
+	/// The 0-based index of the directive in the `FileChanges`.
+	pub directive_index: usize,
+	pub kind: LintKind,
+	pub message: String,
+}
+
+/// Flags suspicious patterns of the model output before applying it (no file system access).
+///
+/// Findings are not errors, the directives may still apply, they are a cheap pre-screen of the output quality.
+pub fn lint_file_changes(changes: &FileChanges) -> Vec<LintFinding> {
+	let mut findings: Vec<LintFinding> = Vec::new();
+	let directives: Vec<&FileDirective> = changes.iter().collect();
+
+	for (idx, directive) in directives.iter().enumerate() {
+		let mut push = |kind: LintKind, message: String| {
+			findings.push(LintFinding {
+				directive_index: idx,
+				kind,
+				message,
+			})
+		};
+
+		// -- Paths
+		for path in directive_paths(directive) {
+			if is_absolute_path(path) {
+				push(LintKind::AbsolutePath, format!("absolute path '{path}'"));
+			}
+			if path.split(['/', '\\']).any(|segment| segment == "..") {
+				push(LintKind::ParentDirSegment, format!("path '{path}' has a '..' segment"));
+			}
+		}
+
+		// -- Patch content
+		if let FileDirective::Patch { file_path, content, .. } = directive {
+			let mut total_added = 0;
+			let mut total_removed = 0;
+			for (hunk_idx, hunk) in split_raw_hunks(&content.content).iter().enumerate() {
+				let body = hunk.lines().skip(1);
+				let added: Vec<&str> = body.clone().filter_map(|l| l.strip_prefix('+')).collect();
+				let removed: Vec<&str> = body.filter_map(|l| l.strip_prefix('-')).collect();
+				if !added.is_empty() && added == removed {
+					push(
+						LintKind::NoopHunk,
+						format!("hunk #{} of '{file_path}' adds the lines it removes", hunk_idx + 1),
+					);
+				}
+				total_added += added.len();
+				total_removed += removed.len();
+			}
+			if total_added == 0 && total_removed == 0 {
+				push(
+					LintKind::EmptyPatch,
+					format!("patch of '{file_path}' has no added or removed lines"),
+				);
+			}
+			if total_removed >= LARGE_DELETION_MIN_LINES {
+				push(
+					LintKind::LargeDeletion,
+					format!("patch of '{file_path}' removes {total_removed} lines"),
+				);
+			}
+		}
+
+		// -- Duplicates
+		let key = directive_key(directive);
+		if directives[..idx]
+			.iter()
+			.any(|prev| directive_key(prev) == key && same_payload(prev, directive))
+		{
+			push(
+				LintKind::DuplicateDirective,
+				format!("duplicate {} directive for '{}'", directive.kind(), key.1),
+			);
+		}
+	}
+
+	findings
+}
+
+// region:    --- Support
+
+/// The paths a directive touches (both sides for copy/rename, the glob of a global replace).
+pub(crate) fn directive_paths(directive: &FileDirective) -> Vec<&str> {
+	match directive {
+		FileDirective::New { file_path, .. }
+		| FileDirective::Patch { file_path, .. }
+		| FileDirective::Append { file_path, .. }
+		| FileDirective::SpanPatch { file_path, .. }
+		| FileDirective::Insert { file_path, .. }
+		| FileDirective::Delete { file_path } => vec![file_path],
+		FileDirective::GlobalReplace { glob, .. } => vec![glob],
+		FileDirective::Copy { from_path, to_path } | FileDirective::Rename { from_path, to_path } => {
+			vec![from_path, to_path]
+		}
+		FileDirective::Fail { .. } => Vec::new(),
+	}
+}
+
+/// `true` for `/x`, `\x`, `~/x`, or a Windows drive path (`C:\x`, `C:/x`).
+fn is_absolute_path(path: &str) -> bool {
+	let bytes = path.as_bytes();
+	path.starts_with(['/', '\\'])
+		|| path.starts_with("~/")
+		|| (bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && matches!(bytes[2], b'/' | b'\\'))
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::extract_file_changes;
+
+	#[test]
+	fn test_lint_file_changes_findings() -> Result<()> {
+		// -- Setup & Fixtures
+		let input = r#"<FILE_CHANGES>
+<FILE_PATCH file_path="src/a.rs">
+@@
+ fn a() {
+-    1
++    1
+ }
+</FILE_PATCH>
+<FILE_PATCH file_path="src/b.rs">
+@@
+ fn b() {
+ }
+</FILE_PATCH>
+<FILE_DELETE file_path="/etc/hosts" />
+<FILE_RENAME from_path="src/c.rs" to_path="../c.rs" />
+<FILE_DELETE file_path="/etc/hosts" />
+<FILE_NEW file_path="ok.txt">
+fine
+</FILE_NEW>
+</FILE_CHANGES>"#;
+		let (changes, _) = extract_file_changes(input, false)?;
+
+		// -- Exec
+		let findings = lint_file_changes(&changes);
+
+		// -- Check
+		let kinds: Vec<(usize, LintKind)> = findings.iter().map(|f| (f.directive_index, f.kind)).collect();
+		assert_eq!(
+			kinds,
+			vec![
+				(0, LintKind::NoopHunk),
+				(1, LintKind::EmptyPatch),
+				(2, LintKind::AbsolutePath),
+				(3, LintKind::ParentDirSegment),
+				(4, LintKind::AbsolutePath),
+				(4, LintKind::DuplicateDirective),
+			]
+		);
+		assert_eq!(
+			findings[0].to_string(),
+			"[NoopHunk] directive #1: hunk #1 of 'src/a.rs' adds the lines it removes"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_lint_file_changes_large_deletion() -> Result<()> {
+		// -- Setup & Fixtures
+		let removed: String = (0..LARGE_DELETION_MIN_LINES).map(|i| format!("-line {i}\n")).collect();
+		let input =
+			format!("<FILE_CHANGES>\n<FILE_PATCH file_path=\"big.txt\">\n@@\n{removed}</FILE_PATCH>\n</FILE_CHANGES>");
+		let (changes, _) = extract_file_changes(&input, false)?;
+
+		// -- Exec
+		let findings = lint_file_changes(&changes);
+
+		// -- Check
+		assert_eq!(findings.len(), 1);
+		assert_eq!(findings[0].kind, LintKind::LargeDeletion);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,71 @@
+/// Creates a unified diff from `old` to `new` with `context_lines` lines of context around each change.
+///
+/// Only the hunks are returned (no `---` / `+++` file header), which is the body expected by `FILE_PATCH`.
+/// Returns an empty string if `old` and `new` are equal.
+pub fn make_patch(old: &str, new: &str, context_lines: usize) -> String {
+	let patch = diffy::DiffOptions::new()
+		.set_context_len(context_lines)
+		.create_patch(old, new)
+		.to_string();
+
+	match patch.find("@@") {
+		Some(idx) => patch[idx..].to_string(),
+		None => String::new(),
+	}
+}
+
+/// Same as `make_patch`, but prefixed with the git-style `--- a/{file_path}` / `+++ b/{file_path}` file header.
+/// Returns an empty string if `old` and `new` are equal.
+pub fn make_file_patch(file_path: &str, old: &str, new: &str, context_lines: usize) -> String {
+	let hunks = make_patch(old, new, context_lines);
+	if hunks.is_empty() {
+		return hunks;
+	}
+	format!("--- a/{file_path}\n+++ b/{file_path}\n{hunks}")
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::applier::apply_patch_incremental;
+
+	#[test]
+	fn test_make_patch_context_lines_and_round_trip() -> Result<()> {
+		// -- Setup & Fixtures
+		let old = "a\nb\nc\nd\ne\nf\n";
+		let new = "a\nb\nc\nD\ne\nf\n";
+
+		// -- Exec
+		let patch = make_patch(old, new, 1);
+
+		// -- Check
+		assert_eq!(patch, "@@ -3,3 +3,3 @@\n c\n-d\n+D\n e\n");
+		let data = apply_patch_incremental(old, &patch)?;
+		assert_eq!(data.new_content, new);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_make_patch_file_header_and_no_change() -> Result<()> {
+		// -- Setup & Fixtures
+		let old = "one\ntwo\n";
+		let new = "one\nthree\n";
+
+		// -- Exec
+		let patch = make_file_patch("src/main.rs", old, new, 3);
+
+		// -- Check
+		assert!(patch.starts_with("--- a/src/main.rs\n+++ b/src/main.rs\n@@ "));
+		assert_eq!(make_patch(old, old, 3), "");
+		assert_eq!(make_file_patch("src/main.rs", old, old, 3), "");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,54 @@
+use crate::{Error, FileDirective, Result};
+use simple_fs::SPath;
+use std::fmt;
+use std::sync::Arc;
+
+type MaterializeFn = dyn Fn(&SPath, &str) -> Result<()> + Send + Sync;
+
+/// A hook materializing a path not present locally before a directive reads it, e.g., to hydrate it
+/// from a sparse checkout (`git sparse-checkout add`) or a virtualized file system (VFS for Git).
+///
+/// The hook gets the base dir and the path relative to it (`/` separated), and is only invoked for a missing path
+/// passing the security checks. A path still missing after the hook fails as before (e.g., `ApplyPathNotFound`).
+///
+/// See `ApplyOptions::materializer`.
+#[derive(Clone)]
+pub struct Materializer {
+	materialize_fn: Arc<MaterializeFn>,
+}
+
+impl Materializer {
+	/// The `materialize_fn` makes the `rel_path` (relative to the base dir) present locally.
+	pub fn new(materialize_fn: impl Fn(&SPath, &str) -> Result<()> + Send + Sync + 'static) -> Self {
+		Self {
+			materialize_fn: Arc::new(materialize_fn),
+		}
+	}
+
+	/// Materializes the path, the hook error wrapped in an `Error::ApplyMaterializeFailed`.
+	pub fn materialize(&self, base_dir: &SPath, rel_path: &str) -> Result<()> {
+		(self.materialize_fn)(base_dir, rel_path)
+			.map_err(|err| Error::apply_materialize_failed(rel_path, err.to_string()))
+	}
+}
+
+impl fmt::Debug for Materializer {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str("Materializer")
+	}
+}
+
+/// The paths a directive reads before changing them (the paths to materialize when missing).
+///
+/// A `FILE_NEW` target, or a copy / rename destination, is written whole, so never materialized.
+pub(crate) fn read_paths(directive: &FileDirective) -> Vec<&str> {
+	match directive {
+		FileDirective::Patch { file_path, .. }
+		| FileDirective::Append { file_path, .. }
+		| FileDirective::SpanPatch { file_path, .. }
+		| FileDirective::Insert { file_path, .. }
+		| FileDirective::Delete { file_path } => vec![file_path.as_str()],
+		FileDirective::Copy { from_path, .. } | FileDirective::Rename { from_path, .. } => vec![from_path.as_str()],
+		FileDirective::New { .. } | FileDirective::GlobalReplace { .. } | FileDirective::Fail { .. } => Vec::new(),
+	}
+}
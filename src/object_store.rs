@@ -0,0 +1,262 @@
+use crate::applier::apply_with_store;
+use crate::apply_store::ApplyStore;
+use crate::{ApplyChangesStatus, ApplyOptions, Error, FileChanges, Result, SecurityPolicy};
+use simple_fs::SPath;
+
+/// The virtual base dir the object keys are mapped under, so the applier path guards still apply
+/// (e.g., a `../x` or `/etc/x` directive path is rejected as outside of the workspace).
+const OBJECT_STORE_ROOT: &str = "/object-store";
+
+/// A minimal object storage (S3/GCS-style) holding one workspace, for `apply_file_changes_to_object_store`.
+///
+/// Keys are relative file paths with `/` separators (e.g., `src/main.rs`). There are no directories,
+/// a "directory" is the set of keys sharing its prefix. Implementations typically add their own bucket
+/// and workspace prefix, and block on their async client.
+pub trait ObjectStore {
+	/// Returns the object bytes, or `None` if the key does not exist.
+	fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+	/// Creates or replaces the object.
+	fn put(&mut self, key: &str, content: &[u8]) -> Result<()>;
+
+	/// Deletes the object (deleting a missing key is not an error).
+	fn delete(&mut self, key: &str) -> Result<()>;
+
+	/// Lists the keys starting with `prefix` (`""` lists all the keys).
+	fn list(&self, prefix: &str) -> Result<Vec<String>>;
+}
+
+/// Same as `apply_file_changes_with_options`, but applied to the workspace stored in `store`
+/// instead of the local file system.
+///
+/// The default strict `SecurityPolicy` is used: every directive path must stay within the workspace.
+pub fn apply_file_changes_to_object_store(
+	store: &mut impl ObjectStore,
+	file_changes: FileChanges,
+	options: impl Into<ApplyOptions>,
+) -> Result<ApplyChangesStatus> {
+	let options: ApplyOptions = options.into();
+	let base_dir = SPath::new(OBJECT_STORE_ROOT);
+
+	Ok(apply_with_store(
+		&mut ObjectApplyStore { store },
+		&base_dir,
+		file_changes,
+		&SecurityPolicy::default(),
+		&options,
+		&mut (),
+	))
+}
+
+// region:    --- ObjectApplyStore
+
+/// Adapts an `ObjectStore` to the applier operations.
+struct ObjectApplyStore<'a, O: ObjectStore> {
+	store: &'a mut O,
+}
+
+impl<O: ObjectStore> ObjectApplyStore<'_, O> {
+	fn key(path: &SPath) -> Result<String> {
+		path.as_str()
+			.strip_prefix(OBJECT_STORE_ROOT)
+			.and_then(|rest| rest.strip_prefix('/'))
+			.map(|key| key.to_string())
+			.ok_or_else(|| Error::security_violation(path.to_string(), OBJECT_STORE_ROOT))
+	}
+
+	/// The keys under the `key` "directory".
+	fn keys_under(&self, key: &str) -> Result<Vec<String>> {
+		self.store.list(&format!("{key}/"))
+	}
+}
+
+impl<O: ObjectStore> ApplyStore for ObjectApplyStore<'_, O> {
+	fn exists(&self, path: &SPath) -> bool {
+		Self::key(path).is_ok_and(|key| matches!(self.store.get(&key), Ok(Some(_))) || self.is_dir(path))
+	}
+
+	fn is_dir(&self, path: &SPath) -> bool {
+		Self::key(path).is_ok_and(|key| self.keys_under(&key).is_ok_and(|keys| !keys.is_empty()))
+	}
+
+	fn read_to_string(&self, path: &SPath) -> Result<String> {
+		let bytes = self.read_bytes(path)?;
+		String::from_utf8(bytes).map_err(|err| Error::io_read_file(path.to_string(), err))
+	}
+
+	fn read_bytes(&self, path: &SPath) -> Result<Vec<u8>> {
+		self.store
+			.get(&Self::key(path)?)?
+			.ok_or_else(|| Error::apply_path_not_found("read", path.to_string()))
+	}
+
+	fn write(&mut self, path: &SPath, content: &[u8]) -> Result<()> {
+		self.store.put(&Self::key(path)?, content)
+	}
+
+	fn rename(&mut self, from: &SPath, to: &SPath) -> Result<()> {
+		let from_key = Self::key(from)?;
+		let to_key = Self::key(to)?;
+
+		// -- A "directory" is renamed object by object
+		let keys = match self.store.get(&from_key)? {
+			Some(_) => vec![from_key.clone()],
+			None => self.keys_under(&from_key)?,
+		};
+		if keys.is_empty() {
+			return Err(Error::apply_path_not_found("rename", from.to_string()));
+		}
+
+		for key in keys {
+			let content = self
+				.store
+				.get(&key)?
+				.ok_or_else(|| Error::apply_path_not_found("rename", key.clone()))?;
+			let new_key = format!("{to_key}{}", &key[from_key.len()..]);
+			self.store.put(&new_key, &content)?;
+			self.store.delete(&key)?;
+		}
+		Ok(())
+	}
+
+	fn delete(&mut self, path: &SPath) -> Result<()> {
+		let key = Self::key(path)?;
+		if self.store.get(&key)?.is_some() {
+			return self.store.delete(&key);
+		}
+		for key in self.keys_under(&key)? {
+			self.store.delete(&key)?;
+		}
+		Ok(())
+	}
+
+	fn list_rel_paths(&self, base_dir: &SPath) -> Result<Vec<String>> {
+		let prefix = match Self::key(base_dir) {
+			Ok(key) => format!("{key}/"),
+			Err(_) => String::new(),
+		};
+		let mut rel_paths: Vec<String> = self
+			.store
+			.list(&prefix)?
+			.into_iter()
+			.filter_map(|key| key.strip_prefix(&prefix).map(|p| p.to_string()))
+			.collect();
+		rel_paths.sort();
+		Ok(rel_paths)
+	}
+
+	fn is_virtual(&self) -> bool {
+		true
+	}
+}
+
+// endregion: --- ObjectApplyStore
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::extract_file_changes;
+	use std::collections::BTreeMap;
+
+	#[derive(Default)]
+	struct MemObjects(BTreeMap<String, Vec<u8>>);
+
+	impl ObjectStore for MemObjects {
+		fn get(&self, key: &str) -> crate::Result<Option<Vec<u8>>> {
+			Ok(self.0.get(key).cloned())
+		}
+
+		fn put(&mut self, key: &str, content: &[u8]) -> crate::Result<()> {
+			self.0.insert(key.to_string(), content.to_vec());
+			Ok(())
+		}
+
+		fn delete(&mut self, key: &str) -> crate::Result<()> {
+			self.0.remove(key);
+			Ok(())
+		}
+
+		fn list(&self, prefix: &str) -> crate::Result<Vec<String>> {
+			Ok(self.0.keys().filter(|k| k.starts_with(prefix)).cloned().collect())
+		}
+	}
+
+	#[test]
+	fn test_object_store_apply_file_changes() -> Result<()> {
+		// -- Setup & Fixtures
+		let mut store = MemObjects::default();
+		store.put("src/main.rs", b"fn main() {\n    println!(\"hi\");\n}\n")?;
+		store.put("docs/a.md", b"a")?;
+		store.put("docs/b.md", b"b")?;
+		let input = r#"
+<FILE_CHANGES>
+<FILE_PATCH file_path="src/main.rs">
+@@
+ fn main() {
+-    println!("hi");
++    println!("hello");
+ }
+</FILE_PATCH>
+<FILE_NEW file_path="README.md">
+# Title
+</FILE_NEW>
+<FILE_RENAME from_path="docs" to_path="guide" />
+<FILE_NEW file_path="../outside.txt">
+x
+</FILE_NEW>
+</FILE_CHANGES>
+"#;
+		let (changes, _) = extract_file_changes(input, false)?;
+
+		// -- Exec
+		let status = apply_file_changes_to_object_store(&mut store, changes, None)?;
+
+		// -- Check
+		let successes: Vec<bool> = status.items.iter().map(|i| i.success()).collect();
+		assert_eq!(successes, vec![true, true, true, false]);
+		let msg = status.items[3].error_msg().unwrap_or_default();
+		assert!(msg.contains("Security violation"), "{msg}");
+		assert_eq!(
+			store.0.keys().map(String::as_str).collect::<Vec<_>>(),
+			vec!["README.md", "guide/a.md", "guide/b.md", "src/main.rs"]
+		);
+		assert_eq!(
+			store.get("src/main.rs")?.ok_or("should have main.rs")?,
+			b"fn main() {\n    println!(\"hello\");\n}\n"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_object_store_validate_all_first_writes_nothing() -> Result<()> {
+		// -- Setup & Fixtures
+		let mut store = MemObjects::default();
+		store.put("a.txt", b"one\n")?;
+		let input = r#"
+<FILE_CHANGES>
+<FILE_NEW file_path="b.txt">
+two
+</FILE_NEW>
+<FILE_DELETE file_path="missing.txt" />
+</FILE_CHANGES>
+"#;
+		let (changes, _) = extract_file_changes(input, false)?;
+
+		// -- Exec
+		let status =
+			apply_file_changes_to_object_store(&mut store, changes, ApplyOptions::default().with_validate_all_first())?;
+
+		// -- Check
+		assert!(status.items.iter().all(|i| !i.success()));
+		assert_eq!(store.0.keys().map(String::as_str).collect::<Vec<_>>(), vec!["a.txt"]);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,21 @@
+use super::matchers::line_matches;
+use super::types::MatchTier;
+use crate::CompleteOptions;
+
+/// Returns the 0-based index of the first line of `lines` matching the `anchor` line, with its match tier.
+///
+/// Each tier is tried over the whole content before the next one (Strict, then Resilient, then Fuzzy),
+/// so an exact match later in the file wins over a lenient one earlier. A blank anchor matches nothing.
+pub(crate) fn find_anchor_line(lines: &[&str], anchor: &str, options: &CompleteOptions) -> Option<(usize, MatchTier)> {
+	if anchor.trim().is_empty() {
+		return None;
+	}
+	[MatchTier::Strict, MatchTier::Resilient, MatchTier::Fuzzy]
+		.into_iter()
+		.find_map(|tier| {
+			lines
+				.iter()
+				.position(|line| line_matches(line, anchor, tier, options))
+				.map(|idx| (idx, tier))
+		})
+}
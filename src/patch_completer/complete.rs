@@ -1,11 +1,12 @@
-use super::MAX_PROXIMITY_FOR_LENIENT;
-use super::matchers::{has_uniform_indent_delta, line_matches, score_candidate};
+use super::line_index::LineIndex;
+use super::matchers::{has_uniform_indent_delta, is_truncation_match, line_matches, score_candidate};
 use super::parse::{
-	collect_raw_hunks, collect_raw_hunks_sanitized, is_wrapper_meta_line, sanitize_wrapper_meta_lines,
-	validate_and_parse_tilde_ranges,
+	collect_raw_hunks, collect_raw_hunks_sanitized, is_wrapper_meta_line, line_body, sanitize_wrapper_meta_lines,
+	strip_hunk_annotations, strip_line_number_prefixes, validate_and_parse_tilde_ranges,
 };
-use super::types::{AdjacentHints, CandidateMatch, HunkBounds, MatchTier, TildeRange};
-use crate::{Error, Result};
+use super::types::{AdjacentHints, CandidateMatch, CompletedPatch, HunkBounds, LenientLine, MatchTier, TildeRange};
+use super::{HUNK_ANNOTATION_PREFIX, MAX_PROXIMITY_FOR_LENIENT};
+use crate::{CompleteOptions, Error, Result};
 use std::borrow::Cow;
 
 /// Completes a raw simplified patch (numberless `@@` hunks) into a fully valid unified diff
@@ -23,22 +24,67 @@ use std::borrow::Cow;
 ///   skipped; blank context lines at/beyond EOF are converted to additions to preserve
 ///   spacing; context that extends past the file is treated as overhang and dropped;
 ///   and hunks with no context/removal lines are treated as appends to the end of the file.
+/// - Strips the `42: ` / `42| ` line-number prefixes echoed on most lines of a hunk.
+/// - Ignores the `# udiffx: ...` annotation lines of an annotated completed patch.
+#[cfg(any(test, feature = "test-support"))]
 pub fn complete(original_content: &str, patch_raw: &str) -> Result<(String, Option<MatchTier>)> {
+	complete_with_options(original_content, patch_raw, &CompleteOptions::default())
+}
+
+/// Same as `complete`, with the lenient matching (and the hunk annotations) controlled by `options`.
+#[cfg(any(test, feature = "test-support"))]
+pub fn complete_with_options(
+	original_content: &str,
+	patch_raw: &str,
+	options: &CompleteOptions,
+) -> Result<(String, Option<MatchTier>)> {
+	let completed = complete_with_report(&LineIndex::new(original_content), patch_raw, options)?;
+	Ok((completed.patch, completed.tier))
+}
+
+/// Same as `complete_with_options`, with the original content given as a prebuilt `LineIndex`
+/// (no I/O, and no copy of the content), to complete many patches against the same content.
+pub fn complete_with_line_index(
+	orig_index: &LineIndex,
+	patch_raw: &str,
+	options: &CompleteOptions,
+) -> Result<(String, Option<MatchTier>)> {
+	let completed = complete_with_report(orig_index, patch_raw, options)?;
+	Ok((completed.patch, completed.tier))
+}
+
+/// Same as `complete_with_line_index`, also reporting whether the truncation matching was used.
+pub(crate) fn complete_with_report(
+	orig_index: &LineIndex,
+	patch_raw: &str,
+	options: &CompleteOptions,
+) -> Result<CompletedPatch> {
 	// Normalize CRLF to LF to prevent subtle mismatches with mixed line endings.
-	let original_content: Cow<'_, str> = if original_content.contains("\r\n") {
-		Cow::Owned(original_content.replace("\r\n", "\n"))
-	} else {
-		Cow::Borrowed(original_content)
-	};
+	// Note: The original lines need none, `str::lines` already strips the `\r\n` endings.
 	let patch_raw: Cow<'_, str> = if patch_raw.contains("\r\n") {
 		Cow::Owned(patch_raw.replace("\r\n", "\n"))
 	} else {
 		Cow::Borrowed(patch_raw)
 	};
+	let orig_lines = orig_index.lines();
+
+	// Strip the match annotations of a previously completed (annotated) patch.
+	let patch_raw: Cow<'_, str> = match strip_hunk_annotations(&patch_raw) {
+		Some(stripped) => Cow::Owned(stripped),
+		None => patch_raw,
+	};
+
+	// Strip the line-number prefixes echoed from numbered context (e.g., `+42: x`), when consistent.
+	let patch_raw: Cow<'_, str> = match strip_line_number_prefixes(&patch_raw, orig_index) {
+		Some(stripped) => Cow::Owned(stripped),
+		None => patch_raw,
+	};
 	let sanitized_patch_raw = sanitize_wrapper_meta_lines(&patch_raw);
 
-	let orig_lines: Vec<&str> = original_content.lines().collect();
 	let mut max_tier: Option<MatchTier> = None;
+	let mut truncation_matched = false;
+	let mut min_score: f64 = 1.;
+	let mut lenient_lines: Vec<LenientLine> = Vec::new();
 
 	// -- First pass: collect all hunk bodies as raw line slices using shared helper.
 	let mut raw_hunks = collect_raw_hunks(&patch_raw);
@@ -90,7 +136,7 @@ pub fn complete(original_content: &str, patch_raw: &str) -> Result<(String, Opti
 
 	// -- Pre-sort hunks by file position to handle out-of-order LLM output.
 	// Only reorder when hunks have confident (Strict) position estimates and are out of order.
-	let raw_hunks = presort_hunks_by_position(&orig_lines, raw_hunks);
+	let raw_hunks = presort_hunks_by_position(orig_lines, raw_hunks);
 
 	// Emit any non-hunk prefix lines (e.g. file headers)
 	for pline in &non_hunk_prefix {
@@ -103,7 +149,7 @@ pub fn complete(original_content: &str, patch_raw: &str) -> Result<(String, Opti
 		let raw_hints = build_adjacent_hints(&raw_hunks, hunk_idx);
 		let raw_hunk_lines = &raw_hunks[hunk_idx];
 
-		let hunk_bounds = match compute_hunk_bounds(&orig_lines, raw_hunk_lines, search_from, &raw_hints) {
+		let hunk_bounds = match compute_hunk_bounds(orig_lines, raw_hunk_lines, search_from, &raw_hints, options) {
 			Ok(bounds) => bounds,
 			Err(raw_err) => {
 				let Some(sanitized_raw_hunks) = &sanitized_raw_hunks else {
@@ -115,7 +161,7 @@ pub fn complete(original_content: &str, patch_raw: &str) -> Result<(String, Opti
 
 				let sanitized_hunk_lines = &sanitized_raw_hunks[hunk_idx];
 				let sanitized_hints = build_adjacent_hints(sanitized_raw_hunks, hunk_idx);
-				match compute_hunk_bounds(&orig_lines, sanitized_hunk_lines, search_from, &sanitized_hints) {
+				match compute_hunk_bounds(orig_lines, sanitized_hunk_lines, search_from, &sanitized_hints, options) {
 					Ok(bounds) => bounds,
 					Err(_) => return Err(raw_err),
 				}
@@ -130,11 +176,20 @@ pub fn complete(original_content: &str, patch_raw: &str) -> Result<(String, Opti
 		if let Some(t) = hunk_bounds.tier {
 			max_tier = Some(max_tier.map(|m| m.max(t)).unwrap_or(t));
 		}
+		truncation_matched |= hunk_bounds.truncation_matched;
+		min_score = min_score.min(hunk_bounds.score);
+		lenient_lines.extend(hunk_bounds.lenient_lines);
 
 		// Update state for next hunk
 		search_from = old_start + old_count.saturating_sub(1) - 1;
 		total_delta += new_count as isize - old_count as isize;
 
+		if options.annotate_hunks {
+			let annotation = hunk_annotation(hunk_idx + 1, old_start, hunk_bounds.tier, hunk_bounds.score);
+			completed_patch.push_str(&annotation);
+			completed_patch.push('\n');
+		}
+
 		// Standard Unified Diff: @@ -start,len +start,len @@
 		completed_patch.push_str(&format!("@@ -{old_start},{old_count} +{new_start},{new_count} @@\n"));
 		for h_line in final_hunk_lines {
@@ -148,10 +203,59 @@ pub fn complete(original_content: &str, patch_raw: &str) -> Result<(String, Opti
 	}
 
 	if raw_hunks.is_empty() && non_hunk_prefix.is_empty() {
-		return Ok((String::new(), None));
+		return Ok(CompletedPatch {
+			patch: String::new(),
+			tier: None,
+			truncation_matched: false,
+			score: 1.,
+			lenient_lines: Vec::new(),
+		});
+	}
+
+	Ok(CompletedPatch {
+		patch: completed_patch,
+		tier: max_tier,
+		truncation_matched,
+		score: min_score,
+		lenient_lines,
+	})
+}
+
+/// Matches a context-only hunk (see `CompleteOptions::assert_context_hunks`) in the original content, as a hunk
+/// removing its lines would be, returning its 1-based start line and match tier (no hunk being completed).
+pub(crate) fn match_context_hunk(
+	orig_index: &LineIndex,
+	raw_hunk: &str,
+	options: &CompleteOptions,
+) -> Result<(usize, Option<MatchTier>)> {
+	// Note: An empty content would "match" as a bootstrapped file.
+	if orig_index.lines().iter().all(|line| line.trim().is_empty()) {
+		return Err(Error::custom("Context not found in an empty content"));
 	}
+	let removal_hunk: String = raw_hunk
+		.lines()
+		.map(|line| match line.strip_prefix(' ') {
+			Some(body) => format!("-{body}\n"),
+			None => format!("{line}\n"),
+		})
+		.collect();
+	let completed = complete_with_report(orig_index, &removal_hunk, options)?;
+	let line = completed
+		.patch
+		.lines()
+		.find_map(|line| line.strip_prefix("@@ -")?.split(',').next()?.parse::<usize>().ok())
+		.ok_or_else(|| Error::custom("Context not found"))?;
+	Ok((line, completed.tier))
+}
 
-	Ok((completed_patch, max_tier))
+/// The `# udiffx: ...` comment line telling how a completed hunk matched (see `CompleteOptions::annotate_hunks`).
+pub(crate) fn hunk_annotation(hunk_number: usize, line: usize, tier: Option<MatchTier>, score: f64) -> String {
+	match tier {
+		Some(tier) => {
+			format!("{HUNK_ANNOTATION_PREFIX} hunk {hunk_number} matched at {line} via {tier:?}, score {score:.2}")
+		}
+		None => format!("{HUNK_ANNOTATION_PREFIX} hunk {hunk_number} added at {line} (no context to match)"),
+	}
 }
 
 // region:    --- Support
@@ -164,7 +268,7 @@ fn estimate_hunk_position(orig_lines: &[&str], hunk_lines: &[&str]) -> Option<us
 		if l.starts_with('+') {
 			return None;
 		}
-		let content = if l.len() > 1 { &l[1..] } else { "" };
+		let content = line_body(l);
 		if content.trim().is_empty() {
 			return None;
 		}
@@ -238,14 +342,14 @@ fn last_context_or_removal_content<'a>(hunk: &[&'a str]) -> Option<&'a str> {
 	hunk.iter()
 		.rev()
 		.find(|l| l.starts_with(' ') || l.starts_with('-'))
-		.map(|l| if l.len() > 1 { &l[1..] } else { "" })
+		.map(|l| line_body(l))
 }
 
 /// Extracts the content (without prefix) of the first context/removal line in a hunk.
 fn first_context_or_removal_content<'a>(hunk: &[&'a str]) -> Option<&'a str> {
 	hunk.iter()
 		.find(|l| l.starts_with(' ') || l.starts_with('-'))
-		.map(|l| if l.len() > 1 { &l[1..] } else { "" })
+		.map(|l| line_body(l))
 }
 
 /// Builds adjacent hints for the hunk at `hunk_idx` from the collected raw hunks.
@@ -337,13 +441,13 @@ fn expand_tilde_ranges(
 
 /// Checks whether an original line at a given index matches a hint line,
 /// using Resilient-tier matching for flexibility.
-fn hint_line_matches(orig_lines: &[&str], orig_idx: usize, hint: &str) -> bool {
+fn hint_line_matches(orig_lines: &[&str], orig_idx: usize, hint: &str, options: &CompleteOptions) -> bool {
 	if orig_idx >= orig_lines.len() {
 		return false;
 	}
 	let orig_line = orig_lines[orig_idx];
 	// Use Resilient matching for hint comparison (trimmed, normalized ws)
-	line_matches(orig_line, hint, MatchTier::Resilient)
+	line_matches(orig_line, hint, MatchTier::Resilient, options)
 }
 
 /// Computes the number of adjacent hint matches for a candidate.
@@ -352,6 +456,7 @@ fn compute_adjacent_hint_matches(
 	candidate_start: usize,
 	candidate_old_count: usize,
 	hints: &AdjacentHints<'_>,
+	options: &CompleteOptions,
 ) -> usize {
 	let mut count = 0;
 
@@ -359,7 +464,7 @@ fn compute_adjacent_hint_matches(
 	if let Some(prev_hint) = hints.prev_hint
 		&& !prev_hint.trim().is_empty()
 		&& candidate_start > 0
-		&& hint_line_matches(orig_lines, candidate_start - 1, prev_hint)
+		&& hint_line_matches(orig_lines, candidate_start - 1, prev_hint, options)
 	{
 		count += 1;
 	}
@@ -369,7 +474,7 @@ fn compute_adjacent_hint_matches(
 		&& !next_hint.trim().is_empty()
 	{
 		let after_idx = candidate_start + candidate_old_count;
-		if hint_line_matches(orig_lines, after_idx, next_hint) {
+		if hint_line_matches(orig_lines, after_idx, next_hint, options) {
 			count += 1;
 		}
 	}
@@ -384,6 +489,7 @@ fn search_candidates_for_tier(
 	search_from: usize,
 	tier: MatchTier,
 	hints: &AdjacentHints<'_>,
+	options: &CompleteOptions,
 ) -> Vec<CandidateMatch> {
 	let mut candidates: Vec<CandidateMatch> = Vec::new();
 
@@ -428,7 +534,7 @@ fn search_candidates_for_tier(
 				continue;
 			}
 
-			let p_line = if hl_line.len() > 1 { &hl_line[1..] } else { "" };
+			let p_line = line_body(hl_line);
 
 			let mut target_idx = i + orig_off;
 
@@ -474,7 +580,7 @@ fn search_candidates_for_tier(
 					// from current position to find the matching line.
 					let mut found = false;
 					for search_idx in target_idx..orig_lines.len() {
-						if line_matches(orig_lines[search_idx], p_line, tier) {
+						if line_matches(orig_lines[search_idx], p_line, tier, options) {
 							// Check that remaining bottom anchors also match consecutively
 							let range = tilde_ranges
 								.iter()
@@ -487,12 +593,8 @@ fn search_candidates_for_tier(
 									all_match = false;
 									break;
 								}
-								let ba_line = if hunk_lines[ba_hl_idx].len() > 1 {
-									&hunk_lines[ba_hl_idx][1..]
-								} else {
-									""
-								};
-								if !line_matches(orig_lines[ba_orig_idx], ba_line, tier) {
+								let ba_line = line_body(hunk_lines[ba_hl_idx]);
+								if !line_matches(orig_lines[ba_orig_idx], ba_line, tier, options) {
 									all_match = false;
 									break;
 								}
@@ -519,7 +621,7 @@ fn search_candidates_for_tier(
 					// Non-first bottom anchor: already verified consecutively when
 					// the first bottom anchor was matched. Record match and advance.
 					let target = i + orig_off;
-					if target < orig_lines.len() && line_matches(orig_lines[target], p_line, tier) {
+					if target < orig_lines.len() && line_matches(orig_lines[target], p_line, tier, options) {
 						if orig_lines[target] == p_line {
 							current_exact_ws_count += 1;
 						}
@@ -529,7 +631,7 @@ fn search_candidates_for_tier(
 						matches = false;
 						break;
 					}
-				} else if line_matches(orig_line, p_line, tier) {
+				} else if line_matches(orig_line, p_line, tier, options) {
 					// Track whether this was an exact whitespace match (no normalization needed)
 					if orig_line == p_line {
 						current_exact_ws_count += 1;
@@ -621,7 +723,8 @@ fn search_candidates_for_tier(
 				oc
 			};
 
-			let adjacent_hint_matches = compute_adjacent_hint_matches(orig_lines, i, candidate_old_count, hints);
+			let adjacent_hint_matches =
+				compute_adjacent_hint_matches(orig_lines, i, candidate_old_count, hints, options);
 
 			candidates.push(CandidateMatch {
 				idx: i,
@@ -646,6 +749,7 @@ fn compute_hunk_bounds(
 	hunk_lines: &[&str],
 	search_from: usize,
 	hints: &AdjacentHints<'_>,
+	options: &CompleteOptions,
 ) -> Result<HunkBounds> {
 	// -- Validate tilde ranges before any matching
 	let tilde_ranges = validate_and_parse_tilde_ranges(hunk_lines)?;
@@ -669,7 +773,7 @@ fn compute_hunk_bounds(
 					final_hunk_lines.push(hl.to_string());
 				} else {
 					// Convert context (' ') or removal ('-') to addition ('+')
-					let content = if hl.len() > 1 { &hl[1..] } else { "" };
+					let content = line_body(hl);
 					final_hunk_lines.push(format!("+{content}"));
 				}
 				new_count += 1;
@@ -681,6 +785,9 @@ fn compute_hunk_bounds(
 				new_count,
 				final_hunk_lines,
 				tier: None,
+				truncation_matched: false,
+				score: 1.,
+				lenient_lines: Vec::new(),
 			});
 		}
 	}
@@ -697,7 +804,7 @@ fn compute_hunk_bounds(
 		let leading_blank_add_count = hunk_lines
 			.iter()
 			.take_while(|l| {
-				let content = if l.len() > 1 { &l[1..] } else { "" };
+				let content = line_body(l);
 				l.starts_with('+') && content.trim().is_empty()
 			})
 			.count();
@@ -711,7 +818,7 @@ fn compute_hunk_bounds(
 			.iter()
 			.rev()
 			.take_while(|l| {
-				let content = if l.len() > 1 { &l[1..] } else { "" };
+				let content = line_body(l);
 				l.starts_with('+') && content.trim().is_empty()
 			})
 			.count();
@@ -759,6 +866,9 @@ fn compute_hunk_bounds(
 			new_count,
 			final_hunk_lines,
 			tier: None,
+			truncation_matched: false,
+			score: 1.,
+			lenient_lines: Vec::new(),
 		});
 	}
 
@@ -767,7 +877,7 @@ fn compute_hunk_bounds(
 	let mut candidates: Vec<CandidateMatch> = Vec::new();
 
 	for tier in tiers {
-		candidates = search_candidates_for_tier(orig_lines, hunk_lines, search_from, tier, hints);
+		candidates = search_candidates_for_tier(orig_lines, hunk_lines, search_from, tier, hints, options);
 		if !candidates.is_empty() {
 			break;
 		}
@@ -789,6 +899,16 @@ fn compute_hunk_bounds(
 
 	let idx = best.idx;
 	let tier = best.tier;
+	let truncation_matched = tier > MatchTier::Strict
+		&& best.matched_orig_indices.iter().any(|(hl_idx, orig_idx)| {
+			is_truncation_match(orig_lines[*orig_idx], line_body(hunk_lines[*hl_idx]), tier, options)
+		});
+	let score = if best.matched_orig_indices.is_empty() {
+		1.
+	} else {
+		best.exact_ws_count as f64 / best.matched_orig_indices.len() as f64
+	};
+	let lenient_lines = lenient_lines(orig_lines, hunk_lines, &best.matched_orig_indices, options);
 	let overhang_hl_indices = best.overhang_hl_indices;
 	let skipped_hl_indices = best.skipped_hl_indices;
 	let converted_to_add_indices = best.converted_to_add_indices;
@@ -845,6 +965,9 @@ fn compute_hunk_bounds(
 			new_count,
 			final_hunk_lines,
 			tier: Some(tier),
+			truncation_matched,
+			score,
+			lenient_lines,
 		});
 	}
 
@@ -903,7 +1026,41 @@ fn compute_hunk_bounds(
 		new_count,
 		final_hunk_lines,
 		tier: Some(tier),
+		truncation_matched,
+		score,
+		lenient_lines,
 	})
 }
 
+
+/// The matched context/removal lines (`(hunk line index, orig line index)`) not equal to their original line,
+/// with the lowest tier matching them (blank lines aside).
+fn lenient_lines(
+	orig_lines: &[&str],
+	hunk_lines: &[&str],
+	matched_orig_indices: &[(usize, usize)],
+	options: &CompleteOptions,
+) -> Vec<LenientLine> {
+	matched_orig_indices
+		.iter()
+		.filter_map(|(hl_idx, orig_idx)| {
+			let orig_line = orig_lines[*orig_idx];
+			let p_line = line_body(hunk_lines[*hl_idx]);
+			if orig_line == p_line || (orig_line.trim().is_empty() && p_line.trim().is_empty()) {
+				return None;
+			}
+			let tier = if line_matches(orig_line, p_line, MatchTier::Resilient, options) {
+				MatchTier::Resilient
+			} else {
+				MatchTier::Fuzzy
+			};
+			Some(LenientLine {
+				line: orig_idx + 1,
+				tier,
+				truncated: is_truncation_match(orig_line, p_line, tier, options),
+			})
+		})
+		.collect()
+}
+
 // endregion: --- Support
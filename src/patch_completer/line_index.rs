@@ -0,0 +1,42 @@
+use std::cell::OnceCell;
+use std::collections::HashSet;
+
+/// The lines of an original content, prebuilt once to complete many patches against the same content
+/// (e.g., candidate patches for one file) without re-splitting it per call.
+///
+/// A CRLF content needs no normalization, as `str::lines` strips the `\r\n` line endings.
+#[derive(Debug, Clone)]
+pub struct LineIndex<'a> {
+	lines: Vec<&'a str>,
+	/// The trimmed lines, built on first use (see `strip_line_number_prefixes`).
+	trimmed_lines: OnceCell<HashSet<&'a str>>,
+}
+
+impl<'a> LineIndex<'a> {
+	pub fn new(content: &'a str) -> Self {
+		Self::from_lines(content.lines().collect())
+	}
+
+	/// From the lines already split by the caller (without their line endings).
+	pub fn from_lines(lines: Vec<&'a str>) -> Self {
+		Self {
+			lines,
+			trimmed_lines: OnceCell::new(),
+		}
+	}
+
+	pub fn lines(&self) -> &[&'a str] {
+		&self.lines
+	}
+
+	pub(super) fn trimmed_lines(&self) -> &HashSet<&'a str> {
+		self.trimmed_lines
+			.get_or_init(|| self.lines.iter().map(|line| line.trim()).collect())
+	}
+}
+
+impl<'a> From<&[&'a str]> for LineIndex<'a> {
+	fn from(lines: &[&'a str]) -> Self {
+		Self::from_lines(lines.to_vec())
+	}
+}
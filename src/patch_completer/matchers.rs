@@ -1,19 +1,34 @@
-use super::SUFFIX_MATCH_MIN_LEN;
+use super::parse::line_body;
 use super::types::{CandidateMatch, MatchTier};
+use crate::CompleteOptions;
 
 /// Collapses runs of whitespace into a single space for normalized comparison.
-fn normalize_ws(s: &str) -> String {
+pub(super) fn normalize_ws(s: &str) -> String {
 	s.split_whitespace().collect::<Vec<_>>().join(" ")
 }
 
-/// Checks if a trimmed line is a Markdown heading.
-fn is_markdown_heading(s: &str) -> bool {
-	s.starts_with('#')
+/// Returns the text of a Markdown heading line (`#` to `######` followed by whitespace, at column 0),
+/// or `None` if the line is not a heading.
+fn markdown_heading_text(line: &str) -> Option<&str> {
+	let rest = line.trim_start_matches('#');
+	let level = line.len() - rest.len();
+	((1..=6).contains(&level) && rest.starts_with([' ', '\t'])).then(|| rest.trim())
 }
 
-/// Strips the leading `#` characters and subsequent whitespace from a Markdown heading.
-fn strip_markdown_heading(s: &str) -> &str {
-	s.trim_start_matches('#').trim_start()
+/// Checks whether two lines are Markdown headings with the same text, regardless of their level.
+/// The patch line may be indented (a common LLM variance), the original line must be at column 0.
+fn markdown_headings_match(orig_line: &str, p_line: &str, case_insensitive: bool) -> bool {
+	let (Some(o_text), Some(p_text)) = (
+		markdown_heading_text(orig_line),
+		markdown_heading_text(p_line.trim_start()),
+	) else {
+		return false;
+	};
+	if case_insensitive {
+		normalize_ws(o_text).to_lowercase() == normalize_ws(p_text).to_lowercase()
+	} else {
+		normalize_ws(o_text) == normalize_ws(p_text)
+	}
 }
 
 /// Checks if a string looks like a comment marker prefix (e.g., "//", "#", "<!--").
@@ -83,9 +98,9 @@ fn normalize_inline_fuzzy(s: &str) -> String {
 }
 
 /// Checks whether one trimmed line is a suffix of the other.
-/// Only applies when the shorter fragment is long enough to be meaningful,
+/// Only applies when the shorter fragment is at least `min_len` long (to be meaningful),
 /// preventing false positives from very short context lines.
-fn suffix_match(orig_trimmed: &str, patch_trimmed: &str, case_insensitive: bool) -> bool {
+fn suffix_match(orig_trimmed: &str, patch_trimmed: &str, case_insensitive: bool, min_len: usize) -> bool {
 	let orig_norm = if case_insensitive {
 		normalize_ws(orig_trimmed).to_lowercase()
 	} else {
@@ -96,7 +111,7 @@ fn suffix_match(orig_trimmed: &str, patch_trimmed: &str, case_insensitive: bool)
 	} else {
 		normalize_ws(patch_trimmed)
 	};
-	if patch_norm.len() >= SUFFIX_MATCH_MIN_LEN && orig_norm.ends_with(&patch_norm) {
+	if patch_norm.len() >= min_len && orig_norm.ends_with(&patch_norm) {
 		// Reject if the non-matching prefix is a comment marker (e.g., "// " or "# ").
 		// This prevents "do something" from suffix-matching "// do something".
 		let prefix = orig_norm[..orig_norm.len() - patch_norm.len()].trim();
@@ -105,7 +120,7 @@ fn suffix_match(orig_trimmed: &str, patch_trimmed: &str, case_insensitive: bool)
 		}
 		return true;
 	}
-	if orig_norm.len() >= SUFFIX_MATCH_MIN_LEN && patch_norm.ends_with(&orig_norm) {
+	if orig_norm.len() >= min_len && patch_norm.ends_with(&orig_norm) {
 		let prefix = patch_norm[..patch_norm.len() - orig_norm.len()].trim();
 		if !prefix.is_empty() && is_comment_marker_prefix(prefix) {
 			return false;
@@ -115,6 +130,48 @@ fn suffix_match(orig_trimmed: &str, patch_trimmed: &str, case_insensitive: bool)
 	false
 }
 
+/// Checks whether the trimmed patch line is the beginning of the original line (a long line truncated by the model),
+/// ignoring a trailing `...` or `…` truncation marker.
+/// Only applies when the patch fragment is at least `min_len` long.
+fn prefix_match(orig_trimmed: &str, patch_trimmed: &str, case_insensitive: bool, min_len: usize) -> bool {
+	let patch_trimmed = patch_trimmed.trim_end_matches("...").trim_end_matches('…').trim_end();
+	let (orig_norm, patch_norm) = if case_insensitive {
+		(
+			normalize_ws(orig_trimmed).to_lowercase(),
+			normalize_ws(patch_trimmed).to_lowercase(),
+		)
+	} else {
+		(normalize_ws(orig_trimmed), normalize_ws(patch_trimmed))
+	};
+	patch_norm.len() >= min_len && patch_norm.len() < orig_norm.len() && orig_norm.starts_with(&patch_norm)
+}
+
+/// Checks whether a line is truncated on either side relative to the other (see `suffix_match` and `prefix_match`),
+/// for the minimum lengths enabled in `options`.
+fn truncation_match(
+	orig_trimmed: &str,
+	patch_trimmed: &str,
+	case_insensitive: bool,
+	options: &CompleteOptions,
+) -> bool {
+	options
+		.suffix_match_min_len
+		.is_some_and(|min_len| suffix_match(orig_trimmed, patch_trimmed, case_insensitive, min_len))
+		|| options
+			.prefix_match_min_len
+			.is_some_and(|min_len| prefix_match(orig_trimmed, patch_trimmed, case_insensitive, min_len))
+}
+
+/// Checks whether the lines match at the given tier only thanks to the truncation (suffix/prefix) matching.
+pub(super) fn is_truncation_match(orig_line: &str, p_line: &str, tier: MatchTier, options: &CompleteOptions) -> bool {
+	let without_truncation = CompleteOptions {
+		suffix_match_min_len: None,
+		prefix_match_min_len: None,
+		..options.clone()
+	};
+	line_matches(orig_line, p_line, tier, options) && !line_matches(orig_line, p_line, tier, &without_truncation)
+}
+
 /// Scores a candidate match. Higher is better.
 /// Criteria:
 ///   - Prefer more exact whitespace matches (no normalization needed).
@@ -139,9 +196,11 @@ pub(super) fn score_candidate(candidate: &CandidateMatch, search_from: usize) ->
 /// Checks whether an original line matches a patch line at the given tier.
 ///
 /// - **Strict**: Character-for-character exact match. No trimming or normalization.
-/// - **Resilient**: Trimmed comparison, normalized whitespace, and suffix match (case-sensitive).
+/// - **Resilient**: Trimmed comparison, normalized whitespace, and suffix/prefix match (case-sensitive).
 /// - **Fuzzy**: Same as Resilient but all comparisons are case-insensitive.
-pub(super) fn line_matches(orig_line: &str, p_line: &str, tier: MatchTier) -> bool {
+///
+/// The Markdown heading leniency of Resilient/Fuzzy is only applied if enabled in `options`.
+pub(super) fn line_matches(orig_line: &str, p_line: &str, tier: MatchTier, options: &CompleteOptions) -> bool {
 	match tier {
 		MatchTier::Strict => orig_line == p_line,
 		MatchTier::Resilient => {
@@ -152,11 +211,8 @@ pub(super) fn line_matches(orig_line: &str, p_line: &str, tier: MatchTier) -> bo
 			}
 			orig_trimmed == p_trimmed
 				|| normalize_ws(orig_trimmed) == normalize_ws(p_trimmed)
-				|| (is_markdown_heading(orig_trimmed)
-					&& is_markdown_heading(p_trimmed)
-					&& normalize_ws(strip_markdown_heading(orig_trimmed))
-						== normalize_ws(strip_markdown_heading(p_trimmed)))
-				|| suffix_match(orig_trimmed, p_trimmed, false)
+				|| (options.markdown_headings && markdown_headings_match(orig_line, p_line, false))
+				|| truncation_match(orig_trimmed, p_trimmed, false, options)
 				|| {
 					// Trailing semicolon/comma tolerance: strip a single trailing `;` or `,`
 					// from both lines and re-compare. This handles common LLM formatting
@@ -192,11 +248,8 @@ pub(super) fn line_matches(orig_line: &str, p_line: &str, tier: MatchTier) -> bo
 
 			o_l == p_l
 				|| normalize_ws(&o_l) == normalize_ws(&p_l)
-				|| (is_markdown_heading(o_t)
-					&& is_markdown_heading(p_t)
-					&& normalize_ws(strip_markdown_heading(o_t)).to_lowercase()
-						== normalize_ws(strip_markdown_heading(p_t)).to_lowercase())
-				|| suffix_match(o_t, p_t, true)
+				|| (options.markdown_headings && markdown_headings_match(orig_line, p_line, true))
+				|| truncation_match(o_t, p_t, true, options)
 				// Also check if they match ignoring backticks (common Markdown LLM variance)
 				|| o_l.replace('`', "") == p_l.replace('`', "")
 				|| normalize_ws(&o_l.replace('`', "")) == normalize_ws(&p_l.replace('`', ""))
@@ -245,11 +298,7 @@ pub(super) fn has_uniform_indent_delta(
 	let mut delta: Option<isize> = None;
 
 	for &(hl_idx, orig_idx) in matched_orig_indices {
-		let p_line = if hunk_lines[hl_idx].len() > 1 {
-			&hunk_lines[hl_idx][1..]
-		} else {
-			""
-		};
+		let p_line = line_body(hunk_lines[hl_idx]);
 		// Skip blank lines; they carry no indentation signal.
 		if p_line.trim().is_empty() {
 			continue;
@@ -1,13 +1,26 @@
 // region:    --- Modules
 
+mod anchor;
 mod complete;
+mod line_index;
 mod matchers;
 mod parse;
+mod similarity;
 mod types;
 
-pub use complete::complete;
-pub use parse::{has_actionable_hunks, has_tilde_ranges, split_raw_hunks};
-pub use types::MatchTier;
+#[cfg(any(test, feature = "test-support"))]
+pub use complete::{complete, complete_with_options};
+pub(crate) use anchor::find_anchor_line;
+pub use complete::complete_with_line_index;
+pub(crate) use complete::{complete_with_report, hunk_annotation, match_context_hunk};
+pub use line_index::LineIndex;
+pub(crate) use parse::{is_hunk_annotation, split_context_hunks};
+pub use parse::{
+	has_actionable_hunks, has_tilde_ranges, hunk_sides, split_raw_hunks, strip_hunk_annotations, unescape_patch_body,
+};
+pub use similarity::{context_similarity, find_hunk_result, nearest_miss};
+pub(crate) use types::CompletedPatch;
+pub use types::{LenientLine, MatchTier};
 
 // endregion: --- Modules
 
@@ -17,13 +30,15 @@ pub use types::MatchTier;
 /// This prevents a hunk from "drifting" too far and causing subsequent hunks to fail.
 const MAX_PROXIMITY_FOR_LENIENT: usize = 1000;
 
-/// Minimum length for a patch context fragment to be eligible for suffix matching.
-/// This prevents very short strings (e.g., `"x"`) from false-positive matching.
-const SUFFIX_MATCH_MIN_LEN: usize = 10;
-
 /// Minimum number of `-` lines required above and below a `~` range-remove marker.
 const TILDE_MIN_ANCHOR_LINES: usize = 2;
 
+/// Minimum number of non-blank context/removal lines required to compute a context similarity.
+const SIMILARITY_MIN_LINES: usize = 3;
+
+/// The prefix of the match annotation lines of a completed patch (see `CompleteOptions::annotate_hunks`).
+const HUNK_ANNOTATION_PREFIX: &str = "# udiffx:";
+
 // endregion: --- Constants
 
 // region:    --- Tests
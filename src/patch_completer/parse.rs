@@ -1,7 +1,9 @@
-use super::TILDE_MIN_ANCHOR_LINES;
+use super::line_index::LineIndex;
 use super::types::TildeRange;
+use super::{HUNK_ANNOTATION_PREFIX, TILDE_MIN_ANCHOR_LINES};
 use crate::{Error, Result};
 use std::borrow::Cow;
+use std::collections::HashSet;
 
 // region:    --- Public Helpers
 
@@ -73,11 +75,99 @@ pub fn split_raw_hunks(patch_raw: &str) -> Vec<String> {
 		.collect()
 }
 
+/// Returns the old side (context and `-` lines) and the new side (context and `+` lines) of a raw hunk,
+/// without their line prefix.
+pub fn hunk_sides(raw_hunk: &str) -> (Vec<&str>, Vec<&str>) {
+	let mut old_side = Vec::new();
+	let mut new_side = Vec::new();
+
+	for line in raw_hunk.lines().skip_while(|l| !l.starts_with("@@")).skip(1) {
+		if line.starts_with('\\') {
+			continue;
+		} else if line.starts_with('-') {
+			old_side.push(line_body(line));
+		} else if line.starts_with('+') {
+			new_side.push(line_body(line));
+		} else {
+			let context = line.strip_prefix(' ').unwrap_or(line);
+			old_side.push(context);
+			new_side.push(context);
+		}
+	}
+
+	(old_side, new_side)
+}
+
 /// Checks whether a raw hunk contains any `~` (tilde range-remove) markers.
 pub fn has_tilde_ranges(hunk_raw: &str) -> bool {
 	hunk_raw.lines().any(|l| l.trim() == "~")
 }
 
+/// Normalizes the patch lines holding literal `\n` escape sequences instead of real newlines (e.g., `@@\n-a\n+b`),
+/// as some models emit them. Returns `None` when no line was normalized.
+///
+/// A line is normalized only if, once its escapes (`\n`, `\t`, `\r`, `\"`, `\\`) are decoded, it splits into
+/// several lines that all look like hunk lines (` `, `+`, `-`, `@@`, or blank). So a context line with a legitimate
+/// string literal (e.g., ` let s = "a\nb";`) is kept as is.
+pub fn unescape_patch_body(patch_raw: &str) -> Option<String> {
+	let mut fired = false;
+	let mut out = String::with_capacity(patch_raw.len());
+
+	for line in patch_raw.split_inclusive('\n') {
+		let (body, eol) = match line.strip_suffix('\n') {
+			Some(body) => (body, "\n"),
+			None => (line, ""),
+		};
+		match unescape_hunk_line(body) {
+			Some(unescaped) => {
+				fired = true;
+				out.push_str(&unescaped);
+			}
+			None => out.push_str(body),
+		}
+		out.push_str(eol);
+	}
+
+	fired.then_some(out)
+}
+
+/// Checks whether a line is a `# udiffx: ...` match annotation (see `CompleteOptions::annotate_hunks`).
+pub(crate) fn is_hunk_annotation(line: &str) -> bool {
+	line.starts_with(HUNK_ANNOTATION_PREFIX)
+}
+
+/// Removes the `# udiffx: ...` match annotation lines of an annotated completed patch, so it can be applied again.
+/// Returns `None` when the patch has no annotation.
+pub fn strip_hunk_annotations(patch_raw: &str) -> Option<String> {
+	if !patch_raw.lines().any(is_hunk_annotation) {
+		return None;
+	}
+
+	let stripped = patch_raw
+		.split_inclusive('\n')
+		.filter(|line| !is_hunk_annotation(line))
+		.collect();
+	Some(stripped)
+}
+
+/// Splits the context-only hunks (no `+`, `-`, or `~` line, at least one non-blank line) of a raw simplified patch,
+/// which `split_raw_hunks` skips, each with its `@@` header (see `CompleteOptions::assert_context_hunks`).
+pub(crate) fn split_context_hunks(patch_raw: &str) -> Vec<String> {
+	let sanitized = sanitize_wrapper_meta_lines(&patch_raw.replace("\r\n", "\n"));
+	collect_hunk_bodies(&sanitized)
+		.into_iter()
+		.filter(|lines| !is_actionable(lines) && lines.iter().any(|line| !line.trim().is_empty()))
+		.map(|lines| {
+			let mut hunk_str = String::from("@@\n");
+			for line in lines {
+				hunk_str.push_str(line);
+				hunk_str.push('\n');
+			}
+			hunk_str
+		})
+		.collect()
+}
+
 // endregion: --- Public Helpers
 
 // region:    --- Internal Parsing
@@ -86,6 +176,21 @@ pub fn has_tilde_ranges(hunk_raw: &str) -> bool {
 ///
 /// Shared by both `split_raw_hunks` and `complete` to avoid duplicating the parsing logic.
 pub(super) fn collect_raw_hunks(patch_text: &str) -> Vec<Vec<&str>> {
+	collect_hunk_bodies(patch_text)
+		.into_iter()
+		.filter(|hunk_lines| is_actionable(hunk_lines))
+		.collect()
+}
+
+/// Whether a hunk has at least one `+`, `-`, or `~` line.
+fn is_actionable(hunk_lines: &[&str]) -> bool {
+	hunk_lines
+		.iter()
+		.any(|l| l.starts_with('+') || l.starts_with('-') || l.trim() == "~")
+}
+
+/// Collects the body lines of each hunk of the patch text (actionable or not).
+fn collect_hunk_bodies(patch_text: &str) -> Vec<Vec<&str>> {
 	let mut raw_hunks: Vec<Vec<&str>> = Vec::new();
 	let mut lines = patch_text.lines().peekable();
 
@@ -109,14 +214,7 @@ pub(super) fn collect_raw_hunks(patch_text: &str) -> Vec<Vec<&str>> {
 				hunk_lines.pop();
 			}
 
-			let has_add = hunk_lines.iter().any(|l| l.starts_with('+'));
-			let has_remove = hunk_lines.iter().any(|l| l.starts_with('-'));
-			let has_tilde = hunk_lines.iter().any(|l| l.trim() == "~");
-			let is_actionable = has_add || has_remove || has_tilde;
-
-			if is_actionable {
-				raw_hunks.push(hunk_lines);
-			}
+			raw_hunks.push(hunk_lines);
 		}
 	}
 
@@ -159,12 +257,7 @@ pub(super) fn collect_raw_hunks_sanitized(patch_text: &str) -> Vec<Vec<&str>> {
 				hunk_lines.pop();
 			}
 
-			let has_add = hunk_lines.iter().any(|l| l.starts_with('+'));
-			let has_remove = hunk_lines.iter().any(|l| l.starts_with('-'));
-			let has_tilde = hunk_lines.iter().any(|l| l.trim() == "~");
-			let is_actionable = has_add || has_remove || has_tilde;
-
-			if is_actionable {
+			if is_actionable(&hunk_lines) {
 				raw_hunks.push(hunk_lines);
 			}
 		}
@@ -244,8 +337,137 @@ pub(super) fn validate_and_parse_tilde_ranges(hunk_lines: &[&str]) -> Result<Vec
 	Ok(ranges)
 }
 
+/// Returns the hunk line without its first (prefix) character, on a char boundary
+/// (a line missing its prefix may start with a multibyte character).
+pub(super) fn line_body(line: &str) -> &str {
+	let mut chars = line.chars();
+	chars.next();
+	chars.as_str()
+}
+
+/// Strips the line-number prefixes (e.g., `+42: x` or ` 42| x`) some models echo from numbered context,
+/// hunk by hunk. Returns `None` when no hunk was stripped.
+///
+/// A hunk is stripped only when at least 80% of its non-blank body lines carry a prefix with the same separator,
+/// and when its prefixed context/removal lines are not found as is in the original (then the numbers are content).
+pub(super) fn strip_line_number_prefixes(patch_raw: &str, orig_index: &LineIndex) -> Option<String> {
+	let orig_lines = orig_index.trimmed_lines();
+	let mut fired = false;
+	let mut out = String::with_capacity(patch_raw.len());
+	let mut hunk: Vec<&str> = Vec::new();
+	let mut in_hunk = false;
+
+	for line in patch_raw.lines() {
+		if line.trim().starts_with("@@") {
+			fired |= push_hunk_lines(&mut out, &hunk, in_hunk, orig_lines);
+			hunk.clear();
+			in_hunk = true;
+			out.push_str(line);
+			out.push('\n');
+		} else if in_hunk {
+			hunk.push(line);
+		} else {
+			out.push_str(line);
+			out.push('\n');
+		}
+	}
+	fired |= push_hunk_lines(&mut out, &hunk, in_hunk, orig_lines);
+
+	fired.then_some(out)
+}
+
 // endregion: --- Internal Parsing
 
+/// Pushes the hunk body lines to `out`, stripped of their line-number prefixes if detected.
+/// Returns `true` if stripped.
+fn push_hunk_lines(out: &mut String, hunk: &[&str], in_hunk: bool, orig_lines: &HashSet<&str>) -> bool {
+	let strip = in_hunk && has_line_number_prefixes(hunk, orig_lines);
+	for line in hunk {
+		match line_number_prefix_end(line).filter(|_| strip) {
+			Some((_, end)) => {
+				out.push_str(&line[..1]);
+				out.push_str(&line[end..]);
+			}
+			None => out.push_str(line),
+		}
+		out.push('\n');
+	}
+	strip
+}
+
+fn has_line_number_prefixes(hunk: &[&str], orig_lines: &HashSet<&str>) -> bool {
+	let body_lines: Vec<&str> = hunk
+		.iter()
+		.copied()
+		.filter(|l| !l.trim().is_empty() && l.trim() != "~")
+		.collect();
+	let prefixed: Vec<(&str, char)> = body_lines
+		.iter()
+		.filter_map(|l| line_number_prefix_end(l).map(|(sep, _)| (*l, sep)))
+		.collect();
+
+	let Some((_, sep)) = prefixed.first() else {
+		return false;
+	};
+	if prefixed.len() < 2 || prefixed.len() * 5 < body_lines.len() * 4 || prefixed.iter().any(|(_, s)| s != sep) {
+		return false;
+	}
+
+	// -- When every prefixed context/removal line is in the original, the numbers are actual content
+	let is_content = prefixed
+		.iter()
+		.all(|(l, _)| l.starts_with('+') || orig_lines.contains(line_body(l).trim()));
+	!is_content
+}
+
+/// Returns the separator (`:` or `|`) and the byte end of a `<prefix char>[ ]<digits>[ ]<sep>[ ]` line prefix.
+fn line_number_prefix_end(line: &str) -> Option<(char, usize)> {
+	let rest = line.strip_prefix([' ', '+', '-'])?;
+	let after_spaces = rest.trim_start_matches(' ');
+	let after_digits = after_spaces.trim_start_matches(|c: char| c.is_ascii_digit());
+	if after_digits.len() == after_spaces.len() {
+		return None;
+	}
+	let after_digits = after_digits.trim_start_matches(' ');
+	let sep = after_digits.chars().next().filter(|c| matches!(c, ':' | '|'))?;
+	let after_sep = &after_digits[1..];
+	let after_sep = after_sep.strip_prefix(' ').unwrap_or(after_sep);
+
+	Some((sep, line.len() - after_sep.len()))
+}
+
+/// Decodes the escapes of a line, if it holds escaped newlines separating hunk-like lines.
+fn unescape_hunk_line(line: &str) -> Option<String> {
+	if !line.contains("\\n") {
+		return None;
+	}
+
+	let mut unescaped = String::with_capacity(line.len());
+	let mut chars = line.chars();
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			unescaped.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('n') => unescaped.push('\n'),
+			Some('t') => unescaped.push('\t'),
+			Some('r') => (),
+			Some('"') => unescaped.push('"'),
+			Some('\\') => unescaped.push('\\'),
+			Some(other) => {
+				unescaped.push('\\');
+				unescaped.push(other);
+			}
+			None => unescaped.push('\\'),
+		}
+	}
+
+	let is_hunk_like = |l: &str| l.is_empty() || l.starts_with([' ', '+', '-']) || l.starts_with("@@");
+	let unescaped = unescaped.trim_end_matches('\n');
+	(unescaped.contains('\n') && unescaped.split('\n').all(is_hunk_like)).then(|| unescaped.to_string())
+}
+
 pub(super) fn is_wrapper_meta_line(trimmed: &str) -> bool {
 	trimmed == "*** Begin Patch" || trimmed == "*** End Patch" || trimmed.starts_with("*** Update File:")
 }
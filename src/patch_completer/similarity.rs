@@ -0,0 +1,90 @@
+use super::SIMILARITY_MIN_LINES;
+use super::matchers::normalize_ws;
+use super::parse::{hunk_sides, line_body, split_raw_hunks};
+use std::collections::HashSet;
+
+/// Returns the fraction (`0.0..=1.0`) of the patch's non-blank context/removal lines that
+/// appear somewhere in `content` (trimmed and whitespace-normalized, position-independent).
+///
+/// Returns `None` when the patch has fewer than `SIMILARITY_MIN_LINES` such lines,
+/// since there is not enough signal to judge whether the patch targets this content.
+pub fn context_similarity(content: &str, patch_raw: &str) -> Option<f64> {
+	let content_lines: HashSet<String> = content
+		.lines()
+		.map(|l| normalize_ws(l.trim()))
+		.filter(|l| !l.is_empty())
+		.collect();
+
+	let hunks = split_raw_hunks(patch_raw);
+	let patch_lines: Vec<String> = hunks
+		.iter()
+		.flat_map(|h| h.lines().skip(1))
+		.filter(|l| l.starts_with(' ') || l.starts_with('-'))
+		.map(|l| normalize_ws(line_body(l).trim()))
+		.filter(|l| !l.is_empty())
+		.collect();
+
+	if patch_lines.len() < SIMILARITY_MIN_LINES {
+		return None;
+	}
+
+	let found = patch_lines.iter().filter(|l| content_lines.contains(*l)).count();
+
+	Some(found as f64 / patch_lines.len() as f64)
+}
+
+/// Returns the 1-based line of `content` where the hunk's old side (context/removal lines) lines up best,
+/// with the fraction (`0.0..=1.0`) of its non-blank lines matching at that position (whitespace-normalized).
+///
+/// Returns `None` when no line matches at any position.
+pub fn nearest_miss(content: &str, raw_hunk: &str) -> Option<(usize, f64)> {
+	let content_lines: Vec<String> = content.lines().map(|l| normalize_ws(l.trim())).collect();
+	let old_lines: Vec<String> = raw_hunk
+		.lines()
+		.skip_while(|l| !l.starts_with("@@"))
+		.skip(1)
+		.filter(|l| !l.starts_with('+'))
+		.map(|l| normalize_ws(line_body(l).trim()))
+		.collect();
+
+	let non_blank = old_lines.iter().filter(|l| !l.is_empty()).count();
+	if non_blank == 0 {
+		return None;
+	}
+
+	let mut best: Option<(usize, usize)> = None;
+	for start in 0..content_lines.len() {
+		let matched = old_lines
+			.iter()
+			.zip(&content_lines[start..])
+			.filter(|(old, line)| !old.is_empty() && old == line)
+			.count();
+		if matched > 0 && best.is_none_or(|(_, best_matched)| matched > best_matched) {
+			best = Some((start, matched));
+		}
+	}
+
+	best.map(|(start, matched)| (start + 1, matched as f64 / non_blank as f64))
+}
+
+/// Returns the 1-based line of `content` where the new side of the hunk (context and `+` lines)
+/// is already present (trailing whitespace ignored), e.g., for an edit that was already applied.
+///
+/// Returns `None` when the hunk adds no non-blank line, since its result would not be distinctive.
+pub fn find_hunk_result(content: &str, raw_hunk: &str) -> Option<usize> {
+	let adds_non_blank = raw_hunk
+		.lines()
+		.skip_while(|l| !l.starts_with("@@"))
+		.skip(1)
+		.any(|l| l.starts_with('+') && !line_body(l).trim().is_empty());
+	if !adds_non_blank {
+		return None;
+	}
+
+	let (_, new_side) = hunk_sides(raw_hunk);
+	let content_lines: Vec<&str> = content.lines().map(str::trim_end).collect();
+	content_lines
+		.windows(new_side.len())
+		.position(|window| window.iter().zip(&new_side).all(|(line, new)| *line == new.trim_end()))
+		.map(|idx| idx + 1)
+}
@@ -1,6 +1,7 @@
 type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
 
 use super::*;
+use crate::CompleteOptions;
 
 #[test]
 fn test_patch_completer_complete_simple() -> Result<()> {
@@ -872,7 +873,7 @@ fn test_patch_completer_complete_removal_suffix_match() -> Result<()> {
 	Ok(())
 }
 
-/// Verifies that a short removal line (below SUFFIX_MATCH_MIN_LEN) does NOT
+/// Verifies that a short removal line (below the default `suffix_match_min_len`) does NOT
 /// false-positive match via suffix matching against a longer original line.
 #[test]
 fn test_patch_completer_complete_removal_short_no_suffix_match() -> Result<()> {
@@ -1811,3 +1812,404 @@ omega
 
 	Ok(())
 }
+
+// -- Context Similarity Tests
+
+#[test]
+fn test_patch_completer_context_similarity_full_and_none() -> Result<()> {
+	// -- Setup & Fixtures
+	let original = "fn a() {\n    let x = 1;\n    let y = 2;\n}\n";
+	let patch = "@@\n fn a() {\n-    let x = 1;\n+    let x = 10;\n     let y = 2;\n";
+
+	// -- Exec
+	let same = context_similarity(original, patch).ok_or("should have similarity")?;
+	let other = context_similarity("struct Other;\nimpl Other {}\n", patch).ok_or("should have similarity")?;
+
+	// -- Check
+	assert_eq!(same, 1.0);
+	assert_eq!(other, 0.0);
+
+	Ok(())
+}
+
+#[test]
+fn test_patch_completer_context_similarity_too_few_lines() -> Result<()> {
+	// -- Setup & Fixtures
+	let patch = "@@\n fn a() {\n+    // added\n";
+
+	// -- Exec
+	let similarity = context_similarity("fn a() {\n}\n", patch);
+
+	// -- Check
+	assert!(similarity.is_none(), "One context line should not be enough signal");
+
+	Ok(())
+}
+
+// -- Nearest Miss Tests
+
+#[test]
+fn test_patch_completer_nearest_miss_best_line() -> Result<()> {
+	// -- Setup & Fixtures
+	let original = "fn a() {}\n\nfn b() {\n    let x = 1;\n    let y = 2;\n}\n";
+	let hunk = "@@\n fn b() {\n-    let x = 100;\n+    let x = 10;\n     let y = 2;\n";
+
+	// -- Exec
+	let (line, similarity) = nearest_miss(original, hunk).ok_or("should have a nearest miss")?;
+
+	// -- Check
+	assert_eq!(line, 3);
+	assert!((similarity - 2.0 / 3.0).abs() < 1e-9, "similarity: {similarity}");
+	assert!(nearest_miss("nothing alike\n", hunk).is_none());
+
+	Ok(())
+}
+
+// -- Multibyte Line Tests
+
+#[test]
+fn test_patch_completer_multibyte_unprefixed_context_no_panic() -> Result<()> {
+	// -- Setup & Fixtures
+	let original = "# Titre é\n\n日本語のコメント\nfn a() {} // 🎉\nlast ü\n";
+	// Context lines missing their ' ' prefix, starting with multibyte characters.
+	let patches = [
+		"@@\n日本語のコメント\n-fn a() {} // 🎉\n+fn a() {} // 🚀\n",
+		"@@\né\n+x\n",
+		"@@\n🎉\n-🎉\n",
+	];
+
+	for patch in patches {
+		// -- Exec
+		let res = std::panic::catch_unwind(|| complete(original, patch));
+		let res_empty = std::panic::catch_unwind(|| complete("", patch));
+
+		// -- Check
+		assert!(res.is_ok(), "Should not panic for patch {patch:?}");
+		assert!(
+			res_empty.is_ok(),
+			"Should not panic on empty original for patch {patch:?}"
+		);
+	}
+
+	Ok(())
+}
+
+#[test]
+fn test_patch_completer_multibyte_content_applies() -> Result<()> {
+	// -- Setup & Fixtures
+	let original = "# Titre é\n\n日本語のコメント\nfn a() {} // 🎉\nlast ü\n";
+	let patch = "@@\n # Titre é\n \n-日本語のコメント\n+中文注释\n fn a() {} // 🎉\n";
+
+	// -- Exec
+	let (completed, _) = complete(original, patch)?;
+	let patch_obj = diffy::Patch::from_str(&completed)?;
+	let new_content = diffy::apply(original, &patch_obj)?;
+
+	// -- Check
+	assert_eq!(new_content, "# Titre é\n\n中文注释\nfn a() {} // 🎉\nlast ü\n");
+
+	Ok(())
+}
+
+// -- Escaped Newlines Tests
+
+#[test]
+fn test_patch_completer_unescape_patch_body_escaped_lines() -> Result<()> {
+	// -- Setup & Fixtures
+	let patch = "@@\\n fn main() {\\n-\\tprintln!(\\\"hi\\\");\\n+\\tprintln!(\\\"hello\\\");\\n }\n";
+
+	// -- Exec
+	let unescaped = unescape_patch_body(patch).ok_or("Should have been normalized")?;
+
+	// -- Check
+	assert_eq!(
+		unescaped,
+		"@@\n fn main() {\n-\tprintln!(\"hi\");\n+\tprintln!(\"hello\");\n }\n"
+	);
+
+	Ok(())
+}
+
+#[test]
+fn test_patch_completer_unescape_patch_body_keeps_string_literals() -> Result<()> {
+	// -- Setup & Fixtures
+	let patch = "@@\n let s = \"a\\nb\";\n-let t = \"\\t\";\n+let t = \"x\\ny\";\n";
+
+	// -- Exec & Check
+	assert_eq!(unescape_patch_body(patch), None);
+
+	Ok(())
+}
+
+// -- Line Number Prefixes Tests
+
+#[test]
+fn test_patch_completer_complete_strips_line_number_prefixes() -> Result<()> {
+	// -- Setup & Fixtures
+	let original = "fn main() {\n    let a = 1;\n    println!(\"{a}\");\n}\n";
+	let patch = "@@\n 1: fn main() {\n-2:     let a = 1;\n+2:     let a = 2;\n 3:     println!(\"{a}\");\n";
+
+	// -- Exec
+	let (completed, _) = complete(original, patch)?;
+
+	// -- Check
+	assert!(completed.contains("-    let a = 1;\n+    let a = 2;\n"), "{completed}");
+
+	Ok(())
+}
+
+#[test]
+fn test_patch_completer_complete_strips_pipe_line_number_prefixes() -> Result<()> {
+	// -- Setup & Fixtures
+	let original = "alpha\nbeta\ngamma\n";
+	let patch = "@@\n 41| alpha\n-42| beta\n+42| BETA\n";
+
+	// -- Exec
+	let (completed, _) = complete(original, patch)?;
+
+	// -- Check
+	assert!(completed.contains(" alpha\n-beta\n+BETA\n"), "{completed}");
+
+	Ok(())
+}
+
+#[test]
+fn test_patch_completer_complete_keeps_numbered_content() -> Result<()> {
+	// -- Setup & Fixtures
+	let original = "codes:\n  200: ok\n  404: not found\n";
+	let patch = "@@\n   200: ok\n-  404: not found\n+  404: missing\n";
+
+	// -- Exec
+	let (completed, _) = complete(original, patch)?;
+
+	// -- Check
+	assert!(completed.contains("-  404: not found\n+  404: missing\n"), "{completed}");
+
+	Ok(())
+}
+
+#[test]
+fn test_patch_completer_complete_keeps_inconsistent_prefixes() -> Result<()> {
+	// -- Setup & Fixtures
+	let original = "a\nb\nc\nd\n";
+	let patch = "@@\n a\n b\n-c\n+1: c\n d\n";
+
+	// -- Exec
+	let (completed, _) = complete(original, patch)?;
+
+	// -- Check
+	assert!(completed.contains("+1: c\n"), "{completed}");
+
+	Ok(())
+}
+
+// -- Markdown Headings Tests
+
+#[test]
+fn test_patch_completer_complete_markdown_headings_option() -> Result<()> {
+	// -- Setup & Fixtures
+	let original = "# Intro\n\ntext\n";
+	let patch = "@@\n ## Intro\n \n-text\n+new text\n";
+
+	// -- Exec
+	let (completed, tier) = complete(original, patch)?;
+	let res = complete_with_options(
+		original,
+		patch,
+		&CompleteOptions::default().with_markdown_headings(false),
+	);
+
+	// -- Check
+	assert!(completed.contains(" # Intro\n \n-text\n+new text\n"), "{completed}");
+	assert_eq!(tier, Some(MatchTier::Resilient));
+	assert!(res.is_err(), "should not match without the markdown headings leniency");
+
+	Ok(())
+}
+
+#[test]
+fn test_patch_completer_complete_markdown_headings_column_zero_only() -> Result<()> {
+	// -- Setup & Fixtures
+	let original = "def run():\n    # setup\n    x = 1\n";
+	let patch = "@@\n     ## setup\n-    x = 1\n+    x = 2\n";
+
+	// -- Exec
+	let res = complete(original, patch);
+
+	// -- Check
+	assert!(res.is_err(), "an indented `#` comment is not a markdown heading");
+
+	Ok(())
+}
+
+#[test]
+fn test_patch_completer_complete_options_for_path() -> Result<()> {
+	// -- Exec & Check
+	assert!(CompleteOptions::for_path("README.md").markdown_headings);
+	assert!(CompleteOptions::for_path("docs/Guide.MDX").markdown_headings);
+	assert!(!CompleteOptions::for_path("scripts/run.sh").markdown_headings);
+	assert!(!CompleteOptions::for_path("src/app.py").markdown_headings);
+	assert!(!CompleteOptions::for_path("Makefile").markdown_headings);
+
+	Ok(())
+}
+
+// -- Truncation Matching Tests
+
+#[test]
+fn test_patch_completer_complete_prefix_match_truncated_line() -> Result<()> {
+	// -- Setup & Fixtures
+	let original = "fn main() {\n    let total = compute_the_total(items, &options, config.max_depth);\n    println!(\"{total}\");\n}\n";
+	let patch = "@@\n-    let total = compute_the_total(items, &options, ...\n+    let total = compute_total(items);\n     println!(\"{total}\");\n";
+
+	// -- Exec
+	let completed = complete_with_report(&LineIndex::new(original), patch, &CompleteOptions::default())?;
+	let res = complete_with_options(
+		original,
+		patch,
+		&CompleteOptions::default().with_prefix_match_min_len(None),
+	);
+
+	// -- Check
+	assert!(
+		completed
+			.patch
+			.contains("-    let total = compute_the_total(items, &options, config.max_depth);\n"),
+		"{}",
+		completed.patch
+	);
+	assert_eq!(completed.tier, Some(MatchTier::Resilient));
+	assert!(completed.truncation_matched);
+	assert!(res.is_err(), "should not match without the prefix matching");
+
+	Ok(())
+}
+
+#[test]
+fn test_patch_completer_complete_prefix_match_min_len() -> Result<()> {
+	// -- Setup & Fixtures
+	// "let total = " is a prefix of both lines, but shorter than the default prefix minimum length.
+	let original = "let total = a + b;\nlet total = c + d;\n";
+	let patch = "@@\n-let total = \n+let total = 0;\n";
+
+	// -- Exec
+	let res = complete(original, patch);
+	let completed = complete_with_report(
+		&LineIndex::new(original),
+		patch,
+		&CompleteOptions::default().with_prefix_match_min_len(Some(5)),
+	)?;
+
+	// -- Check
+	assert!(res.is_err(), "a short fragment should not prefix-match");
+	assert!(completed.patch.contains("-let total = a + b;\n"), "{}", completed.patch);
+	assert!(completed.truncation_matched);
+
+	Ok(())
+}
+
+#[test]
+fn test_patch_completer_complete_suffix_match_min_len() -> Result<()> {
+	// -- Setup & Fixtures
+	let original = "create_connection_pool\nother\n";
+	let patch = "@@\n-_pool\n+create_pool\n";
+
+	// -- Exec
+	let res = complete(original, patch);
+	let completed = complete_with_report(
+		&LineIndex::new(original),
+		patch,
+		&CompleteOptions::default().with_suffix_match_min_len(Some(5)),
+	)?;
+
+	// -- Check
+	assert!(res.is_err(), "a short fragment should not suffix-match");
+	assert!(completed.patch.contains("-create_connection_pool\n"), "{}", completed.patch);
+	assert!(completed.truncation_matched);
+
+	Ok(())
+}
+
+#[test]
+fn test_patch_completer_complete_strict_not_truncation_matched() -> Result<()> {
+	// -- Exec
+	let completed = complete_with_report(
+		&LineIndex::new("a\nb\n"),
+		"@@\n a\n-b\n+B\n",
+		&CompleteOptions::default(),
+	)?;
+
+	// -- Check
+	assert_eq!(completed.tier, Some(MatchTier::Strict));
+	assert!(!completed.truncation_matched);
+
+	Ok(())
+}
+
+// -- Hunk Annotation Tests
+
+#[test]
+fn test_patch_completer_complete_annotate_hunks() -> Result<()> {
+	// -- Setup & Fixtures
+	let original = "fn main() {\n    let x = 1;\n    let y = 2;\n}\n";
+	let patch = "@@\n fn main() {\n-  let x = 1;\n+    let x = 10;\n@@\n-    let y = 2;\n+    let y = 20;\n";
+	let options = CompleteOptions::default().with_annotate_hunks(true);
+
+	// -- Exec
+	let (completed, _) = complete_with_options(original, patch, &options)?;
+
+	// -- Check
+	let annotations: Vec<&str> = completed.lines().filter(|l| l.starts_with("# udiffx:")).collect();
+	assert_eq!(
+		annotations,
+		[
+			"# udiffx: hunk 1 matched at 1 via Resilient, score 0.50",
+			"# udiffx: hunk 2 matched at 3 via Strict, score 1.00",
+		]
+	);
+
+	Ok(())
+}
+
+#[test]
+fn test_patch_completer_complete_annotated_patch_reapplies() -> Result<()> {
+	// -- Setup & Fixtures
+	let original = "a\nb\nc\n";
+	let options = CompleteOptions::default().with_annotate_hunks(true);
+	let (annotated, _) = complete_with_options(original, "@@\n a\n-b\n+B\n", &options)?;
+
+	// -- Exec
+	let (completed, tier) = complete(original, &annotated)?;
+
+	// -- Check
+	assert!(annotated.starts_with("# udiffx: hunk 1 matched at 1 via Strict"), "{annotated}");
+	assert!(!completed.contains("# udiffx:"), "{completed}");
+	assert_eq!(tier, Some(MatchTier::Strict));
+
+	Ok(())
+}
+
+// -- Line Index Tests
+
+#[test]
+fn test_patch_completer_complete_with_line_index_reused() -> Result<()> {
+	// -- Setup & Fixtures
+	let original = "fn main() {\r\n    let x = 1;\r\n    let y = 2;\r\n}\r\n";
+	let lines: Vec<&str> = original.lines().collect();
+	let orig_index = LineIndex::from(lines.as_slice());
+	let options = CompleteOptions::default();
+	let patches = [
+		"@@\n fn main() {\n-    let x = 1;\n+    let x = 10;\n",
+		"@@\n-    let y = 2;\n+    let y = 20;\n }\n",
+	];
+
+	// -- Exec & Check
+	for patch in patches {
+		let from_index = complete_with_line_index(&orig_index, patch, &options)?;
+		let from_content = complete_with_options(original, patch, &options)?;
+		assert_eq!(from_index, from_content);
+		assert_eq!(from_index.1, Some(MatchTier::Strict));
+	}
+
+	Ok(())
+}
@@ -7,12 +7,42 @@ pub enum MatchTier {
 	Fuzzy,
 }
 
+/// A context/removal line of a hunk that matched its content line non-strictly (e.g., in a preview,
+/// where the applier took liberties).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LenientLine {
+	/// The 1-based line of the content.
+	pub line: usize,
+	/// `Resilient` (whitespace, truncation, or heading level differences) or `Fuzzy`.
+	pub tier: MatchTier,
+	/// Whether the hunk line only matched as a truncated line (suffix/prefix match).
+	pub truncated: bool,
+}
+
 pub(super) struct HunkBounds {
 	pub(super) old_start: usize,
 	pub(super) old_count: usize,
 	pub(super) new_count: usize,
 	pub(super) final_hunk_lines: Vec<String>,
 	pub(super) tier: Option<MatchTier>,
+	/// Whether a context/removal line only matched as a truncated line (suffix/prefix match).
+	pub(super) truncation_matched: bool,
+	/// The fraction (`0.0..=1.0`) of the matched context/removal lines equal to the file lines.
+	pub(super) score: f64,
+	/// The matched context/removal lines not equal to the file lines.
+	pub(super) lenient_lines: Vec<LenientLine>,
+}
+
+/// A completed patch with how it was matched, see `complete_with_report`.
+pub(crate) struct CompletedPatch {
+	pub(crate) patch: String,
+	pub(crate) tier: Option<MatchTier>,
+	/// Whether a hunk needed the truncation (suffix/prefix) matching.
+	pub(crate) truncation_matched: bool,
+	/// The lowest hunk score (see `HunkBounds::score`).
+	pub(crate) score: f64,
+	/// The context/removal lines of the hunks matched non-strictly (see `HunkBounds::lenient_lines`).
+	pub(crate) lenient_lines: Vec<LenientLine>,
 }
 
 /// Contextual hints derived from adjacent hunks for disambiguation scoring.
@@ -0,0 +1,102 @@
+use crate::{Error, Result};
+use simple_fs::{ListOptions, SPath, list_files};
+
+/// Maximum edit distance between a missing path and an existing path to be considered "very close".
+const CLOSE_PATH_MAX_DISTANCE: usize = 2;
+
+/// Directories never considered when looking for a fuzzy path candidate.
+const EXCLUDE_GLOBS: &[&str] = &["**/.git/**", "**/target/**", "**/node_modules/**"];
+
+/// Lists the files under `base_dir` as sorted relative paths (with `/` separators), excluding `EXCLUDE_GLOBS`.
+pub(crate) fn list_rel_paths(base_dir: &SPath) -> Result<Vec<String>> {
+	let files = list_files(base_dir, Some(&["**/*"]), Some(ListOptions::new(Some(EXCLUDE_GLOBS))))?;
+	let mut rel_paths: Vec<String> = files
+		.iter()
+		.filter_map(|f| f.diff(base_dir.path()).map(|p| to_slash_path(p.as_str())))
+		.collect();
+	rel_paths.sort();
+	Ok(rel_paths)
+}
+
+/// Resolves a missing directive path to one of the existing `rel_paths` (relative to the same base dir).
+///
+/// - First looks for files with the same basename anywhere under `base_dir`.
+/// - If none, looks for files whose relative path is within `CLOSE_PATH_MAX_DISTANCE` edits.
+///
+/// Returns `Ok(None)` when no candidate is found, and `Err` when multiple candidates are found.
+pub(crate) fn resolve_fuzzy_path(rel_paths: &[String], rel_path: &str) -> Result<Option<String>> {
+	let basename = rel_path.rsplit('/').next().unwrap_or(rel_path);
+	let same_basename: Vec<&String> = rel_paths
+		.iter()
+		.filter(|p| p.rsplit('/').next() == Some(basename))
+		.collect();
+
+	let candidates = if !same_basename.is_empty() {
+		same_basename
+	} else {
+		rel_paths
+			.iter()
+			.filter(|p| edit_distance(p, rel_path) <= CLOSE_PATH_MAX_DISTANCE)
+			.collect()
+	};
+
+	match candidates.as_slice() {
+		[] => Ok(None),
+		[single] => Ok(Some(single.to_string())),
+		_ => Err(Error::apply_ambiguous_path(
+			rel_path,
+			candidates.into_iter().cloned().collect::<Vec<_>>(),
+		)),
+	}
+}
+
+// region:    --- Support
+
+/// Returns the relative path with `/` separators (for output identical across platforms).
+pub(crate) fn to_slash_path(rel_path: &str) -> String {
+	if std::path::MAIN_SEPARATOR == '\\' {
+		rel_path.replace('\\', "/")
+	} else {
+		rel_path.to_string()
+	}
+}
+
+/// Levenshtein distance between two strings (by `char`).
+fn edit_distance(a: &str, b: &str) -> usize {
+	let b_chars: Vec<char> = b.chars().collect();
+	let mut prev: Vec<usize> = (0..=b_chars.len()).collect();
+
+	for (i, a_ch) in a.chars().enumerate() {
+		let mut curr = vec![i + 1; b_chars.len() + 1];
+		for (j, b_ch) in b_chars.iter().enumerate() {
+			let cost = if a_ch == *b_ch { 0 } else { 1 };
+			curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+		}
+		prev = curr;
+	}
+
+	prev[b_chars.len()]
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_path_resolver_edit_distance() -> Result<()> {
+		// -- Exec & Check
+		assert_eq!(edit_distance("src/util.rs", "src/utils.rs"), 1);
+		assert_eq!(edit_distance("src/util.rs", "src/util.rs"), 0);
+		assert_eq!(edit_distance("a.rs", "main.rs"), 3);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,175 @@
+use crate::apply_store::ApplyStore;
+use crate::path_resolver::to_slash_path;
+use crate::{ApplyOptions, Error, Result};
+use simple_fs::SPath;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// A check of the content a directive writes, e.g., a `syn::parse_file` of the Rust files, or a JSON parse
+/// of the `.json` files, to catch a patch producing a syntactically broken file.
+///
+/// A failed validation fails the directive with `Error::ApplyValidationFailed`, and the file keeps its
+/// previous content (the content is validated before being written). Only the UTF-8 contents are validated.
+///
+/// See `ApplyOptions::post_apply_validators`.
+pub trait PostApplyValidator: Send + Sync {
+	/// Whether the file (path relative to the base dir, `/` separated) is validated, e.g., by its extension.
+	/// (default true)
+	fn applies_to(&self, _rel_path: &str) -> bool {
+		true
+	}
+
+	/// Validates the new content of the file, the error being the cause reported.
+	fn validate(&self, rel_path: &str, content: &str) -> Result<()>;
+}
+
+impl fmt::Debug for dyn PostApplyValidator {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str("PostApplyValidator")
+	}
+}
+
+// region:    --- ValidatingStore
+
+/// A store validating the contents written to another store with the `ApplyOptions::post_apply_validators`,
+/// refusing the invalid ones.
+pub(crate) struct ValidatingStore<'a, S: ApplyStore> {
+	base: &'a mut S,
+	base_dir: SPath,
+	validators: Vec<Arc<dyn PostApplyValidator>>,
+}
+
+impl<'a, S: ApplyStore> ValidatingStore<'a, S> {
+	pub(crate) fn new(base: &'a mut S, base_dir: &SPath, options: &ApplyOptions) -> Self {
+		Self {
+			base,
+			base_dir: base_dir.clone(),
+			validators: options.post_apply_validators.clone(),
+		}
+	}
+
+	pub(crate) fn base_mut(&mut self) -> &mut S {
+		self.base
+	}
+
+	fn rel_path(&self, path: &SPath) -> String {
+		path.diff(&self.base_dir)
+			.map(|p| to_slash_path(p.as_str()))
+			.unwrap_or_else(|| path.to_string())
+	}
+
+	fn applies_to(&self, rel_path: &str) -> bool {
+		self.validators.iter().any(|validator| validator.applies_to(rel_path))
+	}
+
+	fn validate(&self, rel_path: &str, content: &[u8]) -> Result<()> {
+		let Ok(content) = std::str::from_utf8(content) else {
+			return Ok(());
+		};
+		for validator in self
+			.validators
+			.iter()
+			.filter(|validator| validator.applies_to(rel_path))
+		{
+			validator
+				.validate(rel_path, content)
+				.map_err(|err| Error::apply_validation_failed(rel_path, err.to_string()))?;
+		}
+		Ok(())
+	}
+}
+
+impl<S: ApplyStore> ApplyStore for ValidatingStore<'_, S> {
+	fn exists(&self, path: &SPath) -> bool {
+		self.base.exists(path)
+	}
+
+	fn is_dir(&self, path: &SPath) -> bool {
+		self.base.is_dir(path)
+	}
+
+	fn read_to_string(&self, path: &SPath) -> Result<String> {
+		self.base.read_to_string(path)
+	}
+
+	fn read_bytes(&self, path: &SPath) -> Result<Vec<u8>> {
+		self.base.read_bytes(path)
+	}
+
+	fn write(&mut self, path: &SPath, content: &[u8]) -> Result<()> {
+		self.validate(&self.rel_path(path), content)?;
+		self.base.write(path, content)
+	}
+
+	fn rename(&mut self, from: &SPath, to: &SPath) -> Result<()> {
+		self.base.rename(from, to)
+	}
+
+	fn delete(&mut self, path: &SPath) -> Result<()> {
+		self.base.delete(path)
+	}
+
+	fn remove(&mut self, path: &SPath) -> Result<()> {
+		self.base.remove(path)
+	}
+
+	fn list_rel_paths(&self, base_dir: &SPath) -> Result<Vec<String>> {
+		self.base.list_rel_paths(base_dir)
+	}
+
+	fn is_virtual(&self) -> bool {
+		self.base.is_virtual()
+	}
+
+	fn is_read_only(&self, path: &SPath) -> bool {
+		self.base.is_read_only(path)
+	}
+
+	fn set_read_only(&mut self, path: &SPath, read_only: bool) -> Result<()> {
+		self.base.set_read_only(path, read_only)
+	}
+
+	fn is_symlink(&self, path: &SPath) -> bool {
+		self.base.is_symlink(path)
+	}
+
+	fn write_backup(&mut self, path: &SPath, content: &[u8]) -> Result<()> {
+		self.base.write_backup(path, content)
+	}
+
+	fn is_same_file(&self, a: &SPath, b: &SPath) -> bool {
+		self.base.is_same_file(a, b)
+	}
+
+	fn remove_symlink(&mut self, path: &SPath) -> Result<()> {
+		self.base.remove_symlink(path)
+	}
+
+	fn read_link(&self, path: &SPath) -> Option<PathBuf> {
+		self.base.read_link(path)
+	}
+
+	fn create_symlink(&mut self, path: &SPath, target: &Path) -> Result<()> {
+		self.base.create_symlink(path, target)
+	}
+
+	/// A validated file is written from memory (the blob is loaded to be validated), otherwise the blob is
+	/// streamed by the base store.
+	fn write_blob(&mut self, path: &SPath, blob_path: &SPath, append: bool) -> Result<()> {
+		if !self.applies_to(&self.rel_path(path)) {
+			return self.base.write_blob(path, blob_path, append);
+		}
+		let blob = std::fs::read(blob_path).map_err(|err| Error::io_read_file(blob_path.to_string(), err))?;
+		let content = if append && self.base.exists(path) {
+			let mut content = self.base.read_bytes(path)?;
+			content.extend(blob);
+			content
+		} else {
+			blob
+		};
+		self.write(path, &content)
+	}
+}
+
+// endregion: --- ValidatingStore
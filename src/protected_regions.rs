@@ -0,0 +1,122 @@
+/// The marker (typically in a comment, e.g., `// udiffx:protect-start`) beginning a protected region.
+const PROTECT_START_MARKER: &str = "udiffx:protect-start";
+
+/// The marker ending a protected region (a region without an end marker is protected until the end of the file).
+const PROTECT_END_MARKER: &str = "udiffx:protect-end";
+
+/// A region of a file the applier must never modify (marker lines included).
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ProtectedRegion<'a> {
+	/// The 1-based line of the start marker.
+	line: usize,
+	lines: Vec<&'a str>,
+	/// Whether the region has no end marker (so it must stay at the end of the file).
+	to_eof: bool,
+}
+
+/// Returns the line of the first protected region of `original` that is not kept as is in `new_content`
+/// (`None` when all the protected regions, if any, are untouched).
+///
+/// The regions must be found in order (and a region without an end marker must stay at the end of the file),
+/// with their line endings ignored.
+pub(crate) fn modified_protected_region(original: &str, new_content: &str) -> Option<usize> {
+	let regions = protected_regions(original);
+	if regions.is_empty() {
+		return None;
+	}
+
+	let new_lines: Vec<&str> = new_content.lines().map(|l| l.trim_end_matches('\r')).collect();
+	let mut search_from = 0;
+	for region in regions {
+		let remaining = new_lines.get(search_from..).unwrap_or_default();
+		let found = if region.to_eof {
+			remaining
+				.ends_with(&region.lines)
+				.then(|| remaining.len() - region.lines.len())
+		} else {
+			remaining
+				.windows(region.lines.len())
+				.position(|window| window == region.lines.as_slice())
+		};
+		match found {
+			Some(offset) => search_from += offset + region.lines.len(),
+			None => return Some(region.line),
+		}
+	}
+
+	None
+}
+
+// region:    --- Support
+
+fn protected_regions(content: &str) -> Vec<ProtectedRegion<'_>> {
+	let mut regions: Vec<ProtectedRegion> = Vec::new();
+	let mut current: Option<ProtectedRegion> = None;
+
+	for (idx, line) in content.lines().enumerate() {
+		let line = line.trim_end_matches('\r');
+		match current.as_mut() {
+			Some(region) => {
+				region.lines.push(line);
+				if line.contains(PROTECT_END_MARKER) {
+					region.to_eof = false;
+					regions.extend(current.take());
+				}
+			}
+			None if line.contains(PROTECT_START_MARKER) => {
+				current = Some(ProtectedRegion {
+					line: idx + 1,
+					lines: vec![line],
+					to_eof: true,
+				});
+			}
+			None => (),
+		}
+	}
+	regions.extend(current);
+
+	regions
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_protected_regions_modified_protected_region() -> Result<()> {
+		// -- Setup & Fixtures
+		let original = "a\n// udiffx:protect-start\nlicense\n// udiffx:protect-end\nb\n";
+		let kept = "a\nA\n// udiffx:protect-start\nlicense\n// udiffx:protect-end\nB\n";
+		let modified = "a\n// udiffx:protect-start\nLICENSE\n// udiffx:protect-end\nb\n";
+
+		// -- Exec & Check
+		assert_eq!(modified_protected_region(original, kept), None);
+		assert_eq!(modified_protected_region(original, modified), Some(2));
+		assert_eq!(modified_protected_region(original, "a\nb\n"), Some(2));
+		assert_eq!(modified_protected_region("x\n", "y\n"), None);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_protected_regions_unterminated_until_eof() -> Result<()> {
+		// -- Setup & Fixtures
+		let original = "fn a() {}\n// udiffx:protect-start\n// generated\nfn b() {}\n";
+		let kept = "fn a() { x() }\n// udiffx:protect-start\n// generated\nfn b() {}\n";
+		let appended = format!("{original}fn c() {{}}\n");
+
+		// -- Exec & Check
+		assert_eq!(modified_protected_region(original, kept), None);
+		assert_eq!(modified_protected_region(original, &appended), Some(2));
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
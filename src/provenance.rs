@@ -0,0 +1,182 @@
+use crate::{ApplyChangesStatus, DirectiveKind};
+use diffy::Line;
+use std::collections::BTreeMap;
+
+/// Where a line of the content written by a directive comes from, relative to the content before the directive
+/// (see `ApplyOptions::record_provenance`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineOrigin {
+	/// Kept from the 0-based line of the content before the directive.
+	Kept(usize),
+	/// Added by the directive.
+	Added,
+	/// Replacing a removed line of the content before the directive.
+	Modified,
+}
+
+/// Where a line of a file at the end of a batch comes from (see `ApplyChangesStatus::provenance`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineProvenance {
+	/// The line was in the file before the batch.
+	Unchanged,
+	/// Added by the directive at this 0-based index of the batch.
+	Added { directive: usize },
+	/// Modified by the directive at this 0-based index of the batch.
+	Modified { directive: usize },
+}
+
+impl ApplyChangesStatus {
+	/// The provenance of each line of the files written by the batch (the index being the 0-based final line),
+	/// by file path, composed from the `DirectiveStatus::line_origins` of the directives in order.
+	///
+	/// A renamed file keeps its provenance, and a deleted file is dropped.
+	/// Empty when the batch was applied without `record_provenance`.
+	pub fn provenance(&self) -> BTreeMap<String, Vec<LineProvenance>> {
+		let mut files: BTreeMap<String, Vec<LineProvenance>> = BTreeMap::new();
+
+		// Note: The directives which did not change anything (failed, skipped, rolled back) have no diff stats.
+		for (index, item) in self
+			.items
+			.iter()
+			.enumerate()
+			.filter(|(_, item)| item.diff_stats.is_some())
+		{
+			match &item.kind {
+				DirectiveKind::Rename { from_path, file_path } => {
+					for (path, lines) in take_under(&mut files, from_path) {
+						let moved_path = format!("{file_path}{}", &path[from_path.len()..]);
+						files.insert(moved_path, lines);
+					}
+				}
+				DirectiveKind::Delete { file_path } => {
+					take_under(&mut files, file_path);
+				}
+				_ => {
+					let Some(line_origins) = &item.line_origins else {
+						continue;
+					};
+					let previous = files.remove(item.file_path()).unwrap_or_default();
+					let lines = line_origins
+						.iter()
+						.map(|origin| match origin {
+							LineOrigin::Kept(line) => previous.get(*line).copied().unwrap_or(LineProvenance::Unchanged),
+							LineOrigin::Added => LineProvenance::Added { directive: index },
+							LineOrigin::Modified => LineProvenance::Modified { directive: index },
+						})
+						.collect();
+					files.insert(item.file_path().to_string(), lines);
+				}
+			}
+		}
+
+		files
+	}
+}
+
+/// Computes the origin of each line of `new` relative to `old`.
+///
+/// In a changed block, the added lines replacing removed lines are `Modified` (paired in order), the others `Added`.
+pub(crate) fn line_origins(old: &str, new: &str) -> Vec<LineOrigin> {
+	let patch = diffy::create_patch(old, new);
+	let mut origins = Vec::new();
+	let mut old_idx = 0;
+	for hunk in patch.hunks() {
+		let range = hunk.old_range();
+		let hunk_start = if range.is_empty() {
+			range.start()
+		} else {
+			range.start() - 1
+		};
+		while old_idx < hunk_start {
+			origins.push(LineOrigin::Kept(old_idx));
+			old_idx += 1;
+		}
+		// The removed lines not yet paired with an added line.
+		let mut removed = 0;
+		for line in hunk.lines() {
+			match line {
+				Line::Context(_) => {
+					removed = 0;
+					origins.push(LineOrigin::Kept(old_idx));
+					old_idx += 1;
+				}
+				Line::Delete(_) => {
+					removed += 1;
+					old_idx += 1;
+				}
+				Line::Insert(_) if removed > 0 => {
+					removed -= 1;
+					origins.push(LineOrigin::Modified);
+				}
+				Line::Insert(_) => origins.push(LineOrigin::Added),
+			}
+		}
+	}
+
+	// Lines past the last hunk are kept from the original in order.
+	let new_len = new.lines().count();
+	while origins.len() < new_len {
+		origins.push(LineOrigin::Kept(old_idx));
+		old_idx += 1;
+	}
+
+	origins
+}
+
+// region:    --- Support
+
+/// Removes the entries at `path` or under it (as a directory).
+fn take_under(files: &mut BTreeMap<String, Vec<LineProvenance>>, path: &str) -> Vec<(String, Vec<LineProvenance>)> {
+	let dir_prefix = format!("{path}/");
+	let keys: Vec<String> = files
+		.keys()
+		.filter(|key| *key == path || key.starts_with(&dir_prefix))
+		.cloned()
+		.collect();
+	keys.into_iter()
+		.filter_map(|key| files.remove(&key).map(|lines| (key, lines)))
+		.collect()
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_provenance_line_origins() -> Result<()> {
+		// -- Setup & Fixtures
+		let old = "a\nb\nc\nd\ne\nf\ng\nh\n";
+		let new = "a\nB\nx\nc\nd\ne\nf\ng\nh\ni\n";
+
+		// -- Exec
+		let origins = line_origins(old, new);
+
+		// -- Check
+		use LineOrigin::*;
+		assert_eq!(
+			origins,
+			[
+				Kept(0),
+				Modified,
+				Added,
+				Kept(2),
+				Kept(3),
+				Kept(4),
+				Kept(5),
+				Kept(6),
+				Kept(7),
+				Added
+			]
+		);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,185 @@
+use crate::{Content, DirectiveStatus, FileChanges, FileDirective};
+use std::fmt;
+use std::sync::Arc;
+
+/// A redaction hook applied to the text udiffx reports (directive bodies, error messages, hunk excerpts),
+/// e.g., to mask the API keys matched by a regex before an audit log or a retry prompt.
+///
+/// See `ApplyOptions::redactor`, `DirectiveStatus::redact`, and `FileChanges::redacted`.
+#[derive(Clone)]
+pub struct Redactor {
+	redact_fn: Arc<dyn Fn(&str) -> String + Send + Sync>,
+}
+
+impl Redactor {
+	/// The `redact_fn` returns the given text with the sensitive parts masked.
+	pub fn new(redact_fn: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+		Self {
+			redact_fn: Arc::new(redact_fn),
+		}
+	}
+
+	pub fn redact(&self, text: &str) -> String {
+		(self.redact_fn)(text)
+	}
+}
+
+impl fmt::Debug for Redactor {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str("Redactor")
+	}
+}
+
+impl DirectiveStatus {
+	/// Redacts the reported texts: the error message, the warnings, the failed hunks (body and cause),
+	/// the no-op edits (current and proposed lines), and the applied diff.
+	pub fn redact(&mut self, redactor: &Redactor) {
+		if let Some(error_msg) = &mut self.error_msg {
+			*error_msg = redactor.redact(error_msg);
+		}
+		for warning in &mut self.warnings {
+			*warning = redactor.redact(warning);
+		}
+		for hunk_error in &mut self.error_hunks {
+			hunk_error.hunk_body = redactor.redact(&hunk_error.hunk_body);
+			hunk_error.cause = redactor.redact(&hunk_error.cause);
+		}
+		for noop_edit in &mut self.noop_edits {
+			noop_edit.current = redactor.redact(&noop_edit.current);
+			noop_edit.proposed = redactor.redact(&noop_edit.proposed);
+		}
+		if let Some(applied_diff) = &mut self.applied_diff {
+			*applied_diff = redactor.redact(applied_diff);
+		}
+	}
+}
+
+impl FileChanges {
+	/// A copy of the directives with their bodies (and `Fail` error messages) redacted, e.g., to log them
+	/// or send them back to a model.
+	///
+	/// A body spilled to a `BlobStore` is loaded (and redacted in memory).
+	pub fn redacted(&self, redactor: &Redactor) -> FileChanges {
+		let redact_content = |content: &Content| Content {
+			content: redactor.redact(&content.load().unwrap_or_default()),
+			code_fence: content.code_fence.clone(),
+			blob_path: None,
+		};
+
+		let directives = self
+			.iter()
+			.map(|directive| match directive {
+				FileDirective::New { file_path, content } => FileDirective::New {
+					file_path: file_path.clone(),
+					content: redact_content(content),
+				},
+				FileDirective::Patch {
+					file_path,
+					content,
+					allow_generated,
+				} => FileDirective::Patch {
+					file_path: file_path.clone(),
+					content: redact_content(content),
+					allow_generated: *allow_generated,
+				},
+				FileDirective::Append { file_path, content } => FileDirective::Append {
+					file_path: file_path.clone(),
+					content: redact_content(content),
+				},
+				FileDirective::SpanPatch {
+					file_path,
+					line,
+					columns,
+					line_hash,
+					replacement,
+				} => FileDirective::SpanPatch {
+					file_path: file_path.clone(),
+					line: *line,
+					columns: columns.clone(),
+					line_hash: line_hash.clone(),
+					replacement: redactor.redact(replacement),
+				},
+				FileDirective::Insert {
+					file_path,
+					anchor,
+					regex,
+					position,
+					content,
+				} => FileDirective::Insert {
+					file_path: file_path.clone(),
+					anchor: anchor.clone(),
+					regex: *regex,
+					position: *position,
+					content: redactor.redact(content),
+				},
+				FileDirective::GlobalReplace {
+					glob,
+					pattern,
+					replacement,
+					regex,
+					max_matches,
+				} => FileDirective::GlobalReplace {
+					glob: glob.clone(),
+					pattern: pattern.clone(),
+					replacement: redactor.redact(replacement),
+					regex: *regex,
+					max_matches: *max_matches,
+				},
+				FileDirective::Fail {
+					kind,
+					file_path,
+					error_msg,
+				} => FileDirective::Fail {
+					kind: kind.clone(),
+					file_path: file_path.clone(),
+					error_msg: redactor.redact(error_msg),
+				},
+				other => other.clone(),
+			})
+			.collect();
+
+		FileChanges::new(directives)
+	}
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_redactor_file_changes_redacted() -> Result<()> {
+		// -- Setup & Fixtures
+		let redactor = Redactor::new(|text| text.replace("sk-123", "[REDACTED]"));
+		let changes = FileChanges::new(vec![
+			FileDirective::New {
+				file_path: ".env".to_string(),
+				content: Content::from_verbatim("KEY=sk-123\n".to_string()),
+			},
+			FileDirective::Fail {
+				kind: "FILE_PATCH".to_string(),
+				file_path: None,
+				error_msg: "Bad body 'sk-123'".to_string(),
+			},
+		]);
+
+		// -- Exec
+		let redacted = changes.redacted(&redactor);
+
+		// -- Check
+		let markdown = redacted.to_markdown();
+		assert!(markdown.contains("KEY=[REDACTED]\n"), "{markdown}");
+		assert!(!markdown.contains("sk-123"));
+		let Some(FileDirective::Fail { error_msg, .. }) = redacted.iter().nth(1) else {
+			return Err("Should have a Fail directive".into());
+		};
+		assert_eq!(error_msg, "Bad body '[REDACTED]'");
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
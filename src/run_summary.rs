@@ -0,0 +1,151 @@
+use crate::patch_completer::split_raw_hunks;
+use crate::{
+	ApplyChangesStatus, DirectiveOutcome, DirectiveStatus, FileChanges, FileDirective, MatchTier, TierHistogram,
+};
+
+/// Max chars of the added line describing a hunk (longer lines are cut with `…`).
+const HUNK_DESCRIPTION_MAX_CHARS: usize = 72;
+
+/// Renders a markdown summary of an apply run, meant to be pasted into a pull-request body.
+///
+/// `file_changes` are the applied changes, and `status` the status returned by the apply
+/// (its items are matched to the directives by position).
+///
+/// The summary has the diff stats, a files changed table, the hunks of each patch described by their first added line,
+/// the warnings, and the match tier usage.
+pub fn run_summary_markdown(file_changes: &FileChanges, status: &ApplyChangesStatus) -> String {
+	let entries: Vec<(&FileDirective, &DirectiveStatus)> = file_changes.iter().zip(&status.items).collect();
+
+	let mut out = String::from("## Summary\n\n");
+	out.push_str(&format!("{}\n", status.diff_stats()));
+
+	// -- Files changed table
+	out.push_str("\n| File | Change | Status | +/- |\n|---|---|---|---|\n");
+	for (_, info) in &entries {
+		let stats = info
+			.diff_stats
+			.map(|s| format!("+{} -{}", s.insertions, s.deletions))
+			.unwrap_or_default();
+		out.push_str(&format!(
+			"| `{}` | {} | {} | {stats} |\n",
+			info.file_path(),
+			info.kind(),
+			status_label(info)
+		));
+	}
+
+	// -- Hunks
+	let mut hunks_section = String::new();
+	for (directive, info) in &entries {
+		let FileDirective::Patch { content, .. } = directive else {
+			continue;
+		};
+		hunks_section.push_str(&format!("- `{}`\n", info.file_path()));
+		for (idx, hunk) in split_raw_hunks(&content.content).iter().enumerate() {
+			let failed = if info.error_hunks.iter().any(|h| h.hunk_index == idx) {
+				" (failed)"
+			} else {
+				""
+			};
+			hunks_section.push_str(&format!("  - Hunk {}{failed}: {}\n", idx + 1, describe_hunk(hunk)));
+		}
+	}
+	if !hunks_section.is_empty() {
+		out.push_str("\n### Hunks\n\n");
+		out.push_str(&hunks_section);
+	}
+
+	// -- Warnings
+	let warnings: Vec<String> = entries
+		.iter()
+		.flat_map(|(_, info)| info.warnings.iter().map(|w| format!("- `{}`: {w}\n", info.file_path())))
+		.collect();
+	if !warnings.is_empty() {
+		out.push_str("\n### Warnings\n\n");
+		out.push_str(&warnings.concat());
+	}
+
+	// -- Tiers (of the patches with at least one applied hunk)
+	let mut histogram = TierHistogram::default();
+	for (_, info) in entries
+		.iter()
+		.filter(|(d, info)| matches!(d, FileDirective::Patch { .. }) && (info.success || info.match_tier.is_some()))
+	{
+		match info.match_tier {
+			Some(MatchTier::Strict) => histogram.strict += 1,
+			Some(MatchTier::Resilient) => histogram.resilient += 1,
+			Some(MatchTier::Fuzzy) => histogram.fuzzy += 1,
+			None => histogram.none += 1,
+		}
+	}
+	if histogram != TierHistogram::default() {
+		out.push_str(&format!("\n### Match tiers\n\n{histogram}\n"));
+	}
+
+	out
+}
+
+// region:    --- Support
+
+fn status_label(info: &DirectiveStatus) -> String {
+	if !info.error_hunks.is_empty() && info.applied_hunk_count() > 0 {
+		return format!("partial ({} of {} hunks)", info.applied_hunk_count(), info.total_hunks);
+	}
+	if info.outcome() == DirectiveOutcome::NoChange {
+		return "no change".to_string();
+	}
+	match (info.success, info.error_msg.as_deref()) {
+		(true, _) => "applied".to_string(),
+		(false, Some(msg)) => format!("failed: {}", msg.lines().next().unwrap_or_default().replace('|', "\\|")),
+		(false, None) => "failed".to_string(),
+	}
+}
+
+/// One line describing a raw hunk: its first non-blank added line, or the count of removed lines.
+fn describe_hunk(hunk: &str) -> String {
+	let added = hunk
+		.lines()
+		.filter_map(|line| line.strip_prefix('+'))
+		.map(str::trim)
+		.find(|line| !line.is_empty());
+	if let Some(line) = added {
+		let mut desc: String = line.chars().take(HUNK_DESCRIPTION_MAX_CHARS).collect();
+		if desc.len() < line.len() {
+			desc.push('…');
+		}
+		return format!("adds `{}`", desc.replace('`', "'"));
+	}
+
+	let removed = hunk.lines().filter(|line| line.starts_with('-')).count();
+	format!("removes {removed} line{}", if removed == 1 { "" } else { "s" })
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_run_summary_describe_hunk() -> Result<()> {
+		// -- Exec & Check
+		assert_eq!(
+			describe_hunk("@@\n fn a() {\n+\n+\tlet x = `1`;\n }\n"),
+			"adds `let x = '1';`"
+		);
+		assert_eq!(
+			describe_hunk("@@\n fn a() {\n-\tlet x = 1;\n-\tlet y = 2;\n }\n"),
+			"removes 2 lines"
+		);
+		let long = format!("@@\n+{}\n", "x".repeat(100));
+		assert_eq!(describe_hunk(&long), format!("adds `{}…`", "x".repeat(72)));
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
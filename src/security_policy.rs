@@ -1,4 +1,5 @@
-use crate::{Error, Result};
+use crate::fs_guard::is_path_under;
+use crate::{Error, GuardPolicy, Result};
 use simple_fs::SPath;
 
 /// A configurable, safe-by-default security policy that controls
@@ -27,6 +28,21 @@ pub struct SecurityPolicy {
 
 	/// When `true`, **all** path checks are disabled. (default false)
 	pub bypass_all_checks: bool,
+
+	/// Globs of the paths (relative to `base_dir`, e.g., `src/gen/**`) holding generated files,
+	/// refused by `FILE_PATCH` when `ApplyOptions::refuse_generated` is set.
+	pub generated_globs: Vec<String>,
+
+	/// The deny and allow globs of the paths the directives can read or write, checked on top of the
+	/// base dir containment (e.g., `GuardPolicy::recommended()` to protect `.git/**` and `.env`).
+	/// (default no globs)
+	pub guard_policy: GuardPolicy,
+
+	/// When `true`, a write through a symlink (the target path or one of its dirs under the base dir is a symlink)
+	/// is refused, even when the link resolves inside the base dir. (default false)
+	///
+	/// Note: A symlink resolving outside of the base dir (and of the `writable_dirs`) is always refused.
+	pub deny_symlink_writes: bool,
 }
 
 /// Constructors
@@ -71,7 +87,7 @@ impl SecurityPolicy {
 		let target_str = target.as_str();
 		// Check explicit writable directories
 		for wd in &self.writable_dirs {
-			if target_str.starts_with(wd.as_str()) {
+			if is_path_under(target_str, wd.as_str()) {
 				return Ok(());
 			}
 		}
@@ -79,7 +95,7 @@ impl SecurityPolicy {
 		use std::env;
 		let cwd = env::current_dir().map_err(|e| Error::io_read_file(".", e))?;
 		let cwd_spath = SPath::from_std_path(cwd).map_err(|e| Error::custom(format!("invalid CWD: {e}")))?;
-		if !target_str.starts_with(cwd_spath.as_str()) {
+		if !is_path_under(target_str, cwd_spath.as_str()) {
 			return Err(Error::security_violation(target.to_string(), cwd_spath.to_string()));
 		}
 		Ok(())
@@ -95,12 +111,12 @@ impl SecurityPolicy {
 			return Ok(());
 		}
 		// Check base_dir first (most common case)
-		if target.as_str().starts_with(base_dir.as_str()) {
+		if is_path_under(target.as_str(), base_dir.as_str()) {
 			return Ok(());
 		}
 		// Check explicit writable directories
 		for wd in &self.writable_dirs {
-			if target.as_str().starts_with(wd.as_str()) {
+			if is_path_under(target.as_str(), wd.as_str()) {
 				return Ok(());
 			}
 		}
@@ -143,6 +159,24 @@ impl SecurityPolicy {
 		self
 	}
 
+	/// Override the generated file globs with the given iterator.
+	pub fn with_generated_globs(mut self, globs: impl IntoIterator<Item = impl Into<String>>) -> Self {
+		self.generated_globs = globs.into_iter().map(|g| g.into()).collect();
+		self
+	}
+
+	/// Set the deny and allow globs of the paths the directives can read or write.
+	pub fn with_guard_policy(mut self, guard_policy: GuardPolicy) -> Self {
+		self.guard_policy = guard_policy;
+		self
+	}
+
+	/// Refuse the writes through a symlink, even resolving inside the base dir.
+	pub fn with_deny_symlink_writes(mut self) -> Self {
+		self.deny_symlink_writes = true;
+		self
+	}
+
 	/// Append additional writable directories to the existing list.
 	pub fn append_writable_dirs(mut self, dirs: impl IntoIterator<Item = impl Into<SPath>>) -> Self {
 		self.writable_dirs.extend(dirs.into_iter().map(|d| d.into()));
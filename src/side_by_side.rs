@@ -0,0 +1,256 @@
+use crate::patch_completer::is_hunk_annotation;
+use crate::{AppliedText, LenientLine, make_patch};
+
+/// One side of a side-by-side row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+	/// 1-based line number in the original (left) or modified (right) content.
+	pub number: usize,
+	pub text: String,
+}
+
+/// The kind of a side-by-side row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowKind {
+	/// Same line on both sides.
+	Context,
+	/// Line only on the left side.
+	Removed,
+	/// Line only on the right side.
+	Added,
+	/// Left line replaced by the right line.
+	Changed,
+}
+
+/// A row of a split view, where `left` is the original line and `right` the modified line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowPair {
+	pub left: Option<Line>,
+	pub right: Option<Line>,
+	pub kind: RowKind,
+	/// How the applier matched the left line non-strictly, if it did (see `side_by_side_preview`).
+	pub lenient_match: Option<LenientLine>,
+}
+
+/// Builds the side-by-side rows of a completed (numbered) unified diff, such as
+/// `ApplyPatchIncrementalData.completed_patches` items or the output of `make_patch`.
+///
+/// Within a hunk, a block of removed lines followed by a block of added lines is aligned
+/// line by line as `Changed` rows, and the remainder is emitted as `Removed` or `Added` rows.
+/// File headers and `\ No newline at end of file` markers are ignored.
+pub fn side_by_side(patch: &str) -> Vec<RowPair> {
+	let mut rows: Vec<RowPair> = Vec::new();
+	let mut left_no = 1;
+	let mut right_no = 1;
+	let mut in_hunk = false;
+	let mut removed: Vec<Line> = Vec::new();
+	let mut added: Vec<Line> = Vec::new();
+
+	for line in patch.lines() {
+		if line.starts_with("@@") {
+			flush_changes(&mut rows, &mut removed, &mut added);
+			if let Some((left_start, right_start)) = parse_hunk_starts(line) {
+				left_no = left_start;
+				right_no = right_start;
+			}
+			in_hunk = true;
+			continue;
+		}
+		if !in_hunk || line.starts_with('\\') || is_hunk_annotation(line) {
+			continue;
+		}
+
+		if let Some(text) = line.strip_prefix('-') {
+			removed.push(Line {
+				number: left_no,
+				text: text.to_string(),
+			});
+			left_no += 1;
+		} else if let Some(text) = line.strip_prefix('+') {
+			added.push(Line {
+				number: right_no,
+				text: text.to_string(),
+			});
+			right_no += 1;
+		} else {
+			flush_changes(&mut rows, &mut removed, &mut added);
+			let text = line.strip_prefix(' ').unwrap_or(line).to_string();
+			rows.push(RowPair {
+				left: Some(Line {
+					number: left_no,
+					text: text.clone(),
+				}),
+				right: Some(Line { number: right_no, text }),
+				kind: RowKind::Context,
+				lenient_match: None,
+			});
+			left_no += 1;
+			right_no += 1;
+		}
+	}
+	flush_changes(&mut rows, &mut removed, &mut added);
+
+	rows
+}
+
+/// Builds the side-by-side rows of the change from `old` to `new` (e.g., for a preview),
+/// with `context_lines` lines of context around each change.
+pub fn side_by_side_from_contents(old: &str, new: &str, context_lines: usize) -> Vec<RowPair> {
+	side_by_side(&make_patch(old, new, context_lines))
+}
+
+/// Builds the side-by-side preview of a patch applied to `original` (see `complete_and_apply`),
+/// with `context_lines` lines of context around each change.
+///
+/// The rows whose left line the applier matched non-strictly (normalized whitespace, truncated line, fuzzy)
+/// have their `lenient_match` set, so a reviewer sees where the applier took liberties.
+pub fn side_by_side_preview(original: &str, applied: &AppliedText, context_lines: usize) -> Vec<RowPair> {
+	let mut original_lf = original.replace("\r\n", "\n");
+	if !original_lf.is_empty() && !original_lf.ends_with('\n') {
+		original_lf.push('\n');
+	}
+
+	let mut rows = side_by_side_from_contents(&original_lf, &applied.content, context_lines);
+	for row in &mut rows {
+		if let Some(left) = &row.left {
+			row.lenient_match = applied
+				.report
+				.lenient_lines
+				.iter()
+				.find(|lenient| lenient.line == left.number)
+				.copied();
+		}
+	}
+	rows
+}
+
+// region:    --- Support
+
+/// Emits the pending removed/added blocks as aligned rows.
+fn flush_changes(rows: &mut Vec<RowPair>, removed: &mut Vec<Line>, added: &mut Vec<Line>) {
+	let mut removed_iter = removed.drain(..);
+	let mut added_iter = added.drain(..);
+
+	loop {
+		let row = match (removed_iter.next(), added_iter.next()) {
+			(Some(left), Some(right)) => RowPair {
+				left: Some(left),
+				right: Some(right),
+				kind: RowKind::Changed,
+				lenient_match: None,
+			},
+			(Some(left), None) => RowPair {
+				left: Some(left),
+				right: None,
+				kind: RowKind::Removed,
+				lenient_match: None,
+			},
+			(None, Some(right)) => RowPair {
+				left: None,
+				right: Some(right),
+				kind: RowKind::Added,
+				lenient_match: None,
+			},
+			(None, None) => break,
+		};
+		rows.push(row);
+	}
+}
+
+/// Parses the start lines of `@@ -l,s +r,s @@`. A `0` start (empty side) is mapped to `1`.
+pub(crate) fn parse_hunk_starts(header: &str) -> Option<(usize, usize)> {
+	let mut parts = header.trim_start_matches('@').split_whitespace();
+	let left = parts.next()?.strip_prefix('-')?;
+	let right = parts.next()?.strip_prefix('+')?;
+	let start = |range: &str| range.split(',').next()?.parse::<usize>().ok().map(|n| n.max(1));
+	Some((start(left)?, start(right)?))
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::MatchTier;
+
+	#[test]
+	fn test_side_by_side_aligns_changed_lines() -> Result<()> {
+		// -- Setup & Fixtures
+		let patch = "@@ -10,4 +10,4 @@\n keep\n-old one\n-old two\n+new one\n same\n+added\n";
+
+		// -- Exec
+		let rows = side_by_side(patch);
+
+		// -- Check
+		let kinds: Vec<RowKind> = rows.iter().map(|r| r.kind).collect();
+		assert_eq!(
+			kinds,
+			vec![
+				RowKind::Context,
+				RowKind::Changed,
+				RowKind::Removed,
+				RowKind::Context,
+				RowKind::Added
+			]
+		);
+		let changed = &rows[1];
+		assert_eq!(
+			changed.left.as_ref().map(|l| (l.number, l.text.as_str())),
+			Some((11, "old one"))
+		);
+		assert_eq!(
+			changed.right.as_ref().map(|l| (l.number, l.text.as_str())),
+			Some((11, "new one"))
+		);
+		let context = &rows[3];
+		assert_eq!(context.left.as_ref().map(|l| l.number), Some(13));
+		assert_eq!(context.right.as_ref().map(|l| l.number), Some(12));
+		assert_eq!(rows[4].right.as_ref().map(|l| l.number), Some(13));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_side_by_side_from_contents_new_file() -> Result<()> {
+		// -- Exec
+		let rows = side_by_side_from_contents("", "a\nb\n", 3);
+
+		// -- Check
+		assert_eq!(rows.len(), 2);
+		assert!(rows.iter().all(|r| r.kind == RowKind::Added && r.left.is_none()));
+		assert_eq!(rows[0].right.as_ref().map(|l| l.number), Some(1));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_side_by_side_preview_marks_lenient_lines() -> Result<()> {
+		// -- Setup & Fixtures
+		let original = "fn main() {\n    let x = 1;\n}\n";
+		let patch = "@@\n fn main()  {\n-  let x = 1;\n+    let x = 2;\n }\n";
+		let applied = crate::complete_and_apply(original, patch, None)?;
+
+		// -- Exec
+		let rows = side_by_side_preview(original, &applied, 3);
+
+		// -- Check
+		let marks: Vec<(RowKind, Option<MatchTier>)> =
+			rows.iter().map(|r| (r.kind, r.lenient_match.map(|l| l.tier))).collect();
+		assert_eq!(
+			marks,
+			[
+				(RowKind::Context, Some(MatchTier::Resilient)),
+				(RowKind::Changed, Some(MatchTier::Resilient)),
+				(RowKind::Context, None),
+			]
+		);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
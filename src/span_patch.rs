@@ -0,0 +1,200 @@
+use crate::content_hash::StableHasher;
+use crate::{Error, Result};
+use std::ops::RangeInclusive;
+
+/// Hex chars of a `line_hash`.
+const LINE_HASH_LEN: usize = 8;
+
+/// The hash of a line (8 hex chars, the end-of-line chars excluded), as given to the model with the file context
+/// so a `FILE_SPAN_PATCH` can reference it (see `FileDirective::SpanPatch`).
+pub fn line_hash(line: &str) -> String {
+	let mut hasher = StableHasher::new();
+	hasher.write_bytes(line.trim_end_matches(['\n', '\r']).as_bytes());
+	hasher.finish_hex()[..LINE_HASH_LEN].to_string()
+}
+
+/// Replaces the `columns` (1-based, inclusive, in graphemes) of the 1-based `line` of the LF-normalized `content`
+/// with `replacement`, if the line still matches `line_hash` (case-insensitive).
+pub(crate) fn apply_span_patch(
+	file_path: &str,
+	content: &str,
+	line: usize,
+	columns: &RangeInclusive<usize>,
+	line_hash_ref: &str,
+	replacement: &str,
+) -> Result<String> {
+	let lines: Vec<&str> = content.split_inclusive('\n').collect();
+	let Some(target) = line.checked_sub(1).and_then(|idx| lines.get(idx)) else {
+		return Err(Error::apply_span_out_of_range(file_path, line, columns, 0));
+	};
+	let (text, eol) = match target.strip_suffix('\n') {
+		Some(text) => (text, "\n"),
+		None => (*target, ""),
+	};
+	if !line_hash(text).eq_ignore_ascii_case(line_hash_ref.trim()) {
+		return Err(Error::apply_span_line_mismatch(file_path, line, line_hash_ref));
+	}
+
+	let clusters = graphemes(text);
+	let (start, end) = (*columns.start(), *columns.end());
+	if start == 0 || start > end || end > clusters.len() {
+		return Err(Error::apply_span_out_of_range(file_path, line, columns, clusters.len()));
+	}
+
+	let mut out = String::with_capacity(content.len() + replacement.len());
+	for before in &lines[..line - 1] {
+		out.push_str(before);
+	}
+	out.push_str(&clusters[..start - 1].concat());
+	out.push_str(replacement);
+	out.push_str(&clusters[end..].concat());
+	out.push_str(eol);
+	for after in &lines[line..] {
+		out.push_str(after);
+	}
+
+	Ok(out)
+}
+
+/// Whether the span patch is applied to `content`: its line no longer matches `line_hash_ref`,
+/// and has `replacement` at the span start.
+pub(crate) fn span_patch_applied(
+	content: &str,
+	line: usize,
+	columns: &RangeInclusive<usize>,
+	line_hash_ref: &str,
+	replacement: &str,
+) -> bool {
+	let Some(text) = line.checked_sub(1).and_then(|idx| content.lines().nth(idx)) else {
+		return false;
+	};
+	if line_hash(text).eq_ignore_ascii_case(line_hash_ref.trim()) {
+		return false;
+	}
+	let clusters = graphemes(text);
+	let start = (*columns.start()).max(1) - 1;
+	start <= clusters.len() && clusters[start..].concat().starts_with(replacement)
+}
+
+/// Parses a `columns` attribute value, `start-end` (1-based, inclusive) or a single column.
+pub(crate) fn parse_columns(value: &str) -> Option<RangeInclusive<usize>> {
+	let (start, end) = match value.split_once('-') {
+		Some((start, end)) => (start.trim().parse().ok()?, end.trim().parse().ok()?),
+		None => {
+			let column = value.trim().parse().ok()?;
+			(column, column)
+		}
+	};
+	(start >= 1 && start <= end).then_some(start..=end)
+}
+
+// region:    --- Support
+
+/// Splits the line into its grapheme clusters (the user-perceived characters, e.g., `é` as `e` + a combining accent,
+/// or a ZWJ emoji sequence), so a column counts as one character in an editor.
+///
+/// Note: An approximation of the Unicode extended grapheme clusters (without a Unicode data dependency):
+///       combining marks, variation selectors, emoji modifiers and tags, ZWJ sequences, and flag pairs.
+fn graphemes(line: &str) -> Vec<&str> {
+	let mut clusters: Vec<&str> = Vec::new();
+	let mut start = 0;
+	let mut prev: Option<char> = None;
+	let mut prev_ri_count = 0;
+	for (idx, ch) in line.char_indices() {
+		let joins = match prev {
+			None => false,
+			Some(prev) => is_extend(ch) || prev == '\u{200D}' || (is_regional_indicator(ch) && prev_ri_count % 2 == 1),
+		};
+		if !joins && idx > 0 {
+			clusters.push(&line[start..idx]);
+			start = idx;
+		}
+		prev_ri_count = if is_regional_indicator(ch) {
+			prev_ri_count + 1
+		} else {
+			0
+		};
+		prev = Some(ch);
+	}
+	if start < line.len() {
+		clusters.push(&line[start..]);
+	}
+	clusters
+}
+
+fn is_extend(ch: char) -> bool {
+	matches!(ch,
+		'\u{0300}'..='\u{036F}'
+		| '\u{1AB0}'..='\u{1AFF}'
+		| '\u{1DC0}'..='\u{1DFF}'
+		| '\u{20D0}'..='\u{20FF}'
+		| '\u{FE20}'..='\u{FE2F}'
+		| '\u{FE00}'..='\u{FE0F}'
+		| '\u{200D}'
+		| '\u{1F3FB}'..='\u{1F3FF}'
+		| '\u{E0020}'..='\u{E007F}'
+		| '\u{E0100}'..='\u{E01EF}')
+}
+
+fn is_regional_indicator(ch: char) -> bool {
+	matches!(ch, '\u{1F1E6}'..='\u{1F1FF}')
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_span_patch_graphemes() -> Result<()> {
+		// -- Exec & Check
+		assert_eq!(graphemes("ab"), ["a", "b"]);
+		assert_eq!(graphemes("e\u{301}x"), ["e\u{301}", "x"]);
+		assert_eq!(graphemes("👩‍💻!"), ["👩‍💻", "!"]);
+		assert_eq!(graphemes("🇫🇷🇯🇵"), ["🇫🇷", "🇯🇵"]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_span_patch_apply_span_patch() -> Result<()> {
+		// -- Setup & Fixtures
+		let content = "fn main() {\n\tlet café = 1;\n}\n";
+		let hash = line_hash("\tlet café = 1;");
+
+		// -- Exec
+		let new_content = apply_span_patch("main.rs", content, 2, &(6..=9), &hash, "total")?;
+
+		// -- Check
+		assert_eq!(new_content, "fn main() {\n\tlet total = 1;\n}\n");
+		let err = apply_span_patch("main.rs", content, 2, &(6..=9), "00000000", "total")
+			.err()
+			.ok_or("should fail")?;
+		assert!(matches!(err, Error::ApplySpanLineMismatch { line: 2, .. }), "{err}");
+		let err = apply_span_patch("main.rs", content, 2, &(6..=20), &hash, "total")
+			.err()
+			.ok_or("should fail")?;
+		assert!(matches!(err, Error::ApplySpanOutOfRange { len: 14, .. }), "{err}");
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_span_patch_parse_columns() -> Result<()> {
+		// -- Exec & Check
+		assert_eq!(parse_columns("9-14"), Some(9..=14));
+		assert_eq!(parse_columns(" 3 "), Some(3..=3));
+		assert_eq!(parse_columns("0-2"), None);
+		assert_eq!(parse_columns("5-4"), None);
+		assert_eq!(parse_columns("x"), None);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
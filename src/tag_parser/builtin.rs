@@ -0,0 +1,173 @@
+use super::{TagElem, TagPart};
+use std::collections::HashMap;
+
+/// Dependency-free tag extraction.
+///
+/// - An element starts at `<NAME` followed by whitespace, `/`, or `>` (earliest match, longest name on ties).
+/// - The opening tag ends at the first `>`, and is self-closing when that `>` is preceded by `/`.
+/// - The content ends at the first `</NAME>`. Without one, the extraction stops (the rest is text).
+pub(super) fn extract_tags(input: &str, tag_names: &[&str], capture_text: bool) -> Vec<TagPart> {
+	let mut parts: Vec<TagPart> = Vec::new();
+	let mut pos = 0;
+	let mut text_start = 0;
+
+	while let Some((start_idx, name)) = find_next_open(input, pos, tag_names) {
+		let after_name = start_idx + 1 + name.len();
+
+		// -- Boundary (e.g., `<FILE_NEWS` is not `<FILE_NEW`)
+		if !matches!(
+			input.as_bytes().get(after_name),
+			Some(b'/' | b'>' | b' ' | b'\n' | b'\t' | b'\r')
+		) {
+			pos = start_idx + 1;
+			continue;
+		}
+
+		// -- Opening tag
+		let Some(gt_offset) = input[start_idx..].find('>') else {
+			break;
+		};
+		let open_end = start_idx + gt_offset;
+		let self_closing = input.as_bytes()[open_end - 1] == b'/';
+		let attrs_end = if self_closing { open_end - 1 } else { open_end };
+		let attrs = parse_attrs(&input[after_name.min(attrs_end)..attrs_end]);
+
+		// -- Content
+		let (content, end) = if self_closing {
+			("", open_end + 1)
+		} else {
+			let close_tag = format!("</{name}>");
+			let Some(close_offset) = input[open_end + 1..].find(&close_tag) else {
+				break;
+			};
+			let close_idx = open_end + 1 + close_offset;
+			(&input[open_end + 1..close_idx], close_idx + close_tag.len())
+		};
+
+		if capture_text && start_idx > text_start {
+			parts.push(TagPart::Text(input[text_start..start_idx].to_string()));
+		}
+		parts.push(TagPart::Elem(TagElem {
+			tag: name.to_string(),
+			attrs,
+			content: content.to_string(),
+		}));
+		pos = end;
+		text_start = end;
+	}
+
+	if capture_text && text_start < input.len() {
+		parts.push(TagPart::Text(input[text_start..].to_string()));
+	}
+
+	parts
+}
+
+// region:    --- Support
+
+/// Finds the earliest `<NAME` at or after `pos` (the longest name wins on ties).
+fn find_next_open<'a>(input: &str, pos: usize, tag_names: &[&'a str]) -> Option<(usize, &'a str)> {
+	let remaining = &input[pos..];
+	let mut selected: Option<(usize, &'a str)> = None;
+
+	for name in tag_names {
+		let Some(offset) = remaining.find(&format!("<{name}")) else {
+			continue;
+		};
+		let idx = pos + offset;
+		selected = match selected {
+			Some((sel_idx, sel_name)) if sel_idx < idx || (sel_idx == idx && sel_name.len() >= name.len()) => {
+				Some((sel_idx, sel_name))
+			}
+			_ => Some((idx, name)),
+		};
+	}
+
+	selected
+}
+
+/// Parses `key="value" key='value' key=value key` attributes (an unclosed quote takes the rest).
+fn parse_attrs(raw: &str) -> HashMap<String, String> {
+	let mut attrs = HashMap::new();
+	let mut rest = raw.trim();
+
+	while !rest.is_empty() {
+		let key_end = rest.find(|c: char| c.is_whitespace() || c == '=').unwrap_or(rest.len());
+		let key = &rest[..key_end];
+		rest = rest[key_end..].trim_start();
+
+		let mut value = "";
+		if let Some(after_eq) = rest.strip_prefix('=') {
+			rest = after_eq.trim_start();
+			match rest.chars().next() {
+				Some(quote @ ('"' | '\'')) => {
+					rest = &rest[1..];
+					match rest.find(quote) {
+						Some(end) => {
+							value = &rest[..end];
+							rest = &rest[end + 1..];
+						}
+						None => {
+							value = rest;
+							rest = "";
+						}
+					}
+				}
+				Some(_) => {
+					let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+					value = &rest[..end];
+					rest = &rest[end..];
+				}
+				None => (),
+			}
+		}
+
+		if !key.is_empty() {
+			attrs.insert(key.to_string(), value.to_string());
+		}
+		rest = rest.trim_start();
+	}
+
+	attrs
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_tag_parser_builtin_extract() -> Result<()> {
+		// -- Setup & Fixtures
+		let input = "intro <FILE file_path=\"a b.txt\" raw>body</FILE><FILES>x</FILES><FILE k='v'/> tail <FILE>open";
+
+		// -- Exec
+		let parts = extract_tags(input, &["FILE"], true);
+
+		// -- Check
+		assert_eq!(parts.len(), 5);
+		assert_eq!(parts[0], TagPart::Text("intro ".to_string()));
+		let TagPart::Elem(elem) = &parts[1] else {
+			return Err("should be an elem".into());
+		};
+		assert_eq!(elem.content, "body");
+		assert_eq!(elem.attrs.get("file_path").map(String::as_str), Some("a b.txt"));
+		assert_eq!(elem.attrs.get("raw").map(String::as_str), Some(""));
+		assert_eq!(parts[2], TagPart::Text("<FILES>x</FILES>".to_string()));
+		let TagPart::Elem(elem) = &parts[3] else {
+			return Err("should be a self-closing elem".into());
+		};
+		assert_eq!(elem.content, "");
+		assert_eq!(elem.attrs.get("k").map(String::as_str), Some("v"));
+		assert_eq!(parts[4], TagPart::Text(" tail <FILE>open".to_string()));
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,16 @@
+use super::{TagElem, TagPart};
+use markex::tag::{self, Part};
+
+pub(super) fn extract_tags(input: &str, tag_names: &[&str], capture_text: bool) -> Vec<TagPart> {
+	tag::extract(input, tag_names, capture_text)
+		.into_iter()
+		.map(|part| match part {
+			Part::Text(text) => TagPart::Text(text),
+			Part::TagElem(elem) => TagPart::Elem(TagElem {
+				tag: elem.tag,
+				attrs: elem.attrs.unwrap_or_default(),
+				content: elem.content,
+			}),
+		})
+		.collect()
+}
@@ -0,0 +1,112 @@
+// region:    --- Modules
+
+mod builtin;
+#[cfg(feature = "markex")]
+mod markex_impl;
+
+// endregion: --- Modules
+
+use std::collections::HashMap;
+
+/// The implementation used to find the `FILE_CHANGES` and directive tags in the model output.
+///
+/// Both follow the same rules (first `>` ends the opening tag, `/>` self-closes, first matching
+/// closing tag ends the content), so the extraction does not silently change with a markex upgrade.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TagParser {
+	/// The `markex` crate (requires the `markex` feature, on by default).
+	#[cfg(feature = "markex")]
+	#[default]
+	Markex,
+	/// The built-in, dependency-free parser (the default without the `markex` feature).
+	#[cfg_attr(not(feature = "markex"), default)]
+	Builtin,
+}
+
+/// A part of the parsed input, either plain text or a tag element.
+#[derive(Debug, PartialEq)]
+pub(crate) enum TagPart {
+	Text(String),
+	Elem(TagElem),
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct TagElem {
+	pub tag: String,
+	pub attrs: HashMap<String, String>,
+	pub content: String,
+}
+
+/// Extracts the `tag_names` elements of `input` (and the text between them when `capture_text` is `true`).
+pub(crate) fn extract_tags(parser: TagParser, input: &str, tag_names: &[&str], capture_text: bool) -> Vec<TagPart> {
+	match parser {
+		#[cfg(feature = "markex")]
+		TagParser::Markex => markex_impl::extract_tags(input, tag_names, capture_text),
+		TagParser::Builtin => builtin::extract_tags(input, tag_names, capture_text),
+	}
+}
+
+// region:    --- Tests
+
+#[cfg(all(test, feature = "markex"))]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_tag_parser_builtin_matches_markex() -> Result<()> {
+		// -- Setup & Fixtures
+		let inputs = [
+			"before <A x=\"1\" y='2'>one</A> mid <B/> <AB>no</AB> <A>unclosed",
+			"<A>\n<B k=v />\n</A>\n<A z>two</A>after",
+			"<B>nested <A>inner</A></B><A",
+			"no tags at all",
+		];
+
+		for input in inputs {
+			// -- Exec
+			let markex = extract_tags(TagParser::Markex, input, &["A", "B"], true);
+			let builtin = extract_tags(TagParser::Builtin, input, &["A", "B"], true);
+
+			// -- Check
+			assert_eq!(builtin, markex, "input: {input}");
+		}
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_tag_parser_fixtures_same_extraction() -> Result<()> {
+		for entry in std::fs::read_dir("tests/data")? {
+			// -- Setup & Fixtures
+			let path = entry?.path();
+			if path.extension().is_none_or(|ext| ext != "md") {
+				continue;
+			}
+			let input = std::fs::read_to_string(&path)?;
+
+			// -- Exec
+			let markex = crate::extract_file_changes_report(
+				&input,
+				true,
+				crate::ExtractOptions::default().with_tag_parser(TagParser::Markex),
+			)?;
+			let builtin = crate::extract_file_changes_report(
+				&input,
+				true,
+				crate::ExtractOptions::default().with_tag_parser(TagParser::Builtin),
+			)?;
+
+			// -- Check
+			assert!(markex.changes.diff(&builtin.changes).is_empty(), "{path:?}");
+			assert_eq!(markex.extruded, builtin.extruded, "{path:?}");
+			assert_eq!(markex.extruded_segments, builtin.extruded_segments, "{path:?}");
+			assert_eq!(markex.warnings, builtin.warnings, "{path:?}");
+		}
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,206 @@
+use crate::{
+	ApplyChangesStatus, ApplyOptions, Error, FileChanges, FileDirective, Result, SecurityPolicy,
+	apply_file_changes_with_options, make_file_patch,
+};
+use simple_fs::{SPath, ensure_file_dir, list_files};
+use std::collections::BTreeSet;
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Number of lines of context of the `TempWorkspace::diff` hunks.
+const DIFF_CONTEXT_LINES: usize = 3;
+
+static WORKSPACE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// The result of `apply_in_temp_workspace`: a temp dir holding the touched (and context) files
+/// with the changes applied, deleted when dropped.
+#[derive(Debug)]
+pub struct TempWorkspace {
+	dir: SPath,
+	source_dir: SPath,
+	/// The paths touched by the directives, relative to both dirs (sorted).
+	touched_paths: Vec<String>,
+	status: ApplyChangesStatus,
+}
+
+/// Applies `file_changes` in a new temp workspace instead of `source_dir`.
+///
+/// Only the files touched by the directives (sources and targets, recursively for directories), plus the
+/// files matching `context_globs` (relative to `source_dir`), are copied into the workspace before applying.
+/// `source_dir` is never written, see `TempWorkspace::promote`.
+pub fn apply_in_temp_workspace(
+	source_dir: impl Into<SPath>,
+	file_changes: FileChanges,
+	context_globs: &[&str],
+) -> Result<TempWorkspace> {
+	// Note: The workspace is created first, so the temp dir is removed (on drop) if anything fails.
+	let mut workspace = TempWorkspace {
+		dir: new_temp_dir()?,
+		source_dir: source_dir.into(),
+		touched_paths: Vec::new(),
+		status: ApplyChangesStatus { items: Vec::new() },
+	};
+	workspace.touched_paths = touched_paths(&workspace.source_dir, &file_changes)?;
+	let (dir, source_dir) = (&workspace.dir, &workspace.source_dir);
+
+	// -- Materialize the touched and context files
+	let mut to_copy: BTreeSet<String> = BTreeSet::new();
+	for rel_path in &workspace.touched_paths {
+		let full_path = source_dir.join(rel_path);
+		if full_path.is_dir() {
+			to_copy.extend(list_rel_files(source_dir, &[&format!("{rel_path}/**/*")])?);
+		} else if full_path.is_file() {
+			to_copy.insert(rel_path.clone());
+		}
+	}
+	if !context_globs.is_empty() {
+		to_copy.extend(list_rel_files(source_dir, context_globs)?);
+	}
+	for rel_path in &to_copy {
+		copy_file(&source_dir.join(rel_path), &dir.join(rel_path))?;
+	}
+
+	// -- Apply (the temp dir is outside of the current dir, so it is explicitly allowed, directives stay confined to it)
+	let policy = SecurityPolicy::from_writable_dirs([dir.clone()]);
+	workspace.status = apply_file_changes_with_options(dir, file_changes, policy, ApplyOptions::default())?;
+
+	Ok(workspace)
+}
+
+impl TempWorkspace {
+	/// The temp dir where the changes were applied (e.g., to run a compiler or tests against).
+	pub fn dir(&self) -> &SPath {
+		&self.dir
+	}
+
+	pub fn status(&self) -> &ApplyChangesStatus {
+		&self.status
+	}
+
+	/// The touched file paths whose content differs from `source_dir` (sorted).
+	/// A touched directory (e.g., renamed) contributes the files under it.
+	pub fn changed_paths(&self) -> Vec<String> {
+		let mut file_paths: BTreeSet<String> = BTreeSet::new();
+		for rel_path in &self.touched_paths {
+			let mut is_dir = false;
+			for base in [&self.source_dir, &self.dir] {
+				if base.join(rel_path).is_dir() {
+					is_dir = true;
+					file_paths.extend(list_rel_files(base, &[&format!("{rel_path}/**/*")]).unwrap_or_default());
+				}
+			}
+			if !is_dir {
+				file_paths.insert(rel_path.clone());
+			}
+		}
+
+		file_paths
+			.into_iter()
+			.filter(|rel_path| self.read_source(rel_path) != self.read_workspace(rel_path))
+			.collect()
+	}
+
+	/// The unified diff (git-style file headers) from `source_dir` to the workspace, for the changed paths.
+	pub fn diff(&self) -> String {
+		self.changed_paths()
+			.iter()
+			.map(|rel_path| {
+				let old = self.read_source(rel_path).unwrap_or_default();
+				let new = self.read_workspace(rel_path).unwrap_or_default();
+				make_file_patch(
+					rel_path,
+					&String::from_utf8_lossy(&old),
+					&String::from_utf8_lossy(&new),
+					DIFF_CONTEXT_LINES,
+				)
+			})
+			.collect()
+	}
+
+	/// Copies the changed files back to `source_dir` (and deletes the ones deleted in the workspace),
+	/// returning the promoted paths.
+	pub fn promote(&self) -> Result<Vec<String>> {
+		let changed_paths = self.changed_paths();
+		for rel_path in &changed_paths {
+			let target = self.source_dir.join(rel_path);
+			let source = self.dir.join(rel_path);
+			if source.is_file() {
+				copy_file(&source, &target)?;
+			} else if target.is_file() {
+				fs::remove_file(&target).map_err(|err| Error::io_delete_file(target.to_string(), err))?;
+			}
+		}
+		Ok(changed_paths)
+	}
+}
+
+impl Drop for TempWorkspace {
+	fn drop(&mut self) {
+		let _ = fs::remove_dir_all(&self.dir);
+	}
+}
+
+// region:    --- Support
+
+impl TempWorkspace {
+	fn read_source(&self, rel_path: &str) -> Option<Vec<u8>> {
+		fs::read(self.source_dir.join(rel_path)).ok()
+	}
+
+	fn read_workspace(&self, rel_path: &str) -> Option<Vec<u8>> {
+		fs::read(self.dir.join(rel_path)).ok()
+	}
+}
+
+/// The sorted, deduped file paths of the directives (both sides for copy/rename, the source files matching the glob
+/// of a global replace). Paths escaping the base dir are ignored (the applier rejects them anyway).
+fn touched_paths(source_dir: &SPath, file_changes: &FileChanges) -> Result<Vec<String>> {
+	let mut paths: BTreeSet<String> = BTreeSet::new();
+	for directive in file_changes {
+		match directive {
+			FileDirective::Copy { from_path, .. } | FileDirective::Rename { from_path, .. } => {
+				paths.insert(from_path.clone());
+			}
+			FileDirective::GlobalReplace { glob, .. } => {
+				paths.extend(list_rel_files(source_dir, &[glob])?);
+				continue;
+			}
+			_ => (),
+		}
+		if let Some(file_path) = directive.file_path() {
+			paths.insert(file_path.to_string());
+		}
+	}
+	Ok(paths
+		.into_iter()
+		.filter(|p| !p.starts_with('/') && !p.split(['/', '\\']).any(|segment| segment == ".."))
+		.collect())
+}
+
+fn list_rel_files(source_dir: &SPath, globs: &[&str]) -> Result<Vec<String>> {
+	let files = list_files(source_dir, Some(globs), None)?;
+	Ok(files
+		.iter()
+		.filter_map(|f| f.diff(source_dir.path()).map(|p| p.to_string()))
+		.collect())
+}
+
+fn copy_file(from: &SPath, to: &SPath) -> Result<()> {
+	ensure_file_dir(to).map_err(Error::simple_fs)?;
+	fs::copy(from, to).map_err(|err| Error::io_create_file(to.to_string(), err))?;
+	Ok(())
+}
+
+fn new_temp_dir() -> Result<SPath> {
+	let nanos = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|d| d.as_nanos())
+		.unwrap_or_default();
+	let counter = WORKSPACE_COUNTER.fetch_add(1, Ordering::Relaxed);
+	let dir = std::env::temp_dir().join(format!("udiffx-ws-{}-{nanos}-{counter}", std::process::id()));
+	fs::create_dir_all(&dir).map_err(|err| Error::io_create_file(dir.to_string_lossy(), err))?;
+	Ok(SPath::from_std_path(dir)?)
+}
+
+// endregion: --- Support
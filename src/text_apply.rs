@@ -0,0 +1,222 @@
+use crate::applier::apply_patch_incremental_with_options;
+use crate::{
+	ApplyOptions, ContextAssertion, DiffStats, HunkDrift, HunkError, LenientLine, MatchTier, NoopEdit, Result,
+	unescape_patch_body,
+};
+
+/// The result of `complete_and_apply`.
+#[derive(Debug, Clone)]
+pub struct AppliedText {
+	/// The patched content (the original content if no hunk applied).
+	pub content: String,
+	pub report: TextApplyReport,
+}
+
+/// How the patch of `complete_and_apply` was applied (same information as the `FILE_PATCH` `DirectiveStatus`).
+#[derive(Debug, Clone, Default)]
+pub struct TextApplyReport {
+	pub total_hunks: usize,
+	/// The most lenient tier needed by the applied hunks.
+	pub match_tier: Option<MatchTier>,
+	pub hunk_errors: Vec<HunkError>,
+	/// Hunks dropped because they would not change the content.
+	pub noop_edits: Vec<NoopEdit>,
+	/// The 0-based indexes of the applied hunks that matched truncated context lines (suffix/prefix matching).
+	pub truncated_hunks: Vec<usize>,
+	/// The drifts of the applied hunks with a numbered header (see `CompleteOptions::max_drift`).
+	pub hunk_drifts: Vec<HunkDrift>,
+	/// The context/removal lines of the applied hunks matched non-strictly (see `side_by_side_preview`).
+	pub lenient_lines: Vec<LenientLine>,
+	/// The checks of the context-only hunks (see `CompleteOptions::assert_context_hunks`).
+	pub context_assertions: Vec<ContextAssertion>,
+	/// Non-fatal notes about how the patch was applied (e.g., normalized escaped newlines).
+	pub warnings: Vec<String>,
+	pub diff_stats: DiffStats,
+}
+
+impl TextApplyReport {
+	/// `true` when no hunk failed (nor context assertion).
+	pub fn is_success(&self) -> bool {
+		self.hunk_errors.is_empty() && self.context_assertions.iter().all(ContextAssertion::passed)
+	}
+}
+
+/// Completes and applies a simplified `FILE_PATCH` body to `original`, without any path or file system
+/// (e.g., for a pre-commit hook or a server function filtering stdin to stdout).
+///
+/// Failed hunks are reported in `AppliedText::report` (the other hunks still apply), so an `Err` is only returned
+/// for an unusable patch. A failed context assertion (see `CompleteOptions::assert_context_hunks`) leaves the content
+/// unchanged. Of the options, only `unescape_patch_bodies` and `complete_options` apply to a text.
+pub fn complete_and_apply(original: &str, patch: &str, options: impl Into<ApplyOptions>) -> Result<AppliedText> {
+	let options: ApplyOptions = options.into();
+	let mut report = TextApplyReport::default();
+
+	let unescaped = options
+		.unescape_patch_bodies
+		.then(|| unescape_patch_body(patch))
+		.flatten();
+	if unescaped.is_some() {
+		report
+			.warnings
+			.push("Normalized the escaped newlines of the patch".to_string());
+	}
+	let patch = unescaped.as_deref().unwrap_or(patch);
+
+	let complete_options = options.complete_options.unwrap_or_default();
+	let data = apply_patch_incremental_with_options(original, patch, &complete_options)?;
+	report.total_hunks = data.total_hunks;
+	report.context_assertions = data.context_assertions;
+	if !report.context_assertions.iter().all(ContextAssertion::passed) {
+		return Ok(AppliedText {
+			content: original.to_string(),
+			report,
+		});
+	}
+	for completed_patch in &data.completed_patches {
+		report.diff_stats += DiffStats::from_unified_diff(completed_patch);
+	}
+	report.diff_stats.files = report.diff_stats.files.min(1);
+	report.match_tier = data.max_tier;
+	report.hunk_errors = data.hunk_errors;
+	report.noop_edits = data.noop_edits;
+	report.truncated_hunks = data.truncated_hunks;
+	report.hunk_drifts = data.hunk_drifts;
+	report.lenient_lines = data.lenient_lines;
+
+	Ok(AppliedText {
+		content: data.new_content,
+		report,
+	})
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Result<T> = core::result::Result<T, Box<dyn std::error::Error>>; // For tests.
+
+	use super::*;
+	use crate::CompleteOptions;
+
+	#[test]
+	fn test_text_apply_complete_and_apply_simple() -> Result<()> {
+		// -- Setup & Fixtures
+		let original = "fn main() {\n    run();\n}\n";
+		let patch = "@@\n fn main() {\n-    run();\n+    run_all();\n }\n@@\n-missing\n+x\n";
+
+		// -- Exec
+		let applied = complete_and_apply(original, patch, None)?;
+
+		// -- Check
+		assert_eq!(applied.content, "fn main() {\n    run_all();\n}\n");
+		assert_eq!(applied.report.total_hunks, 2);
+		assert!(!applied.report.is_success());
+		assert_eq!(applied.report.hunk_errors[0].hunk_index, 1);
+		assert!(applied.report.truncated_hunks.is_empty());
+		assert_eq!(
+			applied.report.diff_stats.to_string(),
+			"1 file changed, 1 insertion(+), 1 deletion(-)"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_text_apply_complete_and_apply_truncated_hunks() -> Result<()> {
+		// -- Setup & Fixtures
+		let original = "fn main() {\n    initialize_database_connection(&config);\n    run();\n}\n";
+		let patch = "@@\n database_connection(&config);\n-    run();\n+    run_all();\n";
+
+		// -- Exec
+		let applied = complete_and_apply(original, patch, None)?;
+
+		// -- Check
+		assert!(applied.content.contains("    run_all();\n"));
+		assert_eq!(applied.report.truncated_hunks, vec![0]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_text_apply_complete_and_apply_unescape() -> Result<()> {
+		// -- Setup & Fixtures
+		let options = ApplyOptions::default().with_unescape_patch_bodies();
+
+		// -- Exec
+		let applied = complete_and_apply("a\nb\n", "@@\\n a\\n-b\\n+B", options)?;
+
+		// -- Check
+		assert_eq!(applied.content, "a\nB\n");
+		assert!(applied.report.is_success());
+		assert_eq!(
+			applied.report.warnings,
+			["Normalized the escaped newlines of the patch"]
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_text_apply_complete_and_apply_max_drift() -> Result<()> {
+		// -- Setup & Fixtures
+		let original: String = (1..=30).map(|n| format!("line {n}\n")).collect();
+		let patch = "@@ -5,1 +5,1 @@\n-line 20\n+LINE 20\n";
+		let options =
+			ApplyOptions::default().with_complete_options(CompleteOptions::default().with_max_drift(Some(10)));
+
+		// -- Exec
+		let applied = complete_and_apply(&original, patch, None)?;
+		let refused = complete_and_apply(&original, patch, options)?;
+
+		// -- Check
+		assert!(applied.content.contains("LINE 20\n"));
+		assert_eq!(applied.report.hunk_drifts[0].expected_line, 5);
+		assert_eq!(applied.report.hunk_drifts[0].matched_line, 20);
+		assert_eq!(applied.report.hunk_drifts[0].drift(), 15);
+		assert_eq!(refused.content, original);
+		assert_eq!(
+			refused.report.hunk_errors[0].cause,
+			"Hunk matched at line 20, 15 lines away from its expected line 5 (max drift 10)"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_text_apply_complete_and_apply_context_assertions() -> Result<()> {
+		// -- Setup & Fixtures
+		let original = "fn a() {\n    one();\n}\n\nfn b() {\n    two();\n}\n";
+		let patch = "@@\n fn b() {\n     two();\n }\n@@\n fn a() {\n-    one();\n+    uno();\n }\n";
+		let missing = "@@\n fn c() {\n }\n@@\n fn a() {\n-    one();\n+    uno();\n }\n";
+		let options =
+			ApplyOptions::default().with_complete_options(CompleteOptions::default().with_assert_context_hunks(true));
+
+		// -- Exec
+		let unchecked = complete_and_apply(original, missing, None)?;
+		let asserted = complete_and_apply(original, patch, options.clone())?;
+		let failed = complete_and_apply(original, missing, options)?;
+
+		// -- Check
+		assert!(unchecked.report.is_success());
+		assert!(unchecked.report.context_assertions.is_empty());
+		assert!(unchecked.content.contains("uno();"));
+		assert!(asserted.report.is_success());
+		assert_eq!(asserted.report.total_hunks, 1);
+		assert_eq!(
+			asserted.report.context_assertions,
+			[ContextAssertion {
+				index: 0,
+				line: Some(5),
+				tier: Some(MatchTier::Strict),
+			}]
+		);
+		assert!(asserted.content.contains("uno();"));
+		assert!(!failed.report.is_success());
+		assert_eq!(failed.report.context_assertions[0].line, None);
+		assert_eq!(failed.content, original);
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,319 @@
+use crate::apply_store::DiskStore;
+use crate::file_insert::file_insert_applied;
+use crate::global_replace::{Replacer, glob_rel_paths};
+use crate::patch_completer::{hunk_sides, split_raw_hunks};
+use crate::span_patch::span_patch_applied;
+use crate::{
+	ApplyChangesStatus, ApplyOptions, CompleteOptions, Content, DirectiveStatus, FileChanges, FileDirective, Result,
+	SecurityPolicy, apply_file_changes_with_options, fs_guard, has_tilde_ranges,
+};
+use derive_more::Display;
+use simple_fs::SPath;
+
+/// The end state of a directive found by `verify_applied`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum VerifyState {
+	/// The directive end state is on disk.
+	#[display("applied")]
+	Applied,
+	/// The directive end state is not (or not fully) on disk.
+	#[display("not applied")]
+	NotApplied,
+	/// A later directive touches the same path, so this one cannot be checked against the final state.
+	#[display("superseded")]
+	Superseded,
+	/// The directive cannot be checked (e.g., a `Fail` directive, a path outside of the base dir, or tilde ranges).
+	#[display("unverifiable")]
+	Unverifiable,
+}
+
+/// The verification of one directive.
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+#[display("directive #{} {kind} '{file_path}': {state}", directive_index + 1)]
+pub struct VerifyItem {
+	/// The 0-based index of the directive in the `FileChanges`.
+	pub directive_index: usize,
+	/// The directive kind (e.g., `Patch`).
+	pub kind: &'static str,
+	pub file_path: String,
+	pub state: VerifyState,
+	/// Why the directive is not `Applied` (e.g., `hunk #2 not found`).
+	pub message: Option<String>,
+}
+
+/// The result of `verify_applied`, with one item per directive (in order).
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+	pub items: Vec<VerifyItem>,
+}
+
+impl VerifyReport {
+	/// `true` when every verifiable directive is applied.
+	pub fn is_applied(&self) -> bool {
+		!self.items.iter().any(|i| i.state == VerifyState::NotApplied)
+	}
+
+	/// The indexes of the directives not applied (e.g., to resume an interrupted run).
+	pub fn not_applied_indexes(&self) -> Vec<usize> {
+		self.items
+			.iter()
+			.filter(|i| i.state == VerifyState::NotApplied)
+			.map(|i| i.directive_index)
+			.collect()
+	}
+}
+
+/// Checks, without writing anything, whether the end state of each directive is present under `base_dir`
+/// (e.g., to resume an interrupted run, or to audit a past one).
+///
+/// - `FILE_NEW`: the file has the directive content.
+/// - `FILE_PATCH`: each hunk result is in the file and its removed lines are gone.
+/// - `FILE_APPEND`: the file ends with the appended content.
+/// - `FILE_SPAN_PATCH`: the line no longer matches its line hash, and has the replacement at the span start.
+/// - `FILE_INSERT`: the inserted lines are at the anchor line (anywhere for a `replace-first` whose anchor is gone).
+/// - `FILE_COPY`: the target exists, with the source content if the source still exists.
+/// - `FILE_RENAME`: the source is gone and the target exists.
+/// - `FILE_DELETE`: the path is gone.
+pub fn verify_applied(base_dir: impl Into<SPath>, file_changes: &FileChanges) -> VerifyReport {
+	let base_dir = base_dir.into();
+	let directives: Vec<&FileDirective> = file_changes.iter().collect();
+
+	let items = directives
+		.iter()
+		.enumerate()
+		.map(|(idx, directive)| {
+			let file_path = directive.file_path().unwrap_or("unknown").to_string();
+			let paths = directive_paths(directive);
+			let is_superseded = directives[idx + 1..]
+				.iter()
+				.any(|later| directive_paths(later).iter().any(|p| paths.contains(p)));
+			let (state, message) = if is_superseded {
+				(VerifyState::Superseded, None)
+			} else {
+				match verify_directive(&base_dir, directive) {
+					Ok(()) => (VerifyState::Applied, None),
+					Err(failure) => failure,
+				}
+			};
+			VerifyItem {
+				directive_index: idx,
+				kind: directive.kind(),
+				file_path,
+				state,
+				message,
+			}
+		})
+		.collect();
+
+	VerifyReport { items }
+}
+
+/// Resumes an interrupted apply of `file_changes`: the leading directives verified as applied (see `verify_applied`)
+/// are skipped, and the batch is applied from the first other one (see `apply_file_changes_with_options`).
+/// The skipped directives are reported as successful, with a warning.
+///
+/// Note: No record of the interrupted run is kept, so what it applied is inferred from the files. The inference is
+///       a heuristic:
+///       - A `Superseded` directive (a later one touches its path) cannot be verified, so the batch resumes from it.
+///         It is applied again, unless its end state is still there (e.g., a superseded `FILE_APPEND` followed by
+///         a `FILE_PATCH` of the appended lines is appended twice).
+///       - The remaining directives are applied with `ApplyOptions::idempotent`, so one already applied (e.g., a
+///         `FILE_NEW` after a superseded directive) succeeds unchanged instead of being applied twice.
+///       - A directive whose end state was already there before the run (e.g., an append of lines the file already
+///         ended with) is taken as applied.
+pub fn resume_apply(
+	base_dir: impl Into<SPath>,
+	file_changes: FileChanges,
+	security_policy: impl Into<SecurityPolicy>,
+	options: impl Into<ApplyOptions>,
+) -> Result<ApplyChangesStatus> {
+	let base_dir = base_dir.into();
+	let report = verify_applied(&base_dir, &file_changes);
+
+	let mut directives: Vec<FileDirective> = file_changes.into_iter().collect();
+	let resume_idx = report
+		.items
+		.iter()
+		.position(|item| item.state != VerifyState::Applied)
+		.unwrap_or(directives.len());
+	let remaining = directives.split_off(resume_idx);
+
+	let mut items: Vec<DirectiveStatus> = directives
+		.iter()
+		.map(|directive| {
+			let mut info = DirectiveStatus::from(directive);
+			info.success = true;
+			info.warnings.push("Already applied, skipped on resume".to_string());
+			info
+		})
+		.collect();
+	let options = ApplyOptions {
+		idempotent: true,
+		..options.into()
+	};
+	let status = apply_file_changes_with_options(base_dir, FileChanges::new(remaining), security_policy, options)?;
+	items.extend(status.items);
+
+	Ok(ApplyChangesStatus { items })
+}
+
+// region:    --- Support
+
+type Failure = (VerifyState, Option<String>);
+
+fn not_applied(message: impl Into<String>) -> Failure {
+	(VerifyState::NotApplied, Some(message.into()))
+}
+
+fn verify_directive(base_dir: &SPath, directive: &FileDirective) -> core::result::Result<(), Failure> {
+	let path = |rel_path: &str| -> core::result::Result<SPath, Failure> {
+		let full_path = base_dir.join(rel_path);
+		// Note: The write guard is used for its lexical containment check (nothing is written).
+		fs_guard::check_for_write(&full_path, base_dir, None, false)
+			.map_err(|err| (VerifyState::Unverifiable, Some(err.to_string())))?;
+		Ok(full_path)
+	};
+	let read = |full_path: &SPath| std::fs::read_to_string(full_path).map_err(|_| not_applied("file not found"));
+	let load = |content: &'_ Content| {
+		content
+			.load()
+			.map(|c| c.into_owned())
+			.map_err(|err| (VerifyState::Unverifiable, Some(err.to_string())))
+	};
+
+	match directive {
+		FileDirective::New { file_path, content } => {
+			if read(&path(file_path)?)? != load(content)? {
+				return Err(not_applied("content differs"));
+			}
+		}
+
+		FileDirective::Patch { file_path, content, .. } => {
+			let current = read(&path(file_path)?)?;
+			let current_lines: Vec<&str> = current.lines().map(str::trim_end).collect();
+			for (hunk_idx, raw_hunk) in split_raw_hunks(&content.content).iter().enumerate() {
+				if has_tilde_ranges(raw_hunk) {
+					return Err((
+						VerifyState::Unverifiable,
+						Some(format!("hunk #{} has tilde ranges", hunk_idx + 1)),
+					));
+				}
+				let (old_side, new_side) = hunk_sides(raw_hunk);
+				let new_present = new_side.is_empty() || contains_lines(&current_lines, &new_side);
+				let old_gone =
+					old_side.is_empty() || old_side == new_side || !contains_lines(&current_lines, &old_side);
+				if !new_present || !old_gone {
+					return Err(not_applied(format!("hunk #{} not found", hunk_idx + 1)));
+				}
+			}
+		}
+
+		FileDirective::Append { file_path, content } => {
+			if !read(&path(file_path)?)?.ends_with(&load(content)?) {
+				return Err(not_applied("appended content not found at the end of the file"));
+			}
+		}
+
+		FileDirective::SpanPatch {
+			file_path,
+			line,
+			columns,
+			line_hash,
+			replacement,
+		} => {
+			if !span_patch_applied(&read(&path(file_path)?)?, *line, columns, line_hash, replacement) {
+				return Err(not_applied("span replacement not found"));
+			}
+		}
+
+		FileDirective::Insert {
+			file_path,
+			anchor,
+			regex,
+			position,
+			content,
+		} => {
+			let options = CompleteOptions::for_path(file_path);
+			if !file_insert_applied(&read(&path(file_path)?)?, anchor, *regex, *position, content, &options) {
+				return Err(not_applied("inserted lines not found"));
+			}
+		}
+
+		FileDirective::GlobalReplace {
+			glob,
+			pattern,
+			replacement,
+			regex,
+			..
+		} => {
+			let unverifiable = |msg: String| (VerifyState::Unverifiable, Some(msg));
+			// Note: A literal replacement holding the pattern leaves matches behind even when applied.
+			if !regex && replacement.contains(pattern.as_str()) {
+				return Err(unverifiable("the replacement contains the pattern".to_string()));
+			}
+			let replacer = Replacer::new(pattern, *regex).map_err(|err| unverifiable(err.to_string()))?;
+			let rel_paths = glob_rel_paths(&DiskStore, base_dir, glob).map_err(|err| unverifiable(err.to_string()))?;
+			for rel_path in rel_paths {
+				if let Ok(content) = std::fs::read_to_string(path(&rel_path)?)
+					&& replacer.count(&content) > 0
+				{
+					return Err(not_applied(format!("pattern still found in '{rel_path}'")));
+				}
+			}
+		}
+
+		FileDirective::Copy { from_path, to_path } => {
+			let (full_from, full_to) = (path(from_path)?, path(to_path)?);
+			let to_bytes = std::fs::read(&full_to).map_err(|_| not_applied("copy target not found"))?;
+			if let Ok(from_bytes) = std::fs::read(&full_from)
+				&& from_bytes != to_bytes
+			{
+				return Err(not_applied("copy target differs from the source"));
+			}
+		}
+
+		FileDirective::Rename { from_path, to_path } => {
+			let (full_from, full_to) = (path(from_path)?, path(to_path)?);
+			if full_from.exists() {
+				return Err(not_applied("rename source still exists"));
+			}
+			if !full_to.exists() {
+				return Err(not_applied("rename target not found"));
+			}
+		}
+
+		FileDirective::Delete { file_path } => {
+			if path(file_path)?.exists() {
+				return Err(not_applied("path still exists"));
+			}
+		}
+
+		FileDirective::Fail { error_msg, .. } => {
+			return Err((VerifyState::Unverifiable, Some(error_msg.clone())));
+		}
+	}
+
+	Ok(())
+}
+
+/// `true` if `lines` holds the `side` lines in sequence (trailing whitespace ignored).
+fn contains_lines(lines: &[&str], side: &[&str]) -> bool {
+	lines.windows(side.len()).any(|window| {
+		window
+			.iter()
+			.zip(side)
+			.all(|(line, expected)| *line == expected.trim_end())
+	})
+}
+
+/// The paths a directive touches (both sides for copy/rename).
+fn directive_paths(directive: &FileDirective) -> Vec<&str> {
+	match directive {
+		FileDirective::Copy { from_path, to_path } | FileDirective::Rename { from_path, to_path } => {
+			vec![from_path.as_str(), to_path.as_str()]
+		}
+		_ => directive.file_path().into_iter().collect(),
+	}
+}
+
+// endregion: --- Support
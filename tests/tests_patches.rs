@@ -3,7 +3,7 @@
 use assertables::{assert_contains, assert_not_contains};
 use simple_fs::SPath;
 use udiffx::for_test::{apply_patch_incremental, split_raw_hunks};
-use udiffx::{FileDirective, extract_file_changes};
+use udiffx::{FileDirective, corpus_stats, extract_file_changes};
 
 mod test_support;
 
@@ -499,6 +499,35 @@ fn test_patches_test_22() -> Result<()> {
 	Ok(())
 }
 
+#[test]
+fn test_patches_corpus_stats() -> Result<()> {
+	// -- Exec
+	let stats = corpus_stats("tests/data/test-patches")?;
+
+	// -- Check
+	assert_eq!(stats.outcomes.len(), 23);
+	// Note: The scenarios using the `TEST_FILE_*` tags have no `FILE_PATCH` for the lib, so they are errors.
+	assert_eq!(stats.outcomes.iter().filter(|o| o.error.is_some()).count(), 5);
+	let failed: Vec<&str> = stats
+		.outcomes
+		.iter()
+		.filter(|o| o.failed_hunks > 0)
+		.map(|o| o.name.as_str())
+		.collect();
+	assert_eq!(failed, vec!["test-06-no-match", "test-22-not-matching"]);
+	assert_eq!(stats.succeeded(), 16);
+	assert_eq!(
+		stats.tier_histogram().to_string(),
+		"strict 7, resilient 3, fuzzy 2, none 6"
+	);
+	assert_contains!(
+		stats.to_string(),
+		"scenarios: 23, succeeded: 16 (69.6%), partial: 0, failed: 7\n"
+	);
+
+	Ok(())
+}
+
 // region:    --- Support
 
 #[derive(Debug)]